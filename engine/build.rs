@@ -1,6 +1,40 @@
+use std::process::Command;
+
 use cfg_aliases::cfg_aliases;
 
+// Runs git with `args` and returns its trimmed output, or `None` if git isn't
+// available or this isn't a git checkout (e.g. when building from a tarball).
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    Some(stdout.trim().to_owned())
+}
+
 fn main() {
+    // Embed the commit being built so that builds can be identified (see
+    // com::get_build_version)
+    let commit = git(&["rev-parse", "--short", "HEAD"])
+        .unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=OPENT5_GIT_COMMIT={commit}");
+    println!("cargo:rerun-if-changed=build.rs");
+    // HEAD only changes when switching branches; committing changes the ref
+    // HEAD points to, so we have to watch that too
+    for path in [
+        Some("HEAD".to_owned()),
+        git(&["symbolic-ref", "-q", "HEAD"]),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        if let Some(path) = git(&["rev-parse", "--git-path", &path]) {
+            println!("cargo:rerun-if-changed={path}");
+        }
+    }
+
     // Setup cfg aliases
     cfg_aliases! {
         // OSes
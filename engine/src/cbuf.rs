@@ -1,10 +1,258 @@
 #![allow(dead_code)]
 
-#[allow(clippy::print_stdout)]
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+use crate::{util::EasierAtomic, *};
+
+lazy_static! {
+    static ref TEXT: Mutex<String> = Mutex::new(String::new());
+    // How many more calls to execute() should leave the queue alone, set by
+    // the wait command
+    static ref WAIT_FRAMES: AtomicUsize = AtomicUsize::new(0);
+}
+
+/// Queues `text` to be run as console input during the next [`execute`].
+#[allow(unused_variables)]
 pub fn add_text(i: i32, text: &str) {
-    println!("cbuf::add_text: ({}) {}", i, text);
+    TEXT.lock().unwrap().push_str(text);
 }
 
 pub fn add_textln(i: i32, text: &str) {
     add_text(i, &format!("{}\n", text));
 }
+
+/// Discards any text queued with [`add_text`] that hasn't been run yet,
+/// along with any `wait` still pending.
+pub fn clear() {
+    TEXT.lock().unwrap().clear();
+    WAIT_FRAMES.store_relaxed(0);
+}
+
+/// Splits `text` into the commands in it, returning them along with where
+/// the text that hasn't been ended by a separator yet starts.
+///
+/// Commands are separated by newlines, and by `;`s that aren't inside double
+/// quotes. Anything from a `//` outside quotes to the end of its line is a
+/// comment.
+#[allow(clippy::indexing_slicing, clippy::string_slice)]
+fn split(text: &str) -> (Vec<&str>, usize) {
+    let bytes = text.as_bytes();
+    let mut commands = Vec::new();
+    // Where the current command starts, or None inside a comment
+    let mut start = Some(0);
+    let mut rest = 0;
+    let mut in_quotes = false;
+    for (i, &b) in bytes.iter().enumerate() {
+        match (b, start) {
+            (b'\n', _) => {
+                if let Some(start) = start {
+                    commands.push(&text[start..i]);
+                }
+                start = Some(i + 1);
+                rest = i + 1;
+                in_quotes = false;
+            }
+            (_, None) => {}
+            (b'"', Some(_)) => in_quotes = in_quotes == false,
+            (b';', Some(s)) if in_quotes == false => {
+                commands.push(&text[s..i]);
+                start = Some(i + 1);
+                rest = i + 1;
+            }
+            (b'/', Some(s))
+                if in_quotes == false && bytes.get(i + 1) == Some(&b'/') =>
+            {
+                commands.push(&text[s..i]);
+                start = None;
+                rest = i;
+            }
+            _ => {}
+        }
+    }
+
+    let commands = commands
+        .into_iter()
+        .map(str::trim)
+        .filter(|c| c.is_empty() == false)
+        .collect();
+    (commands, rest)
+}
+
+/// Splits `text` into the commands in it, the same way [`execute`] does:
+/// on newlines and on `;`s outside double quotes, skipping `//` comments.
+#[allow(clippy::string_slice)]
+pub fn split_commands(text: &str) -> Vec<&str> {
+    let (mut commands, rest) = split(text);
+    // The last command doesn't need a separator after it here
+    let last = text[rest..].trim();
+    if last.is_empty() == false && last.starts_with("//") == false {
+        commands.push(last);
+    }
+    commands
+}
+
+/// Runs every complete command queued with [`add_text`] before this call.
+/// Called once per frame by [`com::frame`].
+///
+/// Anything the commands queue up themselves waits for the next call, so
+/// that a command that queues itself can't keep this from returning. A
+/// `wait` stops the rest of the commands from running until its frames are
+/// up.
+#[allow(unused_variables)]
+pub fn execute(i: i32) {
+    if WAIT_FRAMES.load_relaxed() > 0 {
+        WAIT_FRAMES.fetch_sub(1, Ordering::Relaxed);
+        return;
+    }
+
+    // Take the commands out before running them, since they may queue up
+    // more text.
+    let commands = {
+        let mut text = TEXT.lock().unwrap();
+        let (commands, rest) = split(&text);
+        let commands =
+            commands.into_iter().map(str::to_owned).collect::<Vec<_>>();
+        text.drain(..rest);
+        commands
+    };
+
+    let mut commands = commands.into_iter();
+    while let Some(command) = commands.next() {
+        cmd::execute_string(&command);
+
+        if WAIT_FRAMES.load_relaxed() > 0 {
+            WAIT_FRAMES.fetch_sub(1, Ordering::Relaxed);
+            // The rest still go ahead of anything queued in the meantime
+            let rest = commands.map(|c| format!("{}\n", c)).collect::<String>();
+            TEXT.lock().unwrap().insert_str(0, &rest);
+            return;
+        }
+    }
+}
+
+/// `wait [frames]`: holds off running the rest of the queued commands for
+/// `frames` frames (1 if not given).
+fn wait_f() {
+    let frames = if cmd::argc() > 1 {
+        cmd::argv(1).parse::<usize>().unwrap_or(1).max(1)
+    } else {
+        1
+    };
+    WAIT_FRAMES.store_relaxed(frames);
+}
+
+pub fn add_commands() {
+    cmd::add_command_internal("wait", wait_f).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn register_test_int() {
+        dvar::register_int(
+            "test_int",
+            0,
+            Some(0),
+            Some(100),
+            dvar::DvarFlags::empty(),
+            None,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn execute_runs_every_complete_command() {
+        let _guard = testing::fresh_state();
+        register_test_int();
+
+        add_text(0, "test_int 1; test_int 2\ntest_int 3");
+        execute(0);
+        assert_eq!(dvar::get_int("test_int"), Some(2));
+
+        add_textln(0, "");
+        execute(0);
+        assert_eq!(dvar::get_int("test_int"), Some(3));
+    }
+
+    #[test]
+    fn wait_holds_off_the_rest_for_a_frame() {
+        let _guard = testing::fresh_state();
+        register_test_int();
+        add_commands();
+
+        add_textln(0, "test_int 1; wait; test_int 2");
+        execute(0);
+        assert_eq!(dvar::get_int("test_int"), Some(1));
+        execute(0);
+        assert_eq!(dvar::get_int("test_int"), Some(2));
+    }
+
+    #[test]
+    fn wait_keeps_unfinished_command_behind_the_rest() {
+        let _guard = testing::fresh_state();
+        register_test_int();
+        add_commands();
+
+        add_text(0, "test_int 1; wait; test_int 2; test_int \"3");
+        execute(0);
+        assert_eq!(dvar::get_int("test_int"), Some(1));
+        execute(0);
+        assert_eq!(dvar::get_int("test_int"), Some(2));
+
+        add_text(0, "\"");
+        execute(0);
+        assert_eq!(dvar::get_int("test_int"), Some(2));
+
+        add_textln(0, "");
+        execute(0);
+        assert_eq!(dvar::get_int("test_int"), Some(3));
+    }
+
+    #[test]
+    fn split_commands_honors_quotes_and_comments() {
+        assert_eq!(
+            split_commands("say \"a;b\"; test_int 1 // x; y\ntest_int 2"),
+            ["say \"a;b\"", "test_int 1", "test_int 2"]
+        );
+        assert_eq!(split_commands("say \"http://x\""), ["say \"http://x\""]);
+        assert!(split_commands("// just a comment").is_empty());
+    }
+
+    #[test]
+    fn execute_leaves_unfinished_command_queued() {
+        let _guard = testing::fresh_state();
+        register_test_int();
+
+        add_text(0, "test_int 1; test_int 2");
+        execute(0);
+        assert_eq!(dvar::get_int("test_int"), Some(1));
+
+        add_text(0, "\ntest_int \"3");
+        execute(0);
+        assert_eq!(dvar::get_int("test_int"), Some(2));
+
+        add_text(0, "\"");
+        execute(0);
+        assert_eq!(dvar::get_int("test_int"), Some(2));
+
+        add_text(0, ";");
+        execute(0);
+        assert_eq!(dvar::get_int("test_int"), Some(3));
+    }
+
+    #[test]
+    fn clear_discards_queued_text() {
+        let _guard = testing::fresh_state();
+        register_test_int();
+
+        add_textln(0, "test_int 5");
+        clear();
+        execute(0);
+        assert_eq!(dvar::get_int("test_int"), Some(0));
+    }
+}
@@ -2,10 +2,7 @@
 
 use core::time::Duration;
 
-use crate::{
-    dvar,
-    util::{Angle, Point, Velocity},
-};
+use crate::util::{Angle, Point, Velocity};
 use arrayvec::ArrayString;
 use bitflags::bitflags;
 
@@ -201,15 +198,3 @@ pub struct ClientState {
     score: ScoreCount,
     client_ui_visibility_flags: UiVisibilityFlags,
 }
-
-pub fn register_dvars() {
-    dvar::register_int(
-        "developer",
-        0,
-        Some(0),
-        Some(2),
-        dvar::DvarFlags::empty(),
-        Some("Turn on Development systems"),
-    )
-    .unwrap();
-}
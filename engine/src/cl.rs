@@ -14,8 +14,8 @@ use num_derive::FromPrimitive;
 use crate::{
     cg::{self, Angles3, OffhandId, WeaponId},
     common::{StanceState, Vec3f32},
-    render,
-    util::{Angle, Point, Velocity},
+    dvar, input, render,
+    util::{Angle, Degrees, Point, Velocity},
     vid,
 };
 
@@ -206,10 +206,73 @@ lazy_static! {
     static ref CLS: RwLock<ClientStatic> = RwLock::new(ClientStatic::new());
 }
 
+/// Turns local client `local_client_num`'s view by however far the mouse
+/// has moved since the last call, scaled by `m_yaw` and `m_pitch`.
+///
+/// The movement is consumed even when the client isn't in a game, so that
+/// it doesn't all land at once when they join one.
+#[allow(clippy::cast_possible_truncation)]
+pub fn mouse_move(local_client_num: usize) {
+    let (dx, dy) = input::mouse::consume_move();
+    if local_client_is_in_game(local_client_num) == false {
+        return;
+    }
+
+    let mut clients = get_local_client_globals_mut();
+    let Some(cl) = clients.get_mut(local_client_num) else {
+        return;
+    };
+
+    // Keep the last two frames' movement around for filtering
+    let idx = usize::from(cl.mouse_idx != 0);
+    cl.mouse_dx[idx] = dx.round() as i32;
+    cl.mouse_dy[idx] = dy.round() as i32;
+    cl.mouse_idx ^= 1;
+
+    let yaw = dvar::get_float("m_yaw").unwrap_or(0.0);
+    let pitch = dvar::get_float("m_pitch").unwrap_or(0.0);
+    let (view_pitch, view_yaw, view_roll) = cl.view_angles;
+    cl.view_angles = (
+        view_pitch.turned_by(Degrees::new(dy * pitch)),
+        view_yaw.turned_by(Degrees::new(-dx * yaw)),
+        view_roll,
+    );
+}
+
 pub fn init_renderer() {
     render::begin_registration(&mut CLS.write().unwrap().vid_config);
 }
 
+// TODO - register the rest of the client Dvars and commands
 pub fn init_once_for_all_clients() {
-    cg::register_dvars();
+    dvar::register_float(
+        "m_pitch",
+        0.022,
+        Some(-1.0),
+        Some(1.0),
+        dvar::DvarFlags::ARCHIVE,
+        Some("Degrees the view pitches per unit the mouse moves vertically"),
+    )
+    .unwrap();
+    dvar::register_float(
+        "m_yaw",
+        0.022,
+        Some(-1.0),
+        Some(1.0),
+        dvar::DvarFlags::ARCHIVE,
+        Some("Degrees the view turns per unit the mouse moves horizontally"),
+    )
+    .unwrap();
+    dvar::register_int(
+        "cl_dblTapMaxDelayTime",
+        300,
+        Some(100),
+        Some(1000),
+        dvar::DvarFlags::ARCHIVE,
+        Some(
+            "Maximum time in milliseconds between two presses of a key for \
+             them to count as a double tap",
+        ),
+    )
+    .unwrap();
 }
@@ -20,6 +20,7 @@ pub struct CmdFunction {
     auto_complete_dir: String,
     auto_complete_ext: String,
     function: fn(),
+    developer_only: bool,
 }
 
 // CmdFunctions should only be compared by name, to prevent multiple commands
@@ -85,6 +86,7 @@ impl CmdFunction {
             auto_complete_dir: auto_complete_dir.to_string(),
             auto_complete_ext: auto_complete_ext.to_string(),
             function,
+            developer_only: false,
         }
     }
 }
@@ -94,6 +96,22 @@ lazy_static! {
         RwLock::new(HashMap::new());
 }
 
+/// Unregisters every command.
+///
+/// Lets code that exercises command dispatch on its own (e.g. tests) start
+/// from a clean slate without bringing up the rest of the engine.
+pub fn clear_commands() {
+    CMD_FUNCTIONS.write().unwrap().clear();
+}
+
+static INITIALIZED: util::InitFlag = util::InitFlag::new("cmd::init");
+
+/// Initializes the command system. Has to be called before any commands
+/// are added or run.
+pub fn init() {
+    INITIALIZED.set();
+}
+
 pub fn find(name: &str) -> Option<CmdFunction> {
     let cmd_functions = CMD_FUNCTIONS.read().unwrap();
     cmd_functions.get(name).cloned()
@@ -105,6 +123,7 @@ pub fn exists(name: &str) -> bool {
 }
 
 pub fn add_command_internal(name: &str, function: fn()) -> Option<CmdFunction> {
+    INITIALIZED.debug_check("cmd::add_command_internal");
     if exists(name) {
         com::println!(
             console::Channel::SYSTEM,
@@ -120,6 +139,19 @@ pub fn add_command_internal(name: &str, function: fn()) -> Option<CmdFunction> {
     Some(cmd_functions.get(name).unwrap().clone())
 }
 
+/// Like [`add_command_internal`], but the command refuses to run unless
+/// [`com::is_developer`] is `true`.
+pub fn add_developer_command(
+    name: &str,
+    function: fn(),
+) -> Option<CmdFunction> {
+    add_command_internal(name, function)?;
+    let mut cmd_functions = CMD_FUNCTIONS.write().unwrap();
+    let cmd = cmd_functions.get_mut(name).unwrap();
+    cmd.developer_only = true;
+    Some(cmd.clone())
+}
+
 pub fn remove_command(name: &str) {
     CMD_FUNCTIONS.write().unwrap().remove(name).unwrap();
 }
@@ -180,3 +212,253 @@ pub fn argv(idx: usize) -> String {
     // And return acquired arg
     argv
 }
+
+// Splits a command line into its arguments. Arguments are separated by
+// whitespace, unless they're enclosed in double quotes.
+fn split_args(text: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            args.push(chars.by_ref().take_while(|&c| c != '"').collect());
+        } else {
+            let mut arg = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_whitespace() == false) {
+                arg.push(c);
+            }
+            args.push(arg);
+        }
+    }
+    args
+}
+
+/// Splits `text` into arguments, which can then be retrieved with [`argc`]
+/// and [`argv`] until the matching call to [`end_tokenize`].
+///
+/// Returns [`Err`] (without needing a matching [`end_tokenize`]) if
+/// commands are already nested as deeply as they can be, e.g. by an `exec`
+/// or `vstr` that ends up running itself.
+pub fn tokenize(text: &str) -> Result<(), ()> {
+    let argv = split_args(text);
+    let argc = argv.len();
+    ARGS.with(|args| {
+        let mut args = args.borrow_mut();
+        let nesting = args.argv.len();
+        args.argv.try_push(argv).map_err(|_| ())?;
+        args.nesting = nesting;
+        *args.argc.get_mut(nesting).unwrap() = argc;
+        Ok(())
+    })
+}
+
+/// Discards the arguments from the last call to [`tokenize`], restoring the
+/// ones from before it.
+pub fn end_tokenize() {
+    ARGS.with(|args| {
+        let mut args = args.borrow_mut();
+        let nesting = args.nesting;
+        args.argv.pop();
+        *args.argc.get_mut(nesting).unwrap() = 0;
+        args.nesting = nesting.saturating_sub(1);
+    });
+}
+
+/// Runs a single line of console input, which may either be a command or a
+/// Dvar name (optionally followed by a value to set it to).
+pub fn execute_string(text: &str) {
+    INITIALIZED.debug_check("cmd::execute_string");
+    if tokenize(text).is_err() {
+        com::warnln!(
+            console::Channel::SYSTEM,
+            "cmd::execute_string: commands nested too deeply, not running \
+             '{}'",
+            text
+        );
+        return;
+    }
+    if argc() > 0 {
+        let name = argv(0);
+        if let Some(cmd) = find(&name) {
+            if cmd.developer_only && com::is_developer() == false {
+                com::println!(
+                    console::Channel::DONT_FILTER,
+                    "'{}' is only available with developer enabled",
+                    name
+                );
+            } else {
+                (cmd.function)();
+            }
+        } else if dvar::command() == false {
+            unknown_command(&name);
+        }
+    }
+    end_tokenize();
+}
+
+/// The most suggestions [`unknown_command`] will print.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Returns up to [`MAX_SUGGESTIONS`] of `candidates` that are close enough
+/// to `name` (by edit distance, ignoring case) to plausibly be what was
+/// meant, closest first.
+///
+/// The further a candidate can be from `name` grows with `name`'s length, so
+/// that short names don't match nearly everything.
+pub fn suggestions<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<String> {
+    let name = name.to_lowercase();
+    let threshold = (name.chars().count() / 3).max(1);
+
+    let mut matches = candidates
+        .into_iter()
+        .filter_map(|c| {
+            let distance = util::levenshtein(&name, &c.to_lowercase());
+            (distance <= threshold).then_some((distance, c))
+        })
+        .collect::<Vec<_>>();
+    matches.sort_unstable();
+    matches.dedup();
+    matches
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, c)| c.to_owned())
+        .collect()
+}
+
+// Tells the user `name` isn't a command or Dvar, along with whichever
+// commands or Dvars they might have meant instead.
+fn unknown_command(name: &str) {
+    let commands = CMD_FUNCTIONS
+        .read()
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+    let dvars = dvar::names();
+    let suggestions = suggestions(
+        name,
+        commands.iter().chain(dvars.iter()).map(String::as_str),
+    );
+
+    if suggestions.is_empty() {
+        com::println!(
+            console::Channel::DONT_FILTER,
+            "unknown command '{}'",
+            name
+        );
+    } else {
+        com::println!(
+            console::Channel::DONT_FILTER,
+            "unknown command '{}' - did you mean {}?",
+            name,
+            suggestions
+                .iter()
+                .map(|s| format!("'{}'", s))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    static LAST_ARG: std::sync::Mutex<String> =
+        std::sync::Mutex::new(String::new());
+
+    fn record_f() {
+        CALLS.fetch_add(1, Ordering::SeqCst);
+        *LAST_ARG.lock().unwrap() = argv(1);
+    }
+
+    #[test]
+    fn execute_string_runs_command_with_arguments() {
+        let _guard = testing::fresh_state();
+        CALLS.store(0, Ordering::SeqCst);
+        add_command_internal("test_record", record_f).unwrap();
+
+        execute_string("test_record \"two words\" extra");
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+        assert_eq!(*LAST_ARG.lock().unwrap(), "two words");
+    }
+
+    #[test]
+    fn adding_a_command_twice_fails() {
+        let _guard = testing::fresh_state();
+        assert!(add_command_internal("test_record", record_f).is_some());
+        assert!(add_command_internal("test_record", record_f).is_none());
+    }
+
+    #[test]
+    fn execute_string_sets_dvars() {
+        let _guard = testing::fresh_state();
+        dvar::register_int(
+            "test_int",
+            0,
+            Some(0),
+            Some(10),
+            dvar::DvarFlags::empty(),
+            None,
+        )
+        .unwrap();
+
+        execute_string("test_int 4");
+        assert_eq!(dvar::get_int("test_int"), Some(4));
+    }
+
+    #[test]
+    fn developer_commands_need_developer() {
+        let _guard = testing::fresh_state();
+        CALLS.store(0, Ordering::SeqCst);
+        add_developer_command("test_record", record_f).unwrap();
+
+        execute_string("test_record");
+        assert_eq!(CALLS.load(Ordering::SeqCst), 0);
+    }
+
+    static DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+    fn recurse_f() {
+        DEPTH.fetch_add(1, Ordering::SeqCst);
+        execute_string("test_recurse");
+    }
+
+    #[test]
+    fn runaway_nesting_stops_instead_of_panicking() {
+        let _guard = testing::fresh_state();
+        DEPTH.store(0, Ordering::SeqCst);
+        add_command_internal("test_recurse", recurse_f).unwrap();
+
+        execute_string("test_recurse");
+        assert_eq!(DEPTH.load(Ordering::SeqCst), 8);
+        assert_eq!(argc(), 0);
+    }
+
+    #[test]
+    fn suggestions_are_close_matches_closest_first() {
+        let candidates = ["r_fullscreen", "r_fullscreenx", "com_maxfps"];
+        assert_eq!(
+            suggestions("r_fulscreen", candidates),
+            ["r_fullscreen", "r_fullscreenx"]
+        );
+        assert!(suggestions("zzz", candidates).is_empty());
+    }
+
+    #[test]
+    fn clear_commands_unregisters_everything() {
+        let _guard = testing::fresh_state();
+        add_command_internal("test_record", record_f).unwrap();
+
+        clear_commands();
+        assert!(exists("test_record") == false);
+    }
+}
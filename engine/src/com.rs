@@ -1,19 +1,28 @@
 #![allow(dead_code, clippy::pub_use)]
 
-use crate::{console::Channel, util::EasierAtomic, *};
+use crate::{
+    console::Channel,
+    util::{EasierAtomic, EasierAtomicBool},
+    *,
+};
 use arrayvec::ArrayVec;
 use core::{
-    sync::atomic::{AtomicU64, AtomicUsize},
+    sync::atomic::{AtomicIsize, AtomicU64, AtomicU8, AtomicUsize},
     time::Duration,
 };
 use lazy_static::lazy_static;
 use std::{
+    collections::HashSet,
     fs::File,
+    io::{BufWriter, Write},
     sync::{Mutex, RwLock},
 };
 extern crate alloc;
 use alloc::sync::Arc;
 
+pub mod rng;
+pub use rng::{rand_f32, rand_range, rand_seed};
+
 pub static ERROR_ENTERED: AtomicBool = AtomicBool::new(false);
 
 #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
@@ -100,14 +109,70 @@ lazy_static! {
     static ref PRINT_LOCK: Arc<Mutex<()>> = Arc::new(Mutex::new(()));
 }
 
-// Not sure what to call this, think of a better name later.
-#[doc(hidden)]
-pub enum MessageType {
-    Print,
+/// How severe a printed message is. Messages less severe than
+/// `com_logLevel` aren't printed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum LogLevel {
+    /// Used by [`com::trace!`] and [`com::traceln!`].
+    Trace,
+    /// Used by [`com::dprint!`] and [`com::dprintln!`].
+    Debug,
+    /// Used by [`com::print!`] and [`com::println!`].
+    Info,
+    /// Used by [`com::warn!`] and [`com::warnln!`].
     Warn,
+    /// Used by [`com::print_error!`] and [`com::print_errorln!`].
     Error,
 }
 
+impl LogLevel {
+    const ALL: [Self; 5] = [
+        Self::Trace,
+        Self::Debug,
+        Self::Info,
+        Self::Warn,
+        Self::Error,
+    ];
+
+    /// The name `com_logLevel` uses for this level.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Trace => "trace",
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|l| l.name() == name)
+    }
+}
+
+// Mirrors `com_logLevel`, since printing happens in places (e.g. while the
+// Dvars' lock is already held) where looking up the Dvar would deadlock.
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Debug as u8);
+
+/// Returns the least severe [`LogLevel`] that's currently printed.
+pub fn log_level() -> LogLevel {
+    LogLevel::ALL
+        .into_iter()
+        .find(|&l| l as u8 == LOG_LEVEL.load_relaxed())
+        .unwrap_or(LogLevel::Debug)
+}
+
+// Picks up any changes to `com_logLevel`.
+fn update_log_level() {
+    if let Some(level) = dvar::get_enumeration("com_logLevel")
+        .as_deref()
+        .and_then(LogLevel::from_name)
+    {
+        LOG_LEVEL.store_relaxed(level as u8);
+    }
+}
+
 // We put these in their own submodule so that, e.g., Rust Analyzer doesn't
 // see them. They still have to be public for their associated macros to work.
 
@@ -115,22 +180,31 @@ pub enum MessageType {
 pub mod _internals {
     use crate::util::EasierAtomic;
     use cfg_if::cfg_if;
+    use core::hash::{Hash, Hasher};
+    use std::collections::hash_map::DefaultHasher;
 
     #[doc(hidden)]
     #[allow(clippy::print_stdout, clippy::needless_pass_by_value)]
     pub fn _print(
         channel: super::Channel,
-        _message_type: super::MessageType,
+        level: super::LogLevel,
         arguments: core::fmt::Arguments,
     ) {
+        if level < super::log_level() {
+            return;
+        }
+
         std::print!("({:?}) - {}", channel, arguments);
+        let text = arguments.to_string();
+        super::write_to_log_file(&text);
+        crate::console::append_text(&text);
     }
 
     cfg_if! {
         if #[cfg(debug_assertions)] {
             #[doc(hidden)]
             pub fn _dprint(channel: super::Channel, arguments: core::fmt::Arguments) {
-                _print(channel, super::MessageType::Print, arguments);
+                _print(channel, super::LogLevel::Debug, arguments);
             }
         } else {
             pub fn _dprint(_channel: Channel, _arguments: core::fmt::Arguments) {
@@ -139,16 +213,39 @@ pub mod _internals {
         }
     }
 
+    #[doc(hidden)]
+    pub fn _trace(channel: super::Channel, arguments: core::fmt::Arguments) {
+        _print(channel, super::LogLevel::Trace, arguments);
+    }
+
     #[doc(hidden)]
     #[allow(clippy::needless_pass_by_value)]
     pub fn _warn(channel: super::Channel, arguments: core::fmt::Arguments) {
         _print(
             channel,
-            super::MessageType::Warn,
+            super::LogLevel::Warn,
             format_args!("^3{}", arguments),
         );
     }
 
+    #[doc(hidden)]
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn _warn_once(
+        channel: super::Channel,
+        arguments: core::fmt::Arguments,
+    ) {
+        let message = arguments.to_string();
+        let mut hasher = DefaultHasher::new();
+        message.hash(&mut hasher);
+        if super::WARN_ONCE_HASHES
+            .lock()
+            .unwrap()
+            .insert(hasher.finish())
+        {
+            _warn(channel, format_args!("{}", message));
+        }
+    }
+
     #[doc(hidden)]
     #[allow(clippy::needless_pass_by_value)]
     pub fn _print_error(
@@ -164,7 +261,7 @@ pub mod _internals {
         super::COM_ERROR_PRINTS_COUNT.increment_wrapping();
         _print(
             channel,
-            super::MessageType::Error,
+            super::LogLevel::Error,
             format_args!("{}{}", prefix, arguments),
         );
     }
@@ -207,7 +304,7 @@ pub mod _internals {
 #[macro_export]
 macro_rules! __com_print {
     ($channel:expr, $($arg:tt)*) => {{
-        $crate::com::_internals::_print($channel, $crate::com::MessageType::Print, core::format_args!($($arg)*));
+        $crate::com::_internals::_print($channel, $crate::com::LogLevel::Info, core::format_args!($($arg)*));
     }};
 }
 pub use __com_print as print;
@@ -290,6 +387,45 @@ macro_rules! __com_dprintln {
 }
 pub use __com_dprintln as dprintln;
 
+/// Prints text that's only useful when tracking down a problem, such as
+/// every event as it's handled.
+///
+/// Unlike [`com::dprint!`], it's compiled into release builds too, but it's
+/// only printed if `com_logLevel` is `trace`.
+///
+/// # Example
+///
+/// ```
+/// com::trace!("Hello to com at trace level!");
+/// ```
+macro_rules! __com_trace {
+    ($channel:expr, $($arg:tt)*) => {{
+        $crate::com::_internals::_trace($channel, core::format_args!($($arg)*));
+    }};
+}
+#[allow(unused_imports)]
+pub(crate) use __com_trace as trace;
+
+/// Prints text with a newline appended, if `com_logLevel` is `trace`.
+///
+/// Implemented simply as a wrapper around [`com::trace!`].
+///
+/// # Example
+///
+/// ```
+/// com::traceln!("Hello to com at trace level!");
+/// ```
+macro_rules! __com_traceln {
+    ($channel:expr) => {
+        $crate::com::trace!($channel, "\n")
+    };
+    ($channel:expr, $($arg:tt)*) => {{
+        $crate::com::trace!($channel, "{}\n", core::format_args!($($arg)*));
+    }};
+}
+#[allow(unused_imports)]
+pub(crate) use __com_traceln as traceln;
+
 /// Prints a warning.
 ///
 /// Implemented simply as a wrapper around [`com::print!`].
@@ -334,6 +470,61 @@ macro_rules! __com_warnln {
 }
 pub(crate) use __com_warnln as warnln;
 
+lazy_static! {
+    static ref WARN_ONCE_HASHES: Mutex<HashSet<u64>> =
+        Mutex::new(HashSet::new());
+}
+
+/// Prints a warning, unless the same warning has already been printed.
+///
+/// Warnings are deduplicated by a hash of the formatted message, so a
+/// warning that would otherwise fire every time (e.g., on every device
+/// reset) only shows up once per session, or once per call to
+/// [`reset_warn_once`].
+///
+/// # Example
+///
+/// ```
+/// com::warn_once!("Warning to com, but only once!");
+/// ```
+macro_rules! __com_warn_once {
+    ($channel:expr, $($arg:tt)*) => {{
+        $crate::com::_internals::_warn_once($channel, core::format_args!($($arg)*));
+    }};
+}
+#[allow(unused_imports)]
+pub(crate) use __com_warn_once as warn_once;
+
+/// Prints a warning with a newline appended, unless the same warning has
+/// already been printed.
+///
+/// Implemented simply as a wrapper around [`com::warn_once!`].
+///
+/// # Example
+///
+/// ```
+/// com::warnln_once!("Warning to com, but only once!");
+/// ```
+macro_rules! __com_warnln_once {
+    ($channel:expr) => {
+        $crate::com::warn_once!($channel, "\n")
+    };
+    ($channel:expr, $($arg:tt)*) => {{
+        $crate::com::warn_once!($channel, "{}\n", core::format_args!($($arg)*));
+    }};
+}
+#[allow(unused_imports)]
+pub(crate) use __com_warnln_once as warnln_once;
+
+/// Forgets every warning printed by [`com::warn_once!`], allowing each to
+/// be printed again.
+///
+/// Useful when the warnings are expected to be re-evaluated, e.g. after
+/// the renderer restarts.
+pub fn reset_warn_once() {
+    WARN_ONCE_HASHES.lock().unwrap().clear();
+}
+
 static COM_ERROR_PRINTS_COUNT: AtomicUsize = AtomicUsize::new(0);
 
 /// Prints an error.
@@ -381,13 +572,138 @@ macro_rules! __com_print_errorln {
 pub(crate) use __com_print_errorln as print_errorln;
 
 lazy_static! {
-    static ref LOG_FILE: Arc<RwLock<Option<File>>> =
-        Arc::new(RwLock::new(None));
+    static ref LOG_FILE: Mutex<Option<BufWriter<File>>> = Mutex::new(None);
+}
+
+/// Name of the log file in the user-data folder. Older logs kept by
+/// `con_logRotate` get `.1`, `.2`, etc. appended.
+const LOG_FILE_NAME: &str = "opent5.log";
+
+/// How often the log file is flushed, in milliseconds.
+const LOG_FLUSH_INTERVAL_MS: isize = 1000;
+static LAST_LOG_FLUSH: AtomicIsize = AtomicIsize::new(0);
+
+// A panic while the log file is held (e.g. on a fatal error) shouldn't stop
+// everything after it from being logged, least of all the panic message.
+fn log_file() -> std::sync::MutexGuard<'static, Option<BufWriter<File>>> {
+    LOG_FILE
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
 }
 
 // Check if log file is open
 pub fn log_file_open() -> bool {
-    return LOG_FILE.clone().read().unwrap().is_some();
+    log_file().is_some()
+}
+
+// Appends `text` to the log file, if it's open.
+fn write_to_log_file(text: &str) {
+    if let Some(f) = log_file().as_mut() {
+        // Nowhere to report a failure to, since printing would just end up
+        // back here
+        let _ = f.write_all(text.as_bytes());
+    }
+}
+
+/// Appends `text` to the log file and flushes it, but only if the log file
+/// isn't currently locked. For reporting panics, where the panicking thread
+/// might be the one holding the lock.
+pub fn try_write_to_log_file(text: &str) {
+    let mut f = match LOG_FILE.try_lock() {
+        Ok(f) => f,
+        Err(std::sync::TryLockError::Poisoned(e)) => e.into_inner(),
+        Err(std::sync::TryLockError::WouldBlock) => return,
+    };
+    if let Some(f) = f.as_mut() {
+        let _ = f.write_all(text.as_bytes());
+        let _ = f.flush();
+    }
+}
+
+/// Writes out anything buffered for the log file.
+pub fn flush_log_file() {
+    if let Some(f) = log_file().as_mut() {
+        let _ = f.flush();
+    }
+    LAST_LOG_FLUSH.store_relaxed(sys::milliseconds());
+}
+
+// Flushes the log file if it hasn't been for `LOG_FLUSH_INTERVAL_MS`.
+fn flush_log_file_periodically() {
+    if sys::milliseconds() - LAST_LOG_FLUSH.load_relaxed()
+        >= LOG_FLUSH_INTERVAL_MS
+    {
+        flush_log_file();
+    }
+}
+
+// Shifts `path` to `path.1`, `path.1` to `path.2`, and so on, dropping
+// whatever's past `.keep`. Errors are ignored, since a missing older log
+// is expected and nothing else here is worth refusing to start over.
+fn rotate_logs(path: &std::path::Path, keep: u32) {
+    let numbered = |n: u32| {
+        let mut p = path.as_os_str().to_owned();
+        p.push(format!(".{n}"));
+        std::path::PathBuf::from(p)
+    };
+
+    if keep == 0 {
+        return;
+    }
+
+    let _ = std::fs::remove_file(numbered(keep));
+    for n in (1..keep).rev() {
+        let _ = std::fs::rename(numbered(n), numbered(n + 1));
+    }
+    let _ = std::fs::rename(path, numbered(1));
+}
+
+// Creates the log file in `dir`, rotating any old logs there first.
+fn create_log_file(
+    dir: &std::path::Path,
+    keep: u32,
+) -> std::io::Result<(std::path::PathBuf, File)> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(LOG_FILE_NAME);
+    rotate_logs(&path, keep);
+    File::create(&path).map(|f| (path, f))
+}
+
+/// Opens the log file in the user-data folder (or the working directory,
+/// if the user-data folder isn't writable) if `con_logFile` is set.
+fn open_log_file() {
+    if !dvar::get_bool("con_logFile").unwrap_or(false) {
+        return;
+    }
+
+    let keep = dvar::get_int("con_logRotate")
+        .and_then(|n| u32::try_from(n).ok())
+        .unwrap_or(0);
+    let opened = fs::get_os_folder_path(fs::OsFolder::UserData)
+        .ok_or_else(|| std::io::ErrorKind::NotFound.into())
+        .and_then(|dir| create_log_file(&dir, keep))
+        .or_else(|_| create_log_file(&sys::cwd(), keep));
+
+    match opened {
+        Ok((path, f)) => {
+            *log_file() = Some(BufWriter::new(f));
+            LAST_LOG_FLUSH.store_relaxed(sys::milliseconds());
+            self::println!(Channel::SYSTEM, "Logging to {}", path.display());
+        }
+        Err(e) => {
+            self::warnln!(Channel::SYSTEM, "Couldn't open log file: {}", e);
+        }
+    }
+}
+
+/// Shuts down anything in `com` that needs it before the process exits,
+/// making sure everything logged so far reaches the log file.
+pub fn shutdown() {
+    #[cfg(windows)]
+    platform::os::target::remove_tray_icon();
+    render::shutdown();
+    flush_log_file();
+    *log_file() = None;
 }
 
 /// Throws an error. Not the same as [`com::print_error!`].
@@ -470,6 +786,44 @@ pub const fn get_build_version() -> &'static str {
     "7.0.61 CL(794515) CODPCAB-V6 Fri Nov 05 11:33:52 2010"
 }
 
+/// Returns the engine's version, along with the git commit it was built from
+/// (or "unknown" if it wasn't built from a git checkout). The version of the
+/// game itself is [`get_build_version`].
+pub const fn get_engine_version() -> &'static str {
+    concat!(
+        env!("CARGO_PKG_VERSION"),
+        " (",
+        env!("OPENT5_GIT_COMMIT"),
+        ")"
+    )
+}
+
+/// Returns the name the engine goes by, as opposed to the game it runs
+/// (see [`get_official_build_name_r`]).
+pub const fn get_engine_name() -> &'static str {
+    "OpenT5"
+}
+
+/// Returns the title for the game's main window: `com_windowTitle` if it's
+/// set, or the engine's name and version if it isn't.
+pub fn get_window_title() -> String {
+    dvar::get_string("com_windowTitle")
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| {
+            format!("{} {}", get_engine_name(), get_engine_version())
+        })
+}
+
+// Retitles the main window if `com_windowTitle` has changed since the last
+// call.
+fn update_window_title() {
+    if dvar::is_modified("com_windowTitle") == false {
+        return;
+    }
+    dvar::clear_modified("com_windowTitle").unwrap();
+    render::set_window_title(&get_window_title());
+}
+
 pub const fn get_build_name() -> &'static str {
     "COD_T5_R SP"
 }
@@ -571,6 +925,193 @@ pub fn init() {
 }
 
 fn init_dvars() {
+    dvar::register_int(
+        "developer",
+        0,
+        Some(0),
+        Some(2),
+        dvar::DvarFlags::empty(),
+        Some(
+            "Enable development systems (0 = off, 1 = on, 2 = on with extra \
+             diagnostics)",
+        ),
+    )
+    .unwrap();
+
+    dvar::register_bool(
+        "com_skipIntro",
+        false,
+        dvar::DvarFlags::ARCHIVE,
+        Some("Skip the intro and go straight to the main menu"),
+    )
+    .unwrap();
+
+    dvar::register_bool(
+        "com_introPlayed",
+        false,
+        dvar::DvarFlags::ARCHIVE,
+        Some("True if the intro has already been played"),
+    )
+    .unwrap();
+    startup_variable("com_introPlayed");
+
+    dvar::register_bool(
+        "com_allowMultipleInstances",
+        allow_multiple_instances(),
+        dvar::DvarFlags::WRITE_PROTECTED,
+        Some("Allow more than one copy of the game to run at once"),
+    )
+    .unwrap();
+
+    dvar::register_int(
+        "com_randSeed",
+        0,
+        Some(0),
+        Some(i32::MAX),
+        dvar::DvarFlags::empty(),
+        Some("Random number seed, or 0 to seed from the system time"),
+    )
+    .unwrap();
+    startup_variable("com_randSeed");
+    seed_rng();
+
+    dvar::register_bool(
+        "con_logFile",
+        true,
+        dvar::DvarFlags::ARCHIVE,
+        Some("Write console output to opent5.log"),
+    )
+    .unwrap();
+    startup_variable("con_logFile");
+    dvar::register_int(
+        "con_logRotate",
+        5,
+        Some(0),
+        Some(20),
+        dvar::DvarFlags::ARCHIVE,
+        Some("How many old log files to keep"),
+    )
+    .unwrap();
+    startup_variable("con_logRotate");
+    dvar::register_int(
+        "con_scrollback",
+        i32::try_from(console::SCROLLBACK_DEFAULT_KB).unwrap(),
+        i32::try_from(console::SCROLLBACK_MIN_KB).ok(),
+        i32::try_from(console::SCROLLBACK_MAX_KB).ok(),
+        dvar::DvarFlags::ARCHIVE,
+        Some("Size of the console's scrollback, in KB"),
+    )
+    .unwrap();
+    startup_variable("con_scrollback");
+    console::update_scrollback();
+
+    dvar::register_string(
+        "com_windowTitle",
+        "",
+        dvar::DvarFlags::ARCHIVE,
+        Some(
+            "Title of the game window. Uses the engine's name and version if \
+             empty",
+        ),
+    )
+    .unwrap();
+    startup_variable("com_windowTitle");
+    dvar::clear_modified("com_windowTitle").unwrap();
+
+    dvar::register_bool(
+        "com_showEvents",
+        false,
+        dvar::DvarFlags::empty(),
+        Some("Print each input event as it's queued and handled"),
+    )
+    .unwrap();
+    dvar::register_bool(
+        "com_showInputLatency",
+        false,
+        dvar::DvarFlags::empty(),
+        Some(
+            "Print how long each key, mouse button, and character event \
+             waited between being queued and being handled",
+        ),
+    )
+    .unwrap();
+
+    dvar::register_enumeration(
+        "com_logLevel",
+        LogLevel::Debug.name().to_owned(),
+        Some(LogLevel::ALL.iter().map(|l| l.name().to_owned()).collect()),
+        dvar::DvarFlags::ARCHIVE,
+        Some("Least severe kind of message to print"),
+    )
+    .unwrap();
+    update_log_level();
+
+    dvar::register_float(
+        "timescale",
+        1.0,
+        Some(0.0),
+        Some(1000.0),
+        dvar::DvarFlags::CHEAT_PROTECTED,
+        Some("Scale time by this amount. 1 = normal speed"),
+    )
+    .unwrap();
+
+    dvar::register_int(
+        "com_fixedFrameTime",
+        0,
+        Some(0),
+        Some(1000),
+        dvar::DvarFlags::CHEAT_PROTECTED,
+        Some(
+            "Advance game time by this many milliseconds every frame, \
+             regardless of how long the frame took. 0 = disabled",
+        ),
+    )
+    .unwrap();
+
+    dvar::register_int(
+        "com_maxFrameTime",
+        250,
+        Some(0),
+        Some(10_000),
+        dvar::DvarFlags::empty(),
+        Some(
+            "Longest a single frame can advance game time by, in \
+             milliseconds. 0 = unlimited",
+        ),
+    )
+    .unwrap();
+    dvar::register_bool(
+        "com_logHitches",
+        true,
+        dvar::DvarFlags::ARCHIVE,
+        Some(
+            "Log a warning whenever a frame takes longer than \
+             com_hitchThreshold",
+        ),
+    )
+    .unwrap();
+    dvar::register_int(
+        "com_hitchThreshold",
+        i32::try_from(DEFAULT_HITCH_THRESHOLD_MS).unwrap(),
+        Some(1),
+        Some(10_000),
+        dvar::DvarFlags::ARCHIVE,
+        Some(
+            "How long a frame can take before it's logged as a hitch, in \
+             milliseconds",
+        ),
+    )
+    .unwrap();
+
+    dvar::register_bool(
+        "com_confirmQuit",
+        true,
+        dvar::DvarFlags::ARCHIVE,
+        Some("Ask for confirmation before quitting while in a game"),
+    )
+    .unwrap();
+
     dvar::register_bool(
         "wideScreen",
         true,
@@ -605,6 +1146,124 @@ fn init_dvars() {
         Some("Allow multi-threading"),
     )
     .unwrap();
+
+    dvar::register_bool(
+        "sys_beepOnError",
+        true,
+        dvar::DvarFlags::ARCHIVE,
+        Some("Play the system alert sound when an error dialog is shown"),
+    )
+    .unwrap();
+}
+
+/// Prints everything that's generally useful to have in a bug report: the
+/// build, the system, the renderer, and the current video settings.
+fn systeminfo_f() {
+    fn dvar_or_unset<T: ToString>(value: Option<T>) -> String {
+        value.map_or_else(|| "<not registered>".to_owned(), |v| v.to_string())
+    }
+
+    self::println!(
+        console::Channel::DONT_FILTER,
+        "{} {} build {}-{} {}",
+        get_official_build_name_r(),
+        get_build_version(),
+        get_build_os(),
+        get_build_arch(),
+        get_build_date()
+    );
+    self::println!(
+        console::Channel::DONT_FILTER,
+        "{} {}",
+        get_engine_name(),
+        get_engine_version()
+    );
+    self::println!(console::Channel::DONT_FILTER, "{}", sys::find_info());
+    self::println!(
+        console::Channel::DONT_FILTER,
+        "Renderer: {} on {}",
+        render::backend_name(),
+        render::adapter_name()
+            .unwrap_or_else(|| "<no adapter selected>".to_owned())
+    );
+    self::println!(
+        console::Channel::DONT_FILTER,
+        "GPU memory: {}",
+        render::gpu_memory_mb()
+            .map_or_else(|| "unknown".to_owned(), |mb| format!("{} MB", mb))
+    );
+    {
+        let vid_config = vid::config();
+        self::println!(
+            console::Channel::DONT_FILTER,
+            "Display: {} x {} @ {} Hz ({})",
+            vid_config.display_width,
+            vid_config.display_height,
+            vid_config.display_frequency,
+            if vid_config.is_fullscreen {
+                "fullscreen"
+            } else {
+                "windowed"
+            }
+        );
+    }
+    self::println!(
+        console::Channel::DONT_FILTER,
+        "Input latency: {}",
+        input::latency_ms().map_or_else(
+            || "no input yet".to_owned(),
+            |ms| format!("{:.1} ms", ms)
+        )
+    );
+    self::println!(
+        console::Channel::DONT_FILTER,
+        "r_mode: {}\nr_fullscreen: {}\nr_aaSamples: {}\nr_vsync: {}",
+        dvar_or_unset(dvar::get_enumeration("r_mode")),
+        dvar_or_unset(dvar::get_bool("r_fullscreen")),
+        dvar_or_unset(dvar::get_int("r_aaSamples")),
+        dvar_or_unset(dvar::get_bool("r_vsync"))
+    );
+}
+
+fn add_commands() {
+    cmd::add_command_internal("systeminfo", systeminfo_f).unwrap();
+    cmd::add_command_internal("exec", exec_f).unwrap();
+    cmd::add_command_internal("writeconfig", write_config_f).unwrap();
+    cmd::add_command_internal("restoreDefaults", restore_defaults_f).unwrap();
+}
+
+lazy_static! {
+    static ref INIT_PROGRESS_CALLBACK: RwLock<Option<fn(&str, f32)>> =
+        RwLock::new(None);
+}
+
+/// Sets the function called as [`init`] reaches each of its major
+/// milestones, with a description of the stage being started and roughly
+/// how far through initialization that is (from 0 to 1). Meant for a
+/// splash screen or the console window to show progress with.
+pub fn set_init_progress_callback(callback: fn(stage: &str, fraction: f32)) {
+    *INIT_PROGRESS_CALLBACK.write().unwrap() = Some(callback);
+}
+
+/// Stops reporting [`init`]'s progress.
+pub fn clear_init_progress_callback() {
+    *INIT_PROGRESS_CALLBACK.write().unwrap() = None;
+}
+
+/// Reports that initialization has reached `stage`, `fraction` of the way
+/// through, to the callback set with [`set_init_progress_callback`].
+///
+/// Does nothing if there's no callback, or once initialization is over (so
+/// the subsystems that call this don't have to care whether they're being
+/// initialized for the first time or restarted).
+pub fn report_init_progress(stage: &str, fraction: f32) {
+    let Some(callback) = *INIT_PROGRESS_CALLBACK.read().unwrap() else {
+        return;
+    };
+
+    if phase() == Phase::Init {
+        callback(stage, fraction.clamp(0.0, 1.0));
+    }
 }
 
 fn init_try_block_function() {
@@ -617,9 +1276,41 @@ fn init_try_block_function() {
         console::Channel::SYSTEM,
         "{build_version} {build_name} build {os}-{arch} {build_date}"
     );
+    self::println!(
+        console::Channel::SYSTEM,
+        "{} {}",
+        get_engine_name(),
+        get_engine_version()
+    );
+    self::println!(
+        console::Channel::SYSTEM,
+        "Running on {}",
+        platform::os_version()
+    );
+    report_init_progress("Registering variables", 0.0);
     init_dvars();
+    open_log_file();
+    add_commands();
+    #[cfg(native)]
+    pmem::register();
+    cbuf::add_commands();
+    input::record::add_commands();
+    key::init();
+    locale::register();
+    report_init_progress("Starting the filesystem", 0.2);
     fs::init_filesystem(true);
     cl::init_once_for_all_clients();
+    render::register();
+    // Before anything starts using the Dvars. Any that are registered later
+    // pick up what the configs set them to when they are.
+    report_init_progress("Loading configs", 0.3);
+    load_configs();
+    // Nothing has allocated from pmem yet, so a com_hunkMegs set by the
+    // configs can still be applied
+    #[cfg(native)]
+    pmem::reinit();
+    net::init();
+    report_init_progress("Starting the renderer", 0.4);
     render::init_threads();
     cl::init_renderer();
     render::begin_remote_screen_update();
@@ -628,6 +1319,17 @@ fn init_try_block_function() {
         console::Channel::SYSTEM,
         "--- Common Initialization Complete ---"
     );
+    report_init_progress("Done", 1.0);
+
+    let next = if intro_should_play() {
+        Phase::Intro
+    } else {
+        Phase::Menu
+    };
+    // Fails if the engine's already past Init (e.g. when this runs again
+    // after a restart), in which case it should stay where it is. set_phase
+    // logs it.
+    let _ = set_phase(next);
 }
 
 #[allow(clippy::todo)]
@@ -635,14 +1337,219 @@ pub fn touch_memory() {
     todo!("com::touch_memory");
 }
 
-// TODO - implement
-pub const fn get_icon_rgba() -> Option<Vec<u8>> {
-    None
+// The game's icon, as rows of RGBA pixels from top to bottom
+const ICON_RGBA: &[u8] = include_bytes!("../../assets/icon.rgba");
+const ICON_SIZE: u32 = 32;
+
+/// Returns the game's icon as rows of RGBA pixels from top to bottom,
+/// along with its width and height.
+pub const fn get_icon_rgba() -> (&'static [u8], u32, u32) {
+    (ICON_RGBA, ICON_SIZE, ICON_SIZE)
+}
+
+/// Returns the value given to Dvar `name` with `+set` on the command line,
+/// if any.
+///
+/// Useful for Dvars that need to be read before the command line has been
+/// processed. If `name` is `+set` more than once, the last one wins.
+pub fn startup_variable_value(name: &str) -> Option<String> {
+    let args = std::env::args().collect::<Vec<_>>();
+    args.windows(3)
+        .rev()
+        .find(|w| w[0] == "+set" && w[1].eq_ignore_ascii_case(name))
+        .map(|w| w[2].clone())
 }
 
-// TODO - implement
+/// Applies the value given to Dvar `name` with `+set` on the command line,
+/// if any, ahead of the rest of the command line being processed.
 pub fn startup_variable(name: &str) {
-    com::println!(console::Channel::SYSTEM, "com::startup_variable: {}", name);
+    if let Some(value) = startup_variable_value(name) {
+        let _ = dvar::set_from_string_from_source(
+            name,
+            &value,
+            dvar::SetSource::External,
+        );
+    }
+}
+
+/// Run before any other config, so that key Dvars start from known-good
+/// values no matter what's been done to the user's config.
+const DEFAULT_CFG: &str = include_str!("../../assets/default.cfg");
+
+const CONFIG_FILE_NAME: &str = "config.cfg";
+const AUTOEXEC_FILE_NAME: &str = "autoexec.cfg";
+
+/// Runs each command in the config `text` right away, splitting it up the
+/// same way the command buffer does (see [`cbuf::split_commands`]).
+pub fn exec_config_text(text: &str) {
+    cbuf::split_commands(text)
+        .into_iter()
+        .for_each(cmd::execute_string);
+}
+
+/// Returns where the config called `name` lives: the user-data folder, or
+/// the working directory if there isn't one.
+fn config_path(name: &str) -> std::path::PathBuf {
+    fs::get_os_folder_path(fs::OsFolder::UserData)
+        .unwrap_or_else(sys::cwd)
+        .join(name)
+}
+
+/// Reads the config called `name` (see [`config_path`]), returning [`None`]
+/// if it couldn't be read.
+fn read_config_file(name: &str) -> Option<String> {
+    let path = config_path(name);
+    match std::fs::read_to_string(&path) {
+        Ok(text) => {
+            self::println!(Channel::SYSTEM, "execing {}", name);
+            Some(text)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            self::warnln!(
+                Channel::SYSTEM,
+                "Couldn't read {}: {}",
+                path.display(),
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Runs the config called `name` (see [`config_path`]), returning `false`
+/// if it couldn't be read.
+pub fn exec_config_file(name: &str) -> bool {
+    read_config_file(name)
+        .map(|text| exec_config_text(&text))
+        .is_some()
+}
+
+/// Returns every `+set` on the command line, as `set` commands.
+fn command_line_sets() -> Vec<String> {
+    set_commands(&std::env::args().collect::<Vec<_>>())
+}
+
+/// Turns every `+set <name> <value>` in `args` into a `set` command.
+///
+/// Quoted arguments can't contain a `"` (there's no way to escape one), so
+/// a `+set` with one in its name or value is skipped with a warning, rather
+/// than being run as something other than what was asked for.
+fn set_commands(args: &[String]) -> Vec<String> {
+    args.windows(3)
+        .filter(|w| w[0] == "+set")
+        .filter_map(|w| {
+            if w[1].contains('"') || w[2].contains('"') {
+                self::warnln!(
+                    Channel::SYSTEM,
+                    "WARNING: ignoring +set {} {}, since it contains a '\"'",
+                    w[1],
+                    w[2]
+                );
+                return None;
+            }
+            Some(format!("set \"{}\" \"{}\"", w[1], w[2]))
+        })
+        .collect()
+}
+
+/// Applies each layer of config in turn, each one overriding the ones
+/// before it: the built-in default.cfg, `config` (the user's config.cfg),
+/// `autoexec` (their autoexec.cfg), and finally `sets`, the `+set`s on the
+/// command line.
+fn apply_configs(
+    config: Option<&str>,
+    autoexec: Option<&str>,
+    sets: &[String],
+) {
+    exec_config_text(DEFAULT_CFG);
+    if let Some(config) = config {
+        exec_config_text(config);
+    }
+    dvar::set_loading_autoexec(true);
+    if let Some(autoexec) = autoexec {
+        exec_config_text(autoexec);
+    }
+    dvar::set_loading_autoexec(false);
+    sets.iter()
+        .map(String::as_str)
+        .for_each(cmd::execute_string);
+}
+
+/// Reads the user's configs and applies them (see [`apply_configs`]).
+fn load_configs() {
+    let config = read_config_file(CONFIG_FILE_NAME);
+    let autoexec = read_config_file(AUTOEXEC_FILE_NAME);
+    apply_configs(config.as_deref(), autoexec.as_deref(), &command_line_sets());
+}
+
+fn exec_f() {
+    if cmd::argc() != 2 {
+        self::println!(Channel::DONT_FILTER, "USAGE: exec <filename>");
+        return;
+    }
+
+    let name = cmd::argv(1);
+    if exec_config_file(&name) == false {
+        self::println!(Channel::DONT_FILTER, "couldn't exec {}", name);
+    }
+}
+
+/// Saves every archived Dvar (see [`dvar::archived_config`]) to the config
+/// called `name` (see [`config_path`]), so that they're restored the next
+/// time it's run.
+pub fn write_config_file(name: &str) -> Result<(), fs::WriteFileError> {
+    let path = config_path(name);
+    let r = fs::write_file_atomic(&path, dvar::archived_config().as_bytes());
+    if let Err(ref e) = r {
+        self::warnln!(
+            Channel::SYSTEM,
+            "Couldn't write {}: {}",
+            path.display(),
+            e
+        );
+    }
+    r
+}
+
+fn write_config_f() {
+    let name = match cmd::argc() {
+        1 => CONFIG_FILE_NAME.to_owned(),
+        2 => cmd::argv(1),
+        _ => {
+            self::println!(
+                Channel::DONT_FILTER,
+                "USAGE: writeconfig [filename]"
+            );
+            return;
+        }
+    };
+
+    if write_config_file(&name).is_ok() {
+        self::println!(Channel::DONT_FILTER, "Wrote {}", name);
+    }
+}
+
+fn restore_defaults_f() {
+    exec_config_text(DEFAULT_CFG);
+    self::println!(Channel::DONT_FILTER, "Restored default settings.");
+}
+
+/// Returns `true` if more than one copy of the game is allowed to run at
+/// once, either through `+set com_allowMultipleInstances 1` or the legacy
+/// `allowdupe` switch as the first argument.
+///
+/// Has to be read before the command line has been processed, since the
+/// single-instance check in [`sys::check_crash_or_rerun`] runs before the
+/// Dvar system is even initialized.
+pub fn allow_multiple_instances() -> bool {
+    std::env::args().nth(1).as_deref() == Some("allowdupe")
+        || matches!(
+            startup_variable_value("com_allowMultipleInstances")
+                .as_deref()
+                .map(str::trim),
+            Some("1" | "true")
+        )
 }
 
 lazy_static! {
@@ -657,10 +1564,184 @@ pub fn force_safe_mode() {
     SAFE_MODE.store(true, Ordering::SeqCst);
 }
 
-static FRAME_TIME: AtomicU64 = AtomicU64::new(0);
+/// Seeds the random number generator from `com_randSeed` if it's set, so
+/// that runs can be reproduced, or from the system time otherwise.
+fn seed_rng() {
+    let seed = dvar::get_int("com_randSeed")
+        .and_then(|s| u64::try_from(s).ok())
+        .filter(|&s| s != 0)
+        .unwrap_or_else(rng::time_seed);
+    rand_seed(seed);
+}
+
+/// Returns the value of the `developer` Dvar, or `0` if it hasn't been
+/// registered yet.
+pub fn developer_level() -> i32 {
+    dvar::get_int("developer").unwrap_or(0)
+}
+
+/// Returns `true` if the `developer` Dvar is set to any nonzero level.
+pub fn is_developer() -> bool {
+    developer_level() != 0
+}
+
+static FRAME_COUNT: AtomicU64 = AtomicU64::new(0);
+// All in microseconds, from `sys::micros`
+static FRAME_TIME_US: AtomicU64 = AtomicU64::new(0);
+static FRAME_DELTA_US: AtomicU64 = AtomicU64::new(0);
+static SIM_FRAME_DELTA_US: AtomicU64 = AtomicU64::new(0);
+
+/// Returns how many frames have started since the engine started.
+pub fn frame_count() -> u64 {
+    FRAME_COUNT.load_relaxed()
+}
 
+/// Returns when the current frame started, relative to [`sys::micros`]'s
+/// time base.
 pub fn frame_time() -> Duration {
-    Duration::from_millis(FRAME_TIME.load_relaxed())
+    Duration::from_micros(FRAME_TIME_US.load_relaxed())
+}
+
+/// Returns when the current frame started, in milliseconds (see
+/// [`frame_time`]).
+pub fn frame_time_ms() -> u64 {
+    FRAME_TIME_US.load_relaxed() / 1000
+}
+
+/// Returns how much wall-clock time passed between the start of the previous
+/// frame and this one, in milliseconds.
+///
+/// This is the real, unclamped time, for FPS counters and profiling. Game
+/// logic should use [`sim_frame_delta_ms`] instead.
+pub fn frame_delta_ms() -> u64 {
+    FRAME_DELTA_US.load_relaxed() / 1000
+}
+
+/// Like [`frame_delta_ms`], but in microseconds.
+pub fn frame_delta_us() -> u64 {
+    FRAME_DELTA_US.load_relaxed()
+}
+
+/// Returns how much time the simulation should step by this frame, in
+/// milliseconds, before `timescale` and `com_fixedFrameTime` are applied.
+///
+/// This is [`frame_delta_ms`] capped to `com_maxFrameTime`, so that a long
+/// hitch (loading, alt-tabbing, a breakpoint) doesn't leave the simulation
+/// with a huge step to catch up on, which could take long enough to cause
+/// another hitch, and so on. The catch is that the game appears to pause
+/// during a long stall instead of fast-forwarding through it afterwards.
+pub fn sim_frame_delta_ms() -> u64 {
+    SIM_FRAME_DELTA_US.load_relaxed() / 1000
+}
+
+/// Caps a frame's `real` delta to `max` (in the same units), treating a
+/// `max` of 0 as no cap. See [`sim_frame_delta_ms`].
+pub const fn clamp_frame_delta(real: u64, max: u64) -> u64 {
+    if max == 0 || real <= max {
+        real
+    } else {
+        max
+    }
+}
+
+// Kept in microseconds so that fractional timescales don't lose time to
+// rounding every frame.
+static GAME_TIME_US: AtomicU64 = AtomicU64::new(0);
+
+/// Returns how much game time has passed, in milliseconds.
+///
+/// Unlike [`sys::milliseconds`], game time is scaled by `timescale`, and
+/// advances by exactly `com_fixedFrameTime` each frame when that's set, so
+/// that runs can be reproduced regardless of how fast the machine is.
+pub fn game_time_ms() -> u64 {
+    GAME_TIME_US.load_relaxed() / 1000
+}
+
+/// Returns how much game time a frame that took `real_us` microseconds of
+/// wall-clock time should advance by, in microseconds.
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn game_frame_delta_us(real_us: u64) -> u64 {
+    let fixed = dvar::get_int("com_fixedFrameTime").unwrap_or(0);
+    let us = if fixed > 0 {
+        fixed as u64 * 1000
+    } else {
+        real_us
+    };
+    let timescale = dvar::get_float("timescale").unwrap_or(1.0).max(0.0);
+    (us as f64 * f64::from(timescale)).round() as u64
+}
+
+/// `com_hitchThreshold`'s default, in milliseconds.
+const DEFAULT_HITCH_THRESHOLD_MS: u64 = 100;
+
+/// Times each part of a frame, so that [`check_for_hitch`] can say where a
+/// slow one spent its time.
+struct FrameSections {
+    start_us: u64,
+    last_us: u64,
+    sections: Vec<(&'static str, u64)>,
+}
+
+impl FrameSections {
+    fn new() -> Self {
+        let now = sys::micros();
+        Self {
+            start_us: now,
+            last_us: now,
+            sections: Vec::new(),
+        }
+    }
+
+    /// Records that the part of the frame called `name` just finished.
+    fn end(&mut self, name: &'static str) {
+        let now = sys::micros();
+        self.sections.push((name, now.saturating_sub(self.last_us)));
+        self.last_us = now;
+    }
+
+    fn total_us(&self) -> u64 {
+        self.last_us.saturating_sub(self.start_us)
+    }
+}
+
+/// Whether a frame that took `frame_us` microseconds counts as a hitch when
+/// `com_hitchThreshold` is `threshold_ms`.
+pub const fn is_hitch(frame_us: u64, threshold_ms: u64) -> bool {
+    frame_us > threshold_ms.saturating_mul(1000)
+}
+
+/// Logs a warning with a breakdown of frame number `frame` if it took
+/// longer than `com_hitchThreshold`, and `com_logHitches` is set.
+fn check_for_hitch(frame: u64, sections: &FrameSections) {
+    if dvar::get_bool("com_logHitches").unwrap_or(false) == false {
+        return;
+    }
+
+    let threshold_ms = dvar::get_int("com_hitchThreshold")
+        .and_then(|ms| u64::try_from(ms).ok())
+        .unwrap_or(DEFAULT_HITCH_THRESHOLD_MS);
+    let total_us = sections.total_us();
+    if is_hitch(total_us, threshold_ms) == false {
+        return;
+    }
+
+    let breakdown = sections
+        .sections
+        .iter()
+        .map(|(name, us)| format!("{} {} ms", name, us / 1000))
+        .collect::<Vec<_>>()
+        .join(", ");
+    self::warnln!(
+        console::Channel::SYSTEM,
+        "WARNING: hitch on frame {}: {} ms ({})",
+        frame,
+        total_us / 1000,
+        breakdown
+    );
 }
 
 pub fn quit_f() -> ! {
@@ -669,5 +1750,488 @@ pub fn quit_f() -> ! {
     sys::quit();
 }
 
-#[allow(clippy::missing_const_for_fn)]
-pub fn frame() {}
+static QUIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Asks for the game to quit, e.g. because the user closed the window.
+///
+/// Can be called from any thread. The request is handled by the main thread
+/// during the next [`frame`], which may ask the user to confirm it first
+/// (see [`quit_confirmed`]).
+pub fn request_quit() {
+    QUIT_REQUESTED.store_relaxed(true);
+}
+
+/// Returns whether a requested quit should go ahead.
+///
+/// If the local client is in a game and `com_confirmQuit` is set, the user
+/// is asked first, so that an accidental Alt-F4 doesn't throw away their
+/// session. Otherwise the quit always goes ahead.
+///
+/// Has to be called on the main thread, since some message box
+/// implementations can't be shown from anywhere else.
+fn quit_confirmed() -> bool {
+    assert!(sys::is_main_thread());
+
+    if dvar::get_bool("com_confirmQuit").unwrap_or(false) == false
+        || cl::local_client_is_in_game(0) == false
+    {
+        return true;
+    }
+
+    matches!(
+        sys::message_box(
+            platform::get_window_handle(),
+            "Quit",
+            "Are you sure you want to quit? Your progress in the current \
+             game will be lost.",
+            sys::MessageBoxType::YesNo,
+            Some(sys::MessageBoxIcon::Information),
+        ),
+        Some(sys::MessageBoxResult::Yes)
+    )
+}
+
+fn handle_quit_request() {
+    if QUIT_REQUESTED.swap(false, Ordering::Relaxed) && quit_confirmed() {
+        quit_f();
+    }
+}
+
+/// The phases the engine goes through, in order, from startup onwards.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Subsystems are still being initialized.
+    Init,
+    /// The intro is playing.
+    Intro,
+    /// The main menu is up.
+    Menu,
+    /// A game is in progress.
+    Game,
+}
+
+impl Phase {
+    // Init -> Intro -> Menu <-> Game, with Init -> Menu when the intro is
+    // skipped.
+    const fn can_transition_to(self, next: Self) -> bool {
+        matches!(
+            (self, next),
+            (Self::Init, Self::Intro | Self::Menu)
+                | (Self::Intro | Self::Game, Self::Menu)
+                | (Self::Menu, Self::Game)
+        )
+    }
+}
+
+static PHASE: RwLock<Phase> = RwLock::new(Phase::Init);
+
+/// Returns the phase the engine is currently in.
+pub fn phase() -> Phase {
+    *PHASE.read().unwrap()
+}
+
+/// Transitions the engine to `next`.
+///
+/// Returns [`Err`] (and leaves the phase unchanged, logging a warning) if
+/// the current phase can't transition to `next`.
+pub fn set_phase(next: Phase) -> Result<(), ()> {
+    let mut phase = PHASE.write().unwrap();
+    if !phase.can_transition_to(next) {
+        self::warnln!(
+            console::Channel::SYSTEM,
+            "com::set_phase: invalid transition from {:?} to {:?}",
+            *phase,
+            next,
+        );
+        return Err(());
+    }
+
+    *phase = next;
+    Ok(())
+}
+
+fn intro_should_play() -> bool {
+    dvar::get_bool("com_skipIntro").unwrap() == false
+        && dvar::get_bool("com_introPlayed").unwrap() == false
+}
+
+// TODO - actually play the intro cinematic once there's something to play
+// it with. Until then, the intro phase ends immediately.
+fn intro_frame() {
+    dvar::set_bool_internal("com_introPlayed", true).unwrap();
+    let _ = set_phase(Phase::Menu);
+}
+
+// Follows the local client into and out of games.
+fn update_game_phase() {
+    let in_game = cl::local_client_is_in_game(0);
+    match phase() {
+        Phase::Menu if in_game => {
+            if set_phase(Phase::Game).is_ok() {
+                // A new map gets its own set of one-off warnings
+                reset_warn_once();
+            }
+        }
+        Phase::Game if in_game == false => {
+            let _ = set_phase(Phase::Menu);
+        }
+        _ => {}
+    }
+}
+
+/// Upper bound on the number of window events processed in a single frame.
+///
+/// Without this, a flood of events (e.g. a mouse with a very high polling
+/// rate) could keep [`frame`] draining the queue forever and starve the
+/// rest of the frame.
+const MAX_WINDOW_EVENTS_PER_FRAME: usize = 256;
+
+/// Drains pending events from the main window and dispatches them.
+///
+/// All queued events are processed in one go (up to
+/// [`MAX_WINDOW_EVENTS_PER_FRAME`]), so that bursts of input aren't spread
+/// out over several frames.
+fn pump_window_events() {
+    for _ in 0..MAX_WINDOW_EVENTS_PER_FRAME {
+        let Some(ev) = sys::next_main_window_event() else {
+            break;
+        };
+        self::traceln!(Channel::SYSTEM, "window event: {:?}", ev);
+        sys::handle_main_window_event(ev);
+    }
+}
+
+// Hands the input events queued up by the window event handlers off to
+// whichever subsystem they belong to.
+fn process_events() {
+    let now = sys::milliseconds();
+    while let Some(ev) = sys::next_event() {
+        sys::show_event("drain", &ev);
+        input::record_latency(&ev, now);
+        match *ev.event_type() {
+            sys::EventType::Key(key, down) => key::handle_key_event(key, down),
+            sys::EventType::Mouse(button, down) => {
+                key::handle_mouse_event(button, down);
+            }
+            sys::EventType::Character(c) => key::handle_char_event(c),
+            sys::EventType::Console(ref text) => cbuf::add_textln(0, text),
+            _ => {}
+        }
+    }
+}
+
+pub fn frame() {
+    let first_frame = FRAME_COUNT.load_relaxed() == 0;
+    FRAME_COUNT.increment_wrapping();
+    let now = sys::micros();
+    let last = FRAME_TIME_US.load_relaxed();
+    FRAME_TIME_US.store_relaxed(now);
+    // There's no previous frame to measure the first one from. micros() is
+    // monotonic, but saturate anyway rather than trust that blindly.
+    let real_us = if first_frame {
+        0
+    } else {
+        now.saturating_sub(last)
+    };
+    let max_us = dvar::get_int("com_maxFrameTime")
+        .and_then(|ms| u64::try_from(ms).ok())
+        .unwrap_or(0)
+        * 1000;
+    let sim_us = clamp_frame_delta(real_us, max_us);
+    FRAME_DELTA_US.store_relaxed(real_us);
+    SIM_FRAME_DELTA_US.store_relaxed(sim_us);
+    GAME_TIME_US.fetch_add(game_frame_delta_us(sim_us), Ordering::Relaxed);
+
+    let mut sections = FrameSections::new();
+    update_log_level();
+    console::update_scrollback();
+    pump_window_events();
+    input::record::frame();
+    process_events();
+    cl::mouse_move(0);
+    sections.end("events");
+    net::frame();
+    sections.end("net");
+    cbuf::execute(0);
+    sections.end("commands");
+    update_window_title();
+    render::submit_frame();
+    sections.end("render");
+    locale::frame();
+    flush_log_file_periodically();
+    handle_quit_request();
+    sections.end("misc");
+
+    if phase() == Phase::Intro {
+        intro_frame();
+        sections.end("intro");
+    }
+    update_game_phase();
+
+    check_for_hitch(FRAME_COUNT.load_relaxed(), &sections);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn register_test_int(name: &str, flags: dvar::DvarFlags) {
+        dvar::register_int(name, 0, Some(0), Some(20), flags, None).unwrap();
+    }
+
+    #[test]
+    fn later_config_layers_override_earlier_ones() {
+        let _guard = testing::fresh_state();
+        // default.cfg sets this to 5
+        register_test_int("con_logRotate", dvar::DvarFlags::ARCHIVE);
+        register_test_int("test_a", dvar::DvarFlags::empty());
+        register_test_int("test_b", dvar::DvarFlags::empty());
+        register_test_int("test_c", dvar::DvarFlags::empty());
+
+        apply_configs(
+            Some(
+                "set con_logRotate 6; set test_a 1; set test_b 1\nset test_c 1",
+            ),
+            Some("set test_b 2; set test_c 2"),
+            &["set \"test_c\" \"3\"".to_owned()],
+        );
+        assert_eq!(dvar::get_int("con_logRotate"), Some(6));
+        assert_eq!(dvar::get_int("test_a"), Some(1));
+        assert_eq!(dvar::get_int("test_b"), Some(2));
+        assert_eq!(dvar::get_int("test_c"), Some(3));
+    }
+
+    #[test]
+    fn command_line_sets_skip_values_with_quotes() {
+        let _guard = testing::fresh_state();
+        let args = [
+            "opent5",
+            "+set",
+            "test_a",
+            "two words",
+            "+set",
+            "test_b",
+            "\" test_c \"1",
+        ]
+        .map(ToOwned::to_owned);
+        assert_eq!(set_commands(&args), ["set \"test_a\" \"two words\""]);
+    }
+
+    #[test]
+    fn default_cfg_applies_without_user_configs() {
+        let _guard = testing::fresh_state();
+        register_test_int("con_logRotate", dvar::DvarFlags::ARCHIVE);
+
+        apply_configs(None, None, &[]);
+        assert_eq!(dvar::get_int("con_logRotate"), Some(5));
+    }
+
+    #[test]
+    fn configs_apply_to_dvars_registered_after_them() {
+        let _guard = testing::fresh_state();
+        apply_configs(Some("set test_late 7"), None, &[]);
+
+        register_test_int("test_late", dvar::DvarFlags::LATCHED);
+        assert_eq!(dvar::get_int("test_late"), Some(7));
+        assert!(dvar::has_pending_latch("test_late") == false);
+    }
+
+    fn register_frame_time_dvars() {
+        dvar::register_float(
+            "timescale",
+            1.0,
+            Some(0.0),
+            Some(1000.0),
+            dvar::DvarFlags::CHEAT_PROTECTED,
+            None,
+        )
+        .unwrap();
+        dvar::register_int(
+            "com_fixedFrameTime",
+            0,
+            Some(0),
+            Some(1000),
+            dvar::DvarFlags::CHEAT_PROTECTED,
+            None,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn game_frame_delta_follows_real_time_by_default() {
+        let _guard = testing::fresh_state();
+        // Without the Dvars, game time is just real time
+        assert_eq!(game_frame_delta_us(16_667), 16_667);
+
+        register_frame_time_dvars();
+        assert_eq!(game_frame_delta_us(16_667), 16_667);
+        assert_eq!(game_frame_delta_us(0), 0);
+    }
+
+    #[test]
+    fn fixed_frame_time_replaces_real_time() {
+        let _guard = testing::fresh_state();
+        register_frame_time_dvars();
+        dvar::set_int_internal("com_fixedFrameTime", 50).unwrap();
+
+        assert_eq!(game_frame_delta_us(1), 50_000);
+        assert_eq!(game_frame_delta_us(1_000_000), 50_000);
+
+        // And is scaled like real time would be
+        dvar::set_float_internal("timescale", 0.5).unwrap();
+        assert_eq!(game_frame_delta_us(1), 25_000);
+    }
+
+    #[test]
+    fn timescale_scales_real_time() {
+        let _guard = testing::fresh_state();
+        register_frame_time_dvars();
+
+        dvar::set_float_internal("timescale", 2.0).unwrap();
+        assert_eq!(game_frame_delta_us(10_000), 20_000);
+        dvar::set_float_internal("timescale", 0.25).unwrap();
+        assert_eq!(game_frame_delta_us(10_000), 2_500);
+        dvar::set_float_internal("timescale", 0.0).unwrap();
+        assert_eq!(game_frame_delta_us(10_000), 0);
+    }
+
+    #[test]
+    fn long_hitches_are_clamped() {
+        let _guard = testing::fresh_state();
+        register_frame_time_dvars();
+        let max_us = 250 * 1000;
+
+        // An hour-long stall only advances the simulation by the cap
+        let hitch_us = 60 * 60 * 1_000_000;
+        let sim_us = clamp_frame_delta(hitch_us, max_us);
+        assert_eq!(sim_us, max_us);
+        assert_eq!(game_frame_delta_us(sim_us), max_us);
+        dvar::set_float_internal("timescale", 2.0).unwrap();
+        assert_eq!(game_frame_delta_us(sim_us), 2 * max_us);
+
+        // Ordinary frames are left alone, as is everything with no cap
+        assert_eq!(clamp_frame_delta(16_667, max_us), 16_667);
+        assert_eq!(clamp_frame_delta(max_us, max_us), max_us);
+        assert_eq!(clamp_frame_delta(u64::MAX, 0), u64::MAX);
+    }
+
+    #[test]
+    fn rand_seed_dvar_seeds_the_rng() {
+        let _guard = testing::fresh_state();
+        register_test_int("com_randSeed", dvar::DvarFlags::empty());
+        dvar::set_int_internal("com_randSeed", 7).unwrap();
+
+        seed_rng();
+        let mut expected = rng::Rng::new(7);
+        for _ in 0..10 {
+            assert_eq!(rand_f32(), expected.next_f32());
+        }
+    }
+
+    // Counts how many lines of the console's scrollback contain `text`
+    fn console_count(text: &str) -> usize {
+        console::get_text_copy(console::scrollback_bytes())
+            .lines()
+            .filter(|l| l.contains(text))
+            .count()
+    }
+
+    #[test]
+    fn long_frame_logs_one_hitch() {
+        let _guard = testing::fresh_state();
+        dvar::register_bool(
+            "com_logHitches",
+            true,
+            dvar::DvarFlags::empty(),
+            None,
+        )
+        .unwrap();
+        dvar::register_int(
+            "com_hitchThreshold",
+            100,
+            Some(1),
+            Some(10_000),
+            dvar::DvarFlags::empty(),
+            None,
+        )
+        .unwrap();
+        // Inject the timings rather than actually stalling
+        let frame_of = |sections: Vec<(&'static str, u64)>| FrameSections {
+            start_us: 0,
+            last_us: sections.iter().map(|&(_, us)| us).sum(),
+            sections,
+        };
+        let hitches =
+            |frame: u64| console_count(&format!("hitch on frame {}:", frame));
+
+        let slow = frame_of(vec![("events", 20_000), ("render", 230_000)]);
+        check_for_hitch(9_100_001, &slow);
+        assert_eq!(hitches(9_100_001), 1);
+        assert_eq!(console_count("hitch on frame 9100001: 250 ms"), 1);
+        assert_eq!(console_count("events 20 ms, render 230 ms"), 1);
+
+        let fast = frame_of(vec![("events", 5_000), ("render", 11_000)]);
+        check_for_hitch(9_100_002, &fast);
+        assert_eq!(hitches(9_100_002), 0);
+        // Right at the threshold isn't a hitch yet
+        check_for_hitch(9_100_003, &frame_of(vec![("render", 100_000)]));
+        assert_eq!(hitches(9_100_003), 0);
+
+        dvar::set_bool_internal("com_logHitches", false).unwrap();
+        check_for_hitch(9_100_004, &slow);
+        assert_eq!(hitches(9_100_004), 0);
+    }
+
+    #[test]
+    fn log_level_filters_less_severe_messages() {
+        let _guard = testing::fresh_state();
+        let old_level = LOG_LEVEL.load_relaxed();
+        let print_all = |tag: &str| {
+            traceln!(Channel::SYSTEM, "test_log_level {} trace", tag);
+            super::println!(Channel::SYSTEM, "test_log_level {} info", tag);
+            warnln!(Channel::SYSTEM, "test_log_level {} warn", tag);
+            print_errorln!(Channel::SYSTEM, "test_log_level {} error", tag);
+        };
+
+        LOG_LEVEL.store_relaxed(LogLevel::Trace as u8);
+        print_all("all");
+        LOG_LEVEL.store_relaxed(LogLevel::Warn as u8);
+        print_all("some");
+        LOG_LEVEL.store_relaxed(old_level);
+
+        assert_eq!(console_count("test_log_level all"), 4);
+        assert_eq!(console_count("test_log_level all trace"), 1);
+        assert_eq!(console_count("test_log_level some"), 2);
+        assert_eq!(console_count("test_log_level some trace"), 0);
+        assert_eq!(console_count("test_log_level some info"), 0);
+        assert_eq!(console_count("test_log_level some warn"), 1);
+        assert_eq!(console_count("test_log_level some error"), 1);
+    }
+
+    #[test]
+    fn warn_once_prints_again_after_reset() {
+        let _guard = testing::fresh_state();
+        let message = "test_warn_once_prints_again_after_reset";
+        warnln_once!(Channel::SYSTEM, "{}", message);
+        warnln_once!(Channel::SYSTEM, "{}", message);
+        assert_eq!(console_count(message), 1);
+
+        reset_warn_once();
+        warnln_once!(Channel::SYSTEM, "{}", message);
+        warnln_once!(Channel::SYSTEM, "{}", message);
+        assert_eq!(console_count(message), 2);
+    }
+
+    #[test]
+    fn exec_config_text_keeps_quoted_separators() {
+        let _guard = testing::fresh_state();
+        dvar::register_string("test_str", "", dvar::DvarFlags::empty(), None)
+            .unwrap();
+
+        exec_config_text(
+            "set test_str \"a;b // c\" // comment; set test_str x",
+        );
+        assert_eq!(dvar::get_string("test_str").as_deref(), Some("a;b // c"));
+    }
+}
@@ -0,0 +1,123 @@
+// A small, seedable PRNG for anything in the engine that needs random
+// numbers. It's deliberately not cryptographically secure - the point is
+// that a given seed always produces the same sequence, so that runs can be
+// reproduced (e.g. for timedemos) by setting `com_randSeed`.
+
+use core::ops::Range;
+use lazy_static::lazy_static;
+use std::{
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A SplitMix64 generator.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Creates a generator whose sequence is fully determined by `seed`.
+    pub const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Returns the next 64 random bits.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a float uniformly distributed in `[0.0, 1.0)`.
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    pub fn next_f32(&mut self) -> f32 {
+        // The top 24 bits fit exactly in an f32's mantissa.
+        (self.next_u64() >> 40) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Returns a float uniformly distributed in `[range.start, range.end)`.
+    pub fn next_range(&mut self, range: Range<f32>) -> f32 {
+        range.start + (range.end - range.start) * self.next_f32()
+    }
+}
+
+lazy_static! {
+    static ref RNG: Mutex<Rng> = Mutex::new(Rng::new(time_seed()));
+}
+
+/// Returns a seed derived from the current system time, for when runs don't
+/// need to be reproducible.
+#[allow(clippy::cast_possible_truncation)]
+pub fn time_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+/// Reseeds the engine's random number generator.
+pub fn rand_seed(seed: u64) {
+    *RNG.lock().unwrap() = Rng::new(seed);
+}
+
+/// Returns a random float in `[0.0, 1.0)`.
+pub fn rand_f32() -> f32 {
+    RNG.lock().unwrap().next_f32()
+}
+
+/// Returns a random float in `[min, max)`.
+pub fn rand_range(min: f32, max: f32) -> f32 {
+    RNG.lock().unwrap().next_range(min..max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn fixed_seed_produces_fixed_sequence() {
+        // The reference SplitMix64 outputs for a seed of zero
+        let mut rng = Rng::new(0);
+        assert_eq!(rng.next_u64(), 0xE220_A839_7B1D_CDAF);
+        assert_eq!(rng.next_u64(), 0x6E78_9E6A_A1B9_65F4);
+        assert_eq!(rng.next_u64(), 0x06C4_5D18_8009_454F);
+    }
+
+    #[test]
+    fn same_seed_agrees() {
+        let mut a = Rng::new(0x1234_5678);
+        let mut b = Rng::new(0x1234_5678);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert_ne!(Rng::new(1).next_u64(), Rng::new(2).next_u64());
+    }
+
+    #[test]
+    fn floats_stay_in_range() {
+        let mut rng = Rng::new(42);
+        for _ in 0..1000 {
+            let f = rng.next_f32();
+            assert!((0.0..1.0).contains(&f));
+            let r = rng.next_range(-5.0..5.0);
+            assert!((-5.0..5.0).contains(&r));
+        }
+    }
+
+    #[test]
+    fn reseeding_repeats_the_global_sequence() {
+        // The generator is shared, so keep other tests from using it
+        let _guard = testing::fresh_state();
+        rand_seed(99);
+        let first = [rand_f32(), rand_range(10.0, 20.0), rand_f32()];
+        rand_seed(99);
+        let second = [rand_f32(), rand_range(10.0, 20.0), rand_f32()];
+        assert_eq!(first, second);
+
+        let mut rng = Rng::new(99);
+        assert_eq!(first.first().copied(), Some(rng.next_f32()));
+    }
+}
@@ -26,7 +26,8 @@ cfg_if! {
         use windows::Win32::{
             UI::{
                 WindowsAndMessaging::{SendMessageA, WNDPROC}, Controls::{
-                    EM_SETSEL, EM_LINESCROLL, EM_SCROLLCARET, EM_REPLACESEL
+                    EM_SETSEL, EM_LINESCROLL, EM_SCROLLCARET, EM_REPLACESEL,
+                    EM_LINEFROMCHAR, EM_LINEINDEX, EM_SETLIMITTEXT
                 }
             },
             Foundation::{WPARAM, LPARAM, HWND}
@@ -143,7 +144,11 @@ pub fn s_wcd_mut() -> RwLockWriteGuard<'static, ConsoleData> {
 }
 
 #[cfg(windows)]
-#[allow(clippy::undocumented_unsafe_blocks, clippy::unnecessary_safety_comment)]
+#[allow(
+    clippy::undocumented_unsafe_blocks,
+    clippy::unnecessary_safety_comment,
+    clippy::as_conversions
+)]
 pub fn append_text(text: impl ToString) {
     let text = &text.to_string();
     let clean_text = clean_text(text);
@@ -158,20 +163,54 @@ pub fn append_text(text: impl ToString) {
     // SendMessageA itself might be able to create unsafe behavior
     // with certain messages, but the ones we're passing here
     // are safe.
-    if TEXT_APPENDED.load(Ordering::Relaxed) > 0x4000 {
-        unsafe {
-            SendMessageA(HWND(hwnd as _), EM_SETSEL, WPARAM(0), LPARAM(-1));
-        }
-        TEXT_APPENDED.store(clean_len, Ordering::Relaxed);
-    } else {
+    let limit = console::scrollback_bytes();
+    let appended = TEXT_APPENDED.load(Ordering::Relaxed);
+    if appended > limit {
+        // Drop the oldest whole lines, up to and including the one that
+        // the overflow ends in
+        let overflow = appended - limit;
+        let start = unsafe {
+            let line = SendMessageA(
+                HWND(hwnd as _),
+                EM_LINEFROMCHAR,
+                WPARAM(overflow),
+                LPARAM(0),
+            );
+            SendMessageA(
+                HWND(hwnd as _),
+                EM_LINEINDEX,
+                WPARAM(line.0 as usize + 1),
+                LPARAM(0),
+            )
+        };
+        // EM_LINEINDEX gives -1 if there's no such line, in which case
+        // everything that's already there goes
+        let existing = appended - clean_len;
+        let start = usize::try_from(start.0).unwrap_or(existing).min(existing);
         unsafe {
             SendMessageA(
                 HWND(hwnd as _),
                 EM_SETSEL,
-                WPARAM(0xFFFF),
-                LPARAM(0xFFFF),
+                WPARAM(0),
+                LPARAM(start as isize),
+            );
+            SendMessageA(
+                HWND(hwnd as _),
+                EM_REPLACESEL,
+                WPARAM(0),
+                LPARAM(b"\0".as_ptr() as isize),
             );
         }
+        TEXT_APPENDED.store(appended - start, Ordering::Relaxed);
+    }
+
+    unsafe {
+        SendMessageA(
+            HWND(hwnd as _),
+            EM_SETSEL,
+            WPARAM(0xFFFF),
+            LPARAM(0xFFFF),
+        );
     }
 
     unsafe {
@@ -190,6 +229,25 @@ pub fn append_text(text: impl ToString) {
     }
 }
 
+/// Limits the console window's buffer to `bytes` of text, so that it holds
+/// as much as the console's scrollback does.
+#[cfg(windows)]
+#[allow(clippy::undocumented_unsafe_blocks, clippy::as_conversions)]
+pub fn set_text_limit(bytes: usize) {
+    let Some(buffer_window) = s_wcd().buffer_window else {
+        return;
+    };
+    let hwnd = buffer_window.get_win32().unwrap().hwnd;
+    unsafe {
+        SendMessageA(
+            HWND(hwnd as _),
+            EM_SETLIMITTEXT,
+            WPARAM(bytes),
+            LPARAM(0),
+        );
+    }
+}
+
 #[cfg(not(windows))]
 pub fn append_text(text: &str) {
     com::println!(console::Channel::DONT_FILTER, "conbuf: {}", text);
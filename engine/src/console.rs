@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 
+use core::sync::atomic::AtomicUsize;
 use std::{sync::RwLock, time::Duration};
 extern crate alloc;
 use alloc::sync::Arc;
@@ -8,7 +9,12 @@ use arrayvec::{ArrayString, ArrayVec};
 use bitflags::bitflags;
 use lazy_static::lazy_static;
 
-use crate::common::{Vec2f32, Vec4f32};
+use crate::{
+    common::{Vec2f32, Vec4f32},
+    sys::KeyboardScancode,
+    util::EasierAtomic,
+    *,
+};
 
 #[derive(Clone, Debug)]
 pub struct PrintChannel {
@@ -222,7 +228,7 @@ pub struct Console {
     console_window: MessageWindow,
     console_lines: ArrayVec<MessageLine, 1024>,
     console_messages: ArrayVec<Message, 1024>,
-    console_text: ArrayString<32768>,
+    console_text: String,
     text_temp_line: ArrayString<512>,
     line_offset: usize,
     display_line_offset: usize,
@@ -235,38 +241,328 @@ pub struct Console {
     screen_max: Vec2f32,
     message_buffer: MessageBuffer,
     color: Vec4f32,
+    input_line: String,
 }
 
 lazy_static! {
     static ref CON: RwLock<Console> = RwLock::new(Console::default());
 }
 
-pub fn get_text_copy(_len: usize) -> String {
-    String::new()
-    // let con = CON.read().unwrap();
+/// Smallest and largest values (in KB) `con_scrollback` allows.
+pub const SCROLLBACK_MIN_KB: usize = 4;
+pub const SCROLLBACK_MAX_KB: usize = 1024;
+pub const SCROLLBACK_DEFAULT_KB: usize = 32;
 
-    // if con.console_window.active_line_count == 0 {
-    //     return String::new();
-    // }
+// The scrollback's capacity in bytes, as last applied by
+// `update_scrollback`, i.e. `con_scrollback` after clamping.
+static SCROLLBACK_BYTES: AtomicUsize =
+    AtomicUsize::new(SCROLLBACK_DEFAULT_KB * 1024);
 
-    // let mut line_pos =
-    // con.console_window.lines.get(con.console_window.first_line_index).
-    // unwrap().text_buf_pos; let mut end = con.console_window.text_buf_pos
-    // - line_pos; if end < 0 { end =
-    //   con.console_window.circular_text_buffer.len();
-    // }
+/// Returns the size of the console's scrollback in bytes.
+pub fn scrollback_bytes() -> usize {
+    SCROLLBACK_BYTES.load_relaxed()
+}
+
+/// Picks up any changes to `con_scrollback`, dropping the oldest lines of
+/// the scrollback if it's shrunk.
+pub fn update_scrollback() {
+    if dvar::is_modified("con_scrollback") == false {
+        return;
+    }
+    dvar::clear_modified("con_scrollback").unwrap();
+
+    let bytes = dvar::get_int("con_scrollback")
+        .and_then(|kb| usize::try_from(kb).ok())
+        .unwrap_or(SCROLLBACK_DEFAULT_KB)
+        .clamp(SCROLLBACK_MIN_KB, SCROLLBACK_MAX_KB)
+        * 1024;
+    SCROLLBACK_BYTES.store_relaxed(bytes);
+
+    let mut con = CON.write().unwrap();
+    let text = core::mem::take(&mut con.console_text);
+    append_to_scrollback(&mut con.console_text, &text, bytes);
+    drop(con);
+
+    #[cfg(windows)]
+    conbuf::set_text_limit(bytes);
+}
+
+/// Appends `text` to `scrollback`, then drops as many of the oldest lines as
+/// it takes to bring it back down to at most `capacity` bytes.
+///
+/// Only whole lines are ever dropped, so what's left always starts at the
+/// beginning of a line.
+#[allow(clippy::indexing_slicing)]
+pub fn append_to_scrollback(
+    scrollback: &mut String,
+    text: &str,
+    capacity: usize,
+) {
+    scrollback.push_str(text);
+    if scrollback.len() <= capacity {
+        return;
+    }
+
+    // The first line that starts at or after `overflow` is the oldest one
+    // that can be kept
+    let overflow = scrollback.len() - capacity;
+    let start = scrollback.as_bytes()[overflow - 1..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(scrollback.len(), |i| overflow + i);
+    scrollback.drain(..start);
+}
+
+/// Appends `text` to the console's scrollback, discarding the oldest lines
+/// if there isn't room for it.
+pub fn append_text(text: &str) {
+    let mut con = CON.write().unwrap();
+    append_to_scrollback(&mut con.console_text, text, scrollback_bytes());
+}
+
+/// Whether the console is open (i.e., whether it has the key catcher).
+pub fn is_open() -> bool {
+    key::get_catcher().contains(key::CatcherFlags::CONSOLE)
+}
+
+/// Opens the console if it's closed, or closes it if it's open.
+pub fn toggle() {
+    set_open(is_open() == false);
+}
+
+/// Opens or closes the console.
+///
+/// While it's open, it has the key catcher, so typed keys go to its input
+/// line. On Windows, the separate console window is shown for it.
+/// Elsewhere, the terminal the game was started from (where the console's
+/// output is printed anyway) stands in for that window, with the input line
+/// shown as a prompt below the output.
+pub fn set_open(open: bool) {
+    let catchers = key::get_catcher();
+    key::set_catcher(if open {
+        catchers | key::CatcherFlags::CONSOLE
+    } else {
+        catchers - key::CatcherFlags::CONSOLE
+    });
+    if open == false {
+        // Whatever was being typed is abandoned
+        CON.write().unwrap().input_line.clear();
+    }
+
+    #[cfg(windows)]
+    if open {
+        sys::show_console();
+    } else {
+        sys::destroy_console();
+    }
+    #[cfg(not(windows))]
+    draw_prompt();
+}
+
+// Redraws the input line on the terminal's last line, or just erases it if
+// the console's been closed.
+#[cfg(not(windows))]
+#[allow(clippy::print_stdout)]
+fn draw_prompt() {
+    use std::io::Write;
+
+    erase_prompt();
+    if is_open() {
+        std::print!("]{}", CON.read().unwrap().input_line);
+    }
+    let _ = std::io::stdout().flush();
+}
+
+// Erases the prompt `draw_prompt` drew, with a carriage return and "erase
+// line", so that whatever's printed next starts at the beginning of the
+// line.
+#[cfg(not(windows))]
+#[allow(clippy::print_stdout)]
+fn erase_prompt() {
+    std::print!("\r\x1b[K");
+}
+
+// The console window draws its own input line.
+#[cfg(windows)]
+const fn draw_prompt() {}
+
+#[cfg(windows)]
+const fn erase_prompt() {}
+
+/// Returns (at most) the last `count` lines of the console's scrollback,
+/// oldest first, or [`None`] if the console is locked for writing.
+///
+/// Never blocks or panics, for use from the panic hook.
+pub fn try_recent_lines(count: usize) -> Option<Vec<String>> {
+    let con = match CON.try_read() {
+        Ok(con) => con,
+        Err(std::sync::TryLockError::Poisoned(e)) => e.into_inner(),
+        Err(std::sync::TryLockError::WouldBlock) => return None,
+    };
+    let mut lines = con
+        .console_text
+        .lines()
+        .rev()
+        .take(count)
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+    lines.reverse();
+    Some(lines)
+}
+
+/// Handles a key press while the console has the key catcher.
+pub fn key_event(key: KeyboardScancode) {
+    match key {
+        KeyboardScancode::Enter | KeyboardScancode::NumEnter => {
+            let line = core::mem::take(&mut CON.write().unwrap().input_line);
+            if line.is_empty() == false {
+                erase_prompt();
+                com::println!(Channel::DONT_FILTER, "]{}", line);
+                cbuf::add_textln(0, &line);
+            }
+        }
+        KeyboardScancode::Backspace => {
+            CON.write().unwrap().input_line.pop();
+        }
+        KeyboardScancode::V
+            if sys::modifiers()
+                .intersects(sys::Modifiers::LCTRL | sys::Modifiers::RCTRL) =>
+        {
+            paste();
+        }
+        _ => return,
+    }
+    draw_prompt();
+}
+
+/// Appends the first line of the clipboard's text to the input line.
+fn paste() {
+    let Some(text) = platform::clipboard_get() else {
+        return;
+    };
+
+    let line = text.lines().next().unwrap_or_default();
+    CON.write()
+        .unwrap()
+        .input_line
+        .extend(line.chars().filter(|c| c.is_control() == false));
+}
 
-    // if len - 1 < end {
-    //     line_pos = end - len - 1 + line_pos;
-    //     if con.console_window.circular_text_buffer.len() < line_pos {
-    //         line_pos -= con.console_window.circular_text_buffer.len();
-    //     }
-    //     end = len - 1;
-    // }
+/// Handles a typed character while the console has the key catcher.
+pub fn char_event(c: char) {
+    // The console toggle key still generates a character when it's
+    // pressed, which shouldn't end up in the input line.
+    if c.is_control() || c == '`' || c == '~' {
+        return;
+    }
 
-    // if line_pos < con.console_window.text_buf_pos {
-    //     con.console_window.circular_text_buffer[line_pos..con.console_window.
-    // text_buf_pos] } else {
+    CON.write().unwrap().input_line.push(c);
+    draw_prompt();
+}
+
+/// Returns the most recent whole lines of the console's scrollback that fit
+/// in `len` bytes.
+pub fn get_text_copy(len: usize) -> String {
+    let con = CON.read().unwrap();
+    let mut text = String::new();
+    append_to_scrollback(&mut text, &con.console_text, len);
+    text
+}
 
-    // }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn scrollback_keeps_text_that_fits() {
+        let mut scrollback = String::new();
+        append_to_scrollback(&mut scrollback, "one\n", 16);
+        append_to_scrollback(&mut scrollback, "two\n", 16);
+        assert_eq!(scrollback, "one\ntwo\n");
+    }
+
+    #[test]
+    fn scrollback_drops_oldest_whole_lines() {
+        let mut scrollback = String::new();
+        for i in 0..100 {
+            append_to_scrollback(&mut scrollback, &format!("line {}\n", i), 64);
+            assert!(scrollback.len() <= 64);
+            // Never starts partway through a line
+            assert!(scrollback.starts_with("line "));
+        }
+        assert!(scrollback.ends_with("line 99\n"));
+        assert!(scrollback.contains("line 98\n"));
+        assert!(scrollback.contains("line 90\n") == false);
+    }
+
+    #[test]
+    fn scrollback_keeps_a_line_ending_right_at_the_overflow() {
+        let mut scrollback = String::from("aaa\nbbb\n");
+        // Overflows by exactly the first line
+        append_to_scrollback(&mut scrollback, "ccc\n", 8);
+        assert_eq!(scrollback, "bbb\nccc\n");
+    }
+
+    #[test]
+    fn scrollback_drops_a_line_too_long_to_keep() {
+        let mut scrollback = String::from("short\n");
+        append_to_scrollback(&mut scrollback, &"x".repeat(32), 16);
+        assert_eq!(scrollback, "");
+    }
+
+    #[test]
+    fn toggling_the_console_takes_the_key_catcher() {
+        let _guard = testing::fresh_state();
+        set_open(false);
+
+        toggle();
+        assert!(is_open());
+        assert!(key::get_catcher().contains(key::CatcherFlags::CONSOLE));
+        char_event('x');
+        assert_eq!(CON.read().unwrap().input_line, "x");
+
+        toggle();
+        assert!(is_open() == false);
+        assert_eq!(key::get_catcher(), key::CatcherFlags::empty());
+        // Closing it throws away the unfinished input line
+        assert_eq!(CON.read().unwrap().input_line, "");
+    }
+
+    #[test]
+    fn con_scrollback_resizes_the_scrollback() {
+        let _guard = testing::fresh_state();
+        dvar::register_int(
+            "con_scrollback",
+            i32::try_from(SCROLLBACK_DEFAULT_KB).unwrap(),
+            i32::try_from(SCROLLBACK_MIN_KB).ok(),
+            i32::try_from(SCROLLBACK_MAX_KB).ok(),
+            dvar::DvarFlags::empty(),
+            None,
+        )
+        .unwrap();
+
+        dvar::set_int_internal(
+            "con_scrollback",
+            i32::try_from(SCROLLBACK_MIN_KB).unwrap(),
+        )
+        .unwrap();
+        update_scrollback();
+        assert_eq!(scrollback_bytes(), SCROLLBACK_MIN_KB * 1024);
+        for i in 0..1000 {
+            append_text(&format!("con_scrollback test {}\n", i));
+        }
+        let text = get_text_copy(usize::MAX);
+        assert!(text.len() <= SCROLLBACK_MIN_KB * 1024);
+        assert!(text.contains("con_scrollback test 999\n"));
+        assert!(text.contains("con_scrollback test 0\n") == false);
+
+        dvar::set_int_internal(
+            "con_scrollback",
+            i32::try_from(SCROLLBACK_DEFAULT_KB).unwrap(),
+        )
+        .unwrap();
+        update_scrollback();
+        assert_eq!(scrollback_bytes(), SCROLLBACK_DEFAULT_KB * 1024);
+    }
 }
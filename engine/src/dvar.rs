@@ -14,6 +14,7 @@ pub mod global_fns;
 pub use global_fns::*;
 
 mod cmds;
+pub use cmds::{command, set_loading_autoexec};
 
 /// This file contains all of code related to the Dvar subsystem, including
 /// the [`Dvar`] itself, functions to get, set, and create Dvars, and
@@ -69,7 +70,11 @@ bitflags! {
         /// Flag with unknown purpose. Never(?) manually set by a function,
         /// but some Dvars are registered with it set by default
         const UNKNOWN_00000100_D    = 0x00000100;
-        /// Flag denoting if Dvar's reset value may be changed
+        /// Flag denoting if Dvar's value depends on something that can
+        /// change at runtime (e.g. the display modes the monitor supports).
+        /// Such Dvars are reset to their default by
+        /// dvar::reset_changeable() whenever that happens, since their old
+        /// value may no longer be valid
         const CHANGEABLE_RESET      = 0x00000200;
         /// Flag with unknown purpose. Never(?) manually set by a function,
         /// but some Dvars are registered with it set by default
@@ -224,7 +229,13 @@ impl Dvar {
             }
             DvarValue::String(_) => value.clone(),
             DvarValue::Enumeration(_) => value.clone(),
-            DvarValue::Color(_) => value.clone(),
+            // Colors are stored with each component in [0, 255]
+            DvarValue::Color(c) => DvarValue::Color((
+                c.0.clamp(0.0, 255.0),
+                c.1.clamp(0.0, 255.0),
+                c.2.clamp(0.0, 255.0),
+                c.3.clamp(0.0, 255.0),
+            )),
             DvarValue::Int64(i) => {
                 let min: i64 = domain.as_int64_limits().unwrap().min;
                 let max: i64 = domain.as_int64_limits().unwrap().max;
@@ -291,12 +302,26 @@ impl Dvar {
         self.current != self.latched
     }
 
+    /// Returns whether a set from `set_source` may change the value to
+    /// `value`, printing why not if it can't. Only sets from the user
+    /// ([`SetSource::External`] and [`SetSource::Script`]) are checked;
+    /// the engine's own sets always go through.
+    ///
+    /// `cheats_enabled` is the value of `sv_cheats`, read by the caller
+    /// before it took the lock on [`DVARS`] (see [`cheats_enabled`]).
     #[allow(clippy::needless_pass_by_value)]
     fn can_change_value(
         &self,
         value: &DvarValue,
         set_source: SetSource,
+        cheats_enabled: bool,
     ) -> bool {
+        if matches!(set_source, SetSource::External | SetSource::Script)
+            == false
+        {
+            return true;
+        }
+
         if *value == self.reset {
             return true;
         }
@@ -313,28 +338,22 @@ impl Dvar {
         if self.flags.contains(DvarFlags::WRITE_PROTECTED) {
             com::println!(
                 console::Channel::ERROR,
-                "{} is write protected protected.",
+                "{} is write protected.",
                 self.name,
             );
             return false;
         }
 
-        if self.flags.contains(DvarFlags::CHEAT_PROTECTED)
-            && (find("sv_cheats").unwrap().current.as_bool().unwrap() == false)
-        {
-            true
-        } else {
-            if (set_source == SetSource::External)
-                || (set_source == SetSource::Script)
-            {
-                com::println!(
-                    console::Channel::ERROR,
-                    "{} is cheat protected.",
-                    self.name,
-                );
-            }
-            false
+        if self.flags.contains(DvarFlags::CHEAT_PROTECTED) && !cheats_enabled {
+            com::println!(
+                console::Channel::ERROR,
+                "{} is cheat protected.",
+                self.name,
+            );
+            return false;
         }
+
+        true
     }
 
     // Overwrite latched value with current
@@ -344,9 +363,14 @@ impl Dvar {
         }
     }
 
-    fn set_value(&mut self, value: DvarValue, source: SetSource) {
+    fn set_value(
+        &mut self,
+        value: DvarValue,
+        source: SetSource,
+        cheats_enabled: bool,
+    ) {
         if source == SetSource::External || source == SetSource::Script {
-            if self.can_change_value(&value, source) == false {
+            if self.can_change_value(&value, source, cheats_enabled) == false {
                 return;
             }
             if self.flags.contains(DvarFlags::LATCHED) {
@@ -451,9 +475,18 @@ impl Dvar {
         }
     }
 
-    fn set_variant(&mut self, value: DvarValue, source: SetSource) {
+    // Returns Err if the value was refused (because it's out of the domain,
+    // or the Dvar is read-only, write-protected, or cheat-protected), Ok if
+    // it was either set or latched. `cheats_enabled` is only consulted for
+    // sets from the user, see can_change_value
+    fn set_variant(
+        &mut self,
+        value: DvarValue,
+        source: SetSource,
+        cheats_enabled: bool,
+    ) -> Result<(), ()> {
         if self.name.is_empty() {
-            return;
+            return Err(());
         }
 
         if com::log_file_open() && self.current != value {
@@ -474,15 +507,20 @@ impl Dvar {
             );
             com::println!(console::Channel::ERROR, "{}", self.domain);
             if let DvarValue::Enumeration(_) = value {
-                self.set_variant(self.reset.clone(), source);
+                let _ = self.set_variant(
+                    self.reset.clone(),
+                    source,
+                    cheats_enabled,
+                );
             }
-            return;
+            return Err(());
         }
 
         if source == SetSource::External || source == SetSource::Script {
-            if self.can_change_value(&value, source)
-                && self.flags.contains(DvarFlags::LATCHED)
-            {
+            if self.can_change_value(&value, source, cheats_enabled) == false {
+                return Err(());
+            }
+            if self.flags.contains(DvarFlags::LATCHED) {
                 self.latched = value;
                 if self.latched != self.current {
                     com::println!(
@@ -491,13 +529,13 @@ impl Dvar {
                         self.name,
                     );
                 }
+                return Ok(());
             }
-            return;
         } else if source == SetSource::Devgui
             && self.flags.contains(DvarFlags::ALLOW_SET_FROM_DEVGUI)
         {
             self.latched = value;
-            return;
+            return Ok(());
         }
 
         if self.current != value {
@@ -511,28 +549,39 @@ impl Dvar {
         } else {
             self.latched = self.current.clone();
         }
+        Ok(())
     }
 
     pub fn make_latched_value_current(&mut self) {
-        self.set_variant(self.latched.clone(), SetSource::Internal);
+        let _ =
+            self.set_variant(self.latched.clone(), SetSource::Internal, false);
     }
 
-    fn reset(&mut self, source: SetSource) {
-        self.set_variant(self.reset.clone(), source);
+    fn reset(&mut self, source: SetSource, cheats_enabled: bool) {
+        let _ = self.set_variant(self.reset.clone(), source, cheats_enabled);
     }
 }
 
-lazy_static! {
-    static ref IS_DVAR_SYSTEM_ACTIVE: AtomicBool = AtomicBool::new(false);
+/// Returns whether `sv_cheats` is enabled, or [`false`] if it hasn't been
+/// registered.
+///
+/// Has to be called before taking the write lock on [`DVARS`], since it
+/// takes the read lock itself.
+fn cheats_enabled() -> bool {
+    find("sv_cheats")
+        .and_then(|d| d.current.as_bool())
+        .unwrap_or(false)
 }
 
+static INITIALIZED: util::InitFlag = util::InitFlag::new("dvar::init");
+
 /// Initializes the Dvar subsystem
 ///
 /// Shouldn't ever be called more than once, but doing so
 /// also shouldn't corrupt anything.
 pub fn init() {
-    if IS_DVAR_SYSTEM_ACTIVE.load(Ordering::SeqCst) == false {
-        IS_DVAR_SYSTEM_ACTIVE.store(true, Ordering::SeqCst);
+    if INITIALIZED.is_set() == false {
+        INITIALIZED.set();
         register_bool(
             "sv_restoreDvars",
             true,
@@ -550,3 +599,510 @@ pub fn init() {
         self::cmds::add_commands();
     }
 }
+
+/// Unregisters every [`Dvar`] and returns the subsystem to the state it was
+/// in before [`init`] was called.
+///
+/// Lets code that exercises the Dvar system on its own (e.g. tests) start
+/// from a clean registry without bringing up the rest of the engine.
+pub fn reset_registry() {
+    global_fns::DVARS.write().unwrap().clear();
+    global_fns::clear_aliases();
+    global_fns::clear_pending();
+    global_fns::clear_change_callbacks();
+    *MODIFIED_FLAGS.write().unwrap() = DvarFlags::empty();
+    INITIALIZED.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn registered_dvar_can_be_got() {
+        let _guard = testing::fresh_state();
+        register_int(
+            "test_int",
+            5,
+            Some(0),
+            Some(10),
+            DvarFlags::empty(),
+            None,
+        )
+        .unwrap();
+
+        assert!(exists("test_int"));
+        assert_eq!(get_int("test_int"), Some(5));
+        assert_eq!(get_bool("test_int"), None);
+        assert_eq!(get_int("test_missing"), None);
+    }
+
+    #[test]
+    fn set_updates_value_and_marks_modified() {
+        let _guard = testing::fresh_state();
+        register_bool("test_bool", false, DvarFlags::empty(), None).unwrap();
+        clear_modified("test_bool").unwrap();
+
+        set_bool("test_bool", true).unwrap();
+        assert_eq!(get_bool("test_bool"), Some(true));
+        assert!(is_modified("test_bool"));
+    }
+
+    #[test]
+    fn latched_value_waits_for_make_latched_value_current() {
+        let _guard = testing::fresh_state();
+        register_int(
+            "test_latched",
+            1,
+            Some(0),
+            Some(10),
+            DvarFlags::LATCHED,
+            None,
+        )
+        .unwrap();
+
+        set_int("test_latched", 7).unwrap();
+        assert_eq!(get_int("test_latched"), Some(1));
+        assert!(has_pending_latch("test_latched"));
+
+        make_latched_value_current("test_latched").unwrap();
+        assert_eq!(get_int("test_latched"), Some(7));
+        assert!(has_pending_latch("test_latched") == false);
+    }
+
+    #[test]
+    fn has_pending_latch_until_latch_applied() {
+        let _guard = testing::fresh_state();
+        register_int(
+            "test_latched",
+            1,
+            Some(0),
+            Some(10),
+            DvarFlags::LATCHED,
+            None,
+        )
+        .unwrap();
+        register_int("test_plain", 1, None, None, DvarFlags::empty(), None)
+            .unwrap();
+        assert!(has_pending_latch("test_latched") == false);
+
+        cmd::execute_string("set test_latched 1");
+        assert!(has_pending_latch("test_latched") == false);
+        cmd::execute_string("set test_latched 4");
+        assert!(has_pending_latch("test_latched"));
+        cmd::execute_string("set test_plain 4");
+        assert!(has_pending_latch("test_plain") == false);
+
+        make_latched_value_current("test_latched").unwrap();
+        assert!(has_pending_latch("test_latched") == false);
+        assert_eq!(get_int("test_latched"), Some(4));
+        assert!(has_pending_latch("test_missing") == false);
+    }
+
+    #[test]
+    fn reset_registry_unregisters_everything() {
+        let _guard = testing::fresh_state();
+        register_bool("test_bool", true, DvarFlags::empty(), None).unwrap();
+
+        reset_registry();
+        assert!(exists("test_bool") == false);
+        assert!(exists("sv_cheats") == false);
+    }
+
+    #[test]
+    fn change_callback_runs_when_the_value_changes() {
+        use core::sync::atomic::AtomicUsize;
+        use util::EasierAtomic;
+
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        fn changed() {
+            CALLS.increment_wrapping();
+        }
+
+        let _guard = testing::fresh_state();
+        register_int(
+            "test_int",
+            5,
+            Some(0),
+            Some(10),
+            DvarFlags::LATCHED,
+            None,
+        )
+        .unwrap();
+        assert!(set_change_callback("test_missing", changed).is_err());
+        set_change_callback("test_int", changed).unwrap();
+
+        set_int_internal("test_int", 6).unwrap();
+        assert_eq!(CALLS.load_relaxed(), 1);
+        // Setting the value it already has isn't a change
+        set_int_internal("test_int", 6).unwrap();
+        assert_eq!(CALLS.load_relaxed(), 1);
+
+        // Latched values don't count until they're applied
+        set_from_string_from_source("test_int", "7", SetSource::External)
+            .unwrap();
+        assert_eq!(CALLS.load_relaxed(), 1);
+        make_latched_value_current("test_int").unwrap();
+        assert_eq!(CALLS.load_relaxed(), 2);
+    }
+
+    #[test]
+    fn reset_changeable_resets_only_changeable_dvars() {
+        let _guard = testing::fresh_state();
+        register_enumeration(
+            "test_rate",
+            "60".into(),
+            Some(vec!["60".into(), "75".into(), "144".into()]),
+            DvarFlags::CHANGEABLE_RESET | DvarFlags::LATCHED,
+            None,
+        )
+        .unwrap();
+        register_int(
+            "test_archived",
+            1,
+            Some(0),
+            Some(10),
+            DvarFlags::ARCHIVE,
+            None,
+        )
+        .unwrap();
+        set_enumeration_internal("test_rate", "144").unwrap();
+        set_enumeration("test_rate", "75").unwrap();
+        set_int("test_archived", 7).unwrap();
+
+        // Reset even though "144" is still in the domain
+        reset_changeable();
+        assert_eq!(get_enumeration("test_rate"), Some("60".into()));
+        assert!(has_pending_latch("test_rate") == false);
+        assert_eq!(get_int("test_archived"), Some(7));
+    }
+
+    fn register_test_rate(domain: &[&str], flags: DvarFlags) {
+        register_enumeration(
+            "test_rate",
+            "60".into(),
+            Some(domain.iter().map(|&s| s.to_owned()).collect()),
+            flags,
+            None,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn reregistered_enumeration_keeps_valid_state() {
+        let _guard = testing::fresh_state();
+        register_test_rate(&["60", "75"], DvarFlags::LATCHED);
+        set_enumeration("test_rate", "75").unwrap();
+
+        register_test_rate(&["60", "75", "144"], DvarFlags::ARCHIVE);
+        let d = find("test_rate").unwrap();
+        assert!(d.flags.contains(DvarFlags::LATCHED | DvarFlags::ARCHIVE));
+        assert!(Dvar::value_is_in_domain(
+            &d.domain,
+            DvarValue::Enumeration("144".into())
+        ));
+        assert!(has_pending_latch("test_rate"));
+
+        make_latched_value_current("test_rate").unwrap();
+        assert_eq!(get_enumeration("test_rate"), Some("75".into()));
+    }
+
+    #[test]
+    fn reregistered_enumeration_drops_invalid_values() {
+        let _guard = testing::fresh_state();
+        register_test_rate(&["60", "144"], DvarFlags::empty());
+        set_enumeration("test_rate", "144").unwrap();
+        clear_modified("test_rate").unwrap();
+
+        register_test_rate(&["60", "75"], DvarFlags::empty());
+        assert_eq!(get_enumeration("test_rate"), Some("60".into()));
+        assert!(is_modified("test_rate"));
+        assert!(has_pending_latch("test_rate") == false);
+    }
+
+    #[test]
+    fn enumeration_loaded_before_registration() {
+        let _guard = testing::fresh_state();
+        cmd::execute_string("set test_rate 75");
+        cmd::execute_string("set test_other 144");
+        register_test_rate(&["60", "75"], DvarFlags::empty());
+        register_enumeration(
+            "test_other",
+            "60".into(),
+            Some(vec!["60".into(), "75".into()]),
+            DvarFlags::empty(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(get_enumeration("test_rate"), Some("75".into()));
+        assert_eq!(get_enumeration("test_other"), Some("60".into()));
+    }
+
+    #[test]
+    fn set_clamps_to_domain() {
+        let _guard = testing::fresh_state();
+        register_float(
+            "test_float",
+            0.5,
+            Some(0.0),
+            Some(1.0),
+            DvarFlags::empty(),
+            None,
+        )
+        .unwrap();
+        register_int(
+            "test_int",
+            5,
+            Some(0),
+            Some(10),
+            DvarFlags::empty(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(set_float("test_float", 5.0), Ok(1.0));
+        assert_eq!(get_float("test_float"), Some(1.0));
+        assert_eq!(set_int("test_int", -3), Ok(0));
+        assert_eq!(get_int("test_int"), Some(0));
+
+        set_from_string_from_source("test_int", "50", SetSource::External)
+            .unwrap();
+        assert_eq!(get_int("test_int"), Some(10));
+    }
+
+    #[test]
+    fn set_reports_refused_values() {
+        let _guard = testing::fresh_state();
+        register_int(
+            "test_read_only",
+            5,
+            Some(0),
+            Some(10),
+            DvarFlags::READ_ONLY,
+            None,
+        )
+        .unwrap();
+        register_float(
+            "test_cheat",
+            0.5,
+            Some(0.0),
+            Some(1.0),
+            DvarFlags::CHEAT_PROTECTED,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(set_int("test_read_only", 7), Err(()));
+        assert_eq!(get_int("test_read_only"), Some(5));
+        assert!(set_from_string_from_source(
+            "test_read_only",
+            "7",
+            SetSource::External
+        )
+        .is_err());
+        assert_eq!(set_float("test_cheat", 0.25), Err(()));
+        assert_eq!(get_float("test_cheat"), Some(0.5));
+        set_bool_internal("sv_cheats", true).unwrap();
+        assert_eq!(set_float("test_cheat", 0.25), Ok(0.25));
+        assert_eq!(get_float("test_cheat"), Some(0.25));
+
+        // Internal code isn't subject to the protections
+        assert_eq!(set_int_internal("test_read_only", 7), Ok(7));
+    }
+
+    #[test]
+    fn write_protected_refuses_user_sets_only() {
+        let _guard = testing::fresh_state();
+        register_string("test_info", "old", DvarFlags::WRITE_PROTECTED, None)
+            .unwrap();
+
+        assert_eq!(set_string("test_info", "user"), Err(()));
+        assert!(set_from_string_from_source(
+            "test_info",
+            "user",
+            SetSource::External
+        )
+        .is_err());
+        cmd::execute_string("set test_info user");
+        assert_eq!(get_string("test_info"), Some("old".into()));
+
+        set_string_internal("test_info", "internal").unwrap();
+        assert_eq!(get_string("test_info"), Some("internal".into()));
+    }
+
+    #[test]
+    fn set_latches_clamped_value() {
+        let _guard = testing::fresh_state();
+        register_int(
+            "test_latched",
+            1,
+            Some(0),
+            Some(10),
+            DvarFlags::LATCHED,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(set_int("test_latched", 20), Ok(10));
+        assert_eq!(get_int("test_latched"), Some(1));
+        make_latched_value_current("test_latched").unwrap();
+        assert_eq!(get_int("test_latched"), Some(10));
+    }
+
+    #[test]
+    fn config_strings_round_trip() {
+        let values = [
+            DvarValue::Bool(true),
+            DvarValue::Bool(false),
+            DvarValue::Float(0.25),
+            DvarValue::Vector2((1.0, -2.5)),
+            DvarValue::Vector3((1.0, 0.5, 0.0)),
+            DvarValue::Vector4((0.125, 2.0, -3.0, 4.5)),
+            DvarValue::Int(-7),
+            DvarValue::String("a string".into()),
+            DvarValue::Enumeration("144".into()),
+            DvarValue::Color((255.0, 128.0, 0.0, 64.0)),
+            DvarValue::Int64(i64::MAX),
+            DvarValue::LinearColorRGB((0.5, 0.25, 1.0)),
+            DvarValue::ColorXYZ((0.95, 1.0, 1.09)),
+        ];
+        for value in values {
+            let s = value.to_config_string();
+            assert_eq!(value.parse_as_same_type(&s), Some(value), "{}", s);
+        }
+    }
+
+    #[test]
+    fn bools_parse_from_legacy_spellings() {
+        let b = DvarValue::Bool(false);
+        for s in ["1", "true", "yes", "on", "YES", "On", "2", "-1"] {
+            assert_eq!(b.parse_as_same_type(s), Some(DvarValue::Bool(true)));
+        }
+        for s in ["0", "false", "no", "off", "NO", " 0 "] {
+            assert_eq!(b.parse_as_same_type(s), Some(DvarValue::Bool(false)));
+        }
+        assert_eq!(b.parse_as_same_type("maybe"), None);
+    }
+
+    #[test]
+    fn archived_config_restores_archived_dvars() {
+        let guard = testing::fresh_state();
+        let register = || {
+            register_vector3(
+                "test_vec",
+                (0.0, 0.0, 0.0),
+                None,
+                None,
+                DvarFlags::ARCHIVE,
+                None,
+            )
+            .unwrap();
+            register_color(
+                "test_color",
+                0.0,
+                0.0,
+                0.0,
+                255.0,
+                DvarFlags::ARCHIVE,
+                None,
+            )
+            .unwrap();
+            register_bool("test_bool", false, DvarFlags::ARCHIVE, None)
+                .unwrap();
+            register_int("test_temp", 0, None, None, DvarFlags::empty(), None)
+                .unwrap();
+        };
+        register();
+        set_vector3("test_vec", (1.0, 0.5, -2.0)).unwrap();
+        set_color("test_color", 255.0, 128.0, 0.0, 255.0).unwrap();
+        set_bool("test_bool", true).unwrap();
+        set_int("test_temp", 3).unwrap();
+
+        let config = archived_config();
+        assert!(config.contains("seta test_vec \"1 0.5 -2\"\n"));
+        assert!(config.contains("test_temp") == false);
+
+        // Start over as if the game had been restarted
+        drop(guard);
+        let _guard = testing::fresh_state();
+        register();
+        cbuf::split_commands(&config)
+            .into_iter()
+            .for_each(cmd::execute_string);
+        assert_eq!(get_vector3("test_vec"), Some((1.0, 0.5, -2.0)));
+        assert_eq!(get_color("test_color"), Some((255.0, 128.0, 0.0, 255.0)));
+        assert_eq!(get_bool("test_bool"), Some(true));
+        assert_eq!(get_int("test_temp"), Some(0));
+    }
+
+    #[test]
+    fn alias_gets_and_sets_real_dvar() {
+        let _guard = testing::fresh_state();
+        register_bool("test_new", false, DvarFlags::empty(), None).unwrap();
+        register_alias("test_old", "test_new").unwrap();
+
+        set_bool("test_old", true).unwrap();
+        assert_eq!(get_bool("test_new"), Some(true));
+        assert_eq!(get_bool("test_old"), Some(true));
+        assert_eq!(alias_target("test_old").as_deref(), Some("test_new"));
+    }
+
+    // Counts the deprecation warnings for `alias` in the console
+    fn alias_warning_count(alias: &str) -> usize {
+        let warning = format!("dvar '{}' has been renamed", alias);
+        console::get_text_copy(console::scrollback_bytes())
+            .lines()
+            .filter(|l| l.contains(&warning))
+            .count()
+    }
+
+    #[test]
+    fn alias_warns_once_for_user_input_only() {
+        let _guard = testing::fresh_state();
+        register_bool("test_new", false, DvarFlags::empty(), None).unwrap();
+        register_alias("test_warned", "test_new").unwrap();
+        let before = alias_warning_count("test_warned");
+
+        // The engine's own lookups don't warn
+        set_bool_internal("test_warned", true).unwrap();
+        assert_eq!(get_bool("test_warned"), Some(true));
+        assert_eq!(alias_warning_count("test_warned"), before);
+
+        cmd::execute_string("set test_warned 0");
+        cmd::execute_string("seta test_warned 1");
+        assert_eq!(get_bool("test_new"), Some(true));
+        assert_eq!(alias_warning_count("test_warned"), before + 1);
+    }
+
+    #[test]
+    fn register_rejects_alias_names() {
+        let _guard = testing::fresh_state();
+        register_alias("test_old", "test_new").unwrap();
+
+        assert_eq!(
+            register_bool("test_old", false, DvarFlags::empty(), None),
+            Err(())
+        );
+        assert_eq!(
+            register_int("test_old", 1, None, None, DvarFlags::empty(), None),
+            Err(())
+        );
+        assert_eq!(
+            register_enumeration(
+                "test_old",
+                "a".to_owned(),
+                Some(vec!["a".to_owned()]),
+                DvarFlags::empty(),
+                None,
+            ),
+            Err(())
+        );
+        assert!(names().iter().all(|n| n != "test_old"));
+
+        // The alias still leads to the Dvar once it's registered
+        register_bool("test_new", true, DvarFlags::empty(), None).unwrap();
+        assert_eq!(get_bool("test_old"), Some(true));
+    }
+}
@@ -6,10 +6,11 @@ use super::{
     add_flags, get_bool, get_enumeration,
     global_fns::{exists, find},
     name_is_valid, register_bool, register_color, register_float, register_int,
-    set_bool_from_source, set_float_from_source, set_int64_from_source,
-    set_int_from_source, set_string_from_source,
+    resolve_alias, set_bool_from_source, set_float_from_source,
+    set_from_string_from_source, set_int64_from_source, set_int_from_source,
+    set_pending,
     value::DvarValue,
-    Dvar, DvarFlags, SetSource, DVARS,
+    warn_if_alias, Dvar, DvarFlags, SetSource, DVARS,
 };
 
 use lazy_static::lazy_static;
@@ -33,55 +34,33 @@ fn toggle_simple(name: &str) -> Result<(), ()> {
         }
         DvarValue::Float(f) => {
             let domain = find(name).unwrap().domain;
-            if domain.as_float_limits().unwrap().min > 0.0
-                || domain.as_float_limits().unwrap().max < 1.0
-            {
-                if (value.as_float().unwrap()
-                    - domain.as_float_limits().unwrap().min)
-                    .abs()
-                    < f32::EPSILON
-                {
-                    set_float_from_source(
-                        name,
-                        domain.as_float_limits().unwrap().max,
-                        SetSource::External,
-                    )
+            let limits = domain.as_float_limits().unwrap();
+            let toggled = if limits.min > 0.0 || limits.max < 1.0 {
+                if (f - limits.min).abs() < f32::EPSILON {
+                    limits.max
                 } else {
-                    set_float_from_source(
-                        name,
-                        domain.as_float_limits().unwrap().min,
-                        SetSource::External,
-                    )
+                    limits.min
                 }
             } else if f == 0.0 {
-                set_float_from_source(name, 1.0, SetSource::External)
+                1.0
             } else {
-                set_float_from_source(name, 0.0, SetSource::External)
-            }
+                0.0
+            };
+            set_float_from_source(name, toggled, SetSource::External)
+                .map(|_| ())
         }
         DvarValue::Int(i) => {
             let domain = find(name).unwrap().domain;
-            if domain.as_int_limits().unwrap().max > 0
-                && domain.as_int_limits().unwrap().min < 1
-            {
-                if i == 0 {
-                    set_int_from_source(name, 1, SetSource::External)
-                } else {
-                    set_int_from_source(name, 0, SetSource::External)
-                }
-            } else if i == domain.as_int_limits().unwrap().min {
-                set_int_from_source(
-                    name,
-                    domain.as_int_limits().unwrap().max,
-                    SetSource::External,
-                )
+            let limits = domain.as_int_limits().unwrap();
+            let toggled = if limits.max > 0 && limits.min < 1 {
+                i32::from(i == 0)
+            } else if i == limits.min {
+                limits.max
             } else {
-                set_int_from_source(
-                    name,
-                    domain.as_int_limits().unwrap().min,
-                    SetSource::External,
-                )
-            }
+                limits.min
+            };
+            set_int_from_source(name, toggled, SetSource::External)
+                .map(|_| ())
         }
         DvarValue::Int64(i) => {
             let domain = find(name).unwrap().domain;
@@ -222,13 +201,31 @@ lazy_static! {
         AtomicBool::new(false);
 }
 
+/// Marks whether autoexec.cfg is being run. Dvars it sets are flagged
+/// [`DvarFlags::AUTOEXEC`], and keep what it set them to as their reset
+/// value.
+pub fn set_loading_autoexec(loading: bool) {
+    IS_LOADING_AUTO_EXEC_GLOBAL_FLAG.store(loading, Ordering::SeqCst);
+}
+
 fn set_command(name: &str, value: &str) {
-    if set_string_from_source(name, value, SetSource::External).is_err() {
+    warn_if_alias(name);
+    let from_autoexec = IS_LOADING_AUTO_EXEC_GLOBAL_FLAG.load(Ordering::SeqCst);
+    if exists(name) == false {
+        // Most likely a config setting a Dvar whose subsystem hasn't
+        // started yet, so hold on to the value until it's registered
+        let _ = set_pending(name, value, from_autoexec);
+        return;
+    }
+
+    if set_from_string_from_source(name, value, SetSource::External).is_err()
+    {
         return;
     }
 
-    if IS_LOADING_AUTO_EXEC_GLOBAL_FLAG.load(Ordering::SeqCst) == true {
-        if let Some(d) = DVARS.write().unwrap().get_mut(name) {
+    if from_autoexec == true {
+        let name = resolve_alias(name);
+        if let Some(d) = DVARS.write().unwrap().get_mut(&*name) {
             d.add_flags(DvarFlags::AUTOEXEC);
             d.reset = d.current.clone();
         }
@@ -249,6 +246,45 @@ fn get_combined_string(start_idx: usize) -> String {
     str
 }
 
+// Quotes `dvar`'s value for display, along with its latched value if
+// there's one waiting to be applied, so that it's clear a restart is needed.
+fn display_value(dvar: &Dvar) -> String {
+    if dvar.has_latched_value() {
+        format!("\"{}\" (latched: \"{}\")", dvar.current, dvar.latched)
+    } else {
+        format!("\"{}\"", dvar.current)
+    }
+}
+
+/// Handles console input that starts with a [`Dvar`]'s name, printing the
+/// [`Dvar`]'s value if that's all there is, or setting it to the rest of the
+/// input otherwise.
+///
+/// # Return Value
+///
+/// Returns [`false`] if the first argument isn't the name of a [`Dvar`],
+/// [`true`] otherwise.
+pub fn command() -> bool {
+    let name = cmd::argv(0);
+    let Some(dvar) = find(&name) else {
+        return false;
+    };
+    warn_if_alias(&name);
+
+    if cmd::argc() == 1 {
+        com::println!(
+            console::Channel::DONT_FILTER,
+            "\"{}\" is: {}",
+            dvar.name,
+            display_value(&dvar),
+        );
+    } else {
+        set_command(&name, &get_combined_string(1));
+    }
+
+    true
+}
+
 lazy_static! {
     static ref DVAR_COUNT_LOCAL: AtomicIsize = AtomicIsize::new(0);
 }
@@ -331,7 +367,7 @@ fn list_single(dvar: &Dvar, name: &str) {
 
     com::println!(
         console::Channel::DONT_FILTER,
-        "{}{}{}{}{}{}{}{}{}{}{}{} {} \"{}\"",
+        "{}{}{}{}{}{}{}{}{}{}{}{} {} {}",
         s,
         u,
         r,
@@ -345,7 +381,7 @@ fn list_single(dvar: &Dvar, name: &str) {
         e,
         v,
         dvar.name,
-        dvar.current,
+        display_value(dvar),
     );
     DVAR_COUNT_LOCAL.fetch_add(1, Ordering::SeqCst);
 }
@@ -468,10 +504,11 @@ fn sets_f() {
 
     set_f();
     let name = cmd::argv(1);
+    let name = resolve_alias(&name);
 
     let mut writer = DVARS.write().unwrap();
 
-    if let Some(d) = writer.get_mut(&name) {
+    if let Some(d) = writer.get_mut(&*name) {
         d.add_flags(DvarFlags::SERVER_INFO);
     }
 }
@@ -487,10 +524,11 @@ fn seta_f() {
 
     set_f();
     let name = cmd::argv(1);
+    let name = resolve_alias(&name);
 
     let mut writer = DVARS.write().unwrap();
 
-    if let Some(d) = writer.get_mut(&name) {
+    if let Some(d) = writer.get_mut(&*name) {
         d.add_flags(DvarFlags::ARCHIVE);
     }
 }
@@ -505,8 +543,9 @@ fn set_admin_f() {
     }
 
     let name = cmd::argv(1);
+    let name = resolve_alias(&name);
 
-    if let Some(d) = DVARS.write().unwrap().get_mut(&name) {
+    if let Some(d) = DVARS.write().unwrap().get_mut(&*name) {
         if d.flags.contains(DvarFlags::CON_ACCESS) {
             d.add_flags(DvarFlags::ARCHIVE);
         }
@@ -539,9 +578,10 @@ fn set_from_dvar_f() {
     let dest_dvar_name = cmd::argv(1);
     let source_dvar_name = cmd::argv(2);
 
+    let source = resolve_alias(&source_dvar_name);
     let mut writer = DVARS.write().unwrap();
-    if let Some(d) = writer.get_mut(&source_dvar_name) {
-        set_command(&dest_dvar_name, &d.current.to_string());
+    if let Some(d) = writer.get_mut(&*source) {
+        set_command(&dest_dvar_name, &d.current.to_config_string());
     } else {
         com::println!(
             console::Channel::DONT_FILTER,
@@ -590,8 +630,14 @@ fn reset_f() {
     let name = cmd::argv(1);
 
     if exists(&name) {
+        warn_if_alias(&name);
+        let name = resolve_alias(&name);
+        let cheats_enabled = super::cheats_enabled();
         let mut writer = DVARS.write().unwrap();
-        writer.get_mut(&name).unwrap().reset(SetSource::External);
+        writer
+            .get_mut(&*name)
+            .unwrap()
+            .reset(SetSource::External, cheats_enabled);
     }
 }
 
@@ -885,7 +931,7 @@ fn restore_dvars() {
     DVARS.write().unwrap().values_mut().for_each(|d| {
         if d.loaded_from_save_game == true {
             d.loaded_from_save_game = false;
-            d.set_variant(d.saved.clone(), SetSource::Internal);
+            let _ = d.set_variant(d.saved.clone(), SetSource::Internal, false);
         }
     });
 }
@@ -931,8 +977,8 @@ pub fn add_commands() {
     cmd::add_command_internal("setfromlocString", set_from_localized_string_f)
         .unwrap();
     cmd::add_command_internal("reset", reset_f).unwrap();
-    cmd::add_command_internal("dvarlist", list_f).unwrap();
-    cmd::add_command_internal("dvardump", dump_f).unwrap();
+    cmd::add_developer_command("dvarlist", list_f).unwrap();
+    cmd::add_developer_command("dvardump", dump_f).unwrap();
     cmd::add_command_internal("dvar_bool", register_bool_f).unwrap();
     cmd::add_command_internal("dvar_int", register_int_f).unwrap();
     cmd::add_command_internal("dvar_float", register_float_f).unwrap();
@@ -6,7 +6,7 @@ use lazy_static::lazy_static;
 
 use crate::dvar::Dvar;
 
-use super::DvarFlags;
+use super::{DvarFlags, SetSource};
 
 pub mod register;
 pub use register::*;
@@ -17,6 +17,15 @@ pub use set::*;
 pub mod get;
 pub use get::*;
 
+pub mod alias;
+pub use alias::*;
+
+pub mod pending;
+pub use pending::*;
+
+pub mod callback;
+pub use callback::*;
+
 const DVAR_COUNT_MAX: usize = 4096;
 
 lazy_static! {
@@ -33,6 +42,9 @@ lazy_static! {
 /// exists, or to query the state of said [`Dvar`] at the point in time at which
 /// the copy is made.
 ///
+/// If `name` is an alias (see [`register_alias`]), the [`Dvar`] it's an alias
+/// of is found instead.
+///
 /// # Arguments
 ///
 /// * `name` - A [`String`] that holds the name of the [`Dvar`] to be searched
@@ -57,6 +69,8 @@ lazy_static! {
 /// };
 /// ```
 pub(super) fn find(name: &str) -> Option<Dvar> {
+    let name = resolve_alias(name);
+    let name: &str = &name;
     let reader = DVARS.read().unwrap();
 
     if !reader.contains_key(name) {
@@ -94,6 +108,78 @@ pub fn exists(name: &str) -> bool {
     find(name).is_some()
 }
 
+/// Returns the names of every registered [`Dvar`].
+///
+/// # Panics
+///
+/// Panics if the read lock for [`DVARS`] can't be acquired (usually because
+/// the write lock is held by a function farther up the call stack).
+pub fn names() -> Vec<String> {
+    DVARS.read().unwrap().keys().cloned().collect()
+}
+
+/// Returns a config that restores every [`Dvar`] with
+/// [`DvarFlags::ARCHIVE`] to its current value, as one `seta` command per
+/// line, sorted by name. A latched value waiting to be applied is written
+/// instead of the current one, so that it takes effect on the next start.
+///
+/// # Panics
+///
+/// Panics if the read lock for [`DVARS`] can't be acquired (usually because
+/// the write lock is held by a function farther up the call stack).
+pub fn archived_config() -> String {
+    let dvars = DVARS.read().unwrap();
+    let mut archived = dvars
+        .values()
+        .filter(|d| d.flags.contains(DvarFlags::ARCHIVE))
+        .collect::<Vec<_>>();
+    archived.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    archived
+        .into_iter()
+        .map(|d| {
+            let value = if d.has_latched_value() {
+                &d.latched
+            } else {
+                &d.current
+            };
+            format!("seta {} \"{}\"\n", d.name, value.to_config_string())
+        })
+        .collect()
+}
+
+/// Resets every [`Dvar`] registered with [`DvarFlags::CHANGEABLE_RESET`] to
+/// its reset value, discarding any latched value as well.
+///
+/// Should be called whenever whatever determines the valid values for such
+/// Dvars changes (e.g. when the renderer re-enumerates display modes).
+///
+/// # Panics
+///
+/// Panics if the write lock for [`DVARS`] can't be acquired (usually because
+/// the write lock or a read lock is held by a function farther up the
+/// call stack).
+pub fn reset_changeable() {
+    DVARS
+        .write()
+        .unwrap()
+        .values_mut()
+        .filter(|d| d.flags.contains(DvarFlags::CHANGEABLE_RESET))
+        .for_each(|d| {
+            d.reset(SetSource::Internal, false);
+            d.clear_latched_value();
+        });
+}
+
+/// Returns the `modified` flag of a [`Dvar`], or `false` if it doesn't exist.
+///
+/// # Panics
+///
+/// Panics if the read lock for [`DVARS`] can't be acquired (usually because
+/// the write lock is held by a function farther up the call stack).
+pub fn is_modified(name: &str) -> bool {
+    find(name).map_or(false, |d| d.modified)
+}
+
 /// Clears the `modified` flag of a [`Dvar`], if it exists.
 ///
 /// # Arguments
@@ -192,13 +278,32 @@ pub fn clear_flags(name: &str, flags: DvarFlags) -> Result<(), ()> {
 }
 
 pub fn make_latched_value_current(name: &str) -> Result<(), ()> {
-    let mut writer = DVARS.write().unwrap();
-    if let Some(d) = writer.get_mut(name) {
+    let changed = {
+        let mut writer = DVARS.write().unwrap();
+        let Some(d) = writer.get_mut(name) else {
+            return Err(());
+        };
+        let old = d.current.clone();
         d.make_latched_value_current();
-        return Ok(());
+        d.current != old
     };
 
-    Err(())
+    if changed {
+        run_change_callback(name);
+    }
+    Ok(())
+}
+
+/// Returns `true` if a [`Dvar`] has a latched value waiting to be applied
+/// (i.e. it's been changed, but won't take effect until whatever it's
+/// latched on is restarted), or `false` if it doesn't or doesn't exist.
+///
+/// # Panics
+///
+/// Panics if the read lock for [`DVARS`] can't be acquired (usually because
+/// the write lock is held by a function farther up the call stack).
+pub fn has_pending_latch(name: &str) -> bool {
+    find(name).is_some_and(|d| d.has_latched_value())
 }
 
 // Helper function to check if Dvar name is valid
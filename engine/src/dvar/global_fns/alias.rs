@@ -0,0 +1,110 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    sync::{Mutex, RwLock},
+};
+
+use lazy_static::lazy_static;
+
+use crate::{com, console};
+
+use super::{name_is_valid, DVARS};
+
+lazy_static! {
+    // Each alias, mapped to the name of the Dvar it refers to
+    static ref ALIASES: RwLock<HashMap<String, String>> =
+        RwLock::new(HashMap::new());
+    // The aliases that have already been warned about
+    static ref WARNED_ALIASES: Mutex<HashSet<String>> =
+        Mutex::new(HashSet::new());
+}
+
+/// Makes `old_name` an alias of the [`Dvar`] named `new_name`, so that a
+/// renamed [`Dvar`] can still be got and set (e.g. by configs written by
+/// older builds) under its old name.
+///
+/// Anything done to `old_name` afterwards is done to `new_name` instead,
+/// with a deprecation warning printed the first time the alias is used from
+/// the console or a config.
+/// `new_name` doesn't have to be registered yet.
+///
+/// # Arguments
+/// * `old_name` - The name the [`Dvar`] used to have.
+/// * `new_name` - The name the [`Dvar`] has now.
+///
+/// # Return Value
+///
+/// Returns [`Err`] if either name isn't valid, if they're the same, if a
+/// [`Dvar`] named `old_name` is registered, or if the alias would be part of
+/// a chain (i.e. `new_name` is itself an alias, or something is already an
+/// alias of `old_name`). Returns [`Ok`] otherwise.
+///
+/// # Panics
+/// Panics if the read lock for [`DVARS`] can't be acquired (usually because
+/// the write lock is held by a function farther up the call stack).
+///
+/// # Example
+/// ```
+/// register_alias("r_fullScreen", "r_fullscreen").unwrap();
+/// ```
+pub fn register_alias(old_name: &str, new_name: &str) -> Result<(), ()> {
+    if name_is_valid(old_name) == false
+        || name_is_valid(new_name) == false
+        || old_name == new_name
+        || DVARS.read().unwrap().contains_key(old_name)
+    {
+        return Err(());
+    }
+
+    let mut aliases = ALIASES.write().unwrap();
+    if aliases.contains_key(new_name)
+        || aliases.values().any(|target| target == old_name)
+    {
+        return Err(());
+    }
+
+    aliases.insert(old_name.to_owned(), new_name.to_owned());
+    Ok(())
+}
+
+/// Returns the name of the [`Dvar`] that `name` is an alias of, or [`None`]
+/// if it isn't one (see [`register_alias`]).
+pub fn alias_target(name: &str) -> Option<String> {
+    ALIASES.read().unwrap().get(name).cloned()
+}
+
+/// Removes every alias, and forgets which ones have been warned about.
+pub(in crate::dvar) fn clear_aliases() {
+    ALIASES.write().unwrap().clear();
+    WARNED_ALIASES.lock().unwrap().clear();
+}
+
+/// Returns the name of the [`Dvar`] that `name` refers to: the [`Dvar`] it's
+/// an alias of if it's an alias, or just `name` otherwise.
+///
+/// Doesn't warn about the alias being used, since it's also how the engine
+/// looks up its own Dvars. See [`warn_if_alias`] for that.
+pub(in crate::dvar) fn resolve_alias(name: &str) -> Cow<'_, str> {
+    alias_target(name).map_or(Cow::Borrowed(name), Cow::Owned)
+}
+
+/// Prints a deprecation warning if `name` is an alias (see
+/// [`register_alias`]), unless one has already been printed for it.
+///
+/// Called wherever a name comes from the user, i.e. the console and
+/// configs, rather than from the engine itself.
+pub(in crate::dvar) fn warn_if_alias(name: &str) {
+    let Some(target) = alias_target(name) else {
+        return;
+    };
+
+    if WARNED_ALIASES.lock().unwrap().insert(name.to_owned()) {
+        com::warnln!(
+            console::Channel::SYSTEM,
+            "WARNING: dvar \'{}\' has been renamed to \'{}\'. The old name \
+             may stop working in a future version.",
+            name,
+            target,
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use lazy_static::lazy_static;
+
+use super::{resolve_alias, DVARS};
+
+lazy_static! {
+    // The function to call when each Dvar's current value changes, mapped
+    // to by the Dvars' names
+    static ref CALLBACKS: RwLock<HashMap<String, fn()>> =
+        RwLock::new(HashMap::new());
+}
+
+/// Has `callback` called whenever the current value of the [`Dvar`] named
+/// `name` changes, replacing any callback it already had.
+///
+/// `callback` runs on the thread that made the change, once it's been made
+/// and no locks are held, so it's free to get or set any [`Dvar`]
+/// (including this one). A latched value only counts as a change once it's
+/// applied.
+///
+/// # Return Value
+///
+/// Returns [`Err`] if no [`Dvar`] named `name` is registered, [`Ok`]
+/// otherwise.
+///
+/// # Panics
+/// Panics if the read lock for [`DVARS`] can't be acquired (usually because
+/// the write lock is held by a function farther up the call stack).
+///
+/// # Example
+/// ```
+/// fn mode_changed() {
+///     println!("r_mode is now {}", get_enumeration("r_mode").unwrap());
+/// }
+///
+/// set_change_callback("r_mode", mode_changed).unwrap();
+/// ```
+pub fn set_change_callback(name: &str, callback: fn()) -> Result<(), ()> {
+    let name = resolve_alias(name);
+    if DVARS.read().unwrap().contains_key(&*name) == false {
+        return Err(());
+    }
+
+    CALLBACKS
+        .write()
+        .unwrap()
+        .insert(name.into_owned(), callback);
+    Ok(())
+}
+
+/// Calls the change callback for the [`Dvar`] named `name`, if it has one.
+/// Must be called without holding the lock on [`DVARS`].
+pub(in crate::dvar) fn run_change_callback(name: &str) {
+    // Copied out so the callback can set another one
+    let callback = CALLBACKS.read().unwrap().get(name).copied();
+    if let Some(callback) = callback {
+        callback();
+    }
+}
+
+/// Removes every change callback.
+pub(in crate::dvar) fn clear_change_callbacks() {
+    CALLBACKS.write().unwrap().clear();
+}
@@ -0,0 +1,92 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use lazy_static::lazy_static;
+
+use crate::{
+    com, console,
+    dvar::{Dvar, DvarFlags},
+};
+
+use super::{name_is_valid, resolve_alias, DVARS};
+
+lazy_static! {
+    // Values set for Dvars that haven't been registered yet, mapped to by
+    // the Dvars' names, along with whether autoexec.cfg set them
+    static ref PENDING: Mutex<HashMap<String, (String, bool)>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Holds on to `value` for the [`Dvar`] named `name`, which hasn't been
+/// registered yet (e.g. because a config sets it before the subsystem it
+/// belongs to has started), so that it can be applied once it is.
+///
+/// If `from_autoexec` is `true`, `value` also becomes the [`Dvar`]'s reset
+/// value once it's applied, as it would've if the [`Dvar`] had already been
+/// registered.
+///
+/// # Return Value
+///
+/// Returns [`Err`] if `name` isn't a valid name, [`Ok`] otherwise.
+pub(in crate::dvar) fn set_pending(
+    name: &str,
+    value: &str,
+    from_autoexec: bool,
+) -> Result<(), ()> {
+    let name = resolve_alias(name);
+    if name_is_valid(&name) == false {
+        return Err(());
+    }
+
+    PENDING
+        .lock()
+        .unwrap()
+        .insert(name.into_owned(), (value.to_owned(), from_autoexec));
+    Ok(())
+}
+
+/// Applies the value set for the [`Dvar`] named `name` before it was
+/// registered, if there was one. Called by each of the `register_*`
+/// functions once they've registered a [`Dvar`].
+///
+/// The value is clamped to the [`Dvar`]'s domain, and replaces the
+/// registered one outright, rather than being latched, since nothing can
+/// have used the [`Dvar`] yet.
+pub(in crate::dvar) fn apply_pending(name: &str) {
+    let Some((value, from_autoexec)) = PENDING.lock().unwrap().remove(name)
+    else {
+        return;
+    };
+
+    let applied = DVARS.write().unwrap().get_mut(name).is_some_and(|d| {
+        let Some(parsed) = d
+            .current
+            .parse_as_same_type(&value)
+            .map(|mut v| Dvar::clamp_value_to_domain(&mut v, &d.domain))
+            .filter(|v| Dvar::value_is_in_domain(&d.domain, v.clone()))
+        else {
+            return false;
+        };
+
+        d.current = parsed.clone();
+        d.latched = parsed;
+        if from_autoexec {
+            d.add_flags(DvarFlags::AUTOEXEC);
+            d.reset = d.current.clone();
+        }
+        true
+    });
+
+    if applied == false {
+        com::warnln!(
+            console::Channel::SYSTEM,
+            "WARNING: \'{}\' is not a valid value for dvar \'{}\', ignoring",
+            value,
+            name,
+        );
+    }
+}
+
+/// Discards every value waiting for its [`Dvar`] to be registered.
+pub(in crate::dvar) fn clear_pending() {
+    PENDING.lock().unwrap().clear();
+}
@@ -1,10 +1,33 @@
 use crate::{
     com,
     common::{Vec2f32, Vec3f32, Vec4f32},
-    dvar::{builder::DvarBuilder, DvarFlags},
+    console,
+    dvar::{
+        builder::DvarBuilder,
+        limits::{DvarLimits, DvarLimitsEnumeration},
+        value::DvarValue,
+        Dvar, DvarFlags,
+    },
 };
 
-use super::{exists, DVARS, DVAR_COUNT_MAX};
+use super::{alias_target, apply_pending, exists, DVARS, DVAR_COUNT_MAX};
+
+// A Dvar registered under a name that's an alias (see `register_alias`)
+// could never be got or set, since the alias would always be followed
+// instead, so each of the `register_*` functions refuses to.
+fn name_is_aliased(name: &str) -> bool {
+    let Some(target) = alias_target(name) else {
+        return false;
+    };
+
+    com::print_errorln!(
+        console::Channel::SYSTEM,
+        "Can\'t create dvar \'{}\': it\'s an alias of \'{}\'",
+        name,
+        target,
+    );
+    true
+}
 
 /// Registers a new [`Dvar`] of type [`DvarValue::Bool`],
 /// using the provided name, value, flags, and description,
@@ -38,6 +61,10 @@ pub fn register_bool(
     flags: DvarFlags,
     description: Option<&str>,
 ) -> Result<(), ()> {
+    crate::dvar::INITIALIZED.debug_check("dvar::register");
+    if name_is_aliased(name) {
+        return Err(());
+    }
     if DVARS.read().unwrap().len() + 1 > DVAR_COUNT_MAX {
         com::errorln!(
             com::ErrorParm::FATAL,
@@ -71,6 +98,7 @@ pub fn register_bool(
         );
         Err(())
     } else {
+        apply_pending(name);
         Ok(())
     }
 }
@@ -194,6 +222,10 @@ pub fn register_float(
     flags: DvarFlags,
     description: Option<&str>,
 ) -> Result<(), ()> {
+    crate::dvar::INITIALIZED.debug_check("dvar::register");
+    if name_is_aliased(name) {
+        return Err(());
+    }
     if DVARS.read().unwrap().len() + 1 > DVAR_COUNT_MAX {
         com::errorln!(
             com::ErrorParm::FATAL,
@@ -228,6 +260,7 @@ pub fn register_float(
         );
         Err(())
     } else {
+        apply_pending(name);
         Ok(())
     }
 }
@@ -371,6 +404,10 @@ pub fn register_vector2(
     flags: DvarFlags,
     description: Option<&str>,
 ) -> Result<(), ()> {
+    crate::dvar::INITIALIZED.debug_check("dvar::register");
+    if name_is_aliased(name) {
+        return Err(());
+    }
     if DVARS.read().unwrap().len() + 1 > DVAR_COUNT_MAX {
         com::errorln!(
             com::ErrorParm::FATAL,
@@ -406,6 +443,7 @@ pub fn register_vector2(
         );
         Err(())
     } else {
+        apply_pending(name);
         Ok(())
     }
 }
@@ -550,6 +588,10 @@ pub fn register_vector3(
     flags: DvarFlags,
     description: Option<&str>,
 ) -> Result<(), ()> {
+    crate::dvar::INITIALIZED.debug_check("dvar::register");
+    if name_is_aliased(name) {
+        return Err(());
+    }
     if DVARS.read().unwrap().len() + 1 > DVAR_COUNT_MAX {
         com::errorln!(
             com::ErrorParm::FATAL,
@@ -584,6 +626,7 @@ pub fn register_vector3(
         );
         Err(())
     } else {
+        apply_pending(name);
         Ok(())
     }
 }
@@ -728,6 +771,10 @@ pub fn register_vector4(
     flags: DvarFlags,
     description: Option<&str>,
 ) -> Result<(), ()> {
+    crate::dvar::INITIALIZED.debug_check("dvar::register");
+    if name_is_aliased(name) {
+        return Err(());
+    }
     if DVARS.read().unwrap().len() + 1 > DVAR_COUNT_MAX {
         com::errorln!(
             com::ErrorParm::FATAL,
@@ -763,6 +810,7 @@ pub fn register_vector4(
         );
         Err(())
     } else {
+        apply_pending(name);
         Ok(())
     }
 }
@@ -907,6 +955,10 @@ pub fn register_int(
     flags: DvarFlags,
     description: Option<&str>,
 ) -> Result<(), ()> {
+    crate::dvar::INITIALIZED.debug_check("dvar::register");
+    if name_is_aliased(name) {
+        return Err(());
+    }
     if DVARS.read().unwrap().len() + 1 > DVAR_COUNT_MAX {
         com::errorln!(
             com::ErrorParm::FATAL,
@@ -942,6 +994,7 @@ pub fn register_int(
         );
         Err(())
     } else {
+        apply_pending(name);
         Ok(())
     }
 }
@@ -1076,6 +1129,10 @@ pub fn register_string(
     flags: DvarFlags,
     description: Option<&str>,
 ) -> Result<(), ()> {
+    crate::dvar::INITIALIZED.debug_check("dvar::register");
+    if name_is_aliased(name) {
+        return Err(());
+    }
     if DVARS.read().unwrap().len() + 1 > DVAR_COUNT_MAX {
         com::errorln!(
             com::ErrorParm::FATAL,
@@ -1110,6 +1167,7 @@ pub fn register_string(
         );
         Err(())
     } else {
+        apply_pending(name);
         Ok(())
     }
 }
@@ -1193,9 +1251,82 @@ pub fn reregister_string(
     register_string(name, value, flags, description)
 }
 
+/// Returns `loaded` if it's one of the values in `domain`, or `default` if it
+/// isn't.
+///
+/// Enumeration domains can differ between machines (e.g. `r_mode`'s depends
+/// on the monitor), so a value saved on one might not exist on another. An
+/// out-of-domain value is replaced with `default` and a warning is logged,
+/// rather than being stored and later tripping up code that assumes it's
+/// valid.
+fn enumeration_value_or_default(
+    name: &str,
+    loaded: String,
+    domain: &[String],
+    default: String,
+) -> String {
+    if domain.contains(&loaded) {
+        return loaded;
+    }
+
+    com::warnln!(
+        console::Channel::SYSTEM,
+        "WARNING: \'{}\' is not a valid value for dvar \'{}\', using \'{}\'",
+        loaded,
+        name,
+        default,
+    );
+    default
+}
+
+// Gives the already-registered enumeration `d` the domain `domain`,
+// keeping its flags, modified state, and latched, reset, and saved values.
+// Any of those values that aren't part of the new domain fall back to
+// `default`, marking `d` as modified if its current value changes.
+fn update_enumeration(
+    d: &mut Dvar,
+    domain: &[String],
+    default: String,
+    flags: DvarFlags,
+    description: Option<&str>,
+) {
+    let in_domain = |v: &DvarValue| match v {
+        DvarValue::Enumeration(s) => domain.contains(s),
+        _ => false,
+    };
+
+    d.domain = DvarLimits::Enumeration(DvarLimitsEnumeration::new(domain));
+    d.add_flags(flags);
+    if let Some(description) = description {
+        d.description = description.to_owned();
+    }
+
+    if in_domain(&d.current) == false {
+        let value = enumeration_value_or_default(
+            &d.name,
+            d.current.to_string(),
+            domain,
+            default.clone(),
+        );
+        d.current = DvarValue::Enumeration(value);
+        d.modified = true;
+    }
+    if in_domain(&d.latched) == false {
+        d.latched = d.current.clone();
+    }
+    if in_domain(&d.reset) == false {
+        d.reset = DvarValue::Enumeration(default);
+    }
+    if in_domain(&d.saved) == false {
+        d.saved = d.current.clone();
+    }
+}
+
 /// Registers a new [`Dvar`] of type [`DvarValue::Enumeration`], using the
 /// provided name, value, flags, and description, if a [`Dvar`] with name `name`
-/// doesn't already exist, reregisters said [`Dvar`] if it does.
+/// doesn't already exist. If it does, said [`Dvar`]'s domain is replaced with
+/// `domain`, and any of its values that aren't part of it fall back to
+/// `value`.
 ///
 /// # Arguments
 /// * `name` - A [`String`] that holds the name of the [`Dvar`]
@@ -1229,6 +1360,22 @@ pub fn register_enumeration(
     flags: DvarFlags,
     description: Option<&str>,
 ) -> Result<(), ()> {
+    crate::dvar::INITIALIZED.debug_check("dvar::register");
+    if name_is_aliased(name) {
+        return Err(());
+    }
+    let domain = domain.unwrap_or_default();
+
+    // An enumeration that's already registered (e.g. r_displayRefresh,
+    // which is reregistered whenever the display modes are enumerated)
+    // just gets its domain updated, keeping the rest of its state
+    if let Some(d) = DVARS.write().unwrap().get_mut(name) {
+        if matches!(d.current, DvarValue::Enumeration(_)) {
+            update_enumeration(d, &domain, value, flags, description);
+            return Ok(());
+        }
+    }
+
     if DVARS.read().unwrap().len() + 1 > DVAR_COUNT_MAX {
         com::errorln!(
             com::ErrorParm::FATAL,
@@ -1239,12 +1386,27 @@ pub fn register_enumeration(
         return Err(());
     }
 
+    // A Dvar of another type that already exists under this name is
+    // replaced outright, carrying its value over provided that value is
+    // part of the domain.
+    let loaded = DVARS
+        .write()
+        .unwrap()
+        .remove(name)
+        .map(|d| d.current.to_string());
+    let value = match loaded {
+        Some(loaded) => {
+            enumeration_value_or_default(name, loaded, &domain, value)
+        }
+        None => value,
+    };
+
     let dvar = DvarBuilder::new()
         .name(name)
         .description(description.unwrap_or_default().to_owned())
         .flags(flags)
         .type_enumeration()
-        .domain(&domain.unwrap_or_default())
+        .domain(&domain)
         .value(value)
         .build();
 
@@ -1264,6 +1426,7 @@ pub fn register_enumeration(
         );
         Err(())
     } else {
+        apply_pending(name);
         Ok(())
     }
 }
@@ -1406,6 +1569,10 @@ pub fn register_color(
     let b = blue.clamp(0.0, 1.0).mul_add(255.0, 0.001);
     let a = alpha.clamp(0.0, 1.0).mul_add(255.0, 0.001);
 
+    crate::dvar::INITIALIZED.debug_check("dvar::register");
+    if name_is_aliased(name) {
+        return Err(());
+    }
     if DVARS.write().unwrap().len() + 1 > DVAR_COUNT_MAX {
         com::errorln!(
             com::ErrorParm::FATAL,
@@ -1440,6 +1607,7 @@ pub fn register_color(
         );
         Err(())
     } else {
+        apply_pending(name);
         Ok(())
     }
 }
@@ -1586,6 +1754,10 @@ pub fn register_int64(
     flags: DvarFlags,
     description: Option<&str>,
 ) -> Result<(), ()> {
+    crate::dvar::INITIALIZED.debug_check("dvar::register");
+    if name_is_aliased(name) {
+        return Err(());
+    }
     if DVARS.write().unwrap().len() + 1 > DVAR_COUNT_MAX {
         com::errorln!(
             com::ErrorParm::FATAL,
@@ -1621,6 +1793,7 @@ pub fn register_int64(
         );
         Err(())
     } else {
+        apply_pending(name);
         Ok(())
     }
 }
@@ -1771,6 +1944,10 @@ pub fn register_linear_color_rgb(
     flags: DvarFlags,
     description: Option<&str>,
 ) -> Result<(), ()> {
+    crate::dvar::INITIALIZED.debug_check("dvar::register");
+    if name_is_aliased(name) {
+        return Err(());
+    }
     if DVARS.write().unwrap().len() + 1 > DVAR_COUNT_MAX {
         com::errorln!(
             com::ErrorParm::FATAL,
@@ -1806,6 +1983,7 @@ pub fn register_linear_color_rgb(
         );
         Err(())
     } else {
+        apply_pending(name);
         Ok(())
     }
 }
@@ -1990,6 +2168,10 @@ pub fn register_color_xyz(
     flags: DvarFlags,
     description: Option<&str>,
 ) -> Result<(), ()> {
+    crate::dvar::INITIALIZED.debug_check("dvar::register");
+    if name_is_aliased(name) {
+        return Err(());
+    }
     if DVARS.write().unwrap().len() + 1 > DVAR_COUNT_MAX {
         com::errorln!(
             com::ErrorParm::FATAL,
@@ -2024,6 +2206,7 @@ pub fn register_color_xyz(
         );
         Err(())
     } else {
+        apply_pending(name);
         Ok(())
     }
 }
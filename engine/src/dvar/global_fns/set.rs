@@ -1,13 +1,16 @@
 use crate::{
+    com,
     common::{Vec2f32, Vec3f32, Vec4f32},
-    dvar::{limits::DvarLimits, value::DvarValue, DvarFlags, SetSource},
+    console,
+    dvar::{limits::DvarLimits, value::DvarValue, Dvar, DvarFlags, SetSource},
 };
 
 use super::{
     exists, find, get_enumeration, register_bool, register_color,
     register_color_xyz, register_enumeration, register_float, register_int,
     register_int64, register_linear_color_rgb, register_string,
-    register_vector2, register_vector3, register_vector4, DVARS,
+    register_vector2, register_vector3, register_vector4, resolve_alias,
+    run_change_callback, DVARS,
 };
 
 /// Sets the value of an existing [`Dvar`] from the supplied [`SetSource`]
@@ -48,18 +51,81 @@ pub fn set_variant_from_source(
     value: DvarValue,
     source: SetSource,
 ) -> Result<(), ()> {
-    match find(name) {
-        Some(_) => {
-            DVARS
-                .write()
-                .unwrap()
-                .get_mut(name)
-                .unwrap()
-                .set_variant(value, source);
-            Ok(())
-        }
-        None => Err(()),
+    crate::dvar::INITIALIZED.debug_check("dvar::set");
+    let name = resolve_alias(name);
+    if find(&name).is_none() {
+        return Err(());
+    }
+
+    // sv_cheats has to be read before the write lock is taken, since
+    // reading it takes the read lock
+    let cheats_enabled = crate::dvar::cheats_enabled();
+    // A refused enumeration value still resets the Dvar, so the callback
+    // has to be checked for either way
+    let (res, changed) = {
+        let mut writer = DVARS.write().unwrap();
+        let d = writer.get_mut(&*name).unwrap();
+        let old = d.current.clone();
+        let res = d.set_variant(value, source, cheats_enabled);
+        (res, d.current != old)
+    };
+
+    if changed {
+        run_change_callback(&name);
     }
+    res
+}
+
+/// Sets the value of an existing [`Dvar`] from its string representation.
+///
+/// `value` is parsed into the same type as the [`Dvar`]'s current value
+/// (see [`DvarValue::parse_as_same_type`] for the accepted forms) and
+/// clamped to the [`Dvar`]'s domain, and the [`Dvar`] is then updated from
+/// source `source`.
+///
+/// # Arguments
+/// * `name` - A [`String`] that holds the name of the [`Dvar`]
+/// to be updated.
+/// * `value` - The string representation of the value to set.
+/// * `source` - The [`SetSource`] to set the value with.
+///
+/// # Return Value
+///
+/// Returns [`Err`] if the [`Dvar`] doesn't exist, if `value` couldn't be
+/// parsed, or if the [`Dvar`] refused it (e.g. because it's read-only),
+/// [`Ok`] otherwise.
+///
+/// # Panics
+/// Panics if the write lock for [`DVARS`] can't be acquired (usually because
+/// the write lock or a read lock is held by a function farther up the
+/// call stack).
+///
+/// # Example
+/// ```
+/// set_from_string_from_source("r_clearColor", "255 0 255 255",
+///     SetSource::External);
+/// ```
+pub fn set_from_string_from_source(
+    name: &str,
+    value: &str,
+    source: SetSource,
+) -> Result<(), ()> {
+    let Some(dvar) = find(name) else {
+        return Err(());
+    };
+
+    let Some(mut parsed) = dvar.current.parse_as_same_type(value) else {
+        com::println!(
+            console::Channel::ERROR,
+            "\'{}\' is not a valid value for dvar \'{}\'",
+            value,
+            name,
+        );
+        return Err(());
+    };
+
+    Dvar::clamp_value_to_domain(&mut parsed, &dvar.domain);
+    set_variant_from_source(name, parsed, source)
 }
 
 /// Sets the value of an existing [`Dvar`] from the supplied [`SetSource`]
@@ -230,7 +296,10 @@ pub fn set_or_register_bool(
 ///
 /// # Return Value
 ///
-/// Returns true if set was successful, false otherwise.
+/// `value` is clamped to the [`Dvar`]'s domain rather than rejected.
+/// Returns the clamped value, or [`Err`] if the [`Dvar`] doesn't exist,
+/// isn't a float, or refused the value (e.g. because it's read-only). A
+/// [`DvarFlags::LATCHED`] [`Dvar`] latches the clamped value.
 ///
 /// # Panics
 /// Panics if the write lock for [`DVARS`] can't be acquired (usually because
@@ -252,8 +321,11 @@ pub fn set_float_from_source(
     name: &str,
     value: f32,
     source: SetSource,
-) -> Result<(), ()> {
-    set_variant_from_source(name, DvarValue::Float(value), source)
+) -> Result<f32, ()> {
+    let limits = find(name).ok_or(())?.domain.as_float_limits().ok_or(())?;
+    let value = value.clamp(limits.min, limits.max);
+    set_variant_from_source(name, DvarValue::Float(value), source)?;
+    Ok(value)
 }
 
 /// Sets the value of an existing [`Dvar`].
@@ -269,7 +341,10 @@ pub fn set_float_from_source(
 ///
 /// # Return Value
 ///
-/// Returns true if set was successful, false otherwise.
+/// `value` is clamped to the [`Dvar`]'s domain rather than rejected.
+/// Returns the clamped value, or [`Err`] if the [`Dvar`] doesn't exist,
+/// isn't a float, or refused the value (e.g. because it's read-only). A
+/// [`DvarFlags::LATCHED`] [`Dvar`] latches the clamped value.
 ///
 /// # Panics
 /// Panics if the write lock for [`DVARS`] can't be acquired (usually because
@@ -286,7 +361,7 @@ pub fn set_float_from_source(
 ///     set_float_internal(name, value);
 /// }
 /// ```
-pub fn set_float_internal(name: &str, value: f32) -> Result<(), ()> {
+pub fn set_float_internal(name: &str, value: f32) -> Result<f32, ()> {
     set_float_from_source(name, value, SetSource::Internal)
 }
 
@@ -303,7 +378,10 @@ pub fn set_float_internal(name: &str, value: f32) -> Result<(), ()> {
 ///
 /// # Return Value
 ///
-/// Returns true if set was successful, false otherwise.
+/// `value` is clamped to the [`Dvar`]'s domain rather than rejected.
+/// Returns the clamped value, or [`Err`] if the [`Dvar`] doesn't exist,
+/// isn't a float, or refused the value (e.g. because it's read-only). A
+/// [`DvarFlags::LATCHED`] [`Dvar`] latches the clamped value.
 ///
 /// # Panics
 /// Panics if the write lock for [`DVARS`] can't be acquired (usually because
@@ -320,7 +398,7 @@ pub fn set_float_internal(name: &str, value: f32) -> Result<(), ()> {
 ///     set_float(name, value);
 /// }
 /// ```
-pub fn set_float(name: &str, value: f32) -> Result<(), ()> {
+pub fn set_float(name: &str, value: f32) -> Result<f32, ()> {
     set_float_from_source(name, value, SetSource::External)
 }
 
@@ -375,7 +453,7 @@ pub fn set_or_register_float(
     description: Option<&str>,
 ) -> Result<(), ()> {
     if exists(name) {
-        set_float_internal(name, value)
+        set_float_internal(name, value).map(|_| ())
     } else {
         register_float(name, value, min, max, flags, description)
     }
@@ -890,7 +968,10 @@ pub fn set_or_register_vector4(
 ///
 /// # Return Value
 ///
-/// Returns true if set was successful, false otherwise.
+/// `value` is clamped to the [`Dvar`]'s domain rather than rejected.
+/// Returns the clamped value, or [`Err`] if the [`Dvar`] doesn't exist,
+/// isn't an int, or refused the value (e.g. because it's read-only). A
+/// [`DvarFlags::LATCHED`] [`Dvar`] latches the clamped value.
 ///
 /// # Panics
 /// Panics if the write lock for [`DVARS`] can't be acquired (usually because
@@ -912,8 +993,11 @@ pub fn set_int_from_source(
     name: &str,
     value: i32,
     source: SetSource,
-) -> Result<(), ()> {
-    set_variant_from_source(name, DvarValue::Int(value), source)
+) -> Result<i32, ()> {
+    let limits = find(name).ok_or(())?.domain.as_int_limits().ok_or(())?;
+    let value = value.clamp(limits.min, limits.max);
+    set_variant_from_source(name, DvarValue::Int(value), source)?;
+    Ok(value)
 }
 
 /// Sets the value of an existing [`Dvar`].
@@ -929,7 +1013,10 @@ pub fn set_int_from_source(
 ///
 /// # Return Value
 ///
-/// Returns true if set was successful, false otherwise.
+/// `value` is clamped to the [`Dvar`]'s domain rather than rejected.
+/// Returns the clamped value, or [`Err`] if the [`Dvar`] doesn't exist,
+/// isn't an int, or refused the value (e.g. because it's read-only). A
+/// [`DvarFlags::LATCHED`] [`Dvar`] latches the clamped value.
 ///
 /// # Panics
 /// Panics if the write lock for [`DVARS`] can't be acquired (usually because
@@ -946,7 +1033,7 @@ pub fn set_int_from_source(
 ///     set_int(name, value);
 /// }
 /// ```
-pub fn set_int_internal(name: &str, value: i32) -> Result<(), ()> {
+pub fn set_int_internal(name: &str, value: i32) -> Result<i32, ()> {
     set_int_from_source(name, value, SetSource::Internal)
 }
 
@@ -963,7 +1050,10 @@ pub fn set_int_internal(name: &str, value: i32) -> Result<(), ()> {
 ///
 /// # Return Value
 ///
-/// Returns true if set was successful, false otherwise.
+/// `value` is clamped to the [`Dvar`]'s domain rather than rejected.
+/// Returns the clamped value, or [`Err`] if the [`Dvar`] doesn't exist,
+/// isn't an int, or refused the value (e.g. because it's read-only). A
+/// [`DvarFlags::LATCHED`] [`Dvar`] latches the clamped value.
 ///
 /// # Panics
 /// Panics if the write lock for [`DVARS`] can't be acquired (usually because
@@ -980,7 +1070,7 @@ pub fn set_int_internal(name: &str, value: i32) -> Result<(), ()> {
 ///     set_int(name, value);
 /// }
 /// ```
-pub fn set_int(name: &str, value: i32) -> Result<(), ()> {
+pub fn set_int(name: &str, value: i32) -> Result<i32, ()> {
     set_int_from_source(name, value, SetSource::External)
 }
 
@@ -1028,7 +1118,7 @@ pub fn set_or_register_int(
     description: Option<&str>,
 ) -> Result<(), ()> {
     if exists(name) {
-        set_int_internal(name, value)
+        set_int_internal(name, value).map(|_| ())
     } else {
         register_int(name, value, min, max, flags, description)
     }
@@ -1439,10 +1529,16 @@ pub fn add_to_enumeration_domain(
     name: &str,
     domain_str: &str,
 ) -> Result<(), ()> {
-    match find(name) {
+    let name = resolve_alias(name);
+    match find(&name) {
         Some(d) => match d.current {
             DvarValue::Enumeration(_) => {
-                match &mut DVARS.write().unwrap().get_mut(name).unwrap().domain
+                match &mut DVARS
+                    .write()
+                    .unwrap()
+                    .get_mut(&*name)
+                    .unwrap()
+                    .domain
                 {
                     DvarLimits::Enumeration(l) => {
                         l.strings.insert(domain_str.to_owned());
@@ -1461,10 +1557,16 @@ pub fn remove_from_enumeration_domain(
     name: &str,
     domain_str: &str,
 ) -> Result<(), ()> {
-    match find(name) {
+    let name = resolve_alias(name);
+    match find(&name) {
         Some(d) => match d.current {
             DvarValue::Enumeration(_) => {
-                match &mut DVARS.write().unwrap().get_mut(name).unwrap().domain
+                match &mut DVARS
+                    .write()
+                    .unwrap()
+                    .get_mut(&*name)
+                    .unwrap()
+                    .domain
                 {
                     DvarLimits::Enumeration(l) => {
                         l.strings.remove(&domain_str.to_owned());
@@ -126,4 +126,101 @@ impl DvarValue {
             _ => None,
         }
     }
+
+    /// Parses `s` into a value of the same type as `self`.
+    ///
+    /// Vectors are parsed from their space-separated form (e.g.
+    /// `"1.0 0.5 0.0"`), and colors from their space-separated 0-255 form
+    /// (e.g. `"255 128 0 255"`, with the alpha component being optional).
+    /// Bools also accept the spellings older configs used (`"yes"`,
+    /// `"off"`, any integer, etc.).
+    /// Returns [`None`] if `s` can't be parsed as such a value.
+    pub fn parse_as_same_type(&self, s: &str) -> Option<Self> {
+        let s = s.trim();
+        match self {
+            Self::Bool(_) => parse_bool(s).map(Self::Bool),
+            Self::Float(_) => s.parse().ok().map(Self::Float),
+            Self::Vector2(_) => match *parse_floats(s)?.as_slice() {
+                [x, y] => Some(Self::Vector2((x, y))),
+                _ => None,
+            },
+            Self::Vector3(_) => match *parse_floats(s)?.as_slice() {
+                [x, y, z] => Some(Self::Vector3((x, y, z))),
+                _ => None,
+            },
+            Self::Vector4(_) => match *parse_floats(s)?.as_slice() {
+                [x, y, z, w] => Some(Self::Vector4((x, y, z, w))),
+                _ => None,
+            },
+            Self::Int(_) => s.parse().ok().map(Self::Int),
+            Self::String(_) => Some(Self::String(s.to_owned())),
+            Self::Enumeration(_) => Some(Self::Enumeration(s.to_owned())),
+            Self::Color(_) => {
+                let c = parse_floats(s)?
+                    .into_iter()
+                    .map(|f| f.clamp(0.0, 255.0))
+                    .collect::<Vec<_>>();
+                match *c.as_slice() {
+                    [r, g, b] => Some(Self::Color((r, g, b, 255.0))),
+                    [r, g, b, a] => Some(Self::Color((r, g, b, a))),
+                    _ => None,
+                }
+            }
+            Self::Int64(_) => s.parse().ok().map(Self::Int64),
+            Self::LinearColorRGB(_) => match *parse_floats(s)?.as_slice() {
+                [r, g, b] => Some(Self::LinearColorRGB((r, g, b))),
+                _ => None,
+            },
+            Self::ColorXYZ(_) => match *parse_floats(s)?.as_slice() {
+                [x, y, z] => Some(Self::ColorXYZ((x, y, z))),
+                _ => None,
+            },
+        }
+    }
+
+    /// Formats the value in the form accepted by
+    /// [`DvarValue::parse_as_same_type`], e.g. for writing to a config file.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::match_same_arms
+    )]
+    pub fn to_config_string(&self) -> String {
+        let byte = |f: f32| f.clamp(0.0, 255.0).round() as u8;
+        match self {
+            Self::Bool(b) => if *b { "1" } else { "0" }.to_owned(),
+            Self::Float(f) => f.to_string(),
+            Self::Vector2(v) => format!("{} {}", v.0, v.1),
+            Self::Vector3(v) => format!("{} {} {}", v.0, v.1, v.2),
+            Self::Vector4(v) => format!("{} {} {} {}", v.0, v.1, v.2, v.3),
+            Self::Int(i) => i.to_string(),
+            Self::String(s) => s.clone(),
+            Self::Enumeration(s) => s.clone(),
+            Self::Color(c) => format!(
+                "{} {} {} {}",
+                byte(c.0),
+                byte(c.1),
+                byte(c.2),
+                byte(c.3)
+            ),
+            Self::Int64(i) => i.to_string(),
+            Self::LinearColorRGB(c) => format!("{} {} {}", c.0, c.1, c.2),
+            Self::ColorXYZ(c) => format!("{} {} {}", c.0, c.1, c.2),
+        }
+    }
+}
+
+// Parses a bool from any of the spellings configs have used for one,
+// treating any integer other than 0 as true
+fn parse_bool(s: &str) -> Option<bool> {
+    match s.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" => Some(true),
+        "false" | "no" | "off" => Some(false),
+        s => s.parse::<i64>().ok().map(|i| i != 0),
+    }
+}
+
+// Parses a space-separated list of floats
+fn parse_floats(s: &str) -> Option<Vec<f32>> {
+    s.split_whitespace().map(|t| t.parse().ok()).collect()
 }
@@ -229,8 +229,12 @@ pub fn init_filesystem(dev: bool) {
         *fh = None;
     }
     startup("main", dev);
+    INITIALIZED.set();
 }
 
+static INITIALIZED: util::InitFlag =
+    util::InitFlag::new("fs::init_filesystem");
+
 fn display_path(_pure: bool) {
     todo!()
 }
@@ -357,7 +361,13 @@ fn startup(gamedir: impl AsRef<Path>, _dev: bool) {
     )
 }
 
+// Registers the Dvars read by `startup`. Does nothing if they already have
+// been, since `startup` runs again when restrictions change.
 fn register_dvars() {
+    if dvar::exists("fs_ignoreLocalized") {
+        return;
+    }
+
     dvar::register_bool(
         "fs_ignoreLocalized",
         false,
@@ -365,6 +375,81 @@ fn register_dvars() {
         "Ignore localized files".into(),
     )
     .unwrap();
+    dvar::register_int(
+        "fs_debug",
+        0,
+        Some(0),
+        Some(2),
+        dvar::DvarFlags::empty(),
+        Some("Enable file system debugging information"),
+    )
+    .unwrap();
+    com::startup_variable("fs_debug");
+
+    // The search paths are only built once, so these can't be changed after
+    // startup other than with +set on the command line
+    let path_flags = dvar::DvarFlags::WRITE_PROTECTED;
+    let startup_path = |name: &str, default: String| {
+        com::startup_variable_value(name).unwrap_or(default)
+    };
+    dvar::register_bool(
+        "fs_copyfiles",
+        false,
+        path_flags,
+        Some("Copy all used files to another location"),
+    )
+    .unwrap();
+    dvar::register_bool(
+        "fs_usedevdir",
+        false,
+        path_flags,
+        Some("Use development directories"),
+    )
+    .unwrap();
+    dvar::register_bool(
+        "fs_restrict",
+        false,
+        path_flags,
+        Some("Restrict file access for demos"),
+    )
+    .unwrap();
+    dvar::register_string(
+        "fs_cdpath",
+        &startup_path("fs_cdpath", sys::default_cd_path().to_owned()),
+        path_flags,
+        Some("CD path"),
+    )
+    .unwrap();
+    let basepath =
+        startup_path("fs_basepath", sys::cwd().to_string_lossy().into_owned());
+    dvar::register_string(
+        "fs_basepath",
+        &basepath,
+        path_flags,
+        Some("Base game path"),
+    )
+    .unwrap();
+    dvar::register_string(
+        "fs_homepath",
+        &startup_path("fs_homepath", basepath),
+        path_flags,
+        Some("Game home path"),
+    )
+    .unwrap();
+    dvar::register_string(
+        "fs_basegame",
+        &startup_path("fs_basegame", String::new()),
+        path_flags,
+        Some("Base game name"),
+    )
+    .unwrap();
+    dvar::register_string(
+        "fs_gameDirVar",
+        &startup_path("fs_gameDirVar", String::new()),
+        path_flags,
+        Some("Game data directory; either empty or a subdirectory of mods/"),
+    )
+    .unwrap();
 }
 
 /// Representation of threads that can call functions in this module.
@@ -736,6 +821,19 @@ fn use_searchpath(sp: &Searchpath) -> bool {
     }
 }
 
+/// Returns the on-disk directories (i.e. not IWDs) that files are looked up
+/// in, in the order they're searched, skipping any that aren't in use.
+pub fn searchpath_dirs() -> Vec<PathBuf> {
+    FS_SEARCHPATHS
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|sp| use_searchpath(sp))
+        .filter_map(|sp| sp.qdir.dir())
+        .map(|dir| dir.path.join(&dir.gamedir))
+        .collect()
+}
+
 static FS_IWD_FILE_COUNT: AtomicUsize = AtomicUsize::new(0);
 
 /// Loads a zip file using the supplied file name.
@@ -1272,6 +1370,7 @@ fn open_file_read_for_thread(
 pub fn open_file_read_current_thread(
     filename: impl AsRef<Path>,
 ) -> Result<(Fd, u64), std::io::Error> {
+    INITIALIZED.debug_check("fs::open_file_read");
     if let Some(thread) = get_current_thread() {
         open_file_read_for_thread(filename, thread)
     } else {
@@ -1297,6 +1396,7 @@ pub fn open_file_read(
 ///
 /// Returns an opaque file descriptor on success.
 pub fn open_file_append(filename: impl AsRef<Path>) -> std::io::Result<Fd> {
+    INITIALIZED.debug_check("fs::open_file_append");
     let ospath = build_os_path(
         dvar::get_string("fs_homepath").unwrap(),
         Some(&*FS_GAMEDIR.read().unwrap()),
@@ -1417,6 +1517,7 @@ fn open_file_write_to_dir_for_thread(
 ///
 /// Returns an opaque file descriptor on success.
 pub fn open_file_write(filename: impl AsRef<Path>) -> std::io::Result<Fd> {
+    INITIALIZED.debug_check("fs::open_file_write");
     open_file_write_to_dir_for_thread(
         filename,
         Some(&*FS_GAMEDIR.read().unwrap()),
@@ -1562,35 +1663,120 @@ pub fn read_file(
     }
     let (fd, file_size) = open_file_read_current_thread(filename)?;
     FS_LOADSTACK.increment_wrapping();
-    let mut buf = Vec::with_capacity(file_size as _);
-    read(&fd, &mut buf).map(|_| ReadFile(buf))
+    let mut buf = vec![0; file_size as _];
+    let len = read(&fd, &mut buf)?;
+    buf.truncate(len);
+    Ok(ReadFile(buf))
+}
+
+/// The reasons [`write_file`] can fail.
+#[derive(Debug)]
+pub enum WriteFileError {
+    /// The file, or the directory it's in, can't be written to.
+    PermissionDenied(std::io::Error),
+    /// There's no space left for the file.
+    DiskFull(std::io::Error),
+    /// The path is malformed, or part of it isn't a directory.
+    InvalidPath(std::io::Error),
+    /// Any other I/O error.
+    Other(std::io::Error),
+}
+
+impl From<std::io::Error> for WriteFileError {
+    fn from(e: std::io::Error) -> Self {
+        use std::io::ErrorKind;
+
+        match e.kind() {
+            ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem => {
+                Self::PermissionDenied(e)
+            }
+            ErrorKind::StorageFull
+            | ErrorKind::FilesystemQuotaExceeded
+            | ErrorKind::FileTooLarge => Self::DiskFull(e),
+            ErrorKind::InvalidFilename
+            | ErrorKind::InvalidInput
+            | ErrorKind::NotADirectory
+            | ErrorKind::IsADirectory
+            | ErrorKind::NotFound => Self::InvalidPath(e),
+            _ => Self::Other(e),
+        }
+    }
 }
 
-/// Writes [`data`] into the specified file.
+impl core::fmt::Display for WriteFileError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::PermissionDenied(e) => write!(f, "permission denied ({})", e),
+            Self::DiskFull(e) => write!(f, "disk full ({})", e),
+            Self::InvalidPath(e) => write!(f, "invalid path ({})", e),
+            Self::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Writes [`data`] into the specified file under `fs_homepath`, creating
+/// it and any parent directories if they don't already exist.
 ///
-/// Writes at most [`data.len()`] bytes and returns the number of bytes written
-/// on success.
+/// The write is atomic (see [`write_file_atomic`]), so if it's interrupted,
+/// any existing file is left as it was.
 pub fn write_file(
     path: impl AsRef<Path>,
     data: &[u8],
-) -> std::io::Result<usize> {
+) -> Result<(), WriteFileError> {
+    INITIALIZED.debug_check("fs::write_file");
     assert_ne!(path.as_ref(), Path::new(""));
 
-    if let Ok(fd) = open_file_write(&path) {
-        let r = write(&fd, data);
-        if r.is_err() {
-            delete(&path)?;
-        }
-
-        r
-    } else {
+    let homepath = dvar::get_string("fs_homepath").unwrap();
+    let ospath = build_os_path(
+        homepath,
+        Some(&*FS_GAMEDIR.read().unwrap()),
+        path.as_ref(),
+    );
+    let r = write_file_atomic(&ospath, data);
+    if let Err(ref e) = r {
         com::println!(
             console::Channel::FILES,
-            "Failed to open {}",
-            path.as_ref().display()
+            "Failed to write {}: {}",
+            ospath.display(),
+            e
         );
-        Err(std::io::ErrorKind::NotFound.into())
     }
+    r
+}
+
+/// Writes [`data`] to a temporary file next to [`ospath`], then renames it
+/// over [`ospath`], so that [`ospath`] is either entirely replaced or left
+/// untouched. Parent directories are created if they don't exist.
+///
+/// If the write fails, the temporary file is removed.
+pub fn write_file_atomic(
+    ospath: impl AsRef<Path>,
+    data: &[u8],
+) -> Result<(), WriteFileError> {
+    let ospath = ospath.as_ref();
+    let invalid = || std::io::Error::from(std::io::ErrorKind::InvalidFilename);
+    let dir = ospath.parent().ok_or_else(invalid)?;
+    let name = ospath.file_name().ok_or_else(invalid)?;
+
+    std::fs::create_dir_all(dir)?;
+
+    let mut tmp_name = name.to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = dir.join(tmp_name);
+    let r = std::fs::File::create(&tmp_path)
+        .and_then(|mut f| {
+            f.write_all(data)?;
+            // Make sure the data has actually hit the disk before the
+            // rename makes it the real file
+            f.sync_all()
+        })
+        .and_then(|()| std::fs::rename(&tmp_path, ospath));
+    if let Err(e) = r {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(e.into());
+    }
+
+    Ok(())
 }
 
 /// Copies [`src`] into [`dest`], creating [`dest`] if it doesn't already
@@ -1630,3 +1816,62 @@ pub fn copy_file(
 fn touch_file(filename: impl AsRef<Path>) -> std::io::Result<bool> {
     open_file_read(filename).map(|(_, size)| size != 0xFFFF_FFFF_FFFF_FFFF)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A directory of its own for a test to write into, removed again when
+    // it's dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("opent5-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn write_file_atomic_creates_new_files() {
+        let dir = TempDir::new("write_new");
+        let path = dir.0.join("sub").join("new.cfg");
+
+        write_file_atomic(&path, b"seta test 1\n").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"seta test 1\n");
+        assert!(dir.0.join("sub").join("new.cfg.tmp").exists() == false);
+    }
+
+    #[test]
+    fn write_file_atomic_replaces_existing_files() {
+        let dir = TempDir::new("write_overwrite");
+        let path = dir.0.join("config.cfg");
+        std::fs::write(&path, b"a much longer old file\n").unwrap();
+
+        write_file_atomic(&path, b"new\n").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new\n");
+        assert!(dir.0.join("config.cfg.tmp").exists() == false);
+    }
+
+    #[test]
+    fn write_file_atomic_removes_temp_file_on_failure() {
+        let dir = TempDir::new("write_failure");
+        // A file can't be renamed over a directory that has something in it
+        let path = dir.0.join("config.cfg");
+        std::fs::create_dir(&path).unwrap();
+        std::fs::write(path.join("keep"), b"").unwrap();
+
+        assert!(write_file_atomic(&path, b"new\n").is_err());
+        assert!(path.is_dir());
+        assert!(dir.0.join("config.cfg.tmp").exists() == false);
+    }
+}
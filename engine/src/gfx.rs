@@ -381,9 +381,17 @@ pub struct ListBoxDef {
     row_count: i32,
 }
 
+/// The settings a window is created (or recreated) with.
+///
+/// With the `serde` feature enabled, these can be saved and restored as a
+/// unit. The window and monitor handles only mean something to the running
+/// process, so they're skipped, and come back as [`None`].
 #[derive(Default, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WindowParms {
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub window_handle: Option<WindowHandle>,
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub monitor_handle: Option<MonitorHandle>,
     pub hz: f32,
     pub fullscreen: bool,
@@ -428,6 +436,36 @@ impl WindowTarget {
             handle: None,
         }
     }
+
+    /// Updates the target's dimensions after its window has been resized.
+    ///
+    /// The window shouldn't shrink below the engine's minimum resolution,
+    /// but it can (e.g. when it's snapped or tiled by the window manager),
+    /// in which case the dimensions are clamped to it.
+    ///
+    /// The swapchain presenting to the window is owned by the renderer, so
+    /// this should be called through [`crate::render::resize_window`], which
+    /// reconfigures it to the clamped size.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width < MIN_HORIZONTAL_RESOLUTION || height < MIN_VERTICAL_RESOLUTION
+        {
+            com::dprintln!(
+                console::Channel::GFX,
+                "window resized to {}x{}, below the minimum of {}x{}",
+                width,
+                height,
+                MIN_HORIZONTAL_RESOLUTION,
+                MIN_VERTICAL_RESOLUTION
+            );
+        }
+        self.width = width.max(MIN_HORIZONTAL_RESOLUTION);
+        self.height = height.max(MIN_VERTICAL_RESOLUTION);
+        debug_assert!(
+            self.width >= MIN_HORIZONTAL_RESOLUTION
+                && self.height >= MIN_VERTICAL_RESOLUTION,
+            "window target is below the minimum resolution"
+        );
+    }
 }
 
 impl Default for WindowTarget {
@@ -453,3 +491,49 @@ pub struct Globals {
     pub ui_3d_use_frame_buffer: u8,
     pub ui_3d_render_target: u8,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resize_clamps_to_minimum_resolution() {
+        let mut target = WindowTarget::new();
+        target.resize(1920, 1080);
+        assert_eq!((target.width, target.height), (1920, 1080));
+
+        target.resize(320, 1080);
+        assert_eq!(
+            (target.width, target.height),
+            (MIN_HORIZONTAL_RESOLUTION, 1080)
+        );
+        target.resize(0, 0);
+        assert_eq!(
+            (target.width, target.height),
+            (MIN_HORIZONTAL_RESOLUTION, MIN_VERTICAL_RESOLUTION)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn window_parms_round_trip_through_serde() {
+        let parms = WindowParms {
+            hz: 144.0,
+            fullscreen: true,
+            x: 10,
+            y: 20,
+            scene_width: 2560,
+            scene_height: 1440,
+            display_width: 1920,
+            display_height: 1080,
+            aa_samples: 4,
+            ..WindowParms::new()
+        };
+
+        let json = serde_json::to_string(&parms).unwrap();
+        let restored: WindowParms = serde_json::from_str(&json).unwrap();
+        // WindowParms isn't PartialEq (the handles aren't comparable), but
+        // its Debug output covers every field
+        assert_eq!(format!("{:?}", restored), format!("{:?}", parms));
+    }
+}
@@ -3,33 +3,200 @@
 pub mod gpad;
 pub mod keyboard;
 pub mod mouse;
+pub mod record;
+pub use record::{record_start, record_stop, replay};
 
 use crate::*;
 
+#[cfg(debug_assertions)]
+use crate::util::EasierAtomic;
+#[cfg(debug_assertions)]
+use core::sync::atomic::AtomicUsize;
 use core::sync::atomic::Ordering;
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{Mutex, RwLock},
+};
 
 use lazy_static::lazy_static;
 
+/// The conditions that all have to hold for the game to take input.
+///
+/// Each subsystem sets or clears only its own reason with [`set_reason`],
+/// so that, e.g., the console closing can't turn input back on while the
+/// window is unfocused.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InputReason {
+    /// The game window has focus.
+    WindowFocus,
+    /// The console isn't open.
+    ConsoleClosed,
+    /// The game itself wants input.
+    Gameplay,
+}
+
+impl InputReason {
+    const ALL: [Self; 3] =
+        [Self::WindowFocus, Self::ConsoleClosed, Self::Gameplay];
+}
+
 lazy_static! {
     static ref APP_ACTIVE: AtomicBool = AtomicBool::new(false);
+    // The reasons that currently hold. The window starts out unfocused, but
+    // the console starts out closed and gameplay wants input by default.
+    static ref REASONS: RwLock<HashSet<InputReason>> = RwLock::new(
+        HashSet::from([InputReason::ConsoleClosed, InputReason::Gameplay])
+    );
 }
 
-pub fn activate(app_active: bool) {
-    APP_ACTIVE.store(app_active, Ordering::SeqCst);
-    if app_active == false {
+fn reasons_allow_input(reasons: &HashSet<InputReason>) -> bool {
+    InputReason::ALL.iter().all(|r| reasons.contains(r))
+}
+
+/// Sets whether `reason` holds, then activates or deactivates input
+/// depending on whether every [`InputReason`] now holds.
+pub fn set_reason(reason: InputReason, holds: bool) {
+    let active = {
+        let mut reasons = REASONS.write().unwrap();
+        if holds {
+            reasons.insert(reason);
+        } else {
+            reasons.remove(&reason);
+        }
+        reasons_allow_input(&reasons)
+    };
+
+    APP_ACTIVE.store(active, Ordering::SeqCst);
+    if active == false {
         mouse::deactivate();
     } else {
         mouse::activate(1);
     }
 }
 
+/// Returns whether the game is currently taking input.
+pub fn is_active() -> bool {
+    APP_ACTIVE.load(Ordering::SeqCst)
+}
+
+/// How many of the most recent input events [`latency_ms`] averages over.
+const LATENCY_SAMPLE_COUNT: usize = 64;
+
+lazy_static! {
+    // How long, in milliseconds, each of the most recently handled input
+    // events spent queued
+    static ref LATENCY_SAMPLES: Mutex<VecDeque<isize>> =
+        Mutex::new(VecDeque::with_capacity(LATENCY_SAMPLE_COUNT));
+}
+
+/// Returns how many milliseconds an event queued at `enqueued` waited
+/// before being handled at `processed` (both as returned by
+/// [`sys::milliseconds`]). Never negative.
+pub const fn latency_between(enqueued: isize, processed: isize) -> isize {
+    let latency = processed.saturating_sub(enqueued);
+    if latency < 0 {
+        0
+    } else {
+        latency
+    }
+}
+
+/// Records how long `ev` spent queued, given that it's being handled at
+/// `now`, for [`latency_ms`]. Only key, mouse button, and character events
+/// count; anything else isn't input the player would feel lag on.
+pub fn record_latency(ev: &sys::Event, now: isize) {
+    if matches!(
+        ev.event_type(),
+        sys::EventType::Key(..)
+            | sys::EventType::Mouse(..)
+            | sys::EventType::Character(_)
+    ) == false
+    {
+        return;
+    }
+
+    // Not ev.time(), which is often stamped by the OS on a different clock
+    let latency = latency_between(ev.queued_at(), now);
+    {
+        let mut samples = LATENCY_SAMPLES.lock().unwrap();
+        if samples.len() >= LATENCY_SAMPLE_COUNT {
+            samples.pop_front();
+        }
+        samples.push_back(latency);
+    }
+
+    if dvar::get_bool("com_showInputLatency").unwrap_or(false) {
+        com::println!(
+            console::Channel::SYSTEM,
+            "input latency: {} ms (average {:.1} ms)",
+            latency,
+            latency_ms().unwrap_or_default()
+        );
+    }
+}
+
+/// Returns the average time, in milliseconds, that the most recent input
+/// events spent between being queued and being handled, or [`None`] if
+/// there hasn't been any input yet.
+#[allow(clippy::cast_precision_loss)]
+pub fn latency_ms() -> Option<f32> {
+    let samples = LATENCY_SAMPLES.lock().unwrap();
+    if samples.is_empty() {
+        return None;
+    }
+
+    let total = samples.iter().map(|&ms| ms as f32).sum::<f32>();
+    Some(total / samples.len() as f32)
+}
+
+static INITIALIZED: util::InitFlag = util::InitFlag::new("input::init");
+
+// Number of subsystems started by `startup` that `shutdown` hasn't stopped
+// yet. Only tracked in debug builds, to catch restarts that leak or start
+// something twice.
+#[cfg(debug_assertions)]
+static STARTED_SUBSYSTEMS: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of subsystems (the mouse, gamepads, etc.) currently
+/// started by [`init`] and not yet stopped by [`shutdown`].
+#[cfg(debug_assertions)]
+pub fn started_subsystem_count() -> usize {
+    STARTED_SUBSYSTEMS.load_relaxed()
+}
+
 fn startup() {
     mouse::startup();
+    #[cfg(debug_assertions)]
+    STARTED_SUBSYSTEMS.increment_wrapping();
     gpad::startup();
+    #[cfg(debug_assertions)]
+    STARTED_SUBSYSTEMS.increment_wrapping();
     dvar::clear_modified("in_mouse").unwrap();
 }
 
+/// Initializes this module.
+///
+/// Does nothing if the module is already initialized. Everything acquired
+/// here is released by [`shutdown`], so the two can be paired any number of
+/// times (e.g. by `in_restart`).
 pub fn init() {
+    if INITIALIZED.is_set() {
+        return;
+    }
+
+    // The Dvars outlive `shutdown`, so they're only registered the first
+    // time through. After that, a restart is what applies a latched
+    // `in_mouse`.
+    if dvar::exists("in_mouse") {
+        dvar::make_latched_value_current("in_mouse").unwrap();
+    } else {
+        register_dvars();
+    }
+    startup();
+    INITIALIZED.set();
+}
+
+fn register_dvars() {
     dvar::register_bool(
         "in_mouse",
         true,
@@ -37,15 +204,106 @@ pub fn init() {
         Some("Initialize the mouse drivers"),
     )
     .unwrap();
-    startup();
+    mouse::register_dvars();
+    gpad::register_dvars();
 }
 
 fn is_foreground_window() -> bool {
     platform::get_platform_vars().active_app
 }
 
-pub const fn shutdown() {
-    mouse::deactivate();
+/// Shuts this module down, releasing everything [`init`] acquired.
+///
+/// Does nothing if the module isn't initialized.
+pub fn shutdown() {
+    if INITIALIZED.is_set() == false {
+        return;
+    }
+
+    mouse::shutdown();
+    #[cfg(debug_assertions)]
+    STARTED_SUBSYSTEMS.decrement_wrapping();
+    gpad::shutdown();
+    #[cfg(debug_assertions)]
+    STARTED_SUBSYSTEMS.decrement_wrapping();
+    INITIALIZED.clear();
+
+    #[cfg(debug_assertions)]
+    assert_eq!(
+        started_subsystem_count(),
+        0,
+        "input::shutdown didn't stop everything input::init started"
+    );
+}
+
+/// The size of the virtual screen that 2D elements (menus, the HUD, etc.)
+/// are laid out in, regardless of the window's actual size.
+pub const VIRTUAL_WIDTH: f64 = 640.0;
+pub const VIRTUAL_HEIGHT: f64 = 480.0;
+
+lazy_static! {
+    static ref CURSOR_POSITION: RwLock<(f64, f64)> =
+        RwLock::new((VIRTUAL_WIDTH / 2.0, VIRTUAL_HEIGHT / 2.0));
+}
+
+/// Maps (`x`, `y`) in a `width`x`height` window whose physical aspect ratio
+/// is `aspect` into the virtual 640x480 screen.
+///
+/// The virtual screen is 4:3, so on wider windows it's centered
+/// horizontally, and on narrower ones vertically, with anything outside of
+/// it clamped to its edges.
+pub fn window_to_virtual(
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    aspect: f64,
+) -> (f64, f64) {
+    if width <= 0.0 || height <= 0.0 {
+        return (VIRTUAL_WIDTH / 2.0, VIRTUAL_HEIGHT / 2.0);
+    }
+
+    let aspect = if aspect > 0.0 { aspect } else { width / height };
+    let virtual_aspect = VIRTUAL_WIDTH / VIRTUAL_HEIGHT;
+    let mut u = x.clamp(0.0, width) / width;
+    let mut v = y.clamp(0.0, height) / height;
+    if aspect > virtual_aspect {
+        u = (u - 0.5) * aspect / virtual_aspect + 0.5;
+    } else {
+        v = (v - 0.5) * virtual_aspect / aspect + 0.5;
+    }
+
+    (
+        (u * VIRTUAL_WIDTH).clamp(0.0, VIRTUAL_WIDTH),
+        (v * VIRTUAL_HEIGHT).clamp(0.0, VIRTUAL_HEIGHT),
+    )
+}
+
+/// Returns where the cursor is in the virtual 640x480 screen (see
+/// [`window_to_virtual`]), as of the last time it moved.
+pub fn cursor_position() -> (f64, f64) {
+    *CURSOR_POSITION.read().unwrap()
+}
+
+/// Updates [`cursor_position`] from the cursor having moved to (`x`, `y`)
+/// in the main window.
+pub fn set_cursor_from_window(x: f64, y: f64) {
+    let (width, height, aspect) = {
+        let config = vid::config();
+        (
+            f64::from(config.display_width),
+            f64::from(config.display_height),
+            f64::from(config.aspect_ratio_window),
+        )
+    };
+    *CURSOR_POSITION.write().unwrap() =
+        window_to_virtual(x, y, width, height, aspect);
+}
+
+/// Moves [`cursor_position`] back to the center of the virtual screen.
+pub fn center_cursor() {
+    *CURSOR_POSITION.write().unwrap() =
+        (VIRTUAL_WIDTH / 2.0, VIRTUAL_HEIGHT / 2.0);
 }
 
 #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
@@ -77,3 +335,210 @@ pub fn update_use_count() {
     }
     cg.use_held = false;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    // Sets every reason to `holds`, so each test starts from a known state
+    fn set_all_reasons(holds: bool) {
+        for reason in InputReason::ALL {
+            set_reason(reason, holds);
+        }
+    }
+
+    #[test]
+    fn input_is_suppressed_until_every_reason_holds_again() {
+        let _guard = testing::fresh_state();
+        set_all_reasons(true);
+        assert!(is_active());
+
+        // Losing focus suppresses input even though gameplay wants it
+        set_reason(InputReason::WindowFocus, false);
+        assert!(is_active() == false);
+        set_reason(InputReason::Gameplay, true);
+        assert!(is_active() == false);
+
+        // Still suppressed until the last missing reason is back
+        set_reason(InputReason::ConsoleClosed, false);
+        set_reason(InputReason::WindowFocus, true);
+        assert!(is_active() == false);
+        set_reason(InputReason::ConsoleClosed, true);
+        assert!(is_active());
+
+        set_all_reasons(false);
+        assert!(is_active() == false);
+    }
+
+    #[test]
+    fn setting_a_reason_repeatedly_is_the_same_as_once() {
+        let _guard = testing::fresh_state();
+        set_all_reasons(true);
+
+        set_reason(InputReason::Gameplay, false);
+        set_reason(InputReason::Gameplay, false);
+        assert!(is_active() == false);
+        set_reason(InputReason::Gameplay, true);
+        assert!(is_active());
+        set_reason(InputReason::Gameplay, true);
+        assert!(is_active());
+    }
+
+    #[test]
+    fn latency_between_is_never_negative() {
+        assert_eq!(latency_between(100, 112), 12);
+        assert_eq!(latency_between(100, 100), 0);
+        assert_eq!(latency_between(112, 100), 0);
+    }
+
+    #[test]
+    fn latency_is_measured_from_when_the_event_was_queued() {
+        let _guard = testing::fresh_state();
+        while sys::next_event().is_some() {}
+        LATENCY_SAMPLES.lock().unwrap().clear();
+
+        // An OS timestamp, from a clock that has nothing to do with
+        // sys::milliseconds
+        let os_time = 1_700_000_000;
+        sys::enqueue_event(sys::Event::new(
+            Some(os_time),
+            sys::EventType::Character('a'),
+        ));
+        let ev = sys::next_event().unwrap();
+        assert_eq!(ev.time(), os_time);
+
+        record_latency(&ev, ev.queued_at() + 7);
+        assert_eq!(latency_ms(), Some(7.0));
+    }
+
+    #[test]
+    fn only_player_input_counts_towards_latency() {
+        let _guard = testing::fresh_state();
+        LATENCY_SAMPLES.lock().unwrap().clear();
+
+        let ev = sys::Event::new(None, sys::EventType::Console("x".into()));
+        record_latency(&ev, 50);
+        assert_eq!(latency_ms(), None);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn restarts_release_everything_acquired() {
+        let _guard = testing::fresh_state();
+        // Shutting down when nothing's been started is harmless
+        shutdown();
+        assert_eq!(started_subsystem_count(), 0);
+
+        init();
+        let started = started_subsystem_count();
+        assert_ne!(started, 0);
+        // A second init doesn't start anything twice
+        init();
+        assert_eq!(started_subsystem_count(), started);
+
+        shutdown();
+        assert_eq!(started_subsystem_count(), 0);
+        shutdown();
+        assert_eq!(started_subsystem_count(), 0);
+
+        init();
+        assert_eq!(started_subsystem_count(), started);
+        shutdown();
+        assert_eq!(started_subsystem_count(), 0);
+    }
+
+    #[test]
+    fn restart_keeps_dvars_registered_once() {
+        let _guard = testing::fresh_state();
+        init();
+        dvar::set_bool("in_mouse", false).unwrap();
+        dvar::set_float("sensitivity", 2.0).unwrap();
+
+        // Registering any of them again would be fatal
+        shutdown();
+        init();
+        assert_eq!(dvar::get_bool("in_mouse"), Some(false));
+        assert_eq!(dvar::get_float("sensitivity"), Some(2.0));
+        shutdown();
+    }
+
+    #[track_caller]
+    fn assert_near(actual: (f64, f64), expected: (f64, f64)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-9
+                && (actual.1 - expected.1).abs() < 1e-9,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn standard_windows_scale_straight_to_virtual() {
+        let to_virtual = |x, y| window_to_virtual(x, y, 1280.0, 960.0, 0.0);
+        assert_near(to_virtual(0.0, 0.0), (0.0, 0.0));
+        assert_near(to_virtual(640.0, 480.0), (320.0, 240.0));
+        assert_near(to_virtual(1280.0, 960.0), (640.0, 480.0));
+        assert_near(
+            window_to_virtual(100.0, 50.0, 640.0, 480.0, 4.0 / 3.0),
+            (100.0, 50.0),
+        );
+    }
+
+    #[test]
+    fn wide_windows_center_the_virtual_screen_horizontally() {
+        // At 16:9, the 4:3 screen is 1440 wide with 240 on either side
+        let to_virtual =
+            |x, y| window_to_virtual(x, y, 1920.0, 1080.0, 16.0 / 9.0);
+        assert_near(to_virtual(960.0, 540.0), (320.0, 240.0));
+        assert_near(to_virtual(240.0, 0.0), (0.0, 0.0));
+        assert_near(to_virtual(1680.0, 1080.0), (640.0, 480.0));
+        // The bars on either side are clamped to the virtual screen's edges
+        assert_near(to_virtual(100.0, 540.0), (0.0, 240.0));
+        assert_near(to_virtual(1900.0, 540.0), (640.0, 240.0));
+    }
+
+    #[test]
+    fn narrow_windows_center_the_virtual_screen_vertically() {
+        // At 1:1, the 4:3 screen is 810 high with 135 above and below it
+        let to_virtual = |x, y| window_to_virtual(x, y, 1080.0, 1080.0, 1.0);
+        assert_near(to_virtual(540.0, 540.0), (320.0, 240.0));
+        assert_near(to_virtual(0.0, 135.0), (0.0, 0.0));
+        assert_near(to_virtual(1080.0, 945.0), (640.0, 480.0));
+        assert_near(to_virtual(540.0, 0.0), (320.0, 0.0));
+    }
+
+    #[test]
+    fn aspect_overrides_the_window_shape() {
+        // A 4:3 window displayed as 16:9 (e.g. anamorphic) is treated as wide
+        assert_near(
+            window_to_virtual(160.0, 0.0, 1280.0, 960.0, 16.0 / 9.0),
+            (0.0, 0.0),
+        );
+        // And a window with no size puts the cursor in the middle
+        assert_near(
+            window_to_virtual(10.0, 10.0, 0.0, 0.0, 16.0 / 9.0),
+            (320.0, 240.0),
+        );
+    }
+
+    #[test]
+    fn cursor_follows_the_window_and_recenters() {
+        let _guard = testing::fresh_state();
+        let old_config = *vid::config();
+        {
+            let mut config = vid::CONFIG.write().unwrap();
+            config.display_width = 1920;
+            config.display_height = 1080;
+            config.aspect_ratio_window = 16.0 / 9.0;
+        }
+
+        set_cursor_from_window(1680.0, 0.0);
+        assert_near(cursor_position(), (640.0, 0.0));
+        center_cursor();
+        assert_near(cursor_position(), (320.0, 240.0));
+
+        *vid::CONFIG.write().unwrap() = old_config;
+    }
+}
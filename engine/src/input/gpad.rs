@@ -41,8 +41,18 @@ pub fn startup() {
     init_all();
 }
 
+/// Stops any rumble that [`startup`] or gameplay left running.
+pub fn shutdown() {
+    for gpad in S_GAMEPADS.write().unwrap().iter_mut() {
+        gpad.feedback.rumble.left_motor_speed = 0;
+        gpad.feedback.rumble.right_motor_speed = 0;
+    }
+}
+
+/// Registers the gamepads' Dvars. Called once by [`input::init`], since
+/// they outlive [`shutdown`].
 #[allow(clippy::too_many_lines)]
-fn init_dvars() {
+pub fn register_dvars() {
     dvar::register_int(
         "gpad_debug",
         0,
@@ -107,7 +117,7 @@ fn init_dvars() {
     )
     .unwrap();
     dvar::register_float(
-        "gpad_stick_hysteresis",
+        "gpad_stick_pressed_hysteresis",
         0.1,
         Some(0.0),
         Some(1.0),
@@ -171,7 +181,6 @@ fn init_dvars() {
 }
 
 fn init_all() {
-    init_dvars();
     S_GAMEPADS
         .write()
         .unwrap()
@@ -8,8 +8,6 @@ use bitflags::bitflags;
 use lazy_static::lazy_static;
 use std::sync::RwLock;
 
-use super::gpad;
-
 #[allow(unused_variables)]
 pub const fn activate(param_1: isize) {}
 
@@ -39,6 +37,158 @@ lazy_static! {
         Arc::new(RwLock::new(MouseVars::default()));
 }
 
+/// How many units platforms report for one notch of the mouse wheel.
+pub const WHEEL_DELTA: f32 = 120.0;
+
+#[derive(Copy, Clone, Default)]
+struct WheelState {
+    // Notches scrolled since the last call to `consume_wheel`
+    unconsumed: f32,
+    // Fractions of a notch (from e.g. high-resolution wheels) that haven't
+    // added up to a whole notch yet
+    partial: f32,
+}
+
+lazy_static! {
+    static ref WHEEL: RwLock<WheelState> = RwLock::new(WheelState::default());
+}
+
+/// Converts a wheel scroll reported by the platform (in multiples of
+/// [`WHEEL_DELTA`], positive for scrolling up) to notches.
+pub fn wheel_delta_to_notches(delta: f32) -> f32 {
+    delta / WHEEL_DELTA
+}
+
+/// Adds a wheel scroll reported by the platform to the accumulated wheel
+/// movement.
+///
+/// Returns how many whole notches have been scrolled (positive for up,
+/// negative for down) that haven't been returned before, carrying over any
+/// fraction of a notch to the next call.
+#[allow(clippy::cast_possible_truncation)]
+pub fn accumulate_wheel(delta: f32) -> i32 {
+    let notches = wheel_delta_to_notches(delta);
+    let mut wheel = WHEEL.write().unwrap();
+    wheel.unconsumed += notches;
+    wheel.partial += notches;
+    let whole = wheel.partial.trunc();
+    wheel.partial -= whole;
+    whole as i32
+}
+
+/// Returns how many notches the wheel has been scrolled (positive for up,
+/// negative for down) since the last call.
+pub fn consume_wheel() -> f32 {
+    core::mem::take(&mut WHEEL.write().unwrap().unconsumed)
+}
+
+/// Quake-style acceleration, which grows linearly with the speed of the
+/// mouse (making the curve as a whole quadratic).
+pub const MOUSE_ACCEL_STYLE_LEGACY: &str = "legacy";
+/// Acceleration that grows with the square of the speed of the mouse.
+pub const MOUSE_ACCEL_STYLE_CUBIC: &str = "cubic";
+
+/// The most acceleration can multiply the linear (unaccelerated) movement
+/// by, so that a sudden large delta doesn't send the view spinning.
+pub const MOUSE_ACCEL_MAX_SCALE: f32 = 4.0;
+
+/// Returns the exponent that `m_accelStyle`'s value `style` raises the
+/// mouse's speed to.
+pub fn accel_style_power(style: &str) -> f32 {
+    match style {
+        MOUSE_ACCEL_STYLE_CUBIC => 2.0,
+        _ => 1.0,
+    }
+}
+
+/// Returns how much to scale a mouse movement of `magnitude` units by.
+///
+/// This is `sensitivity + accel * magnitude^power`, clamped so that it's
+/// never more than [`MOUSE_ACCEL_MAX_SCALE`] times `sensitivity`. With
+/// `accel` at zero it's just `sensitivity`, i.e. movement is linear.
+pub fn accel_scale(
+    magnitude: f32,
+    sensitivity: f32,
+    accel: f32,
+    power: f32,
+) -> f32 {
+    let scale = accel.mul_add(magnitude.abs().powf(power), sensitivity);
+    scale.clamp(0.0, sensitivity * MOUSE_ACCEL_MAX_SCALE)
+}
+
+#[derive(Copy, Clone, Default)]
+struct MoveState {
+    // Where the cursor was when it was last reported, if it has been
+    last_pos: Option<(f64, f64)>,
+    // Movement since the last call to `consume_move`
+    unconsumed: (f32, f32),
+}
+
+lazy_static! {
+    static ref MOVE: RwLock<MoveState> = RwLock::new(MoveState::default());
+}
+
+/// Adds the movement from the cursor's last reported position to (`x`,
+/// `y`) to the accumulated mouse movement.
+#[allow(clippy::cast_possible_truncation)]
+pub fn cursor_moved(x: f64, y: f64) {
+    let mut state = MOVE.write().unwrap();
+    if let Some((last_x, last_y)) = state.last_pos {
+        state.unconsumed.0 += (x - last_x) as f32;
+        state.unconsumed.1 += (y - last_y) as f32;
+    }
+    state.last_pos = Some((x, y));
+}
+
+/// Returns how far the mouse has moved since the last call, scaled by
+/// `sensitivity` and accelerated according to `m_acceleration` and
+/// `m_accelStyle`.
+pub fn consume_move() -> (f32, f32) {
+    let (x, y) = core::mem::take(&mut MOVE.write().unwrap().unconsumed);
+    let sensitivity = dvar::get_float("sensitivity").unwrap_or(1.0);
+    let accel = dvar::get_float("m_acceleration").unwrap_or(0.0);
+    let power = accel_style_power(
+        &dvar::get_enumeration("m_accelStyle").unwrap_or_default(),
+    );
+
+    let scale = accel_scale(x.hypot(y), sensitivity, accel, power);
+    (x * scale, y * scale)
+}
+
+/// Registers the mouse's Dvars. Called once by [`input::init`], since they
+/// outlive [`shutdown`].
+pub fn register_dvars() {
+    dvar::register_float(
+        "sensitivity",
+        5.0,
+        Some(0.01),
+        Some(100.0),
+        dvar::DvarFlags::ARCHIVE,
+        Some("Mouse sensitivity"),
+    )
+    .unwrap();
+    dvar::register_float(
+        "m_acceleration",
+        0.0,
+        Some(0.0),
+        Some(10.0),
+        dvar::DvarFlags::ARCHIVE,
+        Some("Extra sensitivity added the faster the mouse moves"),
+    )
+    .unwrap();
+    dvar::register_enumeration(
+        "m_accelStyle",
+        MOUSE_ACCEL_STYLE_LEGACY.into(),
+        Some(vec![
+            MOUSE_ACCEL_STYLE_LEGACY.into(),
+            MOUSE_ACCEL_STYLE_CUBIC.into(),
+        ]),
+        dvar::DvarFlags::ARCHIVE,
+        Some("How mouse acceleration grows with the speed of the mouse"),
+    )
+    .unwrap();
+}
+
 pub fn startup() {
     S_MV.clone().write().unwrap().mouse_initialized = false;
     if dvar::get_bool("in_mouse").unwrap_or(false) == false {
@@ -48,10 +198,18 @@ pub fn startup() {
         // FUN_004682b0();
     }
 
-    gpad::startup();
     dvar::clear_modified("in_mouse").unwrap();
 }
 
+/// Releases everything acquired by [`startup`] and drops any wheel movement
+/// that hasn't been consumed yet.
+pub fn shutdown() {
+    deactivate();
+    *S_MV.clone().write().unwrap() = MouseVars::default();
+    *WHEEL.write().unwrap() = WheelState::default();
+    *MOVE.write().unwrap() = MoveState::default();
+}
+
 pub enum Scancode {
     LClick,
     RClick,
@@ -59,3 +217,76 @@ pub enum Scancode {
     MWheelUp,
     MWheelDown,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn movement_is_linear_without_acceleration() {
+        for magnitude in [0.0, 1.0, 10.0, 1000.0] {
+            assert_eq!(accel_scale(magnitude, 2.5, 0.0, 1.0), 2.5);
+            assert_eq!(accel_scale(magnitude, 2.5, 0.0, 2.0), 2.5);
+        }
+    }
+
+    #[test]
+    fn acceleration_grows_with_speed() {
+        let slow = accel_scale(1.0, 1.0, 0.1, 1.0);
+        let fast = accel_scale(10.0, 1.0, 0.1, 1.0);
+        assert!((slow - 1.1).abs() < 1e-5);
+        assert!((fast - 2.0).abs() < 1e-5);
+
+        // Cubic grows faster than legacy past one unit
+        let cubic = accel_scale(10.0, 1.0, 0.1, 2.0);
+        assert!(cubic > fast);
+    }
+
+    #[test]
+    fn acceleration_is_capped() {
+        assert_eq!(
+            accel_scale(1000.0, 2.0, 10.0, 1.0),
+            2.0 * MOUSE_ACCEL_MAX_SCALE
+        );
+    }
+
+    #[test]
+    fn accel_style_power_defaults_to_legacy() {
+        assert_eq!(accel_style_power(MOUSE_ACCEL_STYLE_LEGACY), 1.0);
+        assert_eq!(accel_style_power(MOUSE_ACCEL_STYLE_CUBIC), 2.0);
+        assert_eq!(accel_style_power("unknown"), 1.0);
+    }
+
+    #[test]
+    fn wheel_delta_is_normalized_to_notches() {
+        assert_eq!(wheel_delta_to_notches(WHEEL_DELTA), 1.0);
+        assert_eq!(wheel_delta_to_notches(-3.0 * WHEEL_DELTA), -3.0);
+        assert_eq!(wheel_delta_to_notches(WHEEL_DELTA / 4.0), 0.25);
+    }
+
+    #[test]
+    fn wheel_accumulates_until_consumed() {
+        let _guard = testing::fresh_state();
+        consume_wheel();
+
+        assert_eq!(accumulate_wheel(2.0 * WHEEL_DELTA), 2);
+        assert_eq!(accumulate_wheel(-WHEEL_DELTA), -1);
+        assert_eq!(consume_wheel(), 1.0);
+        assert_eq!(consume_wheel(), 0.0);
+    }
+
+    #[test]
+    fn partial_notches_carry_over() {
+        let _guard = testing::fresh_state();
+        consume_wheel();
+
+        // A high-resolution wheel reporting quarter notches only produces a
+        // whole notch once four of them have been scrolled
+        for _ in 0..3 {
+            assert_eq!(accumulate_wheel(WHEEL_DELTA / 4.0), 0);
+        }
+        assert_eq!(accumulate_wheel(WHEEL_DELTA / 4.0), 1);
+        assert_eq!(consume_wheel(), 1.0);
+    }
+}
@@ -0,0 +1,384 @@
+// Recording and replaying of input events, so that input-driven features
+// (binds, the UI, etc.) can be exercised the same way every time.
+//
+// A recording is a header followed by one record per event. Each record is
+// its length as a little-endian u32, then the event's time as a
+// little-endian i64, a tag for its type, and whatever that type carries.
+
+use core::mem::size_of;
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufWriter, Read, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use lazy_static::lazy_static;
+
+use crate::{
+    sys::{Event, EventType, MouseScancode},
+    *,
+};
+
+const MAGIC: &[u8; 4] = b"OT5I";
+const VERSION: u8 = 2;
+
+const TAG_NONE: u8 = 0;
+const TAG_KEY: u8 = 1;
+const TAG_MOUSE: u8 = 2;
+const TAG_CHARACTER: u8 = 3;
+const TAG_CONSOLE: u8 = 4;
+
+lazy_static! {
+    static ref RECORDING: Mutex<Option<BufWriter<File>>> = Mutex::new(None);
+    // Events from the recording being replayed that haven't been queued yet,
+    // with their times already remapped to the current session
+    static ref REPLAYING: Mutex<VecDeque<Event>> = Mutex::new(VecDeque::new());
+}
+
+/// Encodes `ev` as a record (without the length prefix).
+pub fn encode_event(ev: &Event) -> Vec<u8> {
+    let mut buf = i64::try_from(ev.time())
+        .unwrap_or_default()
+        .to_le_bytes()
+        .to_vec();
+    match *ev.event_type() {
+        EventType::None => buf.push(TAG_NONE),
+        EventType::Key(key, down) => {
+            buf.push(TAG_KEY);
+            buf.extend((key as u16).to_le_bytes());
+            buf.push(u8::from(down));
+        }
+        EventType::Mouse(button, down) => {
+            buf.extend([TAG_MOUSE, u8::from(down)]);
+            buf.extend(button.name().bytes());
+        }
+        EventType::Character(c) => {
+            buf.push(TAG_CHARACTER);
+            buf.extend(u32::from(c).to_le_bytes());
+        }
+        EventType::Console(ref text) => {
+            buf.push(TAG_CONSOLE);
+            buf.extend(text.bytes());
+        }
+    }
+    buf
+}
+
+/// Decodes a record produced by [`encode_event`], or returns [`None`] if it
+/// isn't a valid one.
+pub fn decode_event(record: &[u8]) -> Option<Event> {
+    let (time, rest) = record.split_at_checked(size_of::<i64>())?;
+    let time = i64::from_le_bytes(time.try_into().ok()?);
+    let (&tag, data) = rest.split_first()?;
+    let event_type = match (tag, data) {
+        (TAG_NONE, []) => EventType::None,
+        (TAG_KEY, &[a, b, down]) => EventType::Key(
+            num::FromPrimitive::from_u16(u16::from_le_bytes([a, b]))?,
+            decode_bool(down)?,
+        ),
+        (TAG_MOUSE, [down, name @ ..]) => EventType::Mouse(
+            MouseScancode::from_name(core::str::from_utf8(name).ok()?)?,
+            decode_bool(*down)?,
+        ),
+        (TAG_CHARACTER, &[a, b, c, d]) => {
+            EventType::Character(char::from_u32(u32::from_le_bytes([
+                a, b, c, d,
+            ]))?)
+        }
+        (TAG_CONSOLE, text) => {
+            EventType::Console(core::str::from_utf8(text).ok()?.to_owned())
+        }
+        _ => return None,
+    };
+    Some(Event::new(Some(isize::try_from(time).ok()?), event_type))
+}
+
+const fn decode_bool(b: u8) -> Option<bool> {
+    match b {
+        0 => Some(false),
+        1 => Some(true),
+        _ => None,
+    }
+}
+
+/// Starts recording every event passed to [`sys::enqueue_event`] to the
+/// file at `path`, replacing the file if it exists. Stops any recording
+/// that was already in progress first.
+pub fn record_start(path: impl AsRef<Path>) -> Result<(), std::io::Error> {
+    record_stop();
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(MAGIC)?;
+    file.write_all(&[VERSION])?;
+    *RECORDING.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+/// Stops the recording started by [`record_start`], if there is one.
+pub fn record_stop() {
+    if let Some(mut file) = RECORDING.lock().unwrap().take() {
+        file.flush().ok();
+    }
+}
+
+/// Returns `true` if events are currently being recorded.
+pub fn is_recording() -> bool {
+    RECORDING.lock().unwrap().is_some()
+}
+
+/// Appends `ev` to the recording, if there is one. Called by
+/// [`sys::enqueue_event`].
+pub fn record_event(ev: &Event) {
+    let mut recording = RECORDING.lock().unwrap();
+    let Some(file) = recording.as_mut() else {
+        return;
+    };
+
+    let record = encode_event(ev);
+    let len = u32::try_from(record.len()).unwrap_or(u32::MAX);
+    let res = file
+        .write_all(&len.to_le_bytes())
+        .and_then(|()| file.write_all(&record));
+    if let Err(e) = res {
+        com::warnln!(
+            console::Channel::SYSTEM,
+            "WARNING: couldn't write to the input recording, stopping: {}",
+            e
+        );
+        *recording = None;
+    }
+}
+
+/// Reads the records out of a recording produced by [`record_start`].
+pub fn read_recording(mut data: &[u8]) -> Option<Vec<Event>> {
+    data = data.strip_prefix(MAGIC)?.strip_prefix(&[VERSION])?;
+
+    let mut events = Vec::new();
+    while data.is_empty() == false {
+        let (len, rest) = data.split_at_checked(size_of::<u32>())?;
+        let len = u32::from_le_bytes(len.try_into().ok()?);
+        let (record, rest) =
+            rest.split_at_checked(usize::try_from(len).ok()?)?;
+        events.push(decode_event(record)?);
+        data = rest;
+    }
+    Some(events)
+}
+
+/// Replays the recording at `path`, replacing any replay already in
+/// progress.
+///
+/// The events are queued by [`frame`] as their times come around, with
+/// the first one happening now and the rest at the same intervals they
+/// were recorded at.
+pub fn replay(path: impl AsRef<Path>) -> Result<(), std::io::Error> {
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+    let events = read_recording(&data)
+        .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::InvalidData))?;
+
+    let offset = events
+        .first()
+        .map_or(0, |ev| sys::milliseconds().wrapping_sub(ev.time()));
+    *REPLAYING.lock().unwrap() = events
+        .into_iter()
+        .map(|ev| {
+            Event::new(
+                Some(ev.time().wrapping_add(offset)),
+                ev.event_type().clone(),
+            )
+        })
+        .collect();
+    Ok(())
+}
+
+/// Queues any events from the recording being replayed whose time has
+/// come. Should be called once per frame, before events are processed.
+pub fn frame() {
+    let now = sys::milliseconds();
+    loop {
+        // Don't hold the lock while queueing, since that might record the
+        // event
+        let Some(ev) = ({
+            let mut replaying = REPLAYING.lock().unwrap();
+            if replaying.front().is_some_and(|ev| ev.time() <= now) {
+                replaying.pop_front()
+            } else {
+                None
+            }
+        }) else {
+            break;
+        };
+        sys::enqueue_event(ev);
+    }
+}
+
+fn record_input_f() {
+    if cmd::argc() != 2 {
+        com::println!(
+            console::Channel::DONT_FILTER,
+            "USAGE: recordinput <file>"
+        );
+        return;
+    }
+
+    let path = cmd::argv(1);
+    match record_start(&path) {
+        Ok(()) => com::println!(
+            console::Channel::DONT_FILTER,
+            "Recording input to {}",
+            path
+        ),
+        Err(e) => com::println!(
+            console::Channel::DONT_FILTER,
+            "Couldn't record input to {}: {}",
+            path,
+            e
+        ),
+    }
+}
+
+fn stop_record_input_f() {
+    if is_recording() == false {
+        com::println!(console::Channel::DONT_FILTER, "Not recording input.");
+        return;
+    }
+
+    record_stop();
+    com::println!(console::Channel::DONT_FILTER, "Stopped recording input.");
+}
+
+fn replay_input_f() {
+    if cmd::argc() != 2 {
+        com::println!(
+            console::Channel::DONT_FILTER,
+            "USAGE: replayinput <file>"
+        );
+        return;
+    }
+
+    let path = cmd::argv(1);
+    if let Err(e) = replay(&path) {
+        com::println!(
+            console::Channel::DONT_FILTER,
+            "Couldn't replay input from {}: {}",
+            path,
+            e
+        );
+    }
+}
+
+pub fn add_commands() {
+    cmd::add_command_internal("recordinput", record_input_f).unwrap();
+    cmd::add_command_internal("stoprecordinput", stop_record_input_f).unwrap();
+    cmd::add_command_internal("replayinput", replay_input_f).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sys::KeyboardScancode, testing};
+
+    // Builds a recording out of `records`, each given without its length
+    fn recording(records: &[&[u8]]) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.push(VERSION);
+        for record in records {
+            let len = u32::try_from(record.len()).unwrap();
+            data.extend(len.to_le_bytes());
+            data.extend(*record);
+        }
+        data
+    }
+
+    #[test]
+    fn every_event_type_round_trips() {
+        let events = [
+            Event::new(Some(0), EventType::None),
+            Event::new(Some(10), EventType::Key(KeyboardScancode::F1, true)),
+            Event::new(
+                Some(20),
+                EventType::Mouse(MouseScancode::WheelUp, false),
+            ),
+            Event::new(Some(30), EventType::Character('\u{e9}')),
+            Event::new(Some(40), EventType::Console("map mp_nuketown".into())),
+        ];
+        let records = events.iter().map(encode_event).collect::<Vec<_>>();
+        let records = records.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+        let read = read_recording(&recording(&records)).unwrap();
+        assert_eq!(read.len(), events.len());
+        for (read, ev) in read.iter().zip(&events) {
+            assert_eq!(read.time(), ev.time());
+            assert_eq!(
+                format!("{:?}", read.event_type()),
+                format!("{:?}", ev.event_type())
+            );
+        }
+    }
+
+    #[test]
+    fn replayed_events_match_the_recorded_ones() {
+        let _guard = testing::fresh_state();
+        while sys::next_event().is_some() {}
+        let path = std::env::temp_dir()
+            .join(format!(".opent5-record-{}", std::process::id()));
+
+        // All at the same time, so they're all due as soon as the replay
+        // starts
+        let time = sys::milliseconds();
+        let events = [
+            EventType::Key(KeyboardScancode::W, true),
+            EventType::Mouse(MouseScancode::LClick, true),
+            EventType::Character('w'),
+            EventType::Console("bind f kill".into()),
+            EventType::Key(KeyboardScancode::W, false),
+        ];
+        record_start(&path).unwrap();
+        for ev in &events {
+            sys::enqueue_event(Event::new(Some(time), ev.clone()));
+        }
+        record_stop();
+        let mut recorded = 0;
+        while sys::next_event().is_some() {
+            recorded += 1;
+        }
+        assert_eq!(recorded, events.len());
+
+        replay(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        frame();
+        for ev in &events {
+            let replayed = sys::next_event().unwrap();
+            assert_eq!(
+                format!("{:?}", replayed.event_type()),
+                format!("{:?}", ev)
+            );
+        }
+        assert!(sys::next_event().is_none());
+    }
+
+    #[test]
+    #[allow(clippy::indexing_slicing)]
+    fn read_recording_rejects_malformed_data() {
+        let record = encode_event(&Event::new(Some(5), EventType::None));
+        let data = recording(&[&record]);
+        assert!(read_recording(&data).is_some());
+
+        // Bad magic
+        let mut bad_magic = data.clone();
+        bad_magic[0] = b'X';
+        assert!(read_recording(&bad_magic).is_none());
+
+        // Truncated, both inside a record and inside its length
+        assert!(read_recording(&data[..data.len() - 1]).is_none());
+        assert!(read_recording(&data[..MAGIC.len() + 3]).is_none());
+
+        // Unknown tag
+        let mut unknown = record.clone();
+        unknown[size_of::<i64>()] = 0xFF;
+        assert!(read_recording(&recording(&[&unknown])).is_none());
+    }
+}
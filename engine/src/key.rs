@@ -1,12 +1,20 @@
 #![allow(dead_code)]
 
-use std::sync::RwLock;
+use core::sync::atomic::AtomicU32;
+use std::{collections::HashMap, sync::RwLock};
 extern crate alloc;
 use alloc::sync::Arc;
 
 use arrayvec::ArrayVec;
+use bitflags::bitflags;
 use lazy_static::lazy_static;
 
+use crate::{
+    sys::{KeyboardScancode, MouseScancode},
+    util::EasierAtomic,
+    *,
+};
+
 #[derive(Copy, Clone, Default)]
 enum LocSelInputState {
     #[default]
@@ -27,6 +35,13 @@ struct KeyState {
     binding2: String,
 }
 
+impl KeyState {
+    fn update(&mut self, down: bool) {
+        self.down = down;
+        self.repeats = if down { self.repeats + 1 } else { 0 };
+    }
+}
+
 #[derive(Clone, Default)]
 struct Field {
     cursor: i32,
@@ -45,6 +60,7 @@ struct PlayerKeyState {
     overstrike_mode: i32,
     any_key_down: i32,
     keys: ArrayVec<KeyState, 256>,
+    mouse_buttons: HashMap<MouseScancode, KeyState>,
     loc_sel_input_state: LocSelInputState,
 }
 
@@ -58,3 +74,277 @@ pub fn clear_states(ids: isize) {
     PLAYER_KEYS.clone().write().unwrap().any_key_down = 0;
     println!("TODO - key::clear_states");
 }
+
+bitflags! {
+    /// The layers that currently want key input (the "key catchers").
+    ///
+    /// Keys go to the highest-priority layer that's set, in the order the
+    /// flags are declared here. With none set, keys go to the game's binds.
+    // TODO - add the UI's catcher (0x10) once there's a UI to send keys to
+    #[derive(Default)]
+    pub struct CatcherFlags: u32 {
+        const CONSOLE = 0x01;
+    }
+}
+
+static CATCHERS: AtomicU32 = AtomicU32::new(0);
+
+pub fn get_catcher() -> CatcherFlags {
+    CatcherFlags::from_bits_truncate(CATCHERS.load_relaxed())
+}
+
+pub fn set_catcher(flags: CatcherFlags) {
+    CATCHERS.store_relaxed(flags.bits());
+    input::set_reason(
+        input::InputReason::ConsoleClosed,
+        flags.contains(CatcherFlags::CONSOLE) == false,
+    );
+}
+
+fn toggle_console_f() {
+    console::toggle();
+}
+
+const TOGGLE_CONSOLE_COMMAND: &str = "toggleconsole";
+
+/// Binds `key` to `binding`, replacing whatever it was bound to before.
+pub fn set_binding(key: KeyboardScancode, binding: &str) {
+    let mut player_keys = PLAYER_KEYS.write().unwrap();
+    let idx = key as usize;
+    while player_keys.keys.len() <= idx {
+        player_keys.keys.push(KeyState::default());
+    }
+    player_keys.keys.get_mut(idx).unwrap().binding = binding.to_owned();
+}
+
+/// Returns the command `key` is bound to, or [`None`] if it isn't bound.
+pub fn binding(key: KeyboardScancode) -> Option<String> {
+    PLAYER_KEYS
+        .read()
+        .unwrap()
+        .keys
+        .get(key as usize)
+        .map(|k| k.binding.clone())
+        .filter(|b| b.is_empty() == false)
+}
+
+/// Binds the mouse button `button` to `binding`, replacing whatever it was
+/// bound to before.
+pub fn set_mouse_binding(button: MouseScancode, binding: &str) {
+    PLAYER_KEYS
+        .write()
+        .unwrap()
+        .mouse_buttons
+        .entry(button)
+        .or_default()
+        .binding = binding.to_owned();
+}
+
+/// Returns the command the mouse button `button` is bound to, or [`None`]
+/// if it isn't bound.
+pub fn mouse_binding(button: MouseScancode) -> Option<String> {
+    PLAYER_KEYS
+        .read()
+        .unwrap()
+        .mouse_buttons
+        .get(&button)
+        .map(|k| k.binding.clone())
+        .filter(|b| b.is_empty() == false)
+}
+
+fn bind_f() {
+    let argc = cmd::argc();
+    if argc < 2 {
+        com::println!(
+            console::Channel::DONT_FILTER,
+            "USAGE: bind <key> [command]"
+        );
+        return;
+    }
+
+    let name = cmd::argv(1);
+    let key = KeyboardScancode::from_name(&name);
+    let button = MouseScancode::from_name(&name);
+    let (key_name, current) = match (key, button) {
+        (Some(key), _) => (key.name().to_owned(), binding(key)),
+        (None, Some(button)) => (button.name(), mouse_binding(button)),
+        (None, None) => {
+            com::println!(
+                console::Channel::DONT_FILTER,
+                "\"{}\" isn't a valid key",
+                name
+            );
+            return;
+        }
+    };
+
+    if argc == 2 {
+        match current {
+            Some(binding) => com::println!(
+                console::Channel::DONT_FILTER,
+                "\"{}\" = \"{}\"",
+                key_name,
+                binding
+            ),
+            None => com::println!(
+                console::Channel::DONT_FILTER,
+                "\"{}\" is not bound",
+                key_name
+            ),
+        }
+        return;
+    }
+
+    let binding = (2..argc).map(cmd::argv).collect::<Vec<_>>().join(" ");
+    if let Some(key) = key {
+        set_binding(key, &binding);
+    } else if let Some(button) = button {
+        set_mouse_binding(button, &binding);
+    }
+}
+
+pub fn init() {
+    cmd::add_command_internal(TOGGLE_CONSOLE_COMMAND, toggle_console_f)
+        .unwrap();
+    cmd::add_command_internal("bind", bind_f).unwrap();
+    set_binding(KeyboardScancode::Tilde, TOGGLE_CONSOLE_COMMAND);
+}
+
+/// Sends a key press to whichever layer has the key catcher.
+///
+/// Releases go to the game's binds if the press did, whoever has the key
+/// catcher by then, and nowhere otherwise. This way a `+` command can't be
+/// left stuck on by opening the console while its key is held.
+pub fn handle_key_event(key: KeyboardScancode, down: bool) {
+    // The console key has to work no matter who has the key catcher,
+    // otherwise the console could never be closed again.
+    if binding(key).as_deref() == Some(TOGGLE_CONSOLE_COMMAND) {
+        if down {
+            console::toggle();
+        }
+        return;
+    }
+
+    if down == false {
+        if key_is_down(key) {
+            execute_binding(key, false);
+        }
+    } else if get_catcher().contains(CatcherFlags::CONSOLE) {
+        console::key_event(key);
+    } else {
+        execute_binding(key, true);
+    }
+}
+
+// Whether `key`'s last press went to the game's binds and it hasn't been
+// released since.
+fn key_is_down(key: KeyboardScancode) -> bool {
+    PLAYER_KEYS
+        .read()
+        .unwrap()
+        .keys
+        .get(key as usize)
+        .is_some_and(|k| k.down)
+}
+
+/// Sends a mouse button press to whichever layer has the key catcher.
+/// Releases go where the press did, as in [`handle_key_event`].
+pub fn handle_mouse_event(button: MouseScancode, down: bool) {
+    if down && get_catcher().contains(CatcherFlags::CONSOLE) {
+        // TODO - scroll the console with the wheel
+        return;
+    }
+
+    let binding = {
+        let mut player_keys = PLAYER_KEYS.write().unwrap();
+        let state = player_keys.mouse_buttons.entry(button).or_default();
+        if down == false && state.down == false {
+            return;
+        }
+        state.update(down);
+        state.binding.clone()
+    };
+    run_binding(&binding, down);
+}
+
+/// Sends a typed character to whichever layer has the key catcher.
+pub fn handle_char_event(c: char) {
+    if get_catcher().contains(CatcherFlags::CONSOLE) {
+        console::char_event(c);
+    }
+}
+
+// Runs the command bound to `key`.
+fn execute_binding(key: KeyboardScancode, down: bool) {
+    let binding = {
+        let mut player_keys = PLAYER_KEYS.write().unwrap();
+        let Some(state) = player_keys.keys.get_mut(key as usize) else {
+            return;
+        };
+        state.update(down);
+        state.binding.clone()
+    };
+
+    run_binding(&binding, down);
+}
+
+// Runs `binding` for a key or button press or release. As in the original
+// engine, releasing a key bound to a `+` command runs the matching `-`
+// command.
+fn run_binding(binding: &str, down: bool) {
+    if binding.is_empty() {
+        return;
+    }
+
+    if down {
+        cbuf::add_textln(0, binding);
+    } else if let Some(command) = binding.strip_prefix('+') {
+        cbuf::add_textln(0, &format!("-{}", command));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::AtomicBool;
+
+    use super::*;
+    use crate::{testing, util::EasierAtomicBool};
+
+    static ATTACKING: AtomicBool = AtomicBool::new(false);
+
+    #[test]
+    fn release_goes_to_the_bind_that_got_the_press() {
+        let _guard = testing::fresh_state();
+        cmd::add_command_internal("+attack", || {
+            ATTACKING.store_relaxed(true);
+        })
+        .unwrap();
+        cmd::add_command_internal("-attack", || {
+            ATTACKING.store_relaxed(false);
+        })
+        .unwrap();
+        set_binding(KeyboardScancode::Space, "+attack");
+        set_catcher(CatcherFlags::empty());
+
+        handle_key_event(KeyboardScancode::Space, true);
+        cbuf::execute(0);
+        assert!(ATTACKING.load_relaxed());
+
+        // Opening the console mid-press mustn't leave it stuck on
+        console::set_open(true);
+        handle_key_event(KeyboardScancode::Space, false);
+        cbuf::execute(0);
+        assert!(ATTACKING.load_relaxed() == false);
+
+        // Presses made while it's open go to the console, so their releases
+        // don't go to the bind either
+        handle_key_event(KeyboardScancode::Space, true);
+        console::set_open(false);
+        ATTACKING.store_relaxed(true);
+        handle_key_event(KeyboardScancode::Space, false);
+        cbuf::execute(0);
+        assert!(ATTACKING.load_relaxed());
+
+        set_binding(KeyboardScancode::Space, "");
+    }
+}
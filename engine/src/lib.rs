@@ -111,6 +111,8 @@ mod rb;
 mod render;
 mod seh;
 mod sys;
+#[cfg(test)]
+mod testing;
 mod util;
 mod vid;
 
@@ -127,6 +129,18 @@ lazy_static! {
     clippy::expect_used
 )]
 pub fn run() {
+    #[cfg(target_arch = "wasm32")]
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    seh::install_panic_hook();
+
+    sys::init_main_thread();
+    #[cfg(windows)]
+    sys::init_dpi_awareness();
+    // Everything after this may register Dvars and commands (or set them),
+    // so these two have to come before anything else
+    cmd::init();
+    dvar::init();
+
     platform::os::target::main();
     platform::display_server::target::init();
     let cmdline = sys::get_cmdline();
@@ -140,33 +154,23 @@ pub fn run() {
         }
     }
 
-    sys::init_main_thread();
-
     #[cfg(native)]
     pmem::init();
 
     locale::init();
 
-    #[allow(clippy::collapsible_if)]
-    if cmdline.get(0..9).unwrap_or_default() != "allowdupe"
-        || cmdline.chars().nth(9).unwrap_or(' ') > ' '
+    if !cmdline.contains("g_connectpaths 3")
+        && sys::check_crash_or_rerun(com::allow_multiple_instances()) == false
     {
-        if !cmdline.contains("g_connectpaths 3") {
-            if sys::check_crash_or_rerun() == false {
-                return;
-            }
-        }
+        return;
     }
 
     if cmdline.contains("nosnd") {
         S_NOSND.store(true, Ordering::SeqCst);
     }
 
-    dvar::init();
-
     cfg_if! {
         if #[cfg(target_arch = "wasm32")] {
-            std::panic::set_hook(Box::new(console_error_panic_hook::hook));
             console_log::init_with_level(
                 log::Level::Warn
             ).expect("Couldn't initialize logger");
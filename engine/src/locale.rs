@@ -1,15 +1,21 @@
 #![allow(dead_code)]
 
-use core::fmt::Display;
-use std::{collections::HashMap, sync::RwLock};
-
-use core::fmt::Write;
+use core::{
+    fmt::{Display, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, RwLock},
+};
 extern crate alloc;
 
+use cfg_if::cfg_if;
 use lazy_static::lazy_static;
-use num::Integer;
 use num_derive::FromPrimitive;
 
+use crate::*;
+
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Copy, Clone, Default, Debug, PartialEq, Eq, FromPrimitive)]
 #[repr(u8)]
@@ -103,65 +109,199 @@ pub fn lang_from_str(lang: &str) -> Option<Language> {
     }
 }
 
+// Parses the contents of localization.txt: the language on the first line,
+// followed by quoted key/value pairs. Falls back to English if the language
+// isn't recognized.
+fn parse(contents: &str) -> Localization {
+    // the language string should be at the beginning
+    // of the file, a single word followed by a newline
+    let lines = contents.trim().split('\n').collect::<Vec<&str>>();
+    let lang = lines
+        .first()
+        .and_then(|l| lang_from_str(l.trim()))
+        .unwrap_or(Language::ENGLISH);
+    // collect the rest of the strings for LOCALIZATION.strings
+    // trim the whitespace from the file,
+    // then split it by quotation marks
+    // and collect the strings
+    let mut t = String::new();
+    lines
+        .get(1..)
+        .unwrap_or_default()
+        .iter()
+        .for_each(|&s| writeln!(t, "{}", s).unwrap());
+
+    let strings = t
+        .split('"')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>();
+
+    let mut map = HashMap::new();
+    let mut strings = strings.into_iter();
+    while let (Some(k), Some(v)) = (strings.next(), strings.next()) {
+        map.insert(k.to_owned(), v.to_owned());
+    }
+
+    Localization {
+        language: lang,
+        strings: map,
+    }
+}
+
+const LOCALIZATION_FILE: &str = "localization.txt";
+
 pub fn init() -> Language {
     // try to read localization.txt
     // if read fails, default to English
-    // if it succeeds, try to copy it into a String
-    let (lang, strings) = std::fs::read("localization.txt").map_or_else(
-        |_| (Language::ENGLISH, Vec::<String>::new()),
-        |v| {
-            let s = String::from_utf8_lossy(&v);
-            // the language string should be at the beginning
-            // of the file, a single word followed by a newline
-            let strings = s.trim().split('\n').collect::<Vec<&str>>();
-            let lang = lang_from_str(strings.first().unwrap());
-            // collect the rest of the strings for LOCALIZATION.strings
-            // trim the whitespace from the file,
-            // then split it by quotation marks
-            // and collect the strings
-            let mut t = String::new();
-            let file_strings: Vec<&str> = strings.get(1..).unwrap().to_vec();
-            file_strings
-                .iter()
-                .for_each(|&s| writeln!(t, "{}", s).unwrap());
-
-            let strings = t
-                .split('"')
-                .collect::<Vec<&str>>()
-                .iter()
-                .map(|&s| s.to_owned().trim().to_owned())
-                .filter(|s| !s.is_empty())
-                .collect::<Vec<String>>();
-            (lang.unwrap_or(Language::ENGLISH), strings)
-        },
+    let localization = std::fs::read(LOCALIZATION_FILE).map_or_else(
+        |_| Localization::default(),
+        |v| parse(&String::from_utf8_lossy(&v)),
     );
 
-    let keys: Vec<String> = strings
-        .iter()
-        .enumerate()
-        .filter(|(i, _)| i.is_even())
-        .map(|(_, s)| s.clone())
-        .collect();
-    let values: Vec<String> = strings
-        .iter()
-        .enumerate()
-        .filter(|(i, _)| i.is_odd())
-        .map(|(_, s)| s.clone())
-        .collect();
-
-    let mut map: HashMap<String, String> = HashMap::new();
-    for i in 0..keys.len() {
-        map.insert(
-            keys.get(i).unwrap().clone(),
-            values.get(i).unwrap().clone(),
+    let lang = localization.language;
+    *LOCALIZATION.write().unwrap() = localization;
+    lang
+}
+
+/// Re-reads the localized strings, so that edits to them take effect
+/// without restarting the game.
+///
+/// The file is looked up through the filesystem's search paths, falling
+/// back to the working directory like [`init`] does. The new table replaces
+/// the old one in a single write, so concurrent [`localize_ref`] calls see
+/// either the old strings or the new ones, never a mix.
+pub fn reload() -> Result<(), std::io::Error> {
+    let contents = fs::read_file(LOCALIZATION_FILE)
+        .map(|f| f.to_vec())
+        .or_else(|_| std::fs::read(LOCALIZATION_FILE))?;
+    let localization = parse(&String::from_utf8_lossy(&contents));
+    let count = localization.strings.len();
+    *LOCALIZATION.write().unwrap() = localization;
+    com::println!(
+        console::Channel::SYSTEM,
+        "Reloaded {} localized strings",
+        count
+    );
+    Ok(())
+}
+
+fn loc_reload_f() {
+    if let Err(e) = reload() {
+        com::println!(
+            console::Channel::ERROR,
+            "Couldn't reload {}: {}",
+            LOCALIZATION_FILE,
+            e
         );
     }
+}
 
-    *LOCALIZATION.write().unwrap() = Localization {
-        language: lang,
-        strings: map,
-    };
-    lang
+/// Registers the localization Dvars and commands. Unlike [`init`], this has
+/// to wait until the Dvar system is up.
+pub fn register() {
+    dvar::register_bool(
+        "loc_watch",
+        false,
+        dvar::DvarFlags::empty(),
+        Some("Reload the localized strings when their files change on disk"),
+    )
+    .unwrap();
+    cmd::add_command_internal("loc_reload", loc_reload_f).unwrap();
+}
+
+cfg_if! {
+    if #[cfg(native)] {
+        use notify::{RecursiveMode, Watcher};
+
+        lazy_static! {
+            static ref WATCHER: Mutex<Option<notify::RecommendedWatcher>> =
+                Mutex::new(None);
+        }
+
+        // Set from the watcher's thread; the reload itself happens on the
+        // main thread in `frame`, since the filesystem only knows about the
+        // engine's own threads.
+        static RELOAD_PENDING: AtomicBool = AtomicBool::new(false);
+
+        // localization.txt itself, or one of the .str string files
+        fn is_localization_file(path: &std::path::Path) -> bool {
+            path.file_name().is_some_and(|n| n == LOCALIZATION_FILE)
+                || path.extension().is_some_and(|e| e == "str")
+        }
+
+        // The directories `reload` can read the file from: the
+        // filesystem's search paths, then the working directory
+        fn watched_dirs() -> Vec<std::path::PathBuf> {
+            let mut dirs = fs::searchpath_dirs();
+            let cwd = sys::cwd();
+            if dirs.contains(&cwd) == false {
+                dirs.push(cwd);
+            }
+            dirs
+        }
+
+        fn start_watching() -> notify::Result<notify::RecommendedWatcher> {
+            let mut watcher = notify::recommended_watcher(
+                |res: notify::Result<notify::Event>| {
+                    if let Ok(ev) = res
+                        && ev.kind.is_modify()
+                        && ev.paths.iter().any(|p| is_localization_file(p))
+                    {
+                        RELOAD_PENDING.store(true, Ordering::Relaxed);
+                    }
+                },
+            )?;
+
+            // Search paths that don't exist (yet) are skipped, as long as
+            // at least one directory can be watched
+            let mut result = Ok(());
+            let mut watched = false;
+            for dir in watched_dirs() {
+                match watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                    Ok(()) => watched = true,
+                    Err(e) => result = Err(e),
+                }
+            }
+            if watched {
+                Ok(watcher)
+            } else {
+                result.map(|()| watcher)
+            }
+        }
+
+        /// Starts or stops watching the localization files as `loc_watch`
+        /// changes, and reloads them if they've changed. Should be called
+        /// once per frame.
+        pub fn frame() {
+            let watch = dvar::get_bool("loc_watch").unwrap_or(false);
+            let mut watcher = WATCHER.lock().unwrap();
+            if watch && watcher.is_none() {
+                match start_watching() {
+                    Ok(w) => *watcher = Some(w),
+                    Err(e) => {
+                        com::println!(
+                            console::Channel::ERROR,
+                            "Couldn't watch the localization files: {}",
+                            e
+                        );
+                        dvar::set_bool_internal("loc_watch", false).unwrap();
+                    }
+                }
+            } else if watch == false && watcher.is_some() {
+                *watcher = None;
+            }
+            drop(watcher);
+
+            if RELOAD_PENDING.swap(false, Ordering::Relaxed) {
+                loc_reload_f();
+            }
+        }
+    } else {
+        /// File watching isn't available on this platform, so `loc_watch`
+        /// does nothing.
+        pub const fn frame() {}
+    }
 }
 
 #[allow(clippy::redundant_closure_for_method_calls)]
@@ -174,3 +314,49 @@ pub fn localize_ref(s: &str) -> String {
         .get(&s.to_owned())
         .map_or_else(|| s.to_owned(), |s| s.clone())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_language_and_strings() {
+        let localization = parse(
+            "french\n\"MENU_PLAY\" \"Jouer\"\n\"MENU_QUIT\" \"Quitter\"\n",
+        );
+        assert_eq!(localization.language, Language::FRENCH);
+        assert_eq!(
+            localization.strings.get("MENU_PLAY").map(String::as_str),
+            Some("Jouer")
+        );
+        assert_eq!(
+            localization.strings.get("MENU_QUIT").map(String::as_str),
+            Some("Quitter")
+        );
+    }
+
+    #[test]
+    fn parse_falls_back_to_english() {
+        let localization = parse("klingon\n\"MENU_PLAY\" \"Play\"\n");
+        assert_eq!(localization.language, Language::ENGLISH);
+        assert_eq!(localization.strings.len(), 1);
+    }
+
+    #[cfg(native)]
+    #[test]
+    fn only_localization_files_trigger_a_reload() {
+        use std::path::Path;
+
+        assert!(is_localization_file(Path::new("main/localization.txt")));
+        assert!(is_localization_file(Path::new("localization.txt")));
+        assert!(is_localization_file(Path::new("main/english.str")));
+        assert!(is_localization_file(Path::new("main/english.txt")) == false);
+        assert!(is_localization_file(Path::new("main/config.cfg")) == false);
+    }
+
+    #[cfg(native)]
+    #[test]
+    fn working_directory_is_always_watched() {
+        assert!(watched_dirs().contains(&sys::cwd()));
+    }
+}
@@ -1,23 +1,711 @@
 use core::{
+    fmt::Display,
     sync::atomic::{AtomicBool, Ordering},
     time::Duration,
 };
+use std::{
+    collections::VecDeque,
+    net::{IpAddr, SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::Mutex,
+};
+
+use lazy_static::lazy_static;
+
+use crate::*;
 
 static NETWORKING_ENABLED: AtomicBool = AtomicBool::new(false);
+static INITIALIZED: util::InitFlag = util::InitFlag::new("net::init");
 
 pub fn networking_enabled() -> bool {
     NETWORKING_ENABLED.load(Ordering::Relaxed)
 }
 
-#[allow(clippy::todo)]
-fn config(_enabled: bool) {
-    todo!()
+/// The port the game binds to when `net_port` hasn't been changed.
+pub const DEFAULT_PORT: u16 = 28960;
+
+/// How many ports after `net_port` to try if it's already in use.
+const PORT_ATTEMPTS: u16 = 10;
+
+/// The largest packet that can be sent or received.
+pub const MAX_PACKET_SIZE: usize = 1400;
+
+/// Where a packet is going to or coming from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NetAddr {
+    /// The other end of the in-process loopback channel, so the client and
+    /// server can talk to each other without any sockets involved.
+    Loopback,
+    Ip(SocketAddr),
+}
+
+impl NetAddr {
+    /// Parses `loopback`, or resolves a `host:port` address (using
+    /// [`DEFAULT_PORT`] if there's no port).
+    pub fn resolve(s: &str) -> Option<Self> {
+        if s.eq_ignore_ascii_case("loopback") {
+            return Some(Self::Loopback);
+        }
+
+        let addr = if s.contains(':') {
+            s.to_socket_addrs().ok()?.next()
+        } else {
+            (s, DEFAULT_PORT).to_socket_addrs().ok()?.next()
+        };
+        addr.map(Self::Ip)
+    }
+}
+
+impl Display for NetAddr {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            Self::Loopback => write!(f, "loopback"),
+            Self::Ip(addr) => write!(f, "{}", addr),
+        }
+    }
+}
+
+/// Which end of the game a packet is being sent or received by.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NetSrc {
+    Client,
+    Server,
+}
+
+impl NetSrc {
+    /// Returns the end that loopback packets sent by `self` arrive at.
+    pub const fn peer(self) -> Self {
+        match self {
+            Self::Client => Self::Server,
+            Self::Server => Self::Client,
+        }
+    }
+}
+
+/// How many packets a [`LoopbackChannel`] holds before it starts dropping
+/// the oldest ones.
+const MAX_LOOPBACK_PACKETS: usize = 16;
+
+/// The packets waiting to be received by one end of the loopback.
+#[derive(Debug, Default)]
+pub struct LoopbackChannel {
+    packets: Mutex<VecDeque<Vec<u8>>>,
+}
+
+impl LoopbackChannel {
+    pub const fn new() -> Self {
+        Self {
+            packets: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Queues `data` to be received from this channel, dropping the oldest
+    /// packet if it's full. Returns how many bytes were sent.
+    pub fn send(&self, data: &[u8]) -> usize {
+        let mut packets = self.packets.lock().unwrap();
+        if packets.len() >= MAX_LOOPBACK_PACKETS {
+            packets.pop_front();
+        }
+
+        let data = data.get(..MAX_PACKET_SIZE).unwrap_or(data);
+        packets.push_back(data.to_vec());
+        data.len()
+    }
+
+    /// Receives the oldest queued packet into `buf`, returning its size, or
+    /// [`None`] if nothing's queued. Anything that doesn't fit in `buf` is
+    /// discarded.
+    pub fn recv(&self, buf: &mut [u8]) -> Option<usize> {
+        let packet = self.packets.lock().unwrap().pop_front()?;
+        let len = packet.len().min(buf.len());
+        buf.get_mut(..len)?
+            .copy_from_slice(packet.get(..len).unwrap_or_default());
+        Some(len)
+    }
+
+    /// Throws away every queued packet.
+    pub fn clear(&self) {
+        self.packets.lock().unwrap().clear();
+    }
+}
+
+// Each end's incoming loopback packets
+static CLIENT_LOOPBACK: LoopbackChannel = LoopbackChannel::new();
+static SERVER_LOOPBACK: LoopbackChannel = LoopbackChannel::new();
+
+/// Returns the channel that `src` receives its loopback packets from.
+pub fn loopback(src: NetSrc) -> &'static LoopbackChannel {
+    match src {
+        NetSrc::Client => &CLIENT_LOOPBACK,
+        NetSrc::Server => &SERVER_LOOPBACK,
+    }
+}
+
+/// A bound UDP socket. Closed when dropped.
+#[derive(Debug)]
+pub struct Socket {
+    inner: UdpSocket,
+}
+
+impl Socket {
+    /// Binds a socket to `ip`:`port`. If that port is already in use, the
+    /// next few ports after it are tried as well.
+    pub fn bind(ip: &str, port: u16) -> std::io::Result<Self> {
+        let mut last_error = None;
+        for port in (0..PORT_ATTEMPTS).map_while(|i| port.checked_add(i)) {
+            match UdpSocket::bind((ip, port)) {
+                Ok(inner) => return Ok(Self { inner }),
+                Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap())
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    pub const fn as_udp_socket(&self) -> &UdpSocket {
+        &self.inner
+    }
+
+    /// Sends `data` to `addr`, returning how many bytes were sent.
+    pub fn send_to(
+        &self,
+        data: &[u8],
+        addr: SocketAddr,
+    ) -> std::io::Result<usize> {
+        let sent = self.inner.send_to(data, addr)?;
+        show_packet("send", sent, NetAddr::Ip(addr));
+        Ok(sent)
+    }
+
+    /// Receives a single packet into `buf`, returning its size and the
+    /// address it came from.
+    pub fn recv_from(
+        &self,
+        buf: &mut [u8],
+    ) -> std::io::Result<(usize, SocketAddr)> {
+        let (received, addr) = self.inner.recv_from(buf)?;
+        show_packet("recv", received, NetAddr::Ip(addr));
+        Ok((received, addr))
+    }
+}
+
+/// Prints a one-line description of a packet if `net_showPackets` is set.
+fn show_packet(direction: &str, size: usize, addr: NetAddr) {
+    if dvar::get_bool("net_showPackets").unwrap_or(false) {
+        com::println!(
+            console::Channel::SYSTEM,
+            "packet {}: {} bytes {}",
+            direction,
+            size,
+            addr
+        );
+    }
+}
+
+lazy_static! {
+    static ref IP_SOCKET: Mutex<Option<Socket>> = Mutex::new(None);
+}
+
+/// Returns the address the game's socket is bound to, or [`None`] if it
+/// isn't bound.
+pub fn local_addr() -> Option<SocketAddr> {
+    IP_SOCKET
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|s| s.local_addr().ok())
+}
+
+/// Sends `data` from `src` to `addr`, returning how many bytes were sent.
+/// Loopback packets go straight into the other end's queue; anything else
+/// goes out over the game's socket.
+pub fn send_to(
+    src: NetSrc,
+    data: &[u8],
+    addr: NetAddr,
+) -> std::io::Result<usize> {
+    match addr {
+        NetAddr::Loopback => {
+            let sent = loopback(src.peer()).send(data);
+            show_packet("send", sent, addr);
+            Ok(sent)
+        }
+        NetAddr::Ip(addr) => IP_SOCKET
+            .lock()
+            .unwrap()
+            .as_ref()
+            .ok_or_else(|| {
+                std::io::Error::from(std::io::ErrorKind::NotConnected)
+            })?
+            .send_to(data, addr),
+    }
+}
+
+/// Receives a single packet for `src` into `buf`, returning its size and
+/// the address it came from, or [`None`] if there's nothing waiting.
+///
+/// Loopback packets are received first. The game's socket is shared by both
+/// ends, so a packet from it goes to whichever end asks first.
+pub fn recv_from(
+    src: NetSrc,
+    buf: &mut [u8],
+) -> std::io::Result<Option<(usize, NetAddr)>> {
+    if let Some(received) = loopback(src).recv(buf) {
+        show_packet("recv", received, NetAddr::Loopback);
+        return Ok(Some((received, NetAddr::Loopback)));
+    }
+
+    let socket = IP_SOCKET.lock().unwrap();
+    let Some(socket) = socket.as_ref() else {
+        return Ok(None);
+    };
+    match socket.recv_from(buf) {
+        Ok((received, addr)) => Ok(Some((received, NetAddr::Ip(addr)))),
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns `true` if `ip` is an address other machines could reach this one
+/// at, i.e. it isn't unspecified, loopback, or link-local.
+pub fn is_shareable_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            (ip.is_unspecified() || ip.is_loopback() || ip.is_link_local())
+                == false
+        }
+        IpAddr::V6(ip) => {
+            // fe80::/10
+            let link_local = ip.segments()[0] & 0xFFC0 == 0xFE80;
+            (ip.is_unspecified() || ip.is_loopback() || link_local) == false
+        }
+    }
+}
+
+/// Returns the addresses other machines could reach this one at, for a
+/// host to share with players connecting to it. Only interfaces that are
+/// up are included, and loopback and link-local addresses are left out.
+///
+/// The addresses are sorted, IPv4 before IPv6, and use the port the game's
+/// socket is bound to (or `net_port`, if it isn't bound). If the interfaces
+/// can't be enumerated, a warning is printed and the result is empty.
+pub fn local_addresses() -> Vec<NetAddr> {
+    let mut ips = match platform::interface_addresses() {
+        Ok(ips) => ips,
+        Err(e) => {
+            com::warnln!(
+                console::Channel::SYSTEM,
+                "Couldn't enumerate network interfaces: {}",
+                e
+            );
+            return Vec::new();
+        }
+    };
+    ips.retain(|&ip| is_shareable_ip(ip));
+    // IpAddr orders every IPv4 address before any IPv6 one
+    ips.sort_unstable();
+    ips.dedup();
+
+    let port = local_addr().map_or_else(
+        || {
+            dvar::get_int("net_port")
+                .and_then(|p| u16::try_from(p).ok())
+                .unwrap_or(DEFAULT_PORT)
+        },
+        |a| a.port(),
+    );
+    ips.into_iter()
+        .map(|ip| NetAddr::Ip(SocketAddr::new(ip, port)))
+        .collect()
+}
+
+/// Marks a connectionless packet, i.e. one that isn't part of a
+/// connection's stream (e.g. a connection request).
+const OOB_PREFIX: [u8; 4] = [0xFF; 4];
+
+static LISTENING: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    // The address `net_connect` is connecting or connected to
+    static ref SERVER_ADDR: Mutex<Option<NetAddr>> = Mutex::new(None);
+}
+
+/// Sends a connectionless packet containing `text` from `src` to `addr`.
+pub fn send_oob(
+    src: NetSrc,
+    addr: NetAddr,
+    text: &str,
+) -> std::io::Result<usize> {
+    let mut data = OOB_PREFIX.to_vec();
+    data.extend(text.bytes());
+    send_to(src, &data, addr)
+}
+
+fn connectionless_packet(data: &[u8], addr: NetAddr) {
+    match String::from_utf8_lossy(data).as_ref() {
+        "connect" if LISTENING.load(Ordering::Relaxed) => {
+            com::println!(console::Channel::SERVER, "{} connected", addr);
+            if let Err(e) = send_oob(NetSrc::Server, addr, "connectResponse") {
+                com::warnln!(
+                    console::Channel::SERVER,
+                    "Couldn't respond to {}: {}",
+                    addr,
+                    e
+                );
+            }
+        }
+        "connectResponse" if *SERVER_ADDR.lock().unwrap() == Some(addr) => {
+            com::println!(console::Channel::CLIENT, "Connected to {}", addr);
+            sys::request_attention_if_inactive(
+                platform::AttentionLevel::Informational,
+            );
+        }
+        text => com::dprintln!(
+            console::Channel::SYSTEM,
+            "Ignoring connectionless packet from {}: {}",
+            addr,
+            text
+        ),
+    }
+}
+
+/// Handles any packets that have arrived for either end. Should be called
+/// once per frame.
+pub fn frame() {
+    let mut buf = [0u8; MAX_PACKET_SIZE];
+    for src in [NetSrc::Server, NetSrc::Client] {
+        loop {
+            let (received, addr) = match recv_from(src, &mut buf) {
+                Ok(Some(packet)) => packet,
+                Ok(None) => break,
+                Err(e) => {
+                    com::warnln!(
+                        console::Channel::SYSTEM,
+                        "Couldn't receive packet: {}",
+                        e
+                    );
+                    break;
+                }
+            };
+
+            // There's nothing to hand sequenced packets to yet
+            let data = buf.get(..received).unwrap_or_default();
+            if let Some(data) = data.strip_prefix(&OOB_PREFIX) {
+                connectionless_packet(data, addr);
+            }
+        }
+    }
+}
+
+fn listen_f() {
+    loopback(NetSrc::Server).clear();
+    LISTENING.store(true, Ordering::Relaxed);
+    if networking_enabled() == false {
+        set_networking_enabled(true);
+    }
+
+    com::println!(
+        console::Channel::DONT_FILTER,
+        "Listening on loopback{}",
+        local_addr()
+            .map(|a| format!(" and {}", a))
+            .unwrap_or_default()
+    );
+}
+
+fn connect_f() {
+    if cmd::argc() != 2 {
+        com::println!(
+            console::Channel::DONT_FILTER,
+            "USAGE: net_connect <address | loopback>"
+        );
+        return;
+    }
+
+    let name = cmd::argv(1);
+    let Some(addr) = NetAddr::resolve(&name) else {
+        com::println!(
+            console::Channel::DONT_FILTER,
+            "Bad server address: {}",
+            name
+        );
+        return;
+    };
+
+    if addr != NetAddr::Loopback && networking_enabled() == false {
+        set_networking_enabled(true);
+    }
+
+    loopback(NetSrc::Client).clear();
+    *SERVER_ADDR.lock().unwrap() = Some(addr);
+    com::println!(console::Channel::DONT_FILTER, "Connecting to {}...", addr);
+    if let Err(e) = send_oob(NetSrc::Client, addr, "connect") {
+        com::println!(
+            console::Channel::DONT_FILTER,
+            "Couldn't connect to {}: {}",
+            addr,
+            e
+        );
+    }
+}
+
+fn local_ip_f() {
+    let addrs = local_addresses();
+    if addrs.is_empty() {
+        com::println!(
+            console::Channel::DONT_FILTER,
+            "No network addresses found."
+        );
+        return;
+    }
+
+    for addr in addrs {
+        com::println!(console::Channel::DONT_FILTER, "{}", addr);
+    }
+}
+
+fn add_commands() {
+    cmd::add_command_internal("net_listen", listen_f).unwrap();
+    cmd::add_command_internal("net_connect", connect_f).unwrap();
+    cmd::add_command_internal("net_localip", local_ip_f).unwrap();
+}
+
+fn open_ip() {
+    let ip = dvar::get_string("net_ip").unwrap_or_else(|| "0.0.0.0".into());
+    let port = dvar::get_int("net_port")
+        .and_then(|p| u16::try_from(p).ok())
+        .unwrap_or(DEFAULT_PORT);
+
+    match Socket::bind(&ip, port) {
+        Ok(socket) => {
+            if let Ok(addr) = socket.local_addr() {
+                com::println!(
+                    console::Channel::SYSTEM,
+                    "Opening IP socket: {}",
+                    addr
+                );
+                if addr.port() != port && port != 0 {
+                    dvar::set_int_internal("net_port", i32::from(addr.port()))
+                        .unwrap();
+                }
+            }
+            // Packets are polled for once a frame, so don't wait around
+            // for them
+            if let Err(e) = socket.as_udp_socket().set_nonblocking(true) {
+                com::warnln!(
+                    console::Channel::SYSTEM,
+                    "Couldn't make the IP socket non-blocking: {}",
+                    e
+                );
+            }
+            *IP_SOCKET.lock().unwrap() = Some(socket);
+        }
+        Err(e) => {
+            com::warnln!(
+                console::Channel::SYSTEM,
+                "Couldn't bind to {}:{}: {}",
+                ip,
+                port,
+                e
+            );
+        }
+    }
+}
+
+fn config(enabled: bool) {
+    // Drop (and thereby close) the old socket before binding a new one, in
+    // case the new one wants the same port
+    *IP_SOCKET.lock().unwrap() = None;
+    NETWORKING_ENABLED.store(enabled, Ordering::Relaxed);
+
+    if enabled {
+        open_ip();
+    }
 }
 
+/// Closes the game's socket and, if networking is enabled, binds a new one
+/// using the current values of `net_ip` and `net_port`.
 pub fn restart() {
+    INITIALIZED.debug_check("net::restart");
     config(networking_enabled());
 }
 
+/// Turns networking on or off, binding or closing the game's socket
+/// accordingly.
+pub fn set_networking_enabled(enabled: bool) {
+    INITIALIZED.debug_check("net::set_networking_enabled");
+    config(enabled);
+}
+
+/// Registers the networking Dvars.
+pub fn init() {
+    dvar::register_string(
+        "net_ip",
+        "0.0.0.0",
+        dvar::DvarFlags::empty(),
+        Some("Network IP address"),
+    )
+    .unwrap();
+    dvar::register_int(
+        "net_port",
+        i32::from(DEFAULT_PORT),
+        Some(0),
+        Some(i32::from(u16::MAX)),
+        dvar::DvarFlags::empty(),
+        Some("Network port"),
+    )
+    .unwrap();
+    dvar::register_bool(
+        "net_showPackets",
+        false,
+        dvar::DvarFlags::empty(),
+        Some("Print each packet as it's sent and received"),
+    )
+    .unwrap();
+    add_commands();
+    INITIALIZED.set();
+}
+
 pub fn sleep(duration: Duration) {
     std::thread::sleep(duration);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    // Receives a packet for `src` off the game's socket, waiting a little
+    // for it to arrive since the socket doesn't block
+    fn wait_for_packet(
+        src: NetSrc,
+        buf: &mut [u8],
+    ) -> Option<(usize, NetAddr)> {
+        for _ in 0..100 {
+            if let Some(packet) = recv_from(src, buf).unwrap() {
+                return Some(packet);
+            }
+            sleep(Duration::from_millis(10));
+        }
+        None
+    }
+
+    #[test]
+    fn restart_rebinds_a_usable_socket() {
+        let _guard = testing::fresh_state();
+        init();
+        dvar::set_string_internal("net_ip", "127.0.0.1").unwrap();
+        dvar::set_int_internal("net_port", 0).unwrap();
+        set_networking_enabled(true);
+        assert!(local_addr().is_some());
+
+        restart();
+        let addr = local_addr().unwrap();
+        assert_ne!(addr.port(), 0);
+        // An ephemeral port is picked again rather than saved to net_port
+        assert_eq!(dvar::get_int("net_port"), Some(0));
+
+        send_to(NetSrc::Client, b"ping", NetAddr::Ip(addr)).unwrap();
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        let (received, from) =
+            wait_for_packet(NetSrc::Server, &mut buf).unwrap();
+        assert_eq!(buf.get(..received), Some(&b"ping"[..]));
+        assert_eq!(from, NetAddr::Ip(addr));
+
+        set_networking_enabled(false);
+        assert_eq!(local_addr(), None);
+    }
+
+    #[test]
+    fn binding_skips_a_port_in_use() {
+        let _guard = testing::fresh_state();
+        init();
+        let taken = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = taken.local_addr().unwrap().port();
+        dvar::set_string_internal("net_ip", "127.0.0.1").unwrap();
+        dvar::set_int_internal("net_port", i32::from(port)).unwrap();
+
+        set_networking_enabled(true);
+        let addr = local_addr().unwrap();
+        assert_ne!(addr.port(), port);
+        assert!(
+            (port..port.saturating_add(PORT_ATTEMPTS)).contains(&addr.port())
+        );
+        assert_eq!(dvar::get_int("net_port"), Some(i32::from(addr.port())));
+
+        set_networking_enabled(false);
+    }
+
+    #[test]
+    fn loopback_delivers_to_the_peer() {
+        let _guard = testing::fresh_state();
+        loopback(NetSrc::Client).clear();
+        loopback(NetSrc::Server).clear();
+
+        assert_eq!(
+            send_to(NetSrc::Client, b"hello", NetAddr::Loopback).unwrap(),
+            5
+        );
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        assert_eq!(recv_from(NetSrc::Client, &mut buf).unwrap(), None);
+        assert_eq!(
+            recv_from(NetSrc::Server, &mut buf).unwrap(),
+            Some((5, NetAddr::Loopback))
+        );
+        assert_eq!(buf.get(..5), Some(&b"hello"[..]));
+    }
+
+    #[test]
+    fn loopback_round_trip() {
+        let _guard = testing::fresh_state();
+        loopback(NetSrc::Client).clear();
+        loopback(NetSrc::Server).clear();
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+
+        send_to(NetSrc::Client, b"request", NetAddr::Loopback).unwrap();
+        let (received, from) =
+            recv_from(NetSrc::Server, &mut buf).unwrap().unwrap();
+        assert_eq!(buf.get(..received), Some(&b"request"[..]));
+
+        // Replying to wherever the packet came from gets back to the client
+        send_to(NetSrc::Server, b"response", from).unwrap();
+        let (received, from) =
+            recv_from(NetSrc::Client, &mut buf).unwrap().unwrap();
+        assert_eq!(buf.get(..received), Some(&b"response"[..]));
+        assert_eq!(from, NetAddr::Loopback);
+        assert_eq!(recv_from(NetSrc::Server, &mut buf).unwrap(), None);
+    }
+
+    #[test]
+    fn connecting_over_loopback() {
+        let _guard = testing::fresh_state();
+        init();
+        // Listening turns networking on, so keep it off the default port
+        dvar::set_string_internal("net_ip", "127.0.0.1").unwrap();
+        dvar::set_int_internal("net_port", 0).unwrap();
+        let connected = || {
+            console::get_text_copy(console::scrollback_bytes())
+                .lines()
+                .filter(|l| l.contains("Connected to loopback"))
+                .count()
+        };
+        let before = connected();
+
+        cmd::execute_string("net_listen");
+        cmd::execute_string("net_connect loopback");
+        // The server gets the request and responds, and then the client
+        // gets the response, all in the same frame
+        frame();
+        assert_eq!(connected(), before + 1);
+
+        LISTENING.store(false, Ordering::Relaxed);
+        *SERVER_ADDR.lock().unwrap() = None;
+        set_networking_enabled(false);
+    }
+}
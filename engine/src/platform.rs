@@ -45,6 +45,142 @@ unsafe impl HasRawWindowHandle for WindowHandle {
 
 pub struct ImageHandle(pub isize);
 
+/// Returns the ratio of physical pixels to the display server's logical
+/// units on `monitor` (e.g. `2.0` for a 200%-scaled or Retina display).
+///
+/// Returns `1.0` if the scale can't be determined.
+pub fn get_dpi_scale(monitor: display_server::target::MonitorHandle) -> f32 {
+    display_server::target::dpi_scale(monitor)
+}
+
+/// Returns a human-readable description of the OS and its version, for
+/// diagnostics. Never fails; returns "Unknown OS" if the OS can't be
+/// identified.
+pub fn os_version() -> String {
+    os::version()
+}
+
+/// Sets the calling thread's name at the OS level, so that it shows up in
+/// native debuggers and profilers. Names longer than the platform allows
+/// are truncated.
+pub fn set_thread_name(name: &str) {
+    os::target::set_thread_name(name);
+}
+
+/// Returns the OS's ID for the calling thread, for passing to
+/// [`set_thread_affinity`] (from this or any other thread).
+pub fn current_thread_id() -> u64 {
+    os::target::current_thread_id()
+}
+
+/// Restricts the thread with ID `thread` (see [`current_thread_id`]) to the
+/// logical CPUs set in `cpu_mask`, bit `n` being CPU `n`.
+///
+/// Returns [`Err`] if the OS refused, or doesn't support pinning threads.
+pub fn set_thread_affinity(thread: u64, cpu_mask: u64) -> Result<(), ()> {
+    os::target::set_thread_affinity(thread, cpu_mask)
+}
+
+/// Returns the logical CPUs the thread with ID `thread` is allowed to run
+/// on, in the same form [`set_thread_affinity`] takes, or [`None`] if the
+/// OS can't tell us.
+pub fn thread_affinity(thread: u64) -> Option<u64> {
+    os::target::thread_affinity(thread)
+}
+
+/// Returns the IP addresses of the machine's network interfaces that are
+/// up, other than loopback interfaces, in no particular order.
+///
+/// Returns [`Err`] if they couldn't be enumerated, or if this platform
+/// doesn't support doing so.
+pub fn interface_addresses() -> std::io::Result<Vec<std::net::IpAddr>> {
+    os::target::interface_addresses()
+}
+
+/// Returns the text currently on the system clipboard, or [`None`] if the
+/// clipboard doesn't hold any text (or this platform doesn't have a
+/// clipboard we can reach).
+pub fn clipboard_get() -> Option<String> {
+    cfg_if::cfg_if! {
+        if #[cfg(any(windows, wayland, appkit, xlib))] {
+            display_server::target::clipboard_get()
+        } else {
+            None
+        }
+    }
+}
+
+/// Replaces the contents of the system clipboard with `text`.
+///
+/// A no-op on platforms without a clipboard we can reach.
+pub fn clipboard_set(text: &str) -> Result<(), ()> {
+    cfg_if::cfg_if! {
+        if #[cfg(any(windows, wayland, appkit, xlib))] {
+            display_server::target::clipboard_set(text)
+        } else {
+            let _ = text;
+            Ok(())
+        }
+    }
+}
+
+/// How insistently [`request_attention`] asks for the user's attention.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AttentionLevel {
+    /// Something the user might want to know about finished (e.g. a
+    /// connection completing). Flashes briefly, where that's supported.
+    Informational,
+    /// Something needs the user (e.g. an error). Keeps flashing until the
+    /// window is focused, where that's supported.
+    Critical,
+}
+
+/// Asks the display server to draw the user's attention to the window
+/// `handle`, e.g. by flashing its taskbar button or bouncing its Dock icon.
+///
+/// A no-op on platforms where we can't.
+pub fn request_attention(handle: WindowHandle, level: AttentionLevel) {
+    cfg_if::cfg_if! {
+        if #[cfg(any(windows, appkit, xlib))] {
+            display_server::target::request_attention(handle, level);
+        } else {
+            let _ = (handle, level);
+        }
+    }
+}
+
+/// Sets the icon of the window `handle` to the `width`x`height` image
+/// `rgba`, given as rows of RGBA pixels from top to bottom. `rgba` must
+/// hold at least `width * height * 4` bytes.
+pub fn set_window_icon(
+    handle: WindowHandle,
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+) {
+    cfg_if::cfg_if! {
+        if #[cfg(any(windows, appkit, xlib))] {
+            display_server::target::set_window_icon(
+                handle, rgba, width, height,
+            );
+        } else {
+            let _ = (handle, rgba, width, height);
+        }
+    }
+}
+
+/// Plays the display server's alert sound, for when something warrants an
+/// audible cue and there's no sound system to play a proper one.
+///
+/// A no-op on platforms where we can't.
+pub fn system_beep() {
+    cfg_if::cfg_if! {
+        if #[cfg(any(windows, appkit, xlib))] {
+            display_server::target::system_beep();
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct PlatformVars {
     pub window_handle: Option<WindowHandle>,
@@ -1,4 +1,4 @@
-use std::arch::x86_64::{CpuidResult, __cpuid};
+use std::arch::x86_64::{CpuidResult, __cpuid, __cpuid_count, _xgetbv};
 
 pub const fn main() {}
 
@@ -7,3 +7,23 @@ pub fn cpuid(leaf: u32) -> CpuidResult {
     // CPUID should always be safe to execute.
     unsafe { __cpuid(leaf) }
 }
+
+pub fn cpuid_count(leaf: u32, sub_leaf: u32) -> CpuidResult {
+    // SAFETY:
+    // CPUID should always be safe to execute.
+    unsafe { __cpuid_count(leaf, sub_leaf) }
+}
+
+/// Reads XCR0, which says which register states the OS saves and restores
+/// on a context switch. Must only be called if CPUID reports OSXSAVE.
+pub fn xcr0() -> u64 {
+    #[target_feature(enable = "xsave")]
+    unsafe fn xgetbv_0() -> u64 {
+        _xgetbv(0)
+    }
+
+    // SAFETY:
+    // XGETBV is available whenever the OS has set CR4.OSXSAVE, which the
+    // caller has checked via CPUID.
+    unsafe { xgetbv_0() }
+}
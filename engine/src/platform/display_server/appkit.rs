@@ -4,7 +4,9 @@
 #![allow(non_upper_case_globals)]
 
 #[link(name = "Cocoa", kind = "framework")]
-extern "C" {}
+extern "C" {
+    pub fn NSBeep();
+}
 
 #[link(name = "ApplicationServices", kind = "framework")]
 extern "C" {
@@ -19,18 +21,24 @@ use std::ptr::addr_of;
 use core_foundation::uuid::CFUUIDRef;
 use core_graphics::display::CGDirectDisplayID;
 use icrate::{
+    ns_string,
     AppKit::{
         NSApp, NSApplication, NSApplicationDelegate,
-        NSApplicationTerminateReply, NSBitsPerPixelFromDepth,
-        NSDeviceIndependentModifierFlagsMask, NSEvent,
-        NSEventTypeApplicationDefined, NSEventTypeFlagsChanged,
-        NSEventTypeKeyDown, NSEventTypeKeyUp, NSEventTypeLeftMouseDown,
-        NSEventTypeLeftMouseUp, NSEventTypeMouseMoved,
-        NSEventTypeOtherMouseDown, NSEventTypeOtherMouseUp,
-        NSEventTypeRightMouseDown, NSEventTypeRightMouseUp,
-        NSEventTypeScrollWheel, NSResponder, NSWindow, NSWindowDelegate,
+        NSApplicationTerminateReply, NSBitmapImageRep, NSBitsPerPixelFromDepth,
+        NSCriticalRequest, NSDeviceIndependentModifierFlagsMask,
+        NSDeviceRGBColorSpace, NSEvent, NSEventTypeApplicationDefined,
+        NSEventTypeFlagsChanged, NSEventTypeKeyDown, NSEventTypeKeyUp,
+        NSEventTypeLeftMouseDown, NSEventTypeLeftMouseUp,
+        NSEventTypeMouseMoved, NSEventTypeOtherMouseDown,
+        NSEventTypeOtherMouseUp, NSEventTypeRightMouseDown,
+        NSEventTypeRightMouseUp, NSEventTypeScrollWheel, NSImage,
+        NSInformationalRequest, NSPasteboard, NSPasteboardTypeString,
+        NSResponder, NSScreen, NSWindow, NSWindowDelegate,
+    },
+    Foundation::{
+        CGPoint, CGSize, NSDate, NSNotification, NSNumber, NSRect, NSSize,
+        NSString,
     },
-    Foundation::{CGPoint, CGSize, NSDate, NSNotification, NSRect, NSSize},
 };
 use objc2::{
     declare::{Ivar, IvarEncode},
@@ -47,7 +55,8 @@ use raw_window_handle::{
 };
 
 use crate::{
-    platform::WindowHandle,
+    com,
+    platform::{AttentionLevel, WindowHandle},
     sys::{self, KeyboardScancode, WindowEvent},
 };
 
@@ -107,6 +116,143 @@ impl MonitorHandle {
 
 pub fn init() {}
 
+#[allow(clippy::cast_possible_truncation)]
+pub fn dpi_scale(monitor: MonitorHandle) -> f32 {
+    let display_id = monitor.get_appkit().unwrap();
+    let screens = unsafe { NSScreen::screens() };
+    screens
+        .iter()
+        .find(|screen| {
+            let description = unsafe { screen.deviceDescription() };
+            let number: *mut NSNumber = unsafe {
+                msg_send![
+                    &description,
+                    objectForKey: ns_string!("NSScreenNumber")
+                ]
+            };
+            !number.is_null()
+                && unsafe { (*number).unsignedIntValue() } == display_id
+        })
+        .map_or(1.0, |screen| unsafe { screen.backingScaleFactor() } as f32)
+}
+
+/// Returns the text currently on the general pasteboard, or [`None`] if it
+/// doesn't hold any text.
+pub fn clipboard_get() -> Option<String> {
+    let pasteboard = unsafe { NSPasteboard::generalPasteboard() };
+    unsafe { pasteboard.stringForType(NSPasteboardTypeString) }
+        .map(|s| s.to_string())
+}
+
+/// Replaces the contents of the general pasteboard with `text`.
+pub fn clipboard_set(text: &str) -> Result<(), ()> {
+    let pasteboard = unsafe { NSPasteboard::generalPasteboard() };
+    unsafe { pasteboard.clearContents() };
+    let written = unsafe {
+        pasteboard.setString_forType(
+            &NSString::from_str(text),
+            NSPasteboardTypeString,
+        )
+    };
+    if written {
+        Ok(())
+    } else {
+        Err(())
+    }
+}
+
+/// Bounces the Dock icon. An [`AttentionLevel::Critical`] request keeps
+/// bouncing until the app is activated. Does nothing if the app is already
+/// active, which is where the window `handle` lives regardless.
+pub fn request_attention(_handle: WindowHandle, level: AttentionLevel) {
+    let request_type = match level {
+        AttentionLevel::Informational => NSInformationalRequest,
+        AttentionLevel::Critical => NSCriticalRequest,
+    };
+    let Some(app) = (unsafe { NSApp }) else {
+        return;
+    };
+    unsafe { app.requestUserAttention(request_type) };
+}
+
+/// Sets the app's Dock icon to the `width`x`height` image `rgba`. AppKit
+/// windows don't have icons of their own, so `handle` makes no difference.
+#[allow(clippy::undocumented_unsafe_blocks)]
+pub fn set_window_icon(
+    _handle: WindowHandle,
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+) {
+    let (Ok(w), Ok(h)) =
+        (NSInteger::try_from(width), NSInteger::try_from(height))
+    else {
+        return;
+    };
+    let Some(len) = w.checked_mul(h).and_then(|n| n.checked_mul(4)) else {
+        return;
+    };
+    let Some(pixels) = usize::try_from(len).ok().and_then(|l| rgba.get(..l))
+    else {
+        return;
+    };
+
+    // Bitmap reps want their alpha premultiplied unless told otherwise
+    let premultiplied = pixels
+        .chunks_exact(4)
+        .flat_map(|p| match *p {
+            [r, g, b, a] => {
+                let scale = |c: u8| {
+                    u8::try_from(u16::from(c) * u16::from(a) / 255)
+                        .unwrap_or(u8::MAX)
+                };
+                [scale(r), scale(g), scale(b), a]
+            }
+            _ => [0; 4],
+        })
+        .collect::<Vec<_>>();
+
+    // With no planes passed in, the rep allocates a buffer of its own for
+    // the pixels to be copied into
+    let Some(rep) = (unsafe {
+        NSBitmapImageRep::initWithBitmapDataPlanes_pixelsWide_pixelsHigh_bitsPerSample_samplesPerPixel_hasAlpha_isPlanar_colorSpaceName_bytesPerRow_bitsPerPixel(
+            NSBitmapImageRep::alloc(),
+            core::ptr::null_mut(),
+            w,
+            h,
+            8,
+            4,
+            true,
+            false,
+            NSDeviceRGBColorSpace,
+            w * 4,
+            32,
+        )
+    }) else {
+        return;
+    };
+    unsafe {
+        core::ptr::copy_nonoverlapping(
+            premultiplied.as_ptr(),
+            rep.bitmapData(),
+            premultiplied.len(),
+        );
+    }
+
+    let size = NSSize::new(f64::from(width), f64::from(height));
+    let image = unsafe { NSImage::initWithSize(NSImage::alloc(), size) };
+    unsafe { image.addRepresentation(&rep) };
+    let Some(app) = (unsafe { NSApp }) else {
+        return;
+    };
+    unsafe { app.setApplicationIconImage(Some(&image)) };
+}
+
+/// Plays the user's alert sound.
+pub fn system_beep() {
+    unsafe { NSBeep() };
+}
+
 pub struct AppKitGlobals {
     app: Id<NSApplication>,
 }
@@ -138,7 +284,9 @@ declare_class!(
             &self,
             _sender: &NSApplication,
         ) -> NSApplicationTerminateReply {
-            sys::set_quit_event();
+            // Cancel the termination and let the main thread quit once it's
+            // been confirmed, the same as closing the window
+            com::request_quit();
             0
         }
     }
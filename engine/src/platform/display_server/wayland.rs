@@ -14,6 +14,19 @@ use crate::platform::WindowHandle;
 
 pub fn init() {}
 
+// TODO - use wl_output's scale once monitors can be enumerated
+pub const fn dpi_scale(_monitor: MonitorHandle) -> f32 {
+    1.0
+}
+
+pub fn clipboard_get() -> Option<String> {
+    None
+}
+
+pub fn clipboard_set(_text: &str) -> Result<(), ()> {
+    Ok(())
+}
+
 pub trait WindowHandleExt {
     fn get_wayland(&self) -> Option<WaylandWindowHandle>;
 }
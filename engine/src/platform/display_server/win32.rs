@@ -6,15 +6,279 @@ use raw_window_handle::{
     HasRawDisplayHandle, RawDisplayHandle, RawWindowHandle, Win32WindowHandle,
     WindowsDisplayHandle,
 };
+
 use windows::Win32::{
-    Foundation::{HMODULE, HWND},
+    Foundation::{BOOL, HANDLE, HGLOBAL, HMODULE, HWND, LPARAM, WPARAM},
     Graphics::Gdi::HMONITOR,
+    System::{
+        DataExchange::{
+            CloseClipboard, EmptyClipboard, GetClipboardData, OpenClipboard,
+            SetClipboardData,
+        },
+        Memory::{
+            GlobalAlloc, GlobalFree, GlobalLock, GlobalSize, GlobalUnlock,
+            GMEM_MOVEABLE,
+        },
+        Ole::CF_UNICODETEXT,
+    },
+    UI::{
+        HiDpi::{
+            GetDpiForMonitor, SetProcessDpiAwarenessContext,
+            DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, MDT_EFFECTIVE_DPI,
+        },
+        WindowsAndMessaging::{
+            CreateIconFromResourceEx, FlashWindowEx, MessageBeep, SendMessageA,
+            FLASHWINFO, FLASHW_ALL, FLASHW_TIMERNOFG, ICON_BIG, ICON_SMALL,
+            LR_DEFAULTCOLOR, MB_ICONERROR, WM_SETICON,
+        },
+    },
 };
 
-use crate::platform::WindowHandle;
+use crate::platform::{AttentionLevel, WindowHandle};
 
 pub fn init() {}
 
+// The DPI Windows considers to be 100% scaling.
+const DEFAULT_DPI: u32 = 96;
+
+#[allow(clippy::cast_precision_loss)]
+pub fn dpi_scale(monitor: MonitorHandle) -> f32 {
+    let mut dpi_x = 0;
+    let mut dpi_y = 0;
+    // SAFETY:
+    // GetDpiForMonitor is an FFI function, requiring use of unsafe.
+    // The out-pointers are valid for the duration of the call.
+    if unsafe {
+        GetDpiForMonitor(
+            monitor.get_win32().unwrap(),
+            MDT_EFFECTIVE_DPI,
+            &mut dpi_x,
+            &mut dpi_y,
+        )
+    }
+    .is_ok()
+        && dpi_x != 0
+    {
+        dpi_x as f32 / DEFAULT_DPI as f32
+    } else {
+        1.0
+    }
+}
+
+/// Returns the DPI that corresponds to the scale `scale` (as returned by
+/// [`dpi_scale`]).
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+pub fn dpi_for_scale(scale: f32) -> u32 {
+    (scale * DEFAULT_DPI as f32).round() as u32
+}
+
+/// Opts the process out of Windows' bitmap scaling, so that window sizes
+/// are in physical pixels on every monitor.
+///
+/// Has to be called before any windows are created, and only once, since
+/// Windows only lets the awareness be set once per process (see
+/// [`crate::sys::init_dpi_awareness`]).
+pub fn set_dpi_aware() {
+    // SAFETY:
+    // SetProcessDpiAwarenessContext is an FFI function, requiring use of
+    // unsafe. It takes no pointers and has no preconditions.
+    unsafe {
+        SetProcessDpiAwarenessContext(
+            DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+        );
+    }
+}
+
+/// Flashes the taskbar button of the window `handle`. An
+/// [`AttentionLevel::Critical`] request keeps flashing until the window is
+/// brought to the foreground.
+pub fn request_attention(handle: WindowHandle, level: AttentionLevel) {
+    let (flags, count) = match level {
+        AttentionLevel::Informational => (FLASHW_ALL, 3),
+        AttentionLevel::Critical => (FLASHW_ALL | FLASHW_TIMERNOFG, 0),
+    };
+    let info = FLASHWINFO {
+        cbSize: core::mem::size_of::<FLASHWINFO>() as _,
+        hwnd: HWND(handle.get_win32().unwrap().hwnd as _),
+        dwFlags: flags,
+        uCount: count,
+        // Use the default cursor blink rate
+        dwTimeout: 0,
+    };
+    // SAFETY:
+    // FlashWindowEx is an FFI function, requiring use of unsafe. `info` is
+    // fully initialized and outlives the call.
+    unsafe { FlashWindowEx(&info) };
+}
+
+// The icon resource format version CreateIconFromResourceEx expects
+const ICON_RESOURCE_VERSION: u32 = 0x0003_0000;
+const BITMAPINFOHEADER_SIZE: u32 = 40;
+
+// Packs `rgba` into the in-memory form of an icon resource, which is what
+// CreateIconFromResourceEx takes: a BITMAPINFOHEADER (whose height is
+// doubled to cover the AND mask too), the pixels as bottom-up BGRA, then
+// the 1bpp AND mask, which is left empty since the alpha channel is used
+// instead.
+fn icon_resource_bits(rgba: &[u8], width: u32, height: u32) -> Option<Vec<u8>> {
+    let w = usize::try_from(width).ok()?;
+    let h = usize::try_from(height).ok()?;
+    if w == 0 || h == 0 {
+        return None;
+    }
+    let pixels = rgba.get(..w.checked_mul(h)?.checked_mul(4)?)?;
+    let mask_stride = (w + 31) / 32 * 4;
+
+    let mut bits = Vec::new();
+    bits.extend(BITMAPINFOHEADER_SIZE.to_le_bytes());
+    bits.extend(i32::try_from(width).ok()?.to_le_bytes());
+    bits.extend(i32::try_from(height).ok()?.checked_mul(2)?.to_le_bytes());
+    // biPlanes and biBitCount
+    bits.extend(1u16.to_le_bytes());
+    bits.extend(32u16.to_le_bytes());
+    // biCompression (BI_RGB) through biClrImportant
+    bits.extend([0; 24]);
+    for row in pixels.chunks_exact(w * 4).rev() {
+        for p in row.chunks_exact(4) {
+            if let [r, g, b, a] = *p {
+                bits.extend([b, g, r, a]);
+            }
+        }
+    }
+    bits.resize(bits.len() + mask_stride * h, 0);
+    Some(bits)
+}
+
+/// Sets the title bar and taskbar icons of the window `handle` to the
+/// `width`x`height` image `rgba`.
+// All uses of unsafe here are for FFI. `bits` outlives the call it's
+// passed to.
+#[allow(
+    clippy::undocumented_unsafe_blocks,
+    clippy::as_conversions,
+    clippy::cast_possible_wrap
+)]
+pub fn set_window_icon(
+    handle: WindowHandle,
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+) {
+    let Some(bits) = icon_resource_bits(rgba, width, height) else {
+        return;
+    };
+    let Ok(icon) = (unsafe {
+        CreateIconFromResourceEx(
+            &bits,
+            BOOL(1),
+            ICON_RESOURCE_VERSION,
+            width as _,
+            height as _,
+            LR_DEFAULTCOLOR,
+        )
+    }) else {
+        return;
+    };
+
+    let hwnd = HWND(handle.get_win32().unwrap().hwnd as _);
+    for size in [ICON_BIG, ICON_SMALL] {
+        unsafe {
+            SendMessageA(hwnd, WM_SETICON, WPARAM(size as _), LPARAM(icon.0));
+        }
+    }
+}
+
+/// Plays the system's error sound.
+pub fn system_beep() {
+    // SAFETY:
+    // MessageBeep is an FFI function, requiring use of unsafe. It doesn't
+    // touch any memory of ours.
+    unsafe { MessageBeep(MB_ICONERROR) };
+}
+
+/// Returns the text currently on the clipboard, or [`None`] if the
+/// clipboard is empty, holds something other than text, or couldn't be
+/// opened.
+// All uses of unsafe here are for FFI. The clipboard is opened before any
+// of its data is touched and closed on every path out of the function.
+#[allow(clippy::undocumented_unsafe_blocks)]
+pub fn clipboard_get() -> Option<String> {
+    if unsafe { OpenClipboard(HWND(0)) }.as_bool() == false {
+        return None;
+    }
+
+    let text = unsafe { GetClipboardData(u32::from(CF_UNICODETEXT.0)) }
+        .ok()
+        .and_then(|data| {
+            let data = HGLOBAL(data.0);
+            let p = unsafe { GlobalLock(data) }.cast::<u16>();
+            if p.is_null() {
+                return None;
+            }
+            // CF_UNICODETEXT data should be null-terminated, but don't read
+            // past the end of the allocation if it isn't
+            let max_len = unsafe { GlobalSize(data) } / 2;
+            let data_u16 = unsafe { core::slice::from_raw_parts(p, max_len) };
+            let len = data_u16
+                .iter()
+                .position(|&c| c == 0)
+                .unwrap_or(data_u16.len());
+            let text = String::from_utf16_lossy(&data_u16[..len]);
+            unsafe { GlobalUnlock(data) };
+            Some(text)
+        });
+
+    unsafe { CloseClipboard() };
+    text
+}
+
+/// Replaces the contents of the clipboard with `text`.
+// All uses of unsafe here are for FFI. The allocation is only written
+// within its bounds, and ownership of it passes to the clipboard once
+// SetClipboardData succeeds.
+#[allow(clippy::undocumented_unsafe_blocks)]
+pub fn clipboard_set(text: &str) -> Result<(), ()> {
+    let wide = text
+        .encode_utf16()
+        .chain(core::iter::once(0))
+        .collect::<Vec<_>>();
+    let data = unsafe {
+        GlobalAlloc(GMEM_MOVEABLE, wide.len() * core::mem::size_of::<u16>())
+    }
+    .map_err(|_| ())?;
+    let p = unsafe { GlobalLock(data) }.cast::<u16>();
+    if p.is_null() {
+        unsafe { GlobalFree(data) }.ok();
+        return Err(());
+    }
+    unsafe {
+        core::ptr::copy_nonoverlapping(wide.as_ptr(), p, wide.len());
+        GlobalUnlock(data);
+    }
+
+    if unsafe { OpenClipboard(HWND(0)) }.as_bool() == false {
+        unsafe { GlobalFree(data) }.ok();
+        return Err(());
+    }
+
+    unsafe { EmptyClipboard() };
+    let res = unsafe {
+        SetClipboardData(u32::from(CF_UNICODETEXT.0), HANDLE(data.0))
+    };
+    unsafe { CloseClipboard() };
+
+    if res.is_err() {
+        unsafe { GlobalFree(data) }.ok();
+        return Err(());
+    }
+
+    Ok(())
+}
+
 pub trait WindowHandleExt {
     fn get_win32(&self) -> Option<Win32WindowHandle>;
     fn from_win32(hwnd: HWND, hinstance: Option<HMODULE>) -> Self;
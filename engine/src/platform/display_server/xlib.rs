@@ -5,13 +5,17 @@
 // have to do this to deal with warnings created from x11 constants
 #![allow(non_upper_case_globals)]
 
-use core::sync::atomic::AtomicU64;
+use core::{
+    sync::atomic::{AtomicU64, AtomicUsize},
+    time::Duration,
+};
 use std::{
     collections::VecDeque,
-    ffi::{c_char, OsString},
+    ffi::{c_char, c_long, c_uint, CStr, OsString},
     os::unix::prelude::OsStrExt,
-    ptr::addr_of_mut,
-    sync::RwLock,
+    ptr::{addr_of, addr_of_mut},
+    sync::{mpsc, Mutex, RwLock},
+    time::Instant,
 };
 
 use lazy_static::lazy_static;
@@ -48,19 +52,28 @@ use x11::{
         XK_X, XK_Y, XK_Z,
     },
     xlib::{
-        Button1, Button2, Button3, Button4, Button5, ButtonPress,
-        ButtonRelease, ConfigureNotify, ControlMask, CreateNotify, CurrentTime,
-        DestroyNotify, FocusIn, FocusOut, KeyPress, KeyRelease, LockMask,
-        Mod1Mask, Mod2Mask, Mod3Mask, Mod4Mask, Mod5Mask, RevertToParent,
-        ShiftMask, XCloseDisplay, XDefaultDepth, XDefaultScreen,
-        XDefaultVisual, XEvent, XInternAtom, XKeycodeToKeysym, XLookupString,
-        XOpenDisplay, XSetInputFocus, XVisualIDFromVisual,
+        AnyPropertyType, Atom, Button1, Button2, Button3, Button4, Button5,
+        ButtonPress, ButtonRelease, ClientMessage, ClientMessageData,
+        ConfigureNotify, ControlMask, CreateNotify, CurrentTime, DestroyNotify,
+        Display, FocusIn, FocusOut, KeyPress, KeyRelease, LockMask, Mod1Mask,
+        Mod2Mask, Mod3Mask, Mod4Mask, Mod5Mask, NoEventMask, PropModeReplace,
+        RevertToParent, SelectionClear, SelectionNotify, SelectionRequest,
+        ShiftMask, SubstructureNotifyMask, SubstructureRedirectMask, Window,
+        XBell, XChangeProperty, XCheckTypedWindowEvent, XClassHint,
+        XClientMessageEvent, XCloseDisplay, XConvertSelection,
+        XCreateSimpleWindow, XDefaultDepth, XDefaultScreen, XDefaultVisual,
+        XDestroyWindow, XDisplayWidth, XDisplayWidthMM, XEvent, XFlush, XFree,
+        XGetSelectionOwner, XGetWindowProperty, XInitThreads, XInternAtom,
+        XKeycodeToKeysym, XLookupString, XNextEvent, XOpenDisplay, XRootWindow,
+        XSelectionEvent, XSendEvent, XSetClassHint, XSetInputFocus,
+        XSetSelectionOwner, XVisualIDFromVisual, XA_ATOM, XA_CARDINAL,
+        XA_STRING,
     },
-    xrandr::RRScreenChangeNotify,
+    xrandr::{RRScreenChangeNotify, XRRFreeMonitors, XRRGetMonitors},
 };
 
 use crate::{
-    platform::WindowHandle,
+    platform::{AttentionLevel, WindowHandle},
     sys::{KeyboardScancode, Modifiers, MouseScancode, WindowEvent},
     util::EasierAtomic,
 };
@@ -128,22 +141,436 @@ pub fn display_name() -> *const c_char {
     }
 }
 
+/// An open connection to the X server, closed when dropped.
+///
+/// Prefer this over calling [`XOpenDisplay`] and [`XCloseDisplay`] manually,
+/// so that the connection is cleaned up on every path out of a function,
+/// including early returns and panics.
+#[derive(Debug)]
+pub struct XDisplayGuard(*mut Display);
+
+// Number of connections currently held open by an `XDisplayGuard`. Only
+// tracked in debug builds, to catch leaked connections.
+#[cfg(debug_assertions)]
+static OPEN_DISPLAY_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of display connections currently held open by an
+/// [`XDisplayGuard`].
+#[cfg(debug_assertions)]
+pub fn open_display_count() -> usize {
+    OPEN_DISPLAY_COUNT.load_relaxed()
+}
+
+impl XDisplayGuard {
+    /// Opens a connection to the display named by [`display_name`].
+    ///
+    /// Returns [`None`] if the connection couldn't be opened.
+    pub fn open() -> Option<Self> {
+        let display = unsafe { XOpenDisplay(display_name()) };
+        if display.is_null() {
+            None
+        } else {
+            #[cfg(debug_assertions)]
+            OPEN_DISPLAY_COUNT.increment_wrapping();
+            Some(Self(display))
+        }
+    }
+
+    /// Returns the raw display pointer.
+    ///
+    /// The pointer is only valid for as long as the guard is alive.
+    pub const fn as_ptr(&self) -> *mut Display {
+        self.0
+    }
+}
+
+impl Drop for XDisplayGuard {
+    fn drop(&mut self) {
+        unsafe {
+            XCloseDisplay(self.0);
+        }
+        #[cfg(debug_assertions)]
+        OPEN_DISPLAY_COUNT.decrement_wrapping();
+    }
+}
+
+// The connection the game's windows are created on and their events are
+// read from. Kept in its own type so it can be stored in a static.
+struct SharedDisplay(*mut Display);
+
+// SAFETY:
+// Xlib connections can be used from any thread once `XInitThreads` has
+// been called, which `init` does before anything else. Access from Rust is
+// also serialized by the mutex `SharedDisplay` lives in.
+unsafe impl Send for SharedDisplay {}
+
+lazy_static! {
+    static ref SHARED_DISPLAY: Mutex<SharedDisplay> =
+        Mutex::new(SharedDisplay(core::ptr::null_mut()));
+}
+
+/// Calls `f` with the shared connection to the X server, opening it first if
+/// this is the first call. Returns [`None`] if the connection couldn't be
+/// opened.
+///
+/// Unlike an [`XDisplayGuard`], the shared connection is never closed, so
+/// pointers to it (e.g. in a [`MonitorHandle`]) stay valid for the rest of
+/// the program. It's also the one windows have to be created on, since
+/// events for a window are only delivered to the connection that created it.
+///
+/// `f` mustn't call `with_display` itself.
+pub fn with_display<T>(f: impl FnOnce(*mut Display) -> T) -> Option<T> {
+    let mut shared = SHARED_DISPLAY.lock().unwrap();
+    if shared.0.is_null() {
+        // SAFETY:
+        // XOpenDisplay is an FFI function, requiring use of unsafe.
+        // `display_name` is either null or a null-terminated string.
+        shared.0 = unsafe { XOpenDisplay(display_name()) };
+        if shared.0.is_null() {
+            return None;
+        }
+    }
+
+    Some(f(shared.0))
+}
+
 pub fn init() {
     let display_env = std::env::var_os("DISPLAY");
     *DISPLAY.write().unwrap() = display_env;
-    let display = unsafe { XOpenDisplay(display_name()) };
-    let atom = unsafe {
+
+    // The shared connection is used from multiple threads, which Xlib only
+    // allows if it's told so before any other call.
+    unsafe { XInitThreads() };
+
+    let atom = with_display(|display| unsafe {
         XInternAtom(
             display,
             cstr!("WM_DELETE_WINDOW").as_ptr(),
             x11::xlib::False,
         )
+    })
+    .expect("couldn't open the X display");
+    assert_ne!(atom, 0);
+    WM_DELETE_WINDOW.store_relaxed(atom);
+}
+
+fn intern_atom(display: &XDisplayGuard, name: &CStr) -> Atom {
+    // SAFETY:
+    // XInternAtom is an FFI function, requiring use of unsafe. The display
+    // is valid for as long as the guard is, and `name` is null-terminated.
+    unsafe { XInternAtom(display.as_ptr(), name.as_ptr(), x11::xlib::False) }
+}
+
+/// Creates a 1x1, never-mapped window. X only hands selections to and from
+/// windows, so the clipboard functions need one to talk to other clients.
+fn create_clipboard_window(display: &XDisplayGuard) -> Window {
+    // SAFETY:
+    // XCreateSimpleWindow and XDefaultScreen are FFI functions, requiring
+    // use of unsafe. The display is valid for as long as the guard is.
+    unsafe {
+        let screen = XDefaultScreen(display.as_ptr());
+        let root = XRootWindow(display.as_ptr(), screen);
+        XCreateSimpleWindow(display.as_ptr(), root, 0, 0, 1, 1, 0, 0, 0)
+    }
+}
+
+/// How long [`clipboard_get`] waits for the clipboard's owner to answer
+/// before giving up.
+const CLIPBOARD_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Returns the text currently on the clipboard, or [`None`] if the
+/// clipboard is empty, holds something other than text, or its owner
+/// didn't answer in time.
+// All uses of unsafe in the following function are either for FFI or for
+// accessing the members of the XEvent union (which is tagged by `type_`,
+// and only read after XCheckTypedWindowEvent has matched the type).
+#[allow(clippy::undocumented_unsafe_blocks)]
+pub fn clipboard_get() -> Option<String> {
+    let display = XDisplayGuard::open()?;
+    let clipboard = intern_atom(&display, cstr!("CLIPBOARD"));
+    let utf8_string = intern_atom(&display, cstr!("UTF8_STRING"));
+    let property = intern_atom(&display, cstr!("OPENT5_CLIPBOARD"));
+    let window = create_clipboard_window(&display);
+
+    unsafe {
+        XConvertSelection(
+            display.as_ptr(),
+            clipboard,
+            utf8_string,
+            property,
+            window,
+            CurrentTime,
+        );
+        XFlush(display.as_ptr());
+    }
+
+    let start = Instant::now();
+    let text = loop {
+        let mut ev = unsafe { core::mem::zeroed::<XEvent>() };
+        if unsafe {
+            XCheckTypedWindowEvent(
+                display.as_ptr(),
+                window,
+                SelectionNotify,
+                addr_of_mut!(ev),
+            )
+        } != 0
+        {
+            // The owner sets the property to None if it couldn't convert
+            // the clipboard's contents to text
+            break if unsafe { ev.selection }.property == 0 {
+                None
+            } else {
+                read_clipboard_property(&display, window, property)
+            };
+        }
+
+        if start.elapsed() >= CLIPBOARD_TIMEOUT {
+            break None;
+        }
+        std::thread::sleep(Duration::from_millis(1));
     };
+
+    unsafe { XDestroyWindow(display.as_ptr(), window) };
+    text
+}
+
+// All uses of unsafe in the following function are for FFI, or for reading
+// the buffer Xlib returns, which is `nitems` bytes long for 8-bit formats.
+#[allow(clippy::undocumented_unsafe_blocks, clippy::cast_possible_truncation)]
+fn read_clipboard_property(
+    display: &XDisplayGuard,
+    window: Window,
+    property: Atom,
+) -> Option<String> {
+    let mut actual_type = 0;
+    let mut actual_format = 0;
+    let mut nitems = 0;
+    let mut bytes_after = 0;
+    let mut data = core::ptr::null_mut();
+    // TODO - owners send large selections incrementally (the INCR
+    // protocol), which isn't handled here
     unsafe {
-        XCloseDisplay(display);
+        XGetWindowProperty(
+            display.as_ptr(),
+            window,
+            property,
+            0,
+            c_long::MAX / 4,
+            x11::xlib::True,
+            AnyPropertyType as _,
+            addr_of_mut!(actual_type),
+            addr_of_mut!(actual_format),
+            addr_of_mut!(nitems),
+            addr_of_mut!(bytes_after),
+            addr_of_mut!(data),
+        );
     }
-    assert_ne!(atom, 0);
-    WM_DELETE_WINDOW.store_relaxed(atom);
+
+    if data.is_null() {
+        return None;
+    }
+
+    let text = if actual_format == 8 {
+        let bytes =
+            unsafe { core::slice::from_raw_parts(data, nitems as usize) };
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    } else {
+        None
+    };
+    unsafe { XFree(data.cast()) };
+    text
+}
+
+/// Replaces the contents of the clipboard with `text`.
+///
+/// X doesn't store the clipboard anywhere - its owner hands the contents
+/// to whoever asks for them. So a background thread is spawned to own the
+/// clipboard and answer requests until some other client takes it over.
+pub fn clipboard_set(text: &str) -> Result<(), ()> {
+    let text = text.to_owned();
+    let (tx, rx) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("clipboard".to_owned())
+        .spawn(move || serve_clipboard(&text, &tx))
+        .map_err(|_| ())?;
+    rx.recv().unwrap_or(Err(()))
+}
+
+// All uses of unsafe in the following function are either for FFI or for
+// accessing the members of the XEvent union. The union accesses are all
+// guarded by a check of the event's type.
+#[allow(
+    clippy::undocumented_unsafe_blocks,
+    clippy::cast_possible_truncation,
+    clippy::cast_possible_wrap
+)]
+fn serve_clipboard(text: &str, ready: &mpsc::Sender<Result<(), ()>>) {
+    let Some(display) = XDisplayGuard::open() else {
+        ready.send(Err(())).ok();
+        return;
+    };
+    let clipboard = intern_atom(&display, cstr!("CLIPBOARD"));
+    let utf8_string = intern_atom(&display, cstr!("UTF8_STRING"));
+    let targets = intern_atom(&display, cstr!("TARGETS"));
+    let window = create_clipboard_window(&display);
+
+    unsafe {
+        XSetSelectionOwner(display.as_ptr(), clipboard, window, CurrentTime);
+    }
+    if unsafe { XGetSelectionOwner(display.as_ptr(), clipboard) } != window {
+        unsafe { XDestroyWindow(display.as_ptr(), window) };
+        ready.send(Err(())).ok();
+        return;
+    }
+    ready.send(Ok(())).ok();
+
+    loop {
+        let mut ev = unsafe { core::mem::zeroed::<XEvent>() };
+        unsafe { XNextEvent(display.as_ptr(), addr_of_mut!(ev)) };
+        match ev.get_type() {
+            // Someone else owns the clipboard now, so we're done
+            SelectionClear => break,
+            SelectionRequest => {
+                let req = unsafe { ev.selection_request };
+                // Obsolete clients don't name a property, in which case
+                // the target's name is used instead
+                let property = if req.property == 0 {
+                    req.target
+                } else {
+                    req.property
+                };
+
+                let mut reply = XSelectionEvent {
+                    type_: SelectionNotify,
+                    serial: 0,
+                    send_event: x11::xlib::True,
+                    display: display.as_ptr(),
+                    requestor: req.requestor,
+                    selection: req.selection,
+                    target: req.target,
+                    property,
+                    time: req.time,
+                };
+
+                if req.target == targets {
+                    let supported = [targets, utf8_string, XA_STRING];
+                    unsafe {
+                        XChangeProperty(
+                            display.as_ptr(),
+                            req.requestor,
+                            property,
+                            XA_ATOM,
+                            32,
+                            PropModeReplace,
+                            supported.as_ptr().cast(),
+                            supported.len() as _,
+                        );
+                    }
+                } else if req.target == utf8_string || req.target == XA_STRING {
+                    unsafe {
+                        XChangeProperty(
+                            display.as_ptr(),
+                            req.requestor,
+                            property,
+                            req.target,
+                            8,
+                            PropModeReplace,
+                            text.as_ptr(),
+                            text.len() as _,
+                        );
+                    }
+                } else {
+                    // Tell the requestor we can't convert to that target
+                    reply.property = 0;
+                }
+
+                let mut ev = XEvent { selection: reply };
+                unsafe {
+                    XSendEvent(
+                        display.as_ptr(),
+                        req.requestor,
+                        x11::xlib::False,
+                        NoEventMask,
+                        addr_of_mut!(ev),
+                    );
+                    XFlush(display.as_ptr());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    unsafe { XDestroyWindow(display.as_ptr(), window) };
+}
+
+// The keycode of the key below Escape (`TLDE` in XKB terms), which is the
+// same with both the evdev and the older kbd driver
+const TLDE_KEYCODE: c_uint = 49;
+
+// The DPI that's considered to be 100% scaling.
+const DEFAULT_DPI: f32 = 96.0;
+const MM_PER_INCH: f32 = 25.4;
+
+#[allow(
+    clippy::undocumented_unsafe_blocks,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+pub fn dpi_scale(monitor: MonitorHandle) -> f32 {
+    let screen = monitor.get_xlib().unwrap().screen;
+    with_display(|display| {
+        // The handle is for an X screen, which may have several RandR
+        // monitors, so look them up on that screen's root window
+        let root_window = unsafe { XRootWindow(display, screen) };
+        let mut nmonitors = 0;
+        let monitors_ptr = unsafe {
+            XRRGetMonitors(
+                display,
+                root_window,
+                x11::xlib::True,
+                addr_of_mut!(nmonitors),
+            )
+        };
+
+        // Prefer XRandR's per-monitor physical size (of the screen's
+        // primary monitor, or its first if none of them are primary), and
+        // fall back to the size of the whole screen if there aren't any.
+        let monitor_size = if monitors_ptr.is_null() {
+            None
+        } else {
+            // SAFETY: assuming the Xlib implementation is conforming,
+            // [monitors_ptr, monitors_ptr + nmonitors) should always
+            // be valid.
+            let monitors = unsafe {
+                core::slice::from_raw_parts(monitors_ptr, nmonitors as _)
+            };
+            let size = monitors
+                .iter()
+                .find(|m| m.primary != 0)
+                .or_else(|| monitors.first())
+                .map(|m| (m.width, m.mwidth));
+            unsafe {
+                XRRFreeMonitors(monitors_ptr);
+            }
+            size
+        };
+        let (width, width_mm) = monitor_size.unwrap_or_else(|| unsafe {
+            (
+                XDisplayWidth(display, screen),
+                XDisplayWidthMM(display, screen),
+            )
+        });
+        if width <= 0 || width_mm <= 0 {
+            return 1.0;
+        }
+
+        // Physical DPIs are rarely exact multiples of 96, so round to the
+        // nearest quarter to land on the scales users actually pick.
+        let dpi = width as f32 * MM_PER_INCH / width_mm as f32;
+        ((dpi / DEFAULT_DPI * 4.0).round() / 4.0).max(1.0)
+    })
+    .unwrap_or(1.0)
 }
 
 pub fn show_window(handle: WindowHandle) {
@@ -153,15 +580,215 @@ pub fn show_window(handle: WindowHandle) {
 
 pub fn focus_window(handle: WindowHandle) {
     let handle = handle.get_xlib().unwrap();
-    let display = unsafe { XOpenDisplay(display_name()) };
-    unsafe {
+    with_display(|display| unsafe {
         XSetInputFocus(display, handle.window, RevertToParent, CurrentTime)
+    });
+}
+
+// _NET_WM_STATE's action for adding a state
+const NET_WM_STATE_ADD: c_long = 1;
+// _NET_WM_STATE's source indication for normal applications
+const NET_WM_SOURCE_APPLICATION: c_long = 1;
+
+/// Asks the window manager to mark the window `handle` as demanding
+/// attention (`_NET_WM_STATE_DEMANDS_ATTENTION`). The window manager decides
+/// how to show that, and clears it once the window is focused, so `level`
+/// makes no difference here.
+// All uses of unsafe here are for FFI. The event is fully initialized
+// before it's sent.
+#[allow(
+    clippy::undocumented_unsafe_blocks,
+    clippy::cast_possible_wrap,
+    clippy::cast_possible_truncation
+)]
+pub fn request_attention(handle: WindowHandle, _level: AttentionLevel) {
+    let handle = handle.get_xlib().unwrap();
+    with_display(|display| {
+        let wm_state =
+            unsafe { XInternAtom(display, cstr!("_NET_WM_STATE").as_ptr(), 0) };
+        let demands_attention = unsafe {
+            XInternAtom(
+                display,
+                cstr!("_NET_WM_STATE_DEMANDS_ATTENTION").as_ptr(),
+                0,
+            )
+        };
+
+        let mut data = ClientMessageData::new();
+        data.set_long(0, NET_WM_STATE_ADD);
+        data.set_long(1, demands_attention as _);
+        data.set_long(2, 0);
+        data.set_long(3, NET_WM_SOURCE_APPLICATION);
+        let mut ev = XEvent {
+            client_message: XClientMessageEvent {
+                type_: ClientMessage,
+                serial: 0,
+                send_event: x11::xlib::True,
+                display,
+                window: handle.window,
+                message_type: wm_state,
+                format: 32,
+                data,
+            },
+        };
+
+        // State changes have to go through the root window, so that the
+        // window manager sees them
+        unsafe {
+            let root = XRootWindow(display, XDefaultScreen(display));
+            XSendEvent(
+                display,
+                root,
+                x11::xlib::False,
+                SubstructureRedirectMask | SubstructureNotifyMask,
+                addr_of_mut!(ev),
+            );
+            XFlush(display);
+        }
+    });
+}
+
+/// Sets the properties the desktop identifies the window `window` by:
+/// `WM_CLASS`, which taskbars match against the game's .desktop file to
+/// pick its icon and name, and `_NET_WM_PID`, which lets window managers
+/// (and `xkill`) find the process it belongs to.
+///
+/// Has to be called with the shared connection, from within
+/// [`with_display`].
+// All uses of unsafe here are for FFI. `display` is open, and everything
+// passed by pointer outlives the call it's passed to.
+#[allow(clippy::undocumented_unsafe_blocks)]
+pub fn set_window_identity(display: *mut Display, window: Window) {
+    // Xlib doesn't write through these, despite them not being const
+    let mut class_hint = XClassHint {
+        res_name: cstr!("opent5").as_ptr().cast_mut(),
+        res_class: cstr!("OpenT5").as_ptr().cast_mut(),
+    };
+    unsafe { XSetClassHint(display, window, addr_of_mut!(class_hint)) };
+
+    let Ok(pid) = c_long::try_from(std::process::id()) else {
+        return;
     };
+    let net_wm_pid =
+        unsafe { XInternAtom(display, cstr!("_NET_WM_PID").as_ptr(), 0) };
+    // Format 32 properties are passed as arrays of longs, whatever size
+    // those are
     unsafe {
-        XCloseDisplay(display);
+        XChangeProperty(
+            display,
+            window,
+            net_wm_pid,
+            XA_CARDINAL,
+            32,
+            PropModeReplace,
+            addr_of!(pid).cast(),
+            1,
+        );
     }
 }
 
+/// Sets the UTF-8 title (`_NET_WM_NAME`) of the window `window`, which
+/// window managers show in preference to the Latin-1 one set by
+/// `XStoreName`.
+///
+/// Has to be called with the shared connection, from within
+/// [`with_display`].
+#[allow(clippy::undocumented_unsafe_blocks, clippy::cast_possible_wrap)]
+pub fn set_net_wm_name(display: *mut Display, window: Window, title: &str) {
+    let net_wm_name =
+        unsafe { XInternAtom(display, cstr!("_NET_WM_NAME").as_ptr(), 0) };
+    let utf8_string =
+        unsafe { XInternAtom(display, cstr!("UTF8_STRING").as_ptr(), 0) };
+    // SAFETY:
+    // XChangeProperty is an FFI function, requiring use of unsafe. `title`
+    // is `title.len()` bytes long, and is copied before the call returns.
+    unsafe {
+        XChangeProperty(
+            display,
+            window,
+            net_wm_name,
+            utf8_string,
+            8,
+            PropModeReplace,
+            title.as_ptr(),
+            title.len() as _,
+        );
+    }
+}
+
+// Packs `rgba` into the form `_NET_WM_ICON` takes: the width, the height,
+// then each pixel as ARGB, each in a long (which is what format 32
+// properties are made of, whatever size those are).
+#[allow(clippy::as_conversions, clippy::cast_possible_wrap)]
+fn net_wm_icon_data(
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+) -> Option<Vec<c_long>> {
+    let len = usize::try_from(width)
+        .ok()?
+        .checked_mul(usize::try_from(height).ok()?)?
+        .checked_mul(4)?;
+    let pixels = rgba.get(..len)?;
+
+    let mut data = Vec::with_capacity(2 + len / 4);
+    data.push(c_long::try_from(width).ok()?);
+    data.push(c_long::try_from(height).ok()?);
+    data.extend(pixels.chunks_exact(4).map(|p| match *p {
+        [r, g, b, a] => u32::from_be_bytes([a, r, g, b]) as c_long,
+        _ => 0,
+    }));
+    Some(data)
+}
+
+/// Sets the icon of the window `handle` (`_NET_WM_ICON`) to the
+/// `width`x`height` image `rgba`.
+// All uses of unsafe here are for FFI. `data` outlives the call it's
+// passed to.
+#[allow(clippy::undocumented_unsafe_blocks, clippy::cast_possible_wrap)]
+pub fn set_window_icon(
+    handle: WindowHandle,
+    rgba: &[u8],
+    width: u32,
+    height: u32,
+) {
+    let Some(data) = net_wm_icon_data(rgba, width, height) else {
+        return;
+    };
+
+    let handle = handle.get_xlib().unwrap();
+    with_display(|display| {
+        let net_wm_icon =
+            unsafe { XInternAtom(display, cstr!("_NET_WM_ICON").as_ptr(), 0) };
+        unsafe {
+            XChangeProperty(
+                display,
+                handle.window,
+                net_wm_icon,
+                XA_CARDINAL,
+                32,
+                PropModeReplace,
+                data.as_ptr().cast(),
+                data.len() as _,
+            );
+            XFlush(display);
+        }
+    });
+}
+
+/// Rings the X server's bell at its configured volume.
+pub fn system_beep() {
+    with_display(|display| {
+        // SAFETY:
+        // XBell and XFlush are FFI functions, requiring use of unsafe.
+        // `display` is the open shared connection.
+        unsafe {
+            XBell(display, 0);
+            XFlush(display);
+        }
+    });
+}
+
 lazy_static! {
     pub static ref WM_DELETE_WINDOW: AtomicU64 = AtomicU64::new(0);
 }
@@ -476,8 +1103,14 @@ impl WindowEventExtXlib for WindowEvent {
                     );
                 };
 
+                // Keysyms depend on the layout even at index 0, so the
+                // console key is recognized by its keycode instead
                 let physical_scancode: Option<KeyboardScancode> =
-                    XlibKeysym(physical_keysym).try_into().ok();
+                    if keycode == TLDE_KEYCODE {
+                        Some(KeyboardScancode::Tilde)
+                    } else {
+                        XlibKeysym(physical_keysym).try_into().ok()
+                    };
                 let Ok(logical_scancode) =
                     XlibKeysym(logical_keysym).try_into()
                 else {
@@ -31,3 +31,51 @@ cfg_if! {
 pub fn main() {
     target::main();
 }
+
+/// Returned by [`version`] when the OS can't be identified.
+pub const UNKNOWN_OS: &str = "Unknown OS";
+
+/// Returns a human-readable description of the running OS and its version
+/// (e.g. "Ubuntu 22.04.3 LTS (Linux 6.5.0-14-generic x86_64)"), or
+/// [`UNKNOWN_OS`] if it can't be determined.
+pub fn version() -> String {
+    target::os_version().unwrap_or_else(|| UNKNOWN_OS.to_owned())
+}
+
+/// Returns the kernel name, release, and machine reported by `uname(2)`,
+/// e.g. "Linux 6.5.0-14-generic x86_64".
+#[cfg(unix)]
+pub(crate) fn uname() -> Option<String> {
+    let uts = nix::sys::utsname::uname().ok()?;
+    Some(format!(
+        "{} {} {}",
+        uts.sysname().to_string_lossy(),
+        uts.release().to_string_lossy(),
+        uts.machine().to_string_lossy(),
+    ))
+}
+
+/// Returns the addresses of every network interface that's up, other than
+/// loopback interfaces, as reported by `getifaddrs(3)`.
+#[cfg(unix)]
+pub(crate) fn ifaddrs() -> std::io::Result<Vec<std::net::IpAddr>> {
+    use nix::{ifaddrs::getifaddrs, net::if_::InterfaceFlags};
+    use std::net::{IpAddr, SocketAddrV4, SocketAddrV6};
+
+    let addrs = getifaddrs()?
+        .filter(|ifa| {
+            ifa.flags.contains(InterfaceFlags::IFF_UP)
+                && ifa.flags.contains(InterfaceFlags::IFF_LOOPBACK) == false
+        })
+        .filter_map(|ifa| {
+            let addr = ifa.address?;
+            if let Some(&sin) = addr.as_sockaddr_in() {
+                Some(IpAddr::V4(*SocketAddrV4::from(sin).ip()))
+            } else {
+                addr.as_sockaddr_in6()
+                    .map(|&sin6| IpAddr::V6(*SocketAddrV6::from(sin6).ip()))
+            }
+        })
+        .collect();
+    Ok(addrs)
+}
@@ -3,3 +3,110 @@
 pub fn main() {
     gtk4::init().unwrap();
 }
+
+/// Returns the distribution's name from `/etc/os-release` followed by the
+/// kernel version, e.g. "Ubuntu 22.04.3 LTS (Linux 6.5.0-14-generic x86_64)".
+pub fn os_version() -> Option<String> {
+    let distro = std::fs::read_to_string("/etc/os-release")
+        .or_else(|_| std::fs::read_to_string("/usr/lib/os-release"))
+        .ok()
+        .and_then(|s| pretty_name(&s));
+    let kernel = super::uname();
+
+    match (distro, kernel) {
+        (Some(d), Some(k)) => Some(format!("{d} ({k})")),
+        (d, k) => d.or(k),
+    }
+}
+
+// Pulls PRETTY_NAME (or NAME, if that's missing) out of an os-release file.
+fn pretty_name(os_release: &str) -> Option<String> {
+    let value = |key: &str| {
+        os_release.lines().find_map(|l| {
+            let v = l.trim().strip_prefix(key)?.strip_prefix('=')?;
+            let v = v.trim_matches(|c| c == '"' || c == '\'');
+            (!v.is_empty()).then(|| v.to_owned())
+        })
+    };
+
+    value("PRETTY_NAME").or_else(|| value("NAME"))
+}
+
+/// Longest name the kernel will accept, excluding the trailing NUL.
+const MAX_THREAD_NAME_LEN: usize = 15;
+
+/// Sets the calling thread's name as seen by debuggers and tools like `top`.
+/// Names longer than the kernel's limit are truncated.
+pub fn set_thread_name(name: &str) {
+    let Ok(name) = std::ffi::CString::new(
+        crate::util::truncate_at_char_boundary(name, MAX_THREAD_NAME_LEN),
+    ) else {
+        return;
+    };
+
+    // SAFETY:
+    // `name` is NUL-terminated and short enough, and pthread_self() is
+    // always a valid thread.
+    unsafe {
+        libc::pthread_setname_np(libc::pthread_self(), name.as_ptr());
+    }
+}
+
+/// How many CPUs an affinity mask covers (one per bit of a `u64`).
+const MASK_CPUS: usize = 64;
+
+/// Returns the calling thread's kernel thread ID, for
+/// [`set_thread_affinity`].
+pub fn current_thread_id() -> u64 {
+    // SAFETY:
+    // gettid is an FFI function, requiring use of unsafe. It can't fail.
+    u64::try_from(unsafe { libc::gettid() }).unwrap_or_default()
+}
+
+/// Restricts the thread with kernel thread ID `thread` to the logical CPUs
+/// set in `cpu_mask`.
+// All uses of unsafe here are for FFI. `set` is a plain bitset that's valid
+// all-zero, and every CPU index passed to CPU_SET is within it.
+#[allow(clippy::undocumented_unsafe_blocks)]
+pub fn set_thread_affinity(thread: u64, cpu_mask: u64) -> Result<(), ()> {
+    let thread = libc::pid_t::try_from(thread).map_err(|_| ())?;
+    let mut set = unsafe { core::mem::zeroed::<libc::cpu_set_t>() };
+    for cpu in 0..MASK_CPUS {
+        if cpu_mask & (1 << cpu) != 0 {
+            unsafe { libc::CPU_SET(cpu, &mut set) };
+        }
+    }
+
+    let size = core::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_setaffinity(thread, size, &set) } == 0 {
+        Ok(())
+    } else {
+        Err(())
+    }
+}
+
+/// Returns the logical CPUs the thread with kernel thread ID `thread` is
+/// allowed to run on, as a mask of the first 64.
+// All uses of unsafe here are for FFI. `set` is a plain bitset that's valid
+// all-zero, and every CPU index passed to CPU_ISSET is within it.
+#[allow(clippy::undocumented_unsafe_blocks)]
+pub fn thread_affinity(thread: u64) -> Option<u64> {
+    let thread = libc::pid_t::try_from(thread).ok()?;
+    let mut set = unsafe { core::mem::zeroed::<libc::cpu_set_t>() };
+    let size = core::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(thread, size, &mut set) } != 0 {
+        return None;
+    }
+
+    Some(
+        (0..MASK_CPUS)
+            .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) })
+            .fold(0, |mask, cpu| mask | 1 << cpu),
+    )
+}
+
+/// Returns the addresses of every network interface that's up, other than
+/// loopback interfaces.
+pub fn interface_addresses() -> std::io::Result<Vec<std::net::IpAddr>> {
+    super::ifaddrs()
+}
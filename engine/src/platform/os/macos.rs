@@ -1 +1,71 @@
 pub fn main() {}
+
+/// Returns the macOS version followed by the kernel version, e.g.
+/// "macOS 14.1.0 (Darwin 23.1.0 arm64)".
+pub fn os_version() -> Option<String> {
+    let kernel = super::uname();
+
+    cfg_if::cfg_if! {
+        if #[cfg(appkit)] {
+            let proc_info = icrate::Foundation::NSProcessInfo::processInfo();
+            // SAFETY:
+            // operatingSystemVersion has no preconditions; it's only unsafe
+            // because it's an FFI call.
+            let v = unsafe { proc_info.operatingSystemVersion() };
+            let macos = format!(
+                "macOS {}.{}.{}",
+                v.majorVersion, v.minorVersion, v.patchVersion
+            );
+            Some(match kernel {
+                Some(k) => format!("{macos} ({k})"),
+                None => macos,
+            })
+        } else {
+            kernel.map(|k| format!("macOS ({k})"))
+        }
+    }
+}
+
+/// Longest name macOS will accept, excluding the trailing NUL.
+const MAX_THREAD_NAME_LEN: usize = 63;
+
+/// Sets the calling thread's name as seen by debuggers and Instruments.
+/// Names longer than the OS's limit are truncated.
+pub fn set_thread_name(name: &str) {
+    let Ok(name) = std::ffi::CString::new(
+        crate::util::truncate_at_char_boundary(name, MAX_THREAD_NAME_LEN),
+    ) else {
+        return;
+    };
+
+    // SAFETY:
+    // `name` is NUL-terminated and short enough. macOS only allows naming
+    // the calling thread, which is what we want anyway.
+    unsafe {
+        libc::pthread_setname_np(name.as_ptr());
+    }
+}
+
+// macOS doesn't let threads be pinned to CPUs (the closest it has is
+// affinity tags, which are only hints), so these do nothing.
+
+#[allow(clippy::missing_const_for_fn)]
+pub fn current_thread_id() -> u64 {
+    0
+}
+
+#[allow(clippy::missing_const_for_fn)]
+pub fn set_thread_affinity(_thread: u64, _cpu_mask: u64) -> Result<(), ()> {
+    Err(())
+}
+
+#[allow(clippy::missing_const_for_fn)]
+pub fn thread_affinity(_thread: u64) -> Option<u64> {
+    None
+}
+
+/// Returns the addresses of every network interface that's up, other than
+/// loopback interfaces.
+pub fn interface_addresses() -> std::io::Result<Vec<std::net::IpAddr>> {
+    super::ifaddrs()
+}
@@ -1 +1,28 @@
 pub fn main() {}
+
+#[allow(clippy::missing_const_for_fn)]
+pub fn set_thread_name(_name: &str) {}
+
+#[allow(clippy::missing_const_for_fn)]
+pub fn os_version() -> Option<String> {
+    None
+}
+
+#[allow(clippy::missing_const_for_fn)]
+pub fn current_thread_id() -> u64 {
+    0
+}
+
+#[allow(clippy::missing_const_for_fn)]
+pub fn set_thread_affinity(_thread: u64, _cpu_mask: u64) -> Result<(), ()> {
+    Err(())
+}
+
+#[allow(clippy::missing_const_for_fn)]
+pub fn thread_affinity(_thread: u64) -> Option<u64> {
+    None
+}
+
+pub fn interface_addresses() -> std::io::Result<Vec<std::net::IpAddr>> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
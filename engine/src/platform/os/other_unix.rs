@@ -33,3 +33,35 @@ impl MonitorHandle {
         }
     }
 }
+
+/// Returns the kernel name and version, e.g. "FreeBSD 14.0-RELEASE amd64".
+pub fn os_version() -> Option<String> {
+    super::uname()
+}
+
+// TODO - pthread_setname_np's signature varies between the BSDs
+#[allow(clippy::missing_const_for_fn)]
+pub fn set_thread_name(_name: &str) {}
+
+// TODO - the BSDs each have their own affinity APIs (cpuset_setaffinity,
+// pthread_setaffinity_np, ...)
+#[allow(clippy::missing_const_for_fn)]
+pub fn current_thread_id() -> u64 {
+    0
+}
+
+#[allow(clippy::missing_const_for_fn)]
+pub fn set_thread_affinity(_thread: u64, _cpu_mask: u64) -> Result<(), ()> {
+    Err(())
+}
+
+#[allow(clippy::missing_const_for_fn)]
+pub fn thread_affinity(_thread: u64) -> Option<u64> {
+    None
+}
+
+/// Returns the addresses of every network interface that's up, other than
+/// loopback interfaces.
+pub fn interface_addresses() -> std::io::Result<Vec<std::net::IpAddr>> {
+    super::ifaddrs()
+}
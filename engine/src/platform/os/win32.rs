@@ -3,25 +3,50 @@
 
 #![allow(non_snake_case)]
 
-use core::{mem::size_of_val, ptr::addr_of};
-use std::sync::RwLock;
+use core::{
+    mem::{size_of, size_of_val},
+    ptr::addr_of,
+    sync::atomic::{AtomicBool, AtomicIsize, Ordering},
+};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::RwLock,
+};
 extern crate alloc;
 use alloc::collections::VecDeque;
 
 use raw_window_handle::{RawWindowHandle, Win32WindowHandle};
 use windows::{
-    core::PCSTR,
-    s,
+    core::{HSTRING, PCSTR},
+    s, w,
     Win32::{
         Foundation::{
-            BOOL, COLORREF, HMODULE, HWND, LPARAM, LRESULT, RECT, WPARAM,
+            CloseHandle, BOOL, COLORREF, ERROR_BUFFER_OVERFLOW, ERROR_NO_DATA,
+            ERROR_SUCCESS, HANDLE, HMODULE, HWND, LPARAM, LRESULT, NTSTATUS,
+            POINT, RECT, WPARAM,
         },
         Graphics::Gdi::{CreateSolidBrush, HDC, HMONITOR},
+        NetworkManagement::{
+            IpHelper::{
+                GetAdaptersAddresses, GAA_FLAG_SKIP_ANYCAST,
+                GAA_FLAG_SKIP_DNS_SERVER, GAA_FLAG_SKIP_MULTICAST,
+                IF_TYPE_SOFTWARE_LOOPBACK, IP_ADAPTER_ADDRESSES_LH,
+            },
+            Ndis::IfOperStatusUp,
+        },
+        Networking::WinSock::{
+            AF_INET, AF_INET6, AF_UNSPEC, SOCKADDR_IN, SOCKADDR_IN6,
+        },
         System::{
             Diagnostics::Debug::{SetErrorMode, SEM_FAILCRITICALERRORS},
             Environment::GetCommandLineA,
             LibraryLoader::GetModuleHandleA,
-            Threading::{GetStartupInfoW, STARTUPINFOW},
+            SystemInformation::OSVERSIONINFOW,
+            Threading::{
+                GetCurrentThread, GetCurrentThreadId, GetStartupInfoW,
+                OpenThread, SetThreadAffinityMask, SetThreadDescription,
+                STARTUPINFOW, THREAD_QUERY_INFORMATION, THREAD_SET_INFORMATION,
+            },
         },
         UI::{
             Input::KeyboardAndMouse::{
@@ -40,15 +65,24 @@ use windows::{
                 VK_SEPARATOR, VK_SHIFT, VK_SNAPSHOT, VK_SPACE, VK_SUBTRACT,
                 VK_TAB, VK_UP, VK_XBUTTON1, VK_XBUTTON2,
             },
+            Shell::{
+                Shell_NotifyIconW, NIF_ICON, NIF_MESSAGE, NIF_TIP, NIM_ADD,
+                NIM_DELETE, NOTIFYICONDATAW,
+            },
             WindowsAndMessaging::{
-                CallWindowProcA, DefWindowProcA, DestroyWindow,
-                GetSystemMetrics, GetWindowTextA, LoadCursorA, LoadIconA,
-                MessageBoxA, PostQuitMessage, RegisterClassExA, SetWindowPos,
-                SetWindowTextA, IDC_ARROW, MB_OK, MSG, SET_WINDOW_POS_FLAGS,
-                SM_REMOTESESSION, WA_INACTIVE, WM_ACTIVATE, WM_CHAR, WM_CLOSE,
-                WM_CREATE, WM_DESTROY, WM_DISPLAYCHANGE, WM_KEYDOWN, WM_KEYUP,
-                WM_KILLFOCUS, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDOWN,
-                WM_MBUTTONUP, WM_MOVE, WM_RBUTTONDOWN, WM_RBUTTONUP,
+                AppendMenuW, CallWindowProcA, CreatePopupMenu, DefWindowProcA,
+                DestroyMenu, DestroyWindow, GetCursorPos, GetSystemMetrics,
+                GetWindowTextA, LoadCursorA, LoadIconA, MessageBoxA,
+                PostMessageA, PostQuitMessage, RegisterClassExA,
+                SetForegroundWindow, SetWindowPos, SetWindowTextA, ShowWindow,
+                TrackPopupMenu, IDC_ARROW, MB_OK, MF_STRING, MSG,
+                SET_WINDOW_POS_FLAGS, SIZE_MINIMIZED, SM_REMOTESESSION,
+                SW_HIDE, SW_RESTORE, TPM_RETURNCMD, TPM_RIGHTBUTTON,
+                WA_INACTIVE, WM_ACTIVATE, WM_APP, WM_CHAR, WM_CLOSE,
+                WM_CONTEXTMENU, WM_CREATE, WM_DESTROY, WM_DISPLAYCHANGE,
+                WM_KEYDOWN, WM_KEYUP, WM_KILLFOCUS, WM_LBUTTONDBLCLK,
+                WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDOWN, WM_MBUTTONUP,
+                WM_MOUSEWHEEL, WM_MOVE, WM_RBUTTONDOWN, WM_RBUTTONUP,
                 WM_SETFOCUS, WM_SIZE, WM_SYSKEYDOWN, WM_SYSKEYUP,
                 WM_XBUTTONDOWN, WM_XBUTTONUP, WNDCLASSEXA,
             },
@@ -60,12 +94,33 @@ use libc::c_int;
 
 use crate::{
     com::{self, ErrorParm},
-    conbuf,
+    conbuf, dvar,
     platform::{display_server::target::WindowHandleExt, WindowHandle},
     sys::{self, KeyboardScancode, Modifiers, MouseScancode, WindowEvent},
     util::{CharFromUtf16Char, HighWord, LowWord},
 };
 
+/// An owned Win32 [`HANDLE`], closed with [`CloseHandle`] when dropped.
+#[derive(Debug)]
+pub struct HandleGuard(pub HANDLE);
+
+impl HandleGuard {
+    pub const fn get(&self) -> HANDLE {
+        self.0
+    }
+}
+
+impl Drop for HandleGuard {
+    fn drop(&mut self) {
+        // SAFETY:
+        // CloseHandle is an FFI function, requiring use of unsafe. The
+        // handle is owned by the guard, so nothing else will close it.
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
+
 // Get info for WinMain (Rust doesn't do this automatically), then call it
 #[allow(
     clippy::panic,
@@ -118,6 +173,11 @@ pub fn main() {
     WinMain(hInstance, None, pCmdLine, nCmdShow);
 }
 
+/// Set when the main window is closed on purpose, so that destroying it ends
+/// the message loop. Otherwise it's only being replaced (e.g. by
+/// `vid_restart`), and the game should carry on.
+static CLOSING: AtomicBool = AtomicBool::new(false);
+
 #[allow(clippy::field_reassign_with_default)]
 unsafe extern "system" fn main_wnd_proc(
     hwnd: HWND,
@@ -136,15 +196,32 @@ unsafe extern "system" fn main_wnd_proc(
             .lock()
             .unwrap()
             .push_back(WindowEvent::Destroyed);
-        PostQuitMessage(0);
+        if CLOSING.swap(false, Ordering::Relaxed) {
+            PostQuitMessage(0);
+        }
         LRESULT(0)
     } else if msg == WM_CLOSE {
         sys::MAIN_WINDOW_EVENTS
             .lock()
             .unwrap()
             .push_back(WindowEvent::CloseRequested);
+        CLOSING.store(true, Ordering::Relaxed);
         DestroyWindow(hwnd);
         LRESULT(0)
+    } else if msg == WM_SIZE
+        && wparam.0 == SIZE_MINIMIZED as usize
+        && dvar::get_bool("r_minimizeToTray").unwrap_or(false)
+    {
+        minimize_to_tray(hwnd);
+        LRESULT(0)
+    } else if msg == WM_TRAY_ICON {
+        // The low word of lParam holds the mouse message that triggered it
+        match u32::from(lparam.low_word()) {
+            WM_LBUTTONUP | WM_LBUTTONDBLCLK => restore_from_tray(hwnd),
+            WM_RBUTTONUP | WM_CONTEXTMENU => show_tray_menu(hwnd),
+            _ => {}
+        }
+        LRESULT(0)
     } else if let Ok(ev) = mesg.try_into() {
         sys::MAIN_WINDOW_EVENTS.lock().unwrap().push_back(ev);
         LRESULT(0)
@@ -153,6 +230,121 @@ unsafe extern "system" fn main_wnd_proc(
     }
 }
 
+/// Sent to the main window by its tray icon when the icon is clicked.
+const WM_TRAY_ICON: u32 = WM_APP + 1;
+const TRAY_MENU_RESTORE: usize = 1;
+const TRAY_MENU_QUIT: usize = 2;
+
+/// The window the tray icon belongs to, or 0 if there's no tray icon.
+static TRAY_ICON_HWND: AtomicIsize = AtomicIsize::new(0);
+
+#[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+fn tray_icon_data(hwnd: HWND) -> NOTIFYICONDATAW {
+    NOTIFYICONDATAW {
+        cbSize: core::mem::size_of::<NOTIFYICONDATAW>() as u32,
+        hWnd: hwnd,
+        uID: 1,
+        ..Default::default()
+    }
+}
+
+// Swaps `hwnd`'s taskbar button for a tray icon. If the icon can't be added,
+// the window is left minimized to the taskbar as usual, since a hidden
+// window with no tray icon couldn't be brought back.
+#[allow(clippy::undocumented_unsafe_blocks)]
+fn minimize_to_tray(hwnd: HWND) {
+    let mut data = tray_icon_data(hwnd);
+    data.uFlags = NIF_MESSAGE | NIF_ICON | NIF_TIP;
+    data.uCallbackMessage = WM_TRAY_ICON;
+    // Same icon as the window class
+    data.hIcon = unsafe {
+        LoadIconA(
+            GetModuleHandleA(None).unwrap_or_default(),
+            PCSTR(0x00000001 as _),
+        )
+    }
+    .unwrap_or_default();
+    // szTip has to stay NUL-terminated, hence leaving the last slot alone
+    let tip_len = data.szTip.len() - 1;
+    for (dst, src) in data
+        .szTip
+        .iter_mut()
+        .take(tip_len)
+        .zip(com::get_official_build_name_r().encode_utf16())
+    {
+        *dst = src;
+    }
+
+    if unsafe { Shell_NotifyIconW(NIM_ADD, &data) }.as_bool() {
+        TRAY_ICON_HWND.store(hwnd.0, Ordering::SeqCst);
+        unsafe { ShowWindow(hwnd, SW_HIDE) };
+    }
+}
+
+/// Removes the main window's tray icon, if it has one. The window itself is
+/// left as it is.
+#[allow(clippy::undocumented_unsafe_blocks)]
+pub fn remove_tray_icon() {
+    let hwnd = TRAY_ICON_HWND.swap(0, Ordering::SeqCst);
+    if hwnd != 0 {
+        unsafe { Shell_NotifyIconW(NIM_DELETE, &tray_icon_data(HWND(hwnd))) };
+    }
+}
+
+#[allow(clippy::undocumented_unsafe_blocks)]
+fn restore_from_tray(hwnd: HWND) {
+    remove_tray_icon();
+    unsafe {
+        ShowWindow(hwnd, SW_RESTORE);
+        SetForegroundWindow(hwnd);
+    }
+    sys::MAIN_WINDOW_EVENTS
+        .lock()
+        .unwrap()
+        .push_back(WindowEvent::Activate);
+}
+
+#[allow(
+    clippy::undocumented_unsafe_blocks,
+    clippy::as_conversions,
+    clippy::cast_sign_loss
+)]
+fn show_tray_menu(hwnd: HWND) {
+    let Ok(menu) = (unsafe { CreatePopupMenu() }) else {
+        return;
+    };
+
+    let mut cursor = POINT::default();
+    let command = unsafe {
+        let _ = AppendMenuW(menu, MF_STRING, TRAY_MENU_RESTORE, w!("Restore"));
+        let _ = AppendMenuW(menu, MF_STRING, TRAY_MENU_QUIT, w!("Quit"));
+        GetCursorPos(&mut cursor);
+        // Without this, the menu won't close when clicking outside of it
+        SetForegroundWindow(hwnd);
+        let command = TrackPopupMenu(
+            menu,
+            TPM_RETURNCMD | TPM_RIGHTBUTTON,
+            cursor.x,
+            cursor.y,
+            0,
+            hwnd,
+            None,
+        );
+        DestroyMenu(menu);
+        command.0 as usize
+    };
+
+    match command {
+        TRAY_MENU_RESTORE => restore_from_tray(hwnd),
+        TRAY_MENU_QUIT => {
+            remove_tray_icon();
+            // Same as clicking the window's close button
+            unsafe { PostMessageA(hwnd, WM_CLOSE, WPARAM(0), LPARAM(0)) };
+        }
+        _ => {}
+    }
+}
+
 #[allow(
     clippy::undocumented_unsafe_blocks,
     clippy::cast_possible_truncation,
@@ -233,6 +425,9 @@ impl TryFrom<MSG> for WindowEvent {
                     Ok(Self::MouseButtonUp(MouseScancode::Button5))
                 }
             }
+            WM_MOUSEWHEEL => Ok(Self::MouseWheelScroll(f32::from(
+                value.wParam.high_word() as i16,
+            ))),
             WM_KEYDOWN | WM_KEYUP | WM_SYSKEYDOWN | WM_SYSKEYUP => {
                 let down = value.message == WM_KEYDOWN
                     || value.message == WM_SYSKEYDOWN;
@@ -275,6 +470,23 @@ impl TryFrom<MSG> for WindowEvent {
                     });
                 }
 
+                // Keys the layout has no virtual key mapping for (e.g.
+                // VK_OEM_8, which is below Escape on UK keyboards) can still
+                // be told apart by where they are
+                if let Some(k) = physical_scancode {
+                    return Ok(if down {
+                        Self::KeyDown {
+                            logical_scancode: k,
+                            physical_scancode,
+                        }
+                    } else {
+                        Self::KeyUp {
+                            logical_scancode: k,
+                            physical_scancode,
+                        }
+                    });
+                }
+
                 Err(())
             }
             WM_CHAR => {
@@ -339,6 +551,10 @@ impl KeyPressInfo {
     }
 }
 
+// A hardware scancode, as given in bits 16-24 of a `WM_KEYDOWN`/`WM_KEYUP`
+// `lParam`. These are PS/2 scan code set 1 codes, with the 0xE0 prefix of
+// extended keys folded into the high byte. Unlike virtual keys, they depend
+// only on where a key is, not on the keyboard layout.
 #[derive(Copy, Clone, Debug)]
 struct OemScancode(u16);
 
@@ -417,7 +633,13 @@ impl TryFrom<OemScancode> for KeyboardScancode {
             0x0057 => Ok(Self::F11),
             0x0058 => Ok(Self::F12),
 
+            0xE037 => Ok(Self::PrtScSysRq),
+            // Alt+PrtSc sends SysRq's own scancode
+            0x0054 => Ok(Self::PrtScSysRq),
             0x0046 => Ok(Self::ScrLk),
+            // Pause is sent as Ctrl+NumLk, which Windows reports without
+            // the extended bit that the real NumLk has
+            0x0045 => Ok(Self::PauseBreak),
             0xE052 => Ok(Self::Insert),
             0xE047 => Ok(Self::Home),
             0xE049 => Ok(Self::PgUp),
@@ -429,6 +651,7 @@ impl TryFrom<OemScancode> for KeyboardScancode {
             0xE050 => Ok(Self::ArrowDown),
             0xE048 => Ok(Self::ArrowUp),
 
+            0xE045 => Ok(Self::NumLk),
             0xE035 => Ok(Self::NumSlash),
             0x0037 => Ok(Self::NumAsterisk),
             0x004A => Ok(Self::NumHyphen),
@@ -455,6 +678,7 @@ impl TryFrom<OemScancode> for KeyboardScancode {
             0x0036 => Ok(Self::RShift),
             0xE038 => Ok(Self::RAlt),
             0xE05C => Ok(Self::RSys),
+            0xE05D => Ok(Self::Menu),
 
             _ => Err(()),
         }
@@ -806,3 +1030,167 @@ fn WinMain(
 
     0
 }
+
+/// Sets the calling thread's name as seen by debuggers and Task Manager.
+pub fn set_thread_name(name: &str) {
+    // Naming threads is purely a debugging aid, so a failure (e.g. on
+    // Windows versions before SetThreadDescription existed) is ignored.
+    // SAFETY:
+    // GetCurrentThread returns a pseudo-handle that's always valid, and
+    // the HSTRING outlives the call.
+    let _ = unsafe {
+        SetThreadDescription(GetCurrentThread(), &HSTRING::from(name))
+    };
+}
+
+/// Returns the calling thread's ID, for [`set_thread_affinity`].
+pub fn current_thread_id() -> u64 {
+    // SAFETY:
+    // GetCurrentThreadId is an FFI function, requiring use of unsafe. It
+    // can't fail.
+    u64::from(unsafe { GetCurrentThreadId() })
+}
+
+/// Restricts the thread with ID `thread` to the logical CPUs set in
+/// `cpu_mask`.
+pub fn set_thread_affinity(thread: u64, cpu_mask: u64) -> Result<(), ()> {
+    let thread = u32::try_from(thread).map_err(|_| ())?;
+    let cpu_mask = usize::try_from(cpu_mask).map_err(|_| ())?;
+    // SAFETY:
+    // OpenThread is an FFI function, requiring use of unsafe. The handle it
+    // returns is owned by the guard until the end of the function.
+    let handle = HandleGuard(
+        unsafe {
+            OpenThread(
+                THREAD_SET_INFORMATION | THREAD_QUERY_INFORMATION,
+                false,
+                thread,
+            )
+        }
+        .map_err(|_| ())?,
+    );
+    // SetThreadAffinityMask returns the old mask, or 0 if it failed
+    // SAFETY:
+    // SetThreadAffinityMask is an FFI function, requiring use of unsafe.
+    // `handle` is open with THREAD_SET_INFORMATION access.
+    if unsafe { SetThreadAffinityMask(handle.get(), cpu_mask) } == 0 {
+        Err(())
+    } else {
+        Ok(())
+    }
+}
+
+/// Windows can't report a thread's affinity without changing it, so this
+/// always returns [`None`].
+#[allow(clippy::missing_const_for_fn)]
+pub fn thread_affinity(_thread: u64) -> Option<u64> {
+    None
+}
+
+#[link(name = "ntdll")]
+extern "system" {
+    fn RtlGetVersion(info: *mut OSVERSIONINFOW) -> NTSTATUS;
+}
+
+/// Returns the Windows version and build number, e.g. "Windows 10.0 (build
+/// 22631)".
+///
+/// Uses `RtlGetVersion` rather than `GetVersionEx`, since the latter lies
+/// about the version to programs without a compatibility manifest.
+#[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+pub fn os_version() -> Option<String> {
+    let mut info = OSVERSIONINFOW {
+        dwOSVersionInfoSize: core::mem::size_of::<OSVERSIONINFOW>() as u32,
+        ..Default::default()
+    };
+
+    // SAFETY:
+    // RtlGetVersion is an FFI function, requiring use of unsafe. `info` is
+    // a valid OSVERSIONINFOW with its size field filled in.
+    if unsafe { RtlGetVersion(&mut info) }.is_err() {
+        return None;
+    }
+
+    let sp = String::from_utf16_lossy(&info.szCSDVersion);
+    let sp = sp.trim_end_matches('\0');
+    let version = format!(
+        "Windows {}.{} (build {})",
+        info.dwMajorVersion, info.dwMinorVersion, info.dwBuildNumber
+    );
+    Some(if sp.is_empty() {
+        version
+    } else {
+        format!("{version} {sp}")
+    })
+}
+
+/// How much space to start with for `GetAdaptersAddresses`'s results. 15 KB
+/// is what Microsoft recommends, and is enough for most machines.
+const ADAPTER_ADDRESSES_SIZE: u32 = 15 * 1024;
+
+/// Returns the addresses of every network adapter that's up, other than
+/// loopback adapters.
+// All uses of unsafe here are for FFI, or for walking the linked lists
+// GetAdaptersAddresses fills `buf` with, which stay valid as long as `buf`
+// does. Each socket address is only read as the type its family says it is.
+#[allow(clippy::undocumented_unsafe_blocks, clippy::as_conversions)]
+pub fn interface_addresses() -> std::io::Result<Vec<IpAddr>> {
+    let flags = GAA_FLAG_SKIP_ANYCAST
+        | GAA_FLAG_SKIP_MULTICAST
+        | GAA_FLAG_SKIP_DNS_SERVER;
+    let mut size = ADAPTER_ADDRESSES_SIZE;
+    // u64s so the adapter structs inside are suitably aligned
+    let mut buf = Vec::<u64>::new();
+    let res = loop {
+        buf.resize((size as usize).div_ceil(size_of::<u64>()), 0);
+        let res = unsafe {
+            GetAdaptersAddresses(
+                u32::from(AF_UNSPEC.0),
+                flags,
+                None,
+                Some(buf.as_mut_ptr().cast::<IP_ADAPTER_ADDRESSES_LH>()),
+                &mut size,
+            )
+        };
+        if res != ERROR_BUFFER_OVERFLOW.0 {
+            break res;
+        }
+    };
+    if res == ERROR_NO_DATA.0 {
+        return Ok(Vec::new());
+    } else if res != ERROR_SUCCESS.0 {
+        return Err(std::io::Error::from_raw_os_error(res as i32));
+    }
+
+    let mut addrs = Vec::new();
+    let mut adapter = buf.as_ptr().cast::<IP_ADAPTER_ADDRESSES_LH>();
+    while let Some(a) = unsafe { adapter.as_ref() } {
+        adapter = a.Next;
+        if a.OperStatus != IfOperStatusUp
+            || a.IfType == IF_TYPE_SOFTWARE_LOOPBACK
+        {
+            continue;
+        }
+
+        let mut unicast = a.FirstUnicastAddress;
+        while let Some(u) = unsafe { unicast.as_ref() } {
+            unicast = u.Next;
+            let sockaddr = u.Address.lpSockaddr;
+            let Some(sa) = (unsafe { sockaddr.as_ref() }) else {
+                continue;
+            };
+            let family = sa.sa_family;
+            if family == AF_INET {
+                let sin = unsafe { &*sockaddr.cast::<SOCKADDR_IN>() };
+                // S_addr is in network byte order
+                let ip = unsafe { sin.sin_addr.S_un.S_addr }.to_ne_bytes();
+                addrs.push(IpAddr::V4(Ipv4Addr::from(ip)));
+            } else if family == AF_INET6 {
+                let sin6 = unsafe { &*sockaddr.cast::<SOCKADDR_IN6>() };
+                let ip = unsafe { sin6.sin6_addr.u.Byte };
+                addrs.push(IpAddr::V6(Ipv6Addr::from(ip)));
+            }
+        }
+    }
+    Ok(addrs)
+}
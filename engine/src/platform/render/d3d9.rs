@@ -10,7 +10,7 @@ use windows::{
         Foundation::HMODULE,
         Graphics::Direct3D9::{
             IDirect3D9, IDirect3DDevice9, IDirect3DQuery9, IDirect3DSurface9,
-            D3DDISPLAYMODE, D3DFORMAT, D3DMULTISAMPLE_TYPE,
+            D3DCAPS9, D3DDISPLAYMODE, D3DFORMAT, D3DMULTISAMPLE_TYPE,
             D3DTEXTUREFILTERTYPE,
         },
     },
@@ -88,6 +88,18 @@ pub struct DxGlobals {
     swap_fence: [Option<Box<IDirect3DQuery9>>; 4],
 }
 
+impl DxGlobals {
+    /// Releases every resource that lives in `D3DPOOL_DEFAULT` (queries and
+    /// render target surfaces), all of which have to be gone before the
+    /// device can be `Reset`.
+    pub fn release_default_pool_resources(&mut self) {
+        self.fence_pool = Default::default();
+        self.swap_fence = Default::default();
+        self.flush_gpu_query = None;
+        self.single_sample_depth_stencil_surface = None;
+    }
+}
+
 unsafe impl Send for DxGlobals {}
 unsafe impl Sync for DxGlobals {}
 
@@ -121,6 +133,8 @@ pub fn gfx_metrics_mut() -> RwLockWriteGuard<'static, GfxMetrics> {
 }
 
 pub const D3DERR_INVALID_CALL: HRESULT = HRESULT(-2005530516);
+pub const D3DERR_DEVICELOST: HRESULT = HRESULT(-2005530520);
+pub const D3DERR_DEVICENOTRESET: HRESULT = HRESULT(-2005530519);
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum DxCapsResponse {
@@ -132,7 +146,8 @@ pub enum DxCapsResponse {
 
 #[derive(Copy, Clone, Debug)]
 pub struct DxCapsCheckBits {
-    pub offset: isize,
+    /// The field of the caps that's checked.
+    pub field: fn(&D3DCAPS9) -> u32,
     pub set_bits: u32,
     pub clear_bits: u32,
     pub response: DxCapsResponse,
@@ -141,7 +156,8 @@ pub struct DxCapsCheckBits {
 
 #[derive(Copy, Clone, Debug)]
 pub struct DxCapsCheckInteger {
-    pub offset: isize,
+    /// The field of the caps that's checked.
+    pub field: fn(&D3DCAPS9) -> u32,
     pub min: u32,
     pub max: u32,
     pub response: DxCapsResponse,
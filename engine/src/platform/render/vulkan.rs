@@ -8,6 +8,8 @@ pub struct VkGlobals {
     pub entry: Option<Entry>,
     pub instance: Option<Instance>,
     pub physical_device: Option<vk::PhysicalDevice>,
+    /// Index of the queue family used for both rendering and presenting.
+    pub queue_family_index: Option<u32>,
     pub device: Option<vk::Device>,
 }
 
@@ -1,12 +1,12 @@
 #![allow(dead_code, clippy::todo)]
 
-use core::sync::atomic::{AtomicIsize, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
 use num_derive::FromPrimitive;
 
 use cfg_if::cfg_if;
 use lazy_static::lazy_static;
 
-use crate::util::WgpuSurface;
+use crate::{render::RenderError, util::WgpuSurface};
 
 pub fn init() {
     env_logger::init();
@@ -59,6 +59,7 @@ macro_rules! render_api_implemented_by_wgpu {
     };
 }
 
+#[derive(Debug)]
 pub struct Instance {
     wgpu_instance: Option<wgpu::Instance>,
 }
@@ -80,8 +81,30 @@ impl Instance {
     }
 }
 
+impl Instance {
+    /// Returns the info of every adapter wgpu can see, on any backend.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn adapter_infos(&self) -> Vec<wgpu::AdapterInfo> {
+        self.wgpu_instance.as_ref().map_or_else(Vec::new, |i| {
+            i.enumerate_adapters(wgpu::Backends::all())
+                .map(|a| a.get_info())
+                .collect()
+        })
+    }
+
+    /// Adapters can't be enumerated on the web, only requested.
+    #[cfg(target_arch = "wasm32")]
+    pub fn adapter_infos(&self) -> Vec<wgpu::AdapterInfo> {
+        Vec::new()
+    }
+}
+
+#[derive(Debug)]
 pub struct Surface {
     wgpu_surface: Option<wgpu::Surface>,
+    // Whether the last [`Surface::configure`] succeeded. Nothing can be
+    // presented until it has.
+    configured: AtomicBool,
 }
 
 impl Surface {
@@ -98,6 +121,7 @@ impl Surface {
                         .create_surface(window)
                         .unwrap()
                 }),
+                configured: AtomicBool::new(false),
             }
         } else {
             todo!("gpu::Instance not yet implemented for {:?}.", render_api!());
@@ -105,6 +129,94 @@ impl Surface {
     }
 }
 
+impl Surface {
+    /// (Re)creates the surface's swapchain at `width`x`height`, in the first
+    /// format `adapter` supports presenting it in. Has to be done before
+    /// the first [`Surface::present_cleared`], and again whenever the
+    /// window is resized.
+    ///
+    /// Returns [`RenderError::UnsupportedCaps`] if `adapter` can't present
+    /// to the surface in any format, in which case the surface is left
+    /// unconfigured.
+    pub fn configure(
+        &self,
+        adapter: &Adapter,
+        device: &Device,
+        width: u32,
+        height: u32,
+    ) -> Result<(), RenderError> {
+        let surface = self.wgpu_surface.as_ref().unwrap();
+        let caps =
+            surface.get_capabilities(adapter.wgpu_adapter.as_ref().unwrap());
+        let Some(&format) = caps.formats.first() else {
+            self.configured.store(false, Ordering::SeqCst);
+            return Err(RenderError::UnsupportedCaps(
+                "the adapter can't present to the window in any format".into(),
+            ));
+        };
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        };
+        surface.configure(device.wgpu_device.as_ref().unwrap(), &config);
+        self.configured.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Whether the surface has been successfully configured, and so can be
+    /// presented to.
+    pub fn is_configured(&self) -> bool {
+        self.configured.load(Ordering::SeqCst)
+    }
+
+    /// Draws a frame that's only been through a render pass loading with
+    /// `load` (i.e. cleared, or left as it was), and presents it.
+    ///
+    /// Returns [`wgpu::SurfaceError::Outdated`] without drawing anything if
+    /// the surface hasn't been configured.
+    pub fn present_cleared(
+        &self,
+        device: &Device,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> Result<(), wgpu::SurfaceError> {
+        if self.is_configured() == false {
+            return Err(wgpu::SurfaceError::Outdated);
+        }
+
+        let frame =
+            self.wgpu_surface.as_ref().unwrap().get_current_texture()?;
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let wgpu_device = device.wgpu_device.as_ref().unwrap();
+        let mut encoder = wgpu_device.create_command_encoder(
+            &wgpu::CommandEncoderDescriptor { label: None },
+        );
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations { load, store: true },
+            })],
+            depth_stencil_attachment: None,
+        });
+        device
+            .wgpu_queue
+            .as_ref()
+            .unwrap()
+            .submit(core::iter::once(encoder.finish()));
+        frame.present();
+        Ok(())
+    }
+}
+
 #[derive(FromPrimitive, PartialEq, Eq, Debug)]
 pub enum DeviceType {
     IntegratedGpu,
@@ -123,6 +235,7 @@ impl AdapterInfo {
     }
 }
 
+#[derive(Debug)]
 pub struct Adapter {
     wgpu_adapter: Option<wgpu::Adapter>,
 }
@@ -191,22 +304,119 @@ impl Adapter {
     }
 }
 
-#[derive(Default)]
+impl Adapter {
+    /// Requests wgpu's software (fallback) adapter, for running without a
+    /// GPU.
+    ///
+    /// Returns [`RenderError::AdapterNotFound`] if the platform doesn't
+    /// have one, or if wgpu doesn't implement its render API.
+    pub async fn new_fallback(
+        instance: &Instance,
+    ) -> Result<Self, RenderError> {
+        if render_api_implemented_by_wgpu!() == false {
+            return Err(RenderError::AdapterNotFound);
+        }
+
+        instance
+            .wgpu_instance
+            .as_ref()
+            .unwrap()
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::LowPower,
+                compatible_surface: None,
+                force_fallback_adapter: true,
+            })
+            .await
+            .map(|a| Self {
+                wgpu_adapter: Some(a),
+            })
+            .ok_or(RenderError::AdapterNotFound)
+    }
+
+    /// Returns all of wgpu's info about the adapter, rather than just what
+    /// [`Adapter::get_info`] reports.
+    pub fn wgpu_info(&self) -> Option<wgpu::AdapterInfo> {
+        self.wgpu_adapter.as_ref().map(wgpu::Adapter::get_info)
+    }
+}
+
+/// The device limits and optional features the renderer cares about, the
+/// wgpu equivalent of what the d3d9 path reads from `D3DCAPS9`.
+#[derive(Clone, Debug, Default)]
+pub struct Capabilities {
+    /// Largest width or height of a 2D texture.
+    pub max_texture_dimension: u32,
+    /// Number of textures a single shader stage can sample from.
+    pub max_sampled_textures: u32,
+    pub max_bind_groups: u32,
+    /// Whether samplers can use anisotropic filtering.
+    pub supports_anisotropy: bool,
+    /// Highest anisotropy level samplers accept (1 if unsupported).
+    pub max_anisotropy: u32,
+    /// MSAA sample counts (besides 1) supported for color render targets.
+    pub msaa_sample_counts: Vec<u32>,
+}
+
+impl Adapter {
+    /// Sample counts checked for MSAA support, in ascending order.
+    const MSAA_SAMPLE_COUNTS: [u32; 4] = [2, 4, 8, 16];
+
+    /// wgpu clamps a sampler's anisotropy to this, regardless of adapter.
+    const MAX_ANISOTROPY: u32 = 16;
+
+    /// Queries the limits and features of the adapter. These are what the
+    /// default device can use, since the device is created with the
+    /// adapter's full limits rather than wgpu's conservative defaults.
+    pub fn capabilities(&self) -> Capabilities {
+        if render_api_implemented_by_wgpu!() {
+            let adapter = self.wgpu_adapter.as_ref().unwrap();
+            let limits = adapter.limits();
+            let supports_anisotropy = adapter
+                .get_downlevel_capabilities()
+                .flags
+                .contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING);
+            let format_flags = adapter
+                .get_texture_format_features(wgpu::TextureFormat::Rgba8Unorm)
+                .flags;
+            let msaa_sample_counts = Self::MSAA_SAMPLE_COUNTS
+                .into_iter()
+                .filter(|&n| format_flags.sample_count_supported(n))
+                .collect();
+
+            Capabilities {
+                max_texture_dimension: limits.max_texture_dimension_2d,
+                max_sampled_textures: limits
+                    .max_sampled_textures_per_shader_stage,
+                max_bind_groups: limits.max_bind_groups,
+                supports_anisotropy,
+                max_anisotropy: if supports_anisotropy {
+                    Self::MAX_ANISOTROPY
+                } else {
+                    1
+                },
+                msaa_sample_counts,
+            }
+        } else {
+            todo!("gpu::Adapter not yet implemented for {:?}.", render_api!());
+        }
+    }
+}
+
+#[derive(Default, Debug)]
 pub struct Device {
     wgpu_device: Option<wgpu::Device>,
+    wgpu_queue: Option<wgpu::Queue>,
 }
 
 impl Device {
     pub async fn new(adapter: &Adapter) -> Option<Self> {
         if render_api_implemented_by_wgpu!() {
-            let device = adapter
-                .wgpu_adapter
-                .as_ref()
-                .unwrap()
+            let wgpu_adapter = adapter.wgpu_adapter.as_ref().unwrap();
+            let device = wgpu_adapter
                 .request_device(
                     &wgpu::DeviceDescriptor {
                         features: wgpu::Features::empty(),
-                        limits: wgpu::Limits::default(),
+                        limits: wgpu_adapter.limits(),
                         label: None,
                     },
                     None, // Trace path
@@ -214,8 +424,9 @@ impl Device {
                 .await;
 
             match device {
-                Ok((d, _)) => Some(Self {
+                Ok((d, q)) => Some(Self {
                     wgpu_device: Some(d),
+                    wgpu_queue: Some(q),
                 }),
                 Err(_) => None,
             }
@@ -10,15 +10,17 @@ use arrayvec::ArrayVec;
 use cfg_if::cfg_if;
 use lazy_static::lazy_static;
 
+use crate::*;
+
 cfg_if! {
     if #[cfg(windows)] {
         use windows::Win32::System::Memory::{
-            VirtualAlloc, MEM_COMMIT, PAGE_READWRITE,
+            VirtualAlloc, VirtualFree, MEM_COMMIT, MEM_RELEASE, PAGE_READWRITE,
         };
     } else if #[cfg(unix)] {
-        use nix::sys::mman::{mmap, MapFlags, ProtFlags};
+        use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
     } else if #[cfg(other_os)] {
-        use libc::malloc;
+        use libc::{free, malloc};
     }
 }
 
@@ -196,22 +198,186 @@ fn alloc<'a>(size: NonZeroUsize) -> Option<&'a mut [u8]> {
     }
 }
 
+#[cfg(windows)]
+fn dealloc(buf: &mut [u8]) {
+    // SAFETY:
+    // VirtualFree is an FFI function, requiring use of unsafe. `buf` was
+    // returned by `alloc`, so it's the whole of a VirtualAlloc allocation,
+    // and it isn't used again after this.
+    let _ = unsafe { VirtualFree(buf.as_mut_ptr().cast(), 0, MEM_RELEASE) };
+}
+
+#[cfg(unix)]
+fn dealloc(buf: &mut [u8]) {
+    // SAFETY:
+    // `buf` was returned by `alloc`, so it's the whole of an mmap mapping,
+    // and it isn't used again after this.
+    let _ = unsafe { munmap(buf.as_mut_ptr().cast(), buf.len()) };
+}
+
+#[cfg(other_os)]
+fn dealloc(buf: &mut [u8]) {
+    // SAFETY:
+    // `buf` was returned by `alloc`, so it's the whole of a malloc
+    // allocation, and it isn't used again after this.
+    unsafe { free(buf.as_mut_ptr().cast()) };
+}
+
 lazy_static! {
     static ref G_PHYSICAL_MEMORY_INIT: AtomicBool = AtomicBool::new(false);
     static ref G_MEM: RwLock<PhysicalMemory<'static>> =
         RwLock::new(PhysicalMemory::new(String::new(), None, 0));
 }
 
-#[allow(clippy::items_after_statements)]
+/// Size of the primary allocation if `com_hunkMegs` isn't set.
+const DEFAULT_HUNK_MEGS: i32 = 300;
+/// Smallest primary allocation that can be requested with `com_hunkMegs`.
+const MIN_HUNK_MEGS: i32 = 64;
+/// Largest primary allocation that can be requested with `com_hunkMegs`.
+const MAX_HUNK_MEGS: i32 = 4096;
+
+fn hunk_megs() -> i32 {
+    // pmem::init runs before the command line has been processed, so
+    // `+set com_hunkMegs` has to be picked out manually.
+    let requested = com::startup_variable_value("com_hunkMegs")
+        .and_then(|v| v.parse::<i32>().ok())
+        .unwrap_or(DEFAULT_HUNK_MEGS);
+    clamp_hunk_megs(requested, system_megs())
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+fn system_megs() -> i32 {
+    sys::system_memory_mb() as i32
+}
+
+// Clamps a requested `com_hunkMegs` to the range it can be set to, and to
+// the `system_megs` MB the system has (if that's known, i.e., nonzero).
+fn clamp_hunk_megs(requested: i32, system_megs: i32) -> i32 {
+    let requested = requested.clamp(MIN_HUNK_MEGS, MAX_HUNK_MEGS);
+    if system_megs != 0 && requested > system_megs {
+        com::warnln!(
+            console::Channel::SYSTEM,
+            "com_hunkMegs ({} MB) exceeds system memory ({} MB), clamping.",
+            requested,
+            system_megs,
+        );
+        system_megs.max(MIN_HUNK_MEGS)
+    } else {
+        requested
+    }
+}
+
+/// Returns the size of the primary allocation, in bytes.
+pub fn capacity() -> usize {
+    G_MEM.read().unwrap().size
+}
+
+/// Returns the number of bytes of the primary allocation currently in use.
+pub fn used() -> usize {
+    let mem = G_MEM.read().unwrap();
+    mem.prim[0].pos + (mem.size - mem.prim[1].pos)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn meminfo_f() {
+    let capacity = capacity();
+    let used = used();
+    com::println!(
+        console::Channel::SYSTEM,
+        "{:.2} MB of {:.2} MB used ({:.2} MB free)",
+        used as f64 / (1024.0 * 1024.0),
+        capacity as f64 / (1024.0 * 1024.0),
+        (capacity - used) as f64 / (1024.0 * 1024.0),
+    );
+}
+
+#[allow(clippy::cast_sign_loss)]
+fn allocate_primary(megs: i32) {
+    let size = NonZeroUsize::new(megs as usize * 1024 * 1024).unwrap();
+    let mut mem = G_MEM.write().unwrap();
+    if let Some(buf) = mem.buf.take() {
+        dealloc(buf);
+    }
+    *mem = PhysicalMemory::new(
+        "main".to_owned(),
+        Some(alloc(size).unwrap()),
+        size.get(),
+    );
+}
+
 pub fn init() {
     if G_PHYSICAL_MEMORY_INIT.load(Ordering::SeqCst) == false {
         G_PHYSICAL_MEMORY_INIT.store(true, Ordering::SeqCst);
+        allocate_primary(hunk_megs());
+    }
+}
+
+/// Reallocates the primary allocation at the size `com_hunkMegs` has
+/// latched, if it's been changed since the allocation was made (e.g. by a
+/// config).
+///
+/// Only does so while nothing has been allocated from it, since anything
+/// that has would be left pointing at freed memory. Otherwise, the value
+/// stays latched until the next time this is called.
+#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+pub fn reinit() {
+    if dvar::has_pending_latch("com_hunkMegs") == false {
+        return;
+    }
 
-        const SIZE: NonZeroUsize = NonZeroUsize::new(0x12C0_0000).unwrap();
-        *G_MEM.write().unwrap() = PhysicalMemory::new(
-            "main".to_owned(),
-            Some(alloc(SIZE).unwrap()),
-            SIZE.get(),
+    if used() != 0 {
+        com::println!(
+            console::Channel::SYSTEM,
+            "com_hunkMegs will be changed once nothing is using the \
+             primary allocation."
         );
+        return;
+    }
+
+    dvar::make_latched_value_current("com_hunkMegs").unwrap();
+    let megs =
+        clamp_hunk_megs(dvar::get_int("com_hunkMegs").unwrap(), system_megs());
+    if megs != (capacity() / (1024 * 1024)) as i32 {
+        allocate_primary(megs);
+    }
+}
+
+/// Registers `com_hunkMegs` and `meminfo`. Unlike [`init`], this has to wait
+/// until the Dvar and command systems are up.
+#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+pub fn register() {
+    // Read from the command line by init (see hunk_megs), and applied from
+    // anywhere else by reinit, so there's no point archiving it
+    dvar::register_int(
+        "com_hunkMegs",
+        (capacity() / (1024 * 1024)) as i32,
+        Some(MIN_HUNK_MEGS),
+        Some(MAX_HUNK_MEGS),
+        dvar::DvarFlags::LATCHED,
+        Some("Size of the primary memory allocation, in MB"),
+    )
+    .unwrap();
+
+    cmd::add_command_internal("meminfo", meminfo_f).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hunk_megs_clamped_to_range() {
+        assert_eq!(clamp_hunk_megs(1, 0), MIN_HUNK_MEGS);
+        assert_eq!(clamp_hunk_megs(-300, 0), MIN_HUNK_MEGS);
+        assert_eq!(clamp_hunk_megs(1_000_000, 0), MAX_HUNK_MEGS);
+        assert_eq!(clamp_hunk_megs(512, 0), 512);
+    }
+
+    #[test]
+    fn hunk_megs_clamped_to_system_memory() {
+        assert_eq!(clamp_hunk_megs(2048, 1024), 1024);
+        assert_eq!(clamp_hunk_megs(512, 1024), 512);
+        // Never below the minimum, however little memory there is
+        assert_eq!(clamp_hunk_megs(512, 32), MIN_HUNK_MEGS);
     }
 }
@@ -1,14 +1,138 @@
 use crate::{
     render::{r_glob, r_glob_mut},
-    sys::handle_main_window_event,
-    util::SignalState,
+    util::{EasierAtomic, SignalState},
     *,
 };
+use core::sync::atomic::{AtomicIsize, AtomicUsize};
+use std::sync::{Mutex, RwLock};
 
+// The time (as returned by `sys::milliseconds`) at which the render thread
+// last finished a unit of work. Read by the watchdog to detect hangs.
+static LAST_SIGNAL_TIME: AtomicIsize = AtomicIsize::new(0);
+
+lazy_static! {
+    // The unit of work the render thread most recently started, so that
+    // the watchdog can report what the thread was doing when it hung.
+    static ref LAST_COMMAND: RwLock<&'static str> = RwLock::new("none");
+}
+
+fn begin_command(name: &'static str) {
+    *LAST_COMMAND.write().unwrap() = name;
+}
+
+fn signal_alive() {
+    LAST_SIGNAL_TIME.store_relaxed(sys::milliseconds());
+}
+
+/// A unit of work for the render thread. The main thread queues these with
+/// [`issue`], and the render thread executes them in order once they've
+/// been handed over by [`submit_commands`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RenderCmd {
+    /// Clears the frame as specified by `r_clear` and `r_clearColor`.
+    Clear,
+    /// Restricts drawing to a rectangle of the target window.
+    SetViewport {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    /// Presents the frame to the target window.
+    Present,
+}
+
+#[derive(Default)]
+struct CommandBuffers {
+    // Commands the main thread is issuing for the next frame
+    back: Vec<RenderCmd>,
+    // Commands handed to the render thread, which it hasn't taken yet
+    front: Vec<RenderCmd>,
+    front_pending: bool,
+}
+
+lazy_static! {
+    static ref COMMANDS: Mutex<CommandBuffers> =
+        Mutex::new(CommandBuffers::default());
+}
+
+static DROPPED_COMMAND_LISTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Queues `cmd` to be executed by the render thread after the next
+/// [`submit_commands`].
+pub fn issue(cmd: RenderCmd) {
+    COMMANDS.lock().unwrap().back.push(cmd);
+}
+
+/// Hands the commands issued since the last call to the render thread, and
+/// wakes it to execute them. Should be called by the main thread once it's
+/// issued everything for a frame.
+///
+/// The main thread is never made to wait on the render thread here: if the
+/// render thread hasn't taken the last list yet (or isn't running at all),
+/// this list is dropped instead.
+pub fn submit_commands() {
+    {
+        let mut cmds = COMMANDS.lock().unwrap();
+        let cmds = &mut *cmds;
+        if cmds.front_pending {
+            cmds.back.clear();
+            DROPPED_COMMAND_LISTS.increment_wrapping();
+            return;
+        }
+
+        core::mem::swap(&mut cmds.back, &mut cmds.front);
+        cmds.back.clear();
+        cmds.front_pending = true;
+    }
+    sys::notify_renderer();
+}
+
+/// Returns how many command lists [`submit_commands`] has had to drop
+/// because the render thread was behind.
+pub fn dropped_command_lists() -> usize {
+    DROPPED_COMMAND_LISTS.load_relaxed()
+}
+
+// Takes the list handed over by the last `submit_commands`, if the render
+// thread hasn't already.
+fn take_commands() -> Option<Vec<RenderCmd>> {
+    let mut cmds = COMMANDS.lock().unwrap();
+    if cmds.front_pending == false {
+        return None;
+    }
+
+    cmds.front_pending = false;
+    Some(core::mem::take(&mut cmds.front))
+}
+
+fn execute_commands(cmds: &[RenderCmd]) {
+    for cmd in cmds {
+        match *cmd {
+            RenderCmd::Clear => render::begin_frame(),
+            RenderCmd::SetViewport {
+                x,
+                y,
+                width,
+                height,
+            } => render::set_viewport(x, y, width, height),
+            RenderCmd::Present => render::end_frame(),
+        }
+    }
+}
+
+/// Executes the commands handed over by the last [`submit_commands`], if the
+/// device can be drawn to. Run by the render thread each time it's woken.
 #[allow(clippy::cast_possible_wrap)]
-fn swap_buffers() {
-    while let Some(ev) = sys::next_main_window_event() {
-        handle_main_window_event(ev);
+pub fn swap_buffers() {
+    // A lost device can't be drawn to, but its commands are still taken so
+    // that the main thread can keep submitting. Window events are pumped by
+    // com::frame alone, so they aren't touched here.
+    let cmds = take_commands();
+    if render::check_device() {
+        if let Some(cmds) = cmds {
+            execute_commands(&cmds);
+        }
     }
 }
 
@@ -18,11 +142,16 @@ pub fn render_thread() -> ! {
         loop {
             if sys::query_backend_event() == SignalState::Cleared {
                 if sys::query_rg_registered_event() == SignalState::Cleared {
+                    begin_command("swap_buffers");
                     swap_buffers();
                 } else {
-                    render::begin_registration_internal().unwrap();
+                    begin_command("begin_registration");
+                    if let Err(e) = render::begin_registration_internal() {
+                        render::fatal_init_error!("{}", e);
+                    }
                     sys::clear_rg_registered_event();
                 }
+                signal_alive();
             } else {
             }
 
@@ -35,5 +164,111 @@ pub fn render_thread() -> ! {
         r_glob_mut().screen_update_notify = true;
         assert_eq!(r_glob().is_rendering_remote_update, false);
         r_glob_mut().is_rendering_remote_update = true;
+        signal_alive();
+    }
+}
+
+const WATCHDOG_INTERVAL: Duration = Duration::from_millis(250);
+
+// Set by the watchdog once it's decided the render thread has hung, for the
+// main thread to raise the fatal error (see `check_render_thread_hung`).
+static RENDER_THREAD_HUNG: AtomicBool = AtomicBool::new(false);
+
+/// Starts a thread that dumps diagnostics and has the main thread abort the
+/// game (see [`check_render_thread_hung`]) if the main thread is left
+/// waiting on the render thread for longer than `r_renderThreadTimeout`
+/// seconds.
+///
+/// Returns `false` if the thread couldn't be created.
+pub fn spawn_watchdog() -> bool {
+    sys::create_thread("Watchdog", watchdog_thread).map_or(false, |h| {
+        h.thread().unpark();
+        true
+    })
+}
+
+fn watchdog_thread() -> ! {
+    loop {
+        std::thread::sleep(WATCHDOG_INTERVAL);
+
+        // Already reported, the main thread just hasn't gotten to it yet
+        if RENDER_THREAD_HUNG.load(Ordering::SeqCst) {
+            continue;
+        }
+
+        // Not registered until the renderer initializes, and 0 disables the
+        // watchdog.
+        let timeout = dvar::get_int("r_renderThreadTimeout").unwrap_or(0);
+        if timeout <= 0 {
+            continue;
+        }
+
+        let Some(wait_start) = render::main_thread_wait_start() else {
+            continue;
+        };
+
+        // The render thread may legitimately have been idle before the main
+        // thread started waiting on it, so only count from whichever
+        // happened last.
+        let elapsed = sys::milliseconds()
+            - LAST_SIGNAL_TIME.load_relaxed().max(wait_start);
+        if elapsed > timeout as isize * 1000 {
+            dump_render_thread_state(elapsed);
+            RENDER_THREAD_HUNG.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Raises a fatal error if the watchdog has found the render thread hung.
+///
+/// Called by the main thread while it waits on the render thread, so that
+/// the error is raised (and shown to the user) from the main thread rather
+/// than the watchdog's.
+pub fn check_render_thread_hung() {
+    if RENDER_THREAD_HUNG.load(Ordering::SeqCst) {
+        com::errorln!(
+            com::ErrorParm::FATAL,
+            "The renderer stopped responding. Check the log for what it was \
+             doing when it stopped."
+        );
     }
 }
+
+fn dump_render_thread_state(elapsed: isize) {
+    com::print_errorln!(
+        console::Channel::GFX,
+        "Render thread hasn't responded in {} ms, it's most likely \
+         deadlocked.",
+        elapsed
+    );
+    com::println!(
+        console::Channel::GFX,
+        "    last command: {}",
+        *LAST_COMMAND.read().unwrap()
+    );
+    com::println!(
+        console::Channel::GFX,
+        "    dropped command lists: {}",
+        dropped_command_lists()
+    );
+    com::println!(
+        console::Channel::GFX,
+        "    backend event: {:?}",
+        sys::query_backend_event()
+    );
+    com::println!(
+        console::Channel::GFX,
+        "    registered event: {:?}",
+        sys::query_rg_registered_event()
+    );
+    com::println!(
+        console::Channel::GFX,
+        "    render completed event: {:?}",
+        sys::query_render_completed_event()
+    );
+    com::println!(
+        console::Channel::GFX,
+        "    device ok event: {:?}",
+        sys::query_render_device_ok_event()
+    );
+}
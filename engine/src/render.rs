@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 
 use crate::{
+    common::Vec4f32,
     gfx::WindowTarget,
     platform::{display_server::target::MonitorHandle, WindowHandle},
     sys::show_window,
@@ -12,19 +13,25 @@ cfg_if! {
     if #[cfg(wgpu)] {
         use pollster::block_on;
         use platform::render::wgpu::Device;
-    } else if #[cfg(d3d9)] {
+        use crate::util::WgpuSurface;
+    }
+}
+
+cfg_if! {
+    if #[cfg(d3d9)] {
         use crate::platform::render::d3d9::{
             Adapter, DxCapsCheckBits, DxCapsCheckInteger, DxCapsResponse,
             ShadowmapBuildTechType, ShadowmapSamplerState, D3DFMT_NULL,
             D3DPTFILTERCAPS_MAGFANISOTROPIC, D3DPTFILTERCAPS_MINFANISOTROPIC,
-            D3D_VENDOR_ID_NVIDIA, D3DERR_INVALID_CALL,
+            D3D_VENDOR_ID_NVIDIA, D3DERR_INVALID_CALL, D3DERR_DEVICELOST,
+            D3DERR_DEVICENOTRESET,
         };
         use core::{ffi::CStr, ptr::addr_of};
         use cstr::cstr;
         use nvapi_sys::{nvapi::NvAPI_Initialize, status::NVAPI_OK};
         use windows::Win32::{
             Graphics::Direct3D9::{
-                Direct3DCreate9, D3DADAPTER_IDENTIFIER9,
+                Direct3DCreate9, IDirect3D9, D3DADAPTER_IDENTIFIER9,
                 D3DCAPS2_FULLSCREENGAMMA, D3DCAPS9, D3DDEVTYPE_HAL,
                 D3DFMT_A8R8G8B8, D3DFMT_D24S8, D3DFMT_D24X8,
                 D3DFMT_R32F, D3DFMT_R5G6B5, D3DFMT_X8R8G8B8,
@@ -35,12 +42,17 @@ cfg_if! {
                 D3DFORMAT, D3DFMT_D24FS8, D3DMULTISAMPLE_TYPE, D3DMULTISAMPLE_NONE,
                 D3DCREATE_HARDWARE_VERTEXPROCESSING, D3DCREATE_MULTITHREADED,
                 D3DPRESENT_INTERVAL_IMMEDIATE, D3DPRESENT_INTERVAL_ONE,
-                D3DSWAPEFFECT_DISCARD,
+                D3DSWAPEFFECT_DISCARD, D3DCLEAR_TARGET, D3DRECT,
+                IDirect3DDevice9, D3DVIEWPORT9,
             },
             System::Threading::Sleep,
             UI::WindowsAndMessaging::{GetSystemMetrics, SM_CXSCREEN, SM_CYSCREEN},
         };
-    } else if #[cfg(vulkan)] {
+    }
+}
+
+cfg_if! {
+    if #[cfg(vulkan)] {
         use ash::extensions::khr;
     }
 }
@@ -48,10 +60,15 @@ cfg_if! {
 use sscanf::scanf;
 extern crate alloc;
 use alloc::collections::VecDeque;
-use core::sync::atomic::AtomicUsize;
+use core::{
+    fmt::Display,
+    sync::atomic::{AtomicIsize, AtomicUsize},
+};
 use std::{
     collections::HashSet,
-    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+    sync::{
+        PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError,
+    },
 };
 
 pub const MIN_HORIZONTAL_RESOLUTION: u32 = 640;
@@ -72,9 +89,10 @@ cfg_if! {
         use windows::Win32::UI::Input::KeyboardAndMouse::SetFocus;
         use windows::Win32::UI::WindowsAndMessaging::{
             WS_EX_LEFT, WS_SYSMENU, WS_CAPTION, WS_VISIBLE, WS_EX_TOPMOST,
-            WS_POPUP, AdjustWindowRectEx, CreateWindowExA, SetWindowPos,
-            HWND_NOTOPMOST, SWP_NOSIZE, SWP_NOMOVE,
+            WS_POPUP, CreateWindowExA, SetWindowPos, HWND_NOTOPMOST,
+            SWP_NOSIZE, SWP_NOMOVE,
         };
+        use windows::Win32::UI::HiDpi::AdjustWindowRectExForDpi;
         use windows::core::{PCSTR, PCWSTR};
         use windows::s;
         use crate::platform::os::target::monitor_enum_proc;
@@ -90,7 +108,7 @@ cfg_if! {
             RawWindowHandle, XlibWindowHandle, XlibDisplayHandle
         };
         use x11::xlib::{
-            XStoreName, XOpenDisplay, XSetWMProtocols, XCloseDisplay,
+            XStoreName, XSetWMProtocols,
             XDefaultScreen, XCreateSimpleWindow, XDefaultVisual, XScreenCount,
             XRootWindow, XScreenOfDisplay, XWhitePixel, XWidthOfScreen,
             XHeightOfScreen, XDestroyWindow, XDefaultDepth, XSetInputFocus,
@@ -132,6 +150,485 @@ fn init_render_thread() {
     }
 }
 
+/// The render backends a build can include. Which of the ones compiled in
+/// is used is chosen with `r_backend` when the renderer initializes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RenderBackend {
+    #[cfg(d3d9)]
+    D3d9,
+    #[cfg(vulkan)]
+    Vulkan,
+    #[cfg(wgpu)]
+    Wgpu,
+}
+
+impl RenderBackend {
+    /// Every backend compiled into this build, native ones first.
+    pub const ALL: &'static [Self] = &[
+        #[cfg(d3d9)]
+        Self::D3d9,
+        #[cfg(vulkan)]
+        Self::Vulkan,
+        #[cfg(wgpu)]
+        Self::Wgpu,
+    ];
+
+    /// Returns the value `r_backend` takes to select this backend.
+    pub const fn dvar_name(self) -> &'static str {
+        match self {
+            #[cfg(d3d9)]
+            Self::D3d9 => "d3d9",
+            #[cfg(vulkan)]
+            Self::Vulkan => "vulkan",
+            #[cfg(wgpu)]
+            Self::Wgpu => "wgpu",
+        }
+    }
+
+    /// Returns the backend's human-readable name.
+    pub const fn name(self) -> &'static str {
+        match self {
+            #[cfg(d3d9)]
+            Self::D3d9 => "Direct3D 9",
+            #[cfg(vulkan)]
+            Self::Vulkan => "Vulkan",
+            #[cfg(wgpu)]
+            Self::Wgpu => "wgpu",
+        }
+    }
+
+    fn from_dvar_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|b| b.dvar_name().eq_ignore_ascii_case(name))
+    }
+
+    // The backend used if `r_backend` hasn't picked one.
+    #[allow(clippy::expect_used)]
+    fn preferred() -> Self {
+        *Self::ALL
+            .first()
+            .expect("the engine was built without any render backends")
+    }
+}
+
+static BACKEND: RwLock<Option<RenderBackend>> = RwLock::new(None);
+
+/// Returns the render backend in use, or the one that will be used if the
+/// renderer hasn't been initialized yet.
+pub fn backend() -> RenderBackend {
+    BACKEND
+        .read()
+        .unwrap()
+        .unwrap_or_else(RenderBackend::preferred)
+}
+
+/// Returns the name of the render backend in use.
+pub fn backend_name() -> &'static str {
+    backend().name()
+}
+
+// Picks the backend `r_backend` asks for. Has to happen before anything
+// backend-specific is initialized, and isn't changed again until the next
+// `vid_restart`.
+fn select_backend() {
+    let requested = dvar::get_enumeration("r_backend").unwrap_or_default();
+    let backend = RenderBackend::from_dvar_name(&requested)
+        .unwrap_or_else(RenderBackend::preferred);
+    *BACKEND.write().unwrap() = Some(backend);
+    com::println!(
+        console::Channel::GFX,
+        "Using render backend {}",
+        backend.name()
+    );
+}
+
+/// Returns the name of the adapter the renderer is using, or [`None`] if
+/// one hasn't been chosen yet.
+pub fn adapter_name() -> Option<String> {
+    match backend() {
+        #[cfg(d3d9)]
+        RenderBackend::D3d9 => adapter_name_d3d9(),
+        #[cfg(vulkan)]
+        RenderBackend::Vulkan => adapter_name_vulkan(),
+        #[cfg(wgpu)]
+        RenderBackend::Wgpu => adapter_name_wgpu(),
+    }
+}
+
+#[cfg(d3d9)]
+#[allow(clippy::undocumented_unsafe_blocks)]
+fn adapter_name_d3d9() -> Option<String> {
+    let dx = platform::render::d3d9::dx();
+    let mut identifier = D3DADAPTER_IDENTIFIER9::default();
+    unsafe {
+        dx.d3d9.as_ref()?.GetAdapterIdentifier(
+            dx.adapter.as_d3d9(),
+            0,
+            addr_of_mut!(identifier),
+        )
+    }
+    .ok()?;
+    CStr::from_bytes_until_nul(&identifier.Description)
+        .ok()
+        .map(|s| s.to_string_lossy().into_owned())
+}
+
+#[cfg(wgpu)]
+fn adapter_name_wgpu() -> Option<String> {
+    RENDER_GLOBALS
+        .read()
+        .unwrap()
+        .adapter
+        .as_ref()
+        .map(|a| a.get_info().name)
+}
+
+#[cfg(vulkan)]
+#[allow(clippy::undocumented_unsafe_blocks)]
+fn adapter_name_vulkan() -> Option<String> {
+    let vk = platform::render::vulkan::vk();
+    let properties = unsafe {
+        vk.instance
+            .as_ref()?
+            .get_physical_device_properties(vk.physical_device?)
+    };
+    Some(
+        unsafe { core::ffi::CStr::from_ptr(properties.device_name.as_ptr()) }
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+/// What `gpulist` prints about an adapter.
+#[derive(Clone, Debug)]
+struct AdapterDescription {
+    name: String,
+    vendor_id: u32,
+    driver_version: String,
+    /// Dedicated video memory in MiB, if the backend can report it.
+    memory_mb: Option<u64>,
+    selected: bool,
+}
+
+/// Returns the name of the GPU vendor with PCI vendor ID `id`.
+fn vendor_name(id: u32) -> String {
+    match id {
+        0x10DE => "NVIDIA".to_owned(),
+        0x1002 | 0x1022 => "AMD".to_owned(),
+        0x8086 => "Intel".to_owned(),
+        0x106B => "Apple".to_owned(),
+        0x5143 => "Qualcomm".to_owned(),
+        0x13B5 => "ARM".to_owned(),
+        _ => format!("0x{:04X}", id),
+    }
+}
+
+/// Lays `adapters` out as a table, one line per adapter after a header,
+/// with `*` marking the one in use.
+fn format_adapter_list(adapters: &[AdapterDescription]) -> Vec<String> {
+    let name_width = adapters
+        .iter()
+        .map(|a| a.name.chars().count())
+        .chain(core::iter::once("Name".len()))
+        .max()
+        .unwrap_or_default();
+    let vendors = adapters
+        .iter()
+        .map(|a| vendor_name(a.vendor_id))
+        .collect::<Vec<_>>();
+    let vendor_width = vendors
+        .iter()
+        .map(String::len)
+        .chain(core::iter::once("Vendor".len()))
+        .max()
+        .unwrap_or_default();
+    let driver_width = adapters
+        .iter()
+        .map(|a| a.driver_version.len())
+        .chain(core::iter::once("Driver".len()))
+        .max()
+        .unwrap_or_default();
+
+    let mut lines = vec![format!(
+        "  # {:name_width$} {:vendor_width$} {:driver_width$} Memory",
+        "Name", "Vendor", "Driver"
+    )];
+    lines.extend(adapters.iter().zip(vendors).enumerate().map(
+        |(i, (a, vendor))| {
+            let memory = a
+                .memory_mb
+                .map_or_else(|| "?".to_owned(), |mb| format!("{} MiB", mb));
+            format!(
+                "{}{:2} {:name_width$} {:vendor_width$} {:driver_width$} {}",
+                if a.selected { '*' } else { ' ' },
+                i,
+                a.name,
+                vendor,
+                a.driver_version,
+                memory
+            )
+        },
+    ));
+    lines
+}
+
+/// Describes every adapter the active backend can see.
+fn adapter_descriptions() -> Vec<AdapterDescription> {
+    match backend() {
+        #[cfg(d3d9)]
+        RenderBackend::D3d9 => adapter_descriptions_d3d9(),
+        #[cfg(vulkan)]
+        RenderBackend::Vulkan => adapter_descriptions_vulkan(),
+        #[cfg(wgpu)]
+        RenderBackend::Wgpu => adapter_descriptions_wgpu(),
+    }
+}
+
+/// D3D9 can only report available memory once a device has been created,
+/// so only the adapter in use has its memory reported.
+#[cfg(d3d9)]
+#[allow(clippy::undocumented_unsafe_blocks)]
+fn adapter_descriptions_d3d9() -> Vec<AdapterDescription> {
+    let dx = platform::render::d3d9::dx();
+    let Some(d3d9) = dx.d3d9.as_ref() else {
+        return Vec::new();
+    };
+
+    (0..unsafe { d3d9.GetAdapterCount() })
+        .filter_map(|adapter| {
+            let mut id = D3DADAPTER_IDENTIFIER9::default();
+            unsafe { d3d9.GetAdapterIdentifier(adapter, 0, addr_of_mut!(id)) }
+                .ok()?;
+            let selected = adapter == dx.adapter.as_d3d9();
+            // Packed as product.version.subversion.build, 16 bits each
+            let v = id.DriverVersion;
+            Some(AdapterDescription {
+                name: CStr::from_bytes_until_nul(&id.Description)
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                vendor_id: id.VendorId,
+                driver_version: format!(
+                    "{}.{}.{}.{}",
+                    (v >> 48) & 0xFFFF,
+                    (v >> 32) & 0xFFFF,
+                    (v >> 16) & 0xFFFF,
+                    v & 0xFFFF
+                ),
+                memory_mb: if selected { gpu_memory_mb() } else { None },
+                selected,
+            })
+        })
+        .collect()
+}
+
+/// Formats a Vulkan `driverVersion`, which is packed the same way as a
+/// Vulkan API version except by NVIDIA.
+#[cfg(vulkan)]
+fn driver_version_vulkan(vendor_id: u32, version: u32) -> String {
+    if vendor_id == 0x10DE {
+        format!(
+            "{}.{}.{}.{}",
+            version >> 22,
+            (version >> 14) & 0xFF,
+            (version >> 6) & 0xFF,
+            version & 0x3F
+        )
+    } else {
+        format!(
+            "{}.{}.{}",
+            ash::vk::api_version_major(version),
+            ash::vk::api_version_minor(version),
+            ash::vk::api_version_patch(version)
+        )
+    }
+}
+
+#[cfg(vulkan)]
+#[allow(clippy::undocumented_unsafe_blocks, clippy::as_conversions)]
+fn adapter_descriptions_vulkan() -> Vec<AdapterDescription> {
+    let vk = platform::render::vulkan::vk();
+    let Some(instance) = vk.instance.as_ref() else {
+        return Vec::new();
+    };
+
+    unsafe { instance.enumerate_physical_devices() }
+        .unwrap_or_default()
+        .into_iter()
+        .map(|pdev| {
+            let properties =
+                unsafe { instance.get_physical_device_properties(pdev) };
+            let memory =
+                unsafe { instance.get_physical_device_memory_properties(pdev) };
+            let bytes = memory.memory_heaps
+                [..memory.memory_heap_count as usize]
+                .iter()
+                .filter(|heap| {
+                    heap.flags.contains(ash::vk::MemoryHeapFlags::DEVICE_LOCAL)
+                })
+                .map(|heap| heap.size)
+                .sum::<u64>();
+            AdapterDescription {
+                name: unsafe {
+                    core::ffi::CStr::from_ptr(properties.device_name.as_ptr())
+                }
+                .to_string_lossy()
+                .into_owned(),
+                vendor_id: properties.vendor_id,
+                driver_version: driver_version_vulkan(
+                    properties.vendor_id,
+                    properties.driver_version,
+                ),
+                memory_mb: Some(bytes / (1024 * 1024)),
+                selected: Some(pdev) == vk.physical_device,
+            }
+        })
+        .collect()
+}
+
+/// wgpu doesn't report adapters' memory.
+#[cfg(wgpu)]
+fn adapter_descriptions_wgpu() -> Vec<AdapterDescription> {
+    let rg = RENDER_GLOBALS.read().unwrap();
+    let Some(instance) = rg.instance.as_ref() else {
+        return Vec::new();
+    };
+    let current = rg.adapter.as_ref().and_then(|a| a.wgpu_info());
+
+    instance
+        .adapter_infos()
+        .into_iter()
+        .map(|info| AdapterDescription {
+            name: format!("{} ({:?})", info.name, info.backend),
+            vendor_id: info.vendor,
+            driver_version: if info.driver_info.is_empty() {
+                info.driver.clone()
+            } else {
+                format!("{} {}", info.driver, info.driver_info)
+            },
+            memory_mb: None,
+            selected: current.as_ref() == Some(&info),
+        })
+        .collect()
+}
+
+fn gpulist_f() {
+    let adapters = adapter_descriptions();
+    if adapters.is_empty() {
+        com::println!(
+            console::Channel::DONT_FILTER,
+            "No adapters found for render backend {}.",
+            backend_name()
+        );
+        return;
+    }
+
+    com::println!(
+        console::Channel::DONT_FILTER,
+        "Adapters for render backend {}:",
+        backend_name()
+    );
+    for line in format_adapter_list(&adapters) {
+        com::println!(console::Channel::DONT_FILTER, "{}", line);
+    }
+}
+
+/// Below this much available GPU memory (in MiB), the default settings are
+/// likely to run into allocation failures.
+const LOW_GPU_MEMORY_MB: u64 = 512;
+
+/// Returns how much GPU memory is available to the game, in MiB, or
+/// [`None`] if the backend or driver doesn't report it.
+///
+/// Only known once the device (or, for Vulkan, the adapter) has been
+/// created.
+pub fn gpu_memory_mb() -> Option<u64> {
+    vid::config().gpu_memory_mb
+}
+
+// Records the GPU memory reported by the backend, and warns if there's
+// little enough of it that allocations are likely to fail.
+fn store_gpu_memory(mb: Option<u64>) {
+    vid::config_mut().gpu_memory_mb = mb;
+
+    let Some(mb) = mb else {
+        com::println!(
+            console::Channel::GFX,
+            "Available GPU memory: unknown (not reported by the driver)",
+        );
+        return;
+    };
+
+    com::println!(console::Channel::GFX, "Available GPU memory: {} MB", mb);
+    if mb < LOW_GPU_MEMORY_MB {
+        com::warnln!(
+            console::Channel::GFX,
+            "WARNING: only {} MB of GPU memory is available. Lowering the \
+             resolution, texture quality, or anti-aliasing is recommended.",
+            mb,
+        );
+    }
+}
+
+// GetAvailableTextureMem is only an estimate (and is capped at 4 GB), but
+// it's the best D3D9 has to offer.
+#[cfg(d3d9)]
+#[allow(clippy::undocumented_unsafe_blocks)]
+fn gpu_memory_mb_d3d9(device: &IDirect3DDevice9) -> Option<u64> {
+    match unsafe { device.GetAvailableTextureMem() } {
+        0 => None,
+        bytes => Some(u64::from(bytes) / (1024 * 1024)),
+    }
+}
+
+// Sums what's left of the budget of every device-local heap. Needs
+// VK_EXT_memory_budget, without which the driver doesn't tell us how much
+// of each heap is in use.
+#[cfg(vulkan)]
+#[allow(clippy::undocumented_unsafe_blocks, clippy::as_conversions)]
+fn gpu_memory_mb_vulkan(
+    instance: &ash::Instance,
+    pdev: ash::vk::PhysicalDevice,
+) -> Option<u64> {
+    let has_budget =
+        unsafe { instance.enumerate_device_extension_properties(pdev) }
+            .ok()?
+            .iter()
+            .any(|e| {
+                let name = unsafe {
+                    core::ffi::CStr::from_ptr(e.extension_name.as_ptr())
+                };
+                name == ash::vk::ExtMemoryBudgetFn::name()
+            });
+    if has_budget == false {
+        return None;
+    }
+
+    let mut budget =
+        ash::vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+    let mut properties = ash::vk::PhysicalDeviceMemoryProperties2::builder()
+        .push_next(&mut budget)
+        .build();
+    unsafe {
+        instance.get_physical_device_memory_properties2(pdev, &mut properties);
+    }
+
+    let heaps = &properties.memory_properties.memory_heaps
+        [..properties.memory_properties.memory_heap_count as usize];
+    let bytes = heaps
+        .iter()
+        .enumerate()
+        .filter(|(_, heap)| {
+            heap.flags.contains(ash::vk::MemoryHeapFlags::DEVICE_LOCAL)
+        })
+        .map(|(i, _)| {
+            budget.heap_budget[i].saturating_sub(budget.heap_usage[i])
+        })
+        .sum::<u64>();
+    Some(bytes / (1024 * 1024))
+}
+
 pub fn init_threads() {
     com::println!(
         console::Channel::GFX,
@@ -139,7 +636,14 @@ pub fn init_threads() {
         std::thread::current().name().unwrap_or("main"),
     );
     init_render_thread();
-    // init_worker_threads();
+    sys::lock_main_thread_affinity();
+    if !rb::spawn_watchdog() {
+        com::warnln!(
+            console::Channel::GFX,
+            "Failed to create render thread watchdog"
+        );
+    }
+    sys::init_worker_threads();
     com::println!(
         console::Channel::GFX,
         "{}: ...succeeded",
@@ -156,10 +660,8 @@ pub fn begin_registration(_vid_config: &mut vid::Config) {
     }
 }
 
-pub fn begin_registration_internal() -> Result<(), ()> {
-    if init().is_err() {
-        return Err(());
-    }
+pub fn begin_registration_internal() -> Result<(), RenderError> {
+    init()?;
     sys::wait_rg_registered_event();
     Ok(())
 }
@@ -185,6 +687,22 @@ pub fn begin_remote_screen_update() {
 
 static G_MAIN_THREAD_BLOCKED: AtomicUsize = AtomicUsize::new(0);
 
+// The time (as returned by `sys::milliseconds`) at which the main thread
+// started waiting on the render thread, or -1 if it isn't waiting.
+static MAIN_THREAD_WAIT_START: AtomicIsize = AtomicIsize::new(-1);
+
+/// Returns the time (as returned by [`sys::milliseconds`]) at which the main
+/// thread started waiting on the render thread, or [`None`] if it isn't
+/// currently waiting.
+pub fn main_thread_wait_start() -> Option<isize> {
+    let start = MAIN_THREAD_WAIT_START.load_relaxed();
+    if start < 0 {
+        None
+    } else {
+        Some(start)
+    }
+}
+
 pub fn end_remote_screen_update() {
     end_remote_screen_update_with(|| {})
 }
@@ -218,10 +736,12 @@ pub fn end_remote_screen_update_with(f: impl Fn()) {
         return;
     }
 
+    MAIN_THREAD_WAIT_START.store_relaxed(sys::milliseconds());
     while r_glob().screen_update_notify == false {
         net::sleep(Duration::from_millis(1));
         f();
         sys::wait_renderer();
+        rb::check_render_thread_hung();
     }
     r_glob_mut().screen_update_notify = false;
     assert!(r_glob().remote_screen_update_nesting > 0);
@@ -231,13 +751,19 @@ pub fn end_remote_screen_update_with(f: impl Fn()) {
         net::sleep(Duration::from_millis(1));
         f();
         sys::wait_renderer();
+        rb::check_render_thread_hung();
         G_MAIN_THREAD_BLOCKED.decrement_wrapping();
     }
+    MAIN_THREAD_WAIT_START.store_relaxed(-1);
     r_glob_mut().screen_update_notify = false;
 }
 
-fn register() {
+/// Registers the renderer's Dvars and commands. Unlike [`init`], this runs
+/// on the main thread, before the configs are loaded.
+pub fn register() {
     register_dvars();
+    cmd::add_command_internal("vid_restart", vid_restart_f).unwrap();
+    cmd::add_command_internal("gpulist", gpulist_f).unwrap();
 }
 
 fn reflection_probe_register_dvars() {
@@ -256,14 +782,263 @@ fn reflection_probe_register_dvars() {
         Some("Enable shadow mapping"),
     )
     .unwrap();
+
+    dvar::register_bool(
+        "ui_showShadowMapOptions",
+        false,
+        dvar::DvarFlags::READ_ONLY,
+        Some("Whether the hardware supports the shadow map options"),
+    )
+    .unwrap();
+}
+
+const CLEAR_NEVER: &str = "never";
+const CLEAR_ALWAYS: &str = "always";
+const CLEAR_DEBUG: &str = "debug";
+
+/// The color the frame is cleared to with `r_clear debug`. Deliberately
+/// garish, so that any gaps in what gets drawn over it are obvious.
+const CLEAR_DEBUG_COLOR: Vec4f32 = (255.0, 0.0, 255.0, 255.0);
+
+/// Returns the color the frame should be cleared to (with each component in
+/// [0, 255]), or [`None`] if it shouldn't be cleared at all (or if the
+/// renderer's Dvars haven't been registered yet).
+pub fn frame_clear_color() -> Option<Vec4f32> {
+    match dvar::get_enumeration("r_clear")?.as_str() {
+        CLEAR_ALWAYS => dvar::get_color("r_clearColor"),
+        CLEAR_DEBUG => Some(CLEAR_DEBUG_COLOR),
+        _ => None,
+    }
+}
+
+/// Clears the target window's back buffer as specified by `r_clear` and
+/// `r_clearColor`.
+///
+/// Executed for [`rb::RenderCmd::Clear`] at the start of every frame. wgpu
+/// clears as part of beginning a render pass, so there's nothing to do here
+/// for it; [`end_frame`] creates its render pass with [`frame_load_op`]
+/// instead.
+pub fn begin_frame() {
+    match backend() {
+        #[cfg(d3d9)]
+        RenderBackend::D3d9 => begin_frame_d3d9(),
+        // TODO - implement
+        #[cfg(vulkan)]
+        RenderBackend::Vulkan => {}
+        #[cfg(wgpu)]
+        RenderBackend::Wgpu => {}
+    }
+}
+
+#[cfg(d3d9)]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn begin_frame_d3d9() {
+    let Some(c) = frame_clear_color() else {
+        return;
+    };
+    let Some(window) = target_window() else {
+        return;
+    };
+
+    let dx = platform::render::d3d9::dx();
+    let Some(device) = dx.device.as_ref() else {
+        return;
+    };
+
+    // D3DCOLOR is packed as ARGB
+    let color = (c.3 as u32) << 24
+        | (c.0 as u32) << 16
+        | (c.1 as u32) << 8
+        | c.2 as u32;
+    // Only clear the part of the back buffer the target window shows
+    let rect = D3DRECT {
+        x1: 0,
+        y1: 0,
+        x2: window.width as _,
+        y2: window.height as _,
+    };
+    // SAFETY:
+    // Clear is an FFI function, requiring use of unsafe. `rect` outlives the
+    // call, and the count of 1 matches the single rect passed.
+    let _ = unsafe {
+        device.Clear(1, addr_of!(rect), D3DCLEAR_TARGET, color, 1.0, 0)
+    };
+}
+
+/// Returns the [`wgpu::LoadOp`] that implements `r_clear` and
+/// `r_clearColor`, for use when beginning the frame's render pass.
+#[cfg(wgpu)]
+pub fn frame_load_op() -> wgpu::LoadOp<wgpu::Color> {
+    frame_clear_color().map_or(wgpu::LoadOp::Load, |c| {
+        wgpu::LoadOp::Clear(wgpu::Color {
+            r: f64::from(c.0) / 255.0,
+            g: f64::from(c.1) / 255.0,
+            b: f64::from(c.2) / 255.0,
+            a: f64::from(c.3) / 255.0,
+        })
+    })
+}
+
+/// Presents the frame started by [`begin_frame`] to the target window.
+///
+/// Executed for [`rb::RenderCmd::Present`] at the end of every frame. If
+/// the device has been lost in the meantime, the frame is dropped;
+/// [`check_device`] will have it reset before the next one.
+pub fn end_frame() {
+    match backend() {
+        #[cfg(d3d9)]
+        RenderBackend::D3d9 => end_frame_d3d9(),
+        // TODO - implement
+        #[cfg(vulkan)]
+        RenderBackend::Vulkan => {}
+        #[cfg(wgpu)]
+        RenderBackend::Wgpu => end_frame_wgpu(),
+    }
+}
+
+#[cfg(d3d9)]
+fn end_frame_d3d9() {
+    let Some(handle) = target_window().and_then(|w| w.handle) else {
+        return;
+    };
+    let hwnd = HWND(handle.get_win32().unwrap().hwnd as _);
+
+    let dx = platform::render::d3d9::dx();
+    let Some(device) = dx.device.as_ref() else {
+        return;
+    };
+
+    // SAFETY:
+    // BeginScene, EndScene and Present are FFI functions, requiring use of
+    // unsafe. Null rects and region present the whole back buffer, and
+    // `hwnd` is the target window, which outlives the call.
+    unsafe {
+        if device.BeginScene().is_err() {
+            return;
+        }
+        let _ = device.EndScene();
+        // D3DERR_DEVICELOST is picked up by `check_device` next frame
+        let _ = device.Present(
+            core::ptr::null(),
+            core::ptr::null(),
+            hwnd,
+            core::ptr::null(),
+        );
+    }
+}
+
+#[cfg(wgpu)]
+fn end_frame_wgpu() {
+    let load = frame_load_op();
+    let rg = RENDER_GLOBALS.read().unwrap();
+    let index = rg.target_window_index;
+    let (Some(surface), Some(device)) =
+        (rg.surfaces.get(index.0), rg.device.as_ref())
+    else {
+        return;
+    };
+
+    match surface.present_cleared(device, load) {
+        Ok(()) | Err(wgpu::SurfaceError::Timeout) => {}
+        Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+            // The swapchain no longer matches the window (or was never
+            // created); recreate it and try again next frame
+            if let Some(window) = rg.windows.get(index.0) {
+                let _ = configure_surface_wgpu(
+                    &rg,
+                    index,
+                    window.width,
+                    window.height,
+                );
+            }
+        }
+        Err(e) => {
+            com::warnln!(
+                console::Channel::GFX,
+                "WARNING: failed to present frame: {}",
+                e
+            );
+        }
+    }
+}
+
+/// Restricts drawing to the `width`x`height` rectangle of the target window
+/// at (`x`, `y`).
+pub fn set_viewport(x: u32, y: u32, width: u32, height: u32) {
+    match backend() {
+        #[cfg(d3d9)]
+        RenderBackend::D3d9 => set_viewport_d3d9(x, y, width, height),
+        // TODO - implement
+        #[cfg(vulkan)]
+        RenderBackend::Vulkan => {}
+        // TODO - apply to the frame's render pass once anything is drawn in
+        // it; clears always cover the whole surface on wgpu
+        #[cfg(wgpu)]
+        RenderBackend::Wgpu => {}
+    }
+}
+
+#[cfg(d3d9)]
+fn set_viewport_d3d9(x: u32, y: u32, width: u32, height: u32) {
+    let dx = platform::render::d3d9::dx();
+    let Some(device) = dx.device.as_ref() else {
+        return;
+    };
+
+    let viewport = D3DVIEWPORT9 {
+        X: x,
+        Y: y,
+        Width: width,
+        Height: height,
+        MinZ: 0.0,
+        MaxZ: 1.0,
+    };
+    // SAFETY:
+    // SetViewport is an FFI function, requiring use of unsafe. `viewport`
+    // outlives the call.
+    let _ = unsafe { device.SetViewport(addr_of!(viewport)) };
+}
+
+/// Issues the render commands for this frame and hands them to the render
+/// thread. Should be called once per frame, from the main thread.
+pub fn submit_frame() {
+    if let Some(window) = target_window() {
+        rb::issue(rb::RenderCmd::SetViewport {
+            x: 0,
+            y: 0,
+            width: window.width,
+            height: window.height,
+        });
+    }
+    rb::issue(rb::RenderCmd::Clear);
+    rb::issue(rb::RenderCmd::Present);
+    rb::submit_commands();
 }
 
 const ASPECT_RATIO_AUTO: &str = "auto";
 const ASPECT_RATIO_STANDARD: &str = "standard";
 const ASPECT_RATIO_16_10: &str = "wide 16:10";
 const ASPECT_RATIO_16_9: &str = "wide 16:9";
+const ASPECT_RATIO_CUSTOM: &str = "custom";
+
+/// The narrowest and widest ratios `r_aspectRatioCustom` can be set to.
+const ASPECT_RATIO_CUSTOM_MIN: f32 = 0.5;
+const ASPECT_RATIO_CUSTOM_MAX: f32 = 4.0;
 
 fn register_dvars() {
+    dvar::register_enumeration(
+        "r_backend",
+        RenderBackend::preferred().dvar_name().into(),
+        Some(
+            RenderBackend::ALL
+                .iter()
+                .map(|b| b.dvar_name().to_owned())
+                .collect(),
+        ),
+        dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::LATCHED,
+        Some("Render backend to use, out of those the game was built with"),
+    )
+    .unwrap();
     dvar::register_bool(
         "r_fullscreen",
         false,
@@ -271,7 +1046,26 @@ fn register_dvars() {
         Some("Display game full screen"),
     )
     .unwrap();
-    dvar::register_enumeration(
+    dvar::register_int(
+        "r_monitor",
+        0,
+        Some(0),
+        Some(8),
+        dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::LATCHED,
+        Some(
+            "Index of the monitor to use when full screen, counting from \
+             1; 0 picks one automatically",
+        ),
+    )
+    .unwrap();
+    dvar::register_bool(
+        "r_multithreaded_device",
+        false,
+        dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::LATCHED,
+        Some("Create the D3D9 device in multithreaded mode"),
+    )
+    .unwrap();
+    dvar::register_enumeration(
         "r_aspectRatio",
         "auto".into(),
         Some(vec![
@@ -279,6 +1073,7 @@ fn register_dvars() {
             ASPECT_RATIO_STANDARD.into(),
             ASPECT_RATIO_16_10.into(),
             ASPECT_RATIO_16_9.into(),
+            ASPECT_RATIO_CUSTOM.into(),
         ]),
         dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::LATCHED,
         Some(
@@ -287,6 +1082,18 @@ fn register_dvars() {
         ),
     )
     .unwrap();
+    dvar::register_float(
+        "r_aspectRatioCustom",
+        16.0 / 9.0,
+        Some(ASPECT_RATIO_CUSTOM_MIN),
+        Some(ASPECT_RATIO_CUSTOM_MAX),
+        dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::LATCHED,
+        Some(
+            "Screen aspect ratio (width / height) to use when r_aspectRatio \
+             is set to custom",
+        ),
+    )
+    .unwrap();
     dvar::register_int(
         "r_aaSamples",
         1,
@@ -306,6 +1113,16 @@ fn register_dvars() {
         ),
     )
     .unwrap();
+    dvar::register_string(
+        "r_adapterLuid",
+        "",
+        dvar::DvarFlags::ARCHIVE,
+        Some(
+            "Adapter to render with, remembered from the last run so that \
+             the same GPU is used every time. Empty to choose automatically",
+        ),
+    )
+    .unwrap();
     dvar::register_string(
         "r_customMode",
         "",
@@ -313,6 +1130,55 @@ fn register_dvars() {
         Some("Special resolution mode for the remote debugger"),
     )
     .unwrap();
+    dvar::register_int(
+        "r_renderThreadTimeout",
+        5,
+        Some(0),
+        Some(300),
+        dvar::DvarFlags::ARCHIVE,
+        Some(
+            "Seconds the main thread will wait on an unresponsive render \
+             thread before aborting; 0 waits forever",
+        ),
+    )
+    .unwrap();
+    dvar::register_bool(
+        "r_minimizeToTray",
+        false,
+        dvar::DvarFlags::ARCHIVE,
+        Some("Minimize the game window to the system tray (Windows only)"),
+    )
+    .unwrap();
+    dvar::register_bool(
+        "r_dpiAware",
+        true,
+        dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::LATCHED,
+        Some(
+            "Size the game window in physical pixels instead of letting the \
+             OS scale it on high-DPI displays",
+        ),
+    )
+    .unwrap();
+    dvar::register_bool(
+        "r_headless",
+        false,
+        dvar::DvarFlags::LATCHED,
+        Some(
+            "Render with a software adapter, so the renderer can start on \
+             machines without a GPU (e.g. for testing). Very slow",
+        ),
+    )
+    .unwrap();
+    dvar::register_bool(
+        "r_refreshRounding",
+        true,
+        dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::LATCHED,
+        Some(
+            "List refresh rates rounded to the nearest whole number, so \
+             that e.g. 59.94 Hz and 60 Hz are a single 60 Hz option",
+        ),
+    )
+    .unwrap();
     dvar::register_int(
         "vid_xpos",
         3,
@@ -331,6 +1197,31 @@ fn register_dvars() {
         "game window vertical position".into(),
     )
     .unwrap();
+    dvar::register_enumeration(
+        "r_clear",
+        CLEAR_ALWAYS.into(),
+        Some(vec![
+            CLEAR_NEVER.into(),
+            CLEAR_ALWAYS.into(),
+            CLEAR_DEBUG.into(),
+        ]),
+        dvar::DvarFlags::empty(),
+        Some(
+            "Controls how the color buffer is cleared at the start of each \
+             frame",
+        ),
+    )
+    .unwrap();
+    dvar::register_color(
+        "r_clearColor",
+        0.0,
+        0.0,
+        0.0,
+        1.0,
+        dvar::DvarFlags::empty(),
+        Some("Color to clear the screen to when clearing the frame buffer"),
+    )
+    .unwrap();
 
     reflection_probe_register_dvars();
 }
@@ -338,17 +1229,15 @@ fn register_dvars() {
 /// Initializes the renderer.
 ///
 /// Should be called before any other functions from this module.
-#[allow(clippy::unnecessary_wraps)]
-fn init() -> Result<(), ()> {
+fn init() -> Result<(), RenderError> {
     com::println!(console::Channel::GFX, "----- render::init -----");
 
-    register();
-
-    init_graphics_api().unwrap();
+    select_backend();
 
-    Ok(())
+    init_graphics_api()
 }
 
+#[derive(Debug)]
 struct MonitorInfo {
     name: String,
     width: u32,
@@ -357,6 +1246,7 @@ struct MonitorInfo {
     video_modes: Vec<VideoMode>,
 }
 
+#[derive(Debug)]
 pub struct RenderGlobals {
     adapter_native_width: u32,
     adapter_native_height: u32,
@@ -365,7 +1255,7 @@ pub struct RenderGlobals {
     video_modes: Vec<VideoMode>,
     resolution_names: HashSet<String>,
     refresh_rate_names: HashSet<String>,
-    target_window_index: i32,
+    target_window_index: WindowIndex,
     #[cfg(any(
         feature = "windows_use_wgpu",
         feature = "macos_use_wgpu",
@@ -384,6 +1274,14 @@ pub struct RenderGlobals {
         feature = "linux_use_wgpu"
     ))]
     instance: Option<platform::render::wgpu::Instance>,
+    // One surface per window, indexed the same as `windows`. The target
+    // window's is the one frames are presented to.
+    #[cfg(any(
+        feature = "windows_use_wgpu",
+        feature = "macos_use_wgpu",
+        feature = "linux_use_wgpu"
+    ))]
+    surfaces: Vec<platform::render::wgpu::Surface>,
     windows: Vec<WindowTarget>,
 }
 
@@ -397,7 +1295,7 @@ impl RenderGlobals {
             video_modes: Vec::new(),
             resolution_names: HashSet::new(),
             refresh_rate_names: HashSet::new(),
-            target_window_index: 0,
+            target_window_index: WindowIndex::PRIMARY,
             #[cfg(any(
                 feature = "windows_use_wgpu",
                 feature = "macos_use_wgpu",
@@ -416,6 +1314,12 @@ impl RenderGlobals {
                 feature = "linux_use_wgpu"
             ))]
             instance: None,
+            #[cfg(any(
+                feature = "windows_use_wgpu",
+                feature = "macos_use_wgpu",
+                feature = "linux_use_wgpu"
+            ))]
+            surfaces: Vec::new(),
             windows: Vec::new(),
         }
     }
@@ -427,14 +1331,68 @@ impl Default for RenderGlobals {
     }
 }
 
+/// Identifies one of the windows the renderer draws to (see [`add_window`]).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct WindowIndex(usize);
+
+impl WindowIndex {
+    /// The game's main window, which is always the first one added.
+    pub const PRIMARY: Self = Self(0);
+}
+
 lazy_static! {
     pub static ref RENDER_GLOBALS: RwLock<RenderGlobals> =
         RwLock::new(RenderGlobals::default());
 }
 
+/// The reasons renderer initialization can fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RenderError {
+    /// No graphics adapter suitable for rendering could be found.
+    AdapterNotFound,
+    /// The graphics API refused to create a device (or the interface
+    /// needed to create one). Holds the API's reason, if it gave one.
+    DeviceCreationFailed(String),
+    /// None of the display's video modes meet the minimum resolution.
+    NoValidVideoModes,
+    /// The game window couldn't be created with any of the attempted
+    /// settings.
+    WindowCreationFailed,
+    /// The adapter or driver is missing a capability the renderer needs.
+    UnsupportedCaps(String),
+    /// The resolution couldn't be changed without restarting the renderer.
+    /// Holds the reason.
+    ModeChangeFailed(String),
+}
+
+impl Display for RenderError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            Self::AdapterNotFound => {
+                write!(f, "No suitable graphics adapter found")
+            }
+            Self::DeviceCreationFailed(s) => {
+                write!(f, "Couldn't create a Renderer device: {}", s)
+            }
+            Self::NoValidVideoModes => write!(
+                f,
+                "No valid resolutions of {} x {} or above found",
+                MIN_HORIZONTAL_RESOLUTION, MIN_VERTICAL_RESOLUTION
+            ),
+            Self::WindowCreationFailed => {
+                write!(f, "Couldn't create a window")
+            }
+            Self::UnsupportedCaps(s) => write!(f, "{}", s),
+            Self::ModeChangeFailed(s) => {
+                write!(f, "Couldn't change the resolution in place: {}", s)
+            }
+        }
+    }
+}
+
 #[allow(clippy::needless_pass_by_value)]
 #[doc(hidden)]
-fn _fatal_init_error_internal(error: impl ToString) -> ! {
+pub fn _fatal_init_error_internal(error: impl ToString) -> ! {
     com::println!(
         console::Channel::GFX,
         "********** Device returned an unrecoverable error code during \
@@ -458,6 +1416,7 @@ macro_rules! fatal_init_error {
         $crate::render::_fatal_init_error_internal(core::format_args!($($arg)*));
     }};
 }
+pub(crate) use fatal_init_error;
 
 /// Called when the renderer encounters a fatal error during initialization.
 ///
@@ -472,7 +1431,8 @@ macro_rules! fatal_init_errorln {
     }};
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VideoMode {
     pub width: u32,
     pub height: u32,
@@ -580,6 +1540,94 @@ fn closest_refresh_rate_for_mode(
     None
 }
 
+/// Returns the refresh rate closest to `hz` out of those `video_modes` has
+/// for `width`x`height`, or [`None`] if it has no modes at that resolution.
+pub fn nearest_refresh_rate(
+    video_modes: &[VideoMode],
+    width: u32,
+    height: u32,
+    hz: f32,
+) -> Option<f32> {
+    video_modes
+        .iter()
+        .filter(|m| m.width == width && m.height == height)
+        .map(|m| m.refresh)
+        .min_by(|a, b| (a - hz).abs().total_cmp(&(b - hz).abs()))
+}
+
+/// Returns the name `r_displayRefresh` lists the refresh rate `hz` under,
+/// rounded to the nearest whole number if `round` is set.
+pub fn refresh_rate_name(hz: f32, round: bool) -> String {
+    format!("{} Hz", if round { hz.round() } else { hz })
+}
+
+/// Returns the names of the refresh rates in `video_modes`, in the order
+/// they first appear, for `r_displayRefresh`. With `round` set, rates that
+/// round to the same whole number (e.g. 59.94 and 60) share one name.
+pub fn refresh_rate_names(
+    video_modes: &[VideoMode],
+    round: bool,
+) -> Vec<String> {
+    let mut names = Vec::new();
+    for m in video_modes {
+        let name = refresh_rate_name(m.refresh, round);
+        if names.contains(&name) == false {
+            names.push(name);
+        }
+    }
+    names
+}
+
+fn refresh_rounding() -> bool {
+    dvar::get_bool("r_refreshRounding").unwrap_or(true)
+}
+
+/// Environment variable that turns on headless mode the same way
+/// `r_headless` does, for machines (e.g. CI runners) where setting a dvar
+/// before the renderer starts is awkward. Any value other than empty or
+/// `0` counts.
+pub const HEADLESS_ENV_VAR: &str = "OPENT5_HEADLESS";
+
+/// Returns `true` if the renderer should run headless, i.e. use a software
+/// adapter (wgpu's fallback adapter, or d3d9's reference rasterizer)
+/// instead of a GPU.
+///
+/// Headless mode only exists so the renderer can initialize on machines
+/// without a GPU, e.g. for tests. Performance is irrelevant in it, and the
+/// software adapters are far too slow to actually play on.
+pub fn headless() -> bool {
+    dvar::get_bool("r_headless").unwrap_or(false)
+        || std::env::var_os(HEADLESS_ENV_VAR)
+            .is_some_and(|v| v.is_empty() == false && v != "0")
+}
+
+// Checks the refresh rate set in `r_displayRefresh` against the ones
+// supported at `width`x`height`, snapping it to the nearest supported one
+// (with a warning) if it isn't. Returns `None` if `r_displayRefresh` can't
+// be parsed or there are no modes at that resolution to check against.
+fn requested_refresh_rate(width: u32, height: u32) -> Option<f32> {
+    let requested = dvar::get_enumeration("r_displayRefresh")
+        .and_then(|r| scanf!(r, "{} Hz", f32).ok())?;
+    let video_modes = RENDER_GLOBALS.read().unwrap().video_modes.clone();
+    let hz = nearest_refresh_rate(&video_modes, width, height, requested)?;
+    // A rounded name stands for every rate that rounds to it, so 60 Hz
+    // picking the display's 59.94 Hz isn't worth warning about
+    let listed = refresh_rate_name(hz, refresh_rounding())
+        == refresh_rate_name(requested, false);
+    if listed == false && (hz - requested).abs() >= f32::EPSILON {
+        com::warnln!(
+            console::Channel::GFX,
+            "WARNING: r_displayRefresh {} Hz isn't supported at {}x{}, \
+             using {} Hz instead",
+            requested,
+            width,
+            height,
+            hz
+        );
+    }
+    Some(hz)
+}
+
 #[allow(
     clippy::cast_sign_loss,
     clippy::std_instead_of_core,
@@ -622,19 +1670,33 @@ fn set_wnd_parms(wnd_parms: &mut gfx::WindowParms) {
     if !wnd_parms.fullscreen {
         wnd_parms.hz = 60.0;
     } else {
-        let hz = closest_refresh_rate_for_mode(
+        let hz = requested_refresh_rate(
             wnd_parms.display_width,
             wnd_parms.display_height,
-            wnd_parms.hz,
         )
+        .or_else(|| {
+            closest_refresh_rate_for_mode(
+                wnd_parms.display_width,
+                wnd_parms.display_height,
+                wnd_parms.hz,
+            )
+        })
         .unwrap();
+        // Keep the exact rate for setting the mode, but store the name it's
+        // listed under
         wnd_parms.hz = hz;
-        dvar::set_string_internal("r_displayRefresh", &format!("{} Hz", hz))
-            .unwrap();
+        dvar::set_string_internal(
+            "r_displayRefresh",
+            &refresh_rate_name(hz, refresh_rounding()),
+        )
+        .unwrap();
     }
 
-    wnd_parms.x = dvar::get_int("vid_xpos").unwrap().clamp(0, i32::MAX) as _;
-    wnd_parms.y = dvar::get_int("vid_ypos").unwrap().clamp(0, i32::MAX) as _;
+    // vid_xpos and vid_ypos are already kept within their registered
+    // ranges; only negative positions need handling, since they can't be
+    // stored in the window parameters.
+    wnd_parms.x = dvar::get_int("vid_xpos").unwrap().max(0) as _;
+    wnd_parms.y = dvar::get_int("vid_ypos").unwrap().max(0) as _;
     wnd_parms.window_handle = None;
     wnd_parms.aa_samples =
         dvar::get_int("r_aaSamples").unwrap().clamp(0, i32::MAX) as _;
@@ -693,6 +1755,11 @@ fn store_window_settings(wnd_parms: &mut gfx::WindowParms) -> Result<(), ()> {
             ASPECT_RATIO_STANDARD => 4.0 / 3.0,
             ASPECT_RATIO_16_10 => 16.0 / 10.0,
             ASPECT_RATIO_16_9 => 16.0 / 9.0,
+            // The Dvar's domain should already keep it in range, but clamp
+            // anyway since a bad ratio would break all the math below
+            ASPECT_RATIO_CUSTOM => dvar::get_float("r_aspectRatioCustom")
+                .unwrap()
+                .clamp(ASPECT_RATIO_CUSTOM_MIN, ASPECT_RATIO_CUSTOM_MAX),
             _ => panic!(
                 "unhandled case, aspectRatio = {}",
                 dvar::get_enumeration("r_aspectRatio").unwrap()
@@ -720,6 +1787,7 @@ fn store_window_settings(wnd_parms: &mut gfx::WindowParms) -> Result<(), ()> {
 
     vid_config.is_tool_mode = dvar::get_bool("r_reflectionProbeGenerate")
         .map_or(false, |enabled| enabled);
+    vid_config.bump_generation();
 
     Ok(())
 }
@@ -728,6 +1796,7 @@ fn store_window_settings(wnd_parms: &mut gfx::WindowParms) -> Result<(), ()> {
 fn reduce_window_settings() -> Result<(), ()> {
     if dvar::get_int("r_aaSamples").unwrap() > 1 {
         dvar::set_int("r_aaSamples", dvar::get_int("r_aaSamples").unwrap() - 1)
+            .map(|_| ())
     } else {
         if dvar::get_enumeration("r_displayRefresh")
             .unwrap()
@@ -748,24 +1817,62 @@ fn reduce_window_settings() -> Result<(), ()> {
     }
 }
 
+/// Returns the adapter `r_adapterLuid` pins the game to, or [`None`] if it
+/// isn't pinned to one.
+fn pinned_adapter_id() -> Option<String> {
+    dvar::get_string("r_adapterLuid").filter(|id| id.is_empty() == false)
+}
+
+fn warn_pinned_adapter_missing(id: &str) {
+    com::warnln!(
+        console::Channel::GFX,
+        "WARNING: pinned adapter {} (r_adapterLuid) wasn't found, choosing \
+         one automatically",
+        id
+    );
+}
+
+/// Pins the game to the adapter identified by `id` (or unpins it, if
+/// [`None`]), so that later runs choose the same one.
+fn pin_adapter(id: Option<String>) {
+    dvar::set_string_internal("r_adapterLuid", &id.unwrap_or_default())
+        .unwrap();
+}
+
 #[cfg(wgpu)]
-#[allow(clippy::unnecessary_wraps)]
-fn choose_adapter() -> Option<platform::render::wgpu::Adapter> {
+fn choose_adapter_wgpu() -> Option<platform::render::wgpu::Adapter> {
     let rg = RENDER_GLOBALS.write().unwrap();
-    let adapter = block_on(platform::render::wgpu::Adapter::new(
-        rg.instance.as_ref().unwrap(),
-        None,
-    ));
-    Some(adapter)
+    let instance = rg.instance.as_ref().unwrap();
+    let adapter = if headless() {
+        com::println!(
+            console::Channel::GFX,
+            "Headless mode, using the software adapter..."
+        );
+        block_on(platform::render::wgpu::Adapter::new_fallback(instance))
+            .ok()?
+    } else {
+        block_on(platform::render::wgpu::Adapter::new(instance, None))
+    };
+    adapter.wgpu_info().is_some().then_some(adapter)
 }
 
 #[cfg(d3d9)]
-fn choose_adapter() -> Option<Adapter> {
+fn choose_adapter_d3d9() -> Option<Adapter> {
     let hmonitor = choose_monitor().get_win32().unwrap();
     let dx = platform::render::d3d9::dx();
 
     let adapter_count = unsafe { dx.d3d9.as_ref().unwrap().GetAdapterCount() };
 
+    if let Some(pinned) = pinned_adapter_id() {
+        if let Some(adapter) = (0..adapter_count).find(|&a| {
+            adapter_id_d3d9(dx.d3d9.as_ref().unwrap(), a).as_deref()
+                == Some(pinned.as_str())
+        }) {
+            return Some(Adapter::from_d3d9(adapter));
+        }
+        warn_pinned_adapter_missing(&pinned);
+    }
+
     let mut id = D3DADAPTER_IDENTIFIER9::default();
     for adapter in 0..adapter_count {
         if hmonitor.0 != 0 {
@@ -794,6 +1901,19 @@ fn choose_adapter() -> Option<Adapter> {
     Some(Adapter::from_d3d9(adapter_count))
 }
 
+/// Returns a string identifying `adapter` across runs, for
+/// `r_adapterLuid`.
+///
+/// `IDirect3D9` can't get an adapter's LUID (which doesn't survive a reboot
+/// anyway), so this uses the adapter's device identifier instead.
+#[cfg(d3d9)]
+#[allow(clippy::undocumented_unsafe_blocks)]
+fn adapter_id_d3d9(d3d9: &IDirect3D9, adapter: u32) -> Option<String> {
+    let mut id = D3DADAPTER_IDENTIFIER9::default();
+    unsafe { d3d9.GetAdapterIdentifier(adapter, 0, addr_of_mut!(id)) }.ok()?;
+    Some(format!("guid:{:?}", id.DeviceIdentifier))
+}
+
 #[cfg(windows)]
 #[allow(clippy::undocumented_unsafe_blocks)]
 fn available_monitors() -> VecDeque<MonitorHandle> {
@@ -1051,7 +2171,9 @@ fn monitor_info(monitor_handle: MonitorHandle) -> Option<MonitorInfo> {
     clippy::cast_lossless,
     clippy::cast_possible_wrap
 )]
-pub fn create_window_2(wnd_parms: &mut gfx::WindowParms) -> Result<(), ()> {
+pub fn create_window_2(
+    wnd_parms: &mut gfx::WindowParms,
+) -> Result<(), RenderError> {
     assert!(wnd_parms.window_handle.is_none());
 
     let (dw_ex_style, dw_style) = if wnd_parms.fullscreen == false {
@@ -1081,13 +2203,23 @@ pub fn create_window_2(wnd_parms: &mut gfx::WindowParms) -> Result<(), ()> {
         top: 0,
         bottom: wnd_parms.display_height as _,
     };
+    // Once Windows stops scaling the window for us, the frame around the
+    // client area has to be sized for the monitor's scale by hand
+    let scale = window_dpi_scale(wnd_parms);
+    let dpi = platform::display_server::win32::dpi_for_scale(scale);
     unsafe {
-        AdjustWindowRectEx(addr_of_mut!(rect), dw_style, false, dw_ex_style);
+        AdjustWindowRectExForDpi(
+            addr_of_mut!(rect),
+            dw_style,
+            false,
+            dw_ex_style,
+            dpi,
+        );
     }
     let hinstance = unsafe { GetModuleHandleA(None) }.unwrap_or_default();
     let height = rect.bottom - rect.top;
     let width = rect.right - rect.left;
-    let window_name = CString::new(com::get_official_build_name_r()).unwrap();
+    let window_name = CString::new(com::get_window_title()).unwrap();
     let hwnd = unsafe {
         CreateWindowExA(
             dw_ex_style,
@@ -1108,7 +2240,7 @@ pub fn create_window_2(wnd_parms: &mut gfx::WindowParms) -> Result<(), ()> {
     if hwnd.0 == 0 {
         com::println!(console::Channel::GFX, "Couldn't create a window.");
         wnd_parms.window_handle = None;
-        Err(())
+        Err(RenderError::WindowCreationFailed)
     } else {
         let mut handle = Win32WindowHandle::empty();
         handle.hinstance = hinstance.0 as _;
@@ -1166,7 +2298,9 @@ fn monitor_info(_monitor: MonitorHandle) -> Option<MonitorInfo> {
 }
 
 #[cfg(wayland)]
-pub fn create_window_2(_wnd_parms: &mut gfx::WindowParms) -> Result<(), ()> {
+pub fn create_window_2(
+    _wnd_parms: &mut gfx::WindowParms,
+) -> Result<(), RenderError> {
     todo!()
 }
 
@@ -1196,7 +2330,10 @@ fn monitor_info(_monitor: MonitorHandle) -> Option<MonitorInfo> {
 }
 
 #[cfg(appkit)]
-pub fn create_window_2(wnd_parms: &mut gfx::WindowParms) -> Result<(), ()> {
+#[allow(clippy::cast_precision_loss)]
+pub fn create_window_2(
+    wnd_parms: &mut gfx::WindowParms,
+) -> Result<(), RenderError> {
     assert!(wnd_parms.window_handle.is_none());
 
     autoreleasepool(|pool| {
@@ -1246,11 +2383,15 @@ pub fn create_window_2(wnd_parms: &mut gfx::WindowParms) -> Result<(), ()> {
         unsafe { app_menu.addItem(&quit_menu_item) };
         unsafe { app_menu_item.setSubmenu(Some(&app_menu)) };
 
+        // AppKit sizes windows in points, and the backbuffer is sized in
+        // pixels, so the content rect has to be scaled down for the
+        // backbuffer to cover the window exactly.
+        let scale = window_dpi_scale(wnd_parms);
         let rect = NSRect::new(
             CGPoint::new(wnd_parms.x as _, wnd_parms.y as _),
             CGSize::new(
-                wnd_parms.display_width as _,
-                wnd_parms.display_height as _,
+                (wnd_parms.display_width as f32 / scale) as _,
+                (wnd_parms.display_height as f32 / scale) as _,
             ),
         );
         let window_style =
@@ -1272,7 +2413,7 @@ pub fn create_window_2(wnd_parms: &mut gfx::WindowParms) -> Result<(), ()> {
         }) else {
             com::println!(console::Channel::GFX, "Couldn't create a window.");
             wnd_parms.window_handle = None;
-            return Err(());
+            return Err(RenderError::WindowCreationFailed);
         };
 
         let _window_controller = Id::autorelease(
@@ -1290,7 +2431,7 @@ pub fn create_window_2(wnd_parms: &mut gfx::WindowParms) -> Result<(), ()> {
         let wdg = WindowDelegate::new(unsafe { window.windowNumber() });
         unsafe { window.setDelegate(Some(&ProtocolObject::from_id(wdg))) };
 
-        let title = NSString::from_str(com::get_official_build_name_r());
+        let title = NSString::from_str(&com::get_window_title());
         unsafe { window.setTitle(&title) };
 
         let mut handle = AppKitWindowHandle::empty();
@@ -1313,18 +2454,20 @@ pub fn create_window_2(wnd_parms: &mut gfx::WindowParms) -> Result<(), ()> {
 #[cfg(xlib)]
 #[allow(clippy::undocumented_unsafe_blocks)]
 fn available_monitors() -> VecDeque<MonitorHandle> {
-    let display =
-        unsafe { XOpenDisplay(platform::display_server::xlib::display_name()) };
-    let num_screens = unsafe { XScreenCount(display) };
-    unsafe { XCloseDisplay(display) };
-    let mut monitors = VecDeque::new();
-    for i in 0..num_screens {
-        let mut handle = XlibDisplayHandle::empty();
-        handle.display = display.cast();
-        handle.screen = i as _;
-        monitors.push_back(MonitorHandle::Xlib(handle));
-    }
-    monitors
+    // The handles keep a pointer to the display, so it has to be the shared
+    // connection, which is never closed
+    platform::display_server::xlib::with_display(|display| {
+        let num_screens = unsafe { XScreenCount(display) };
+        (0..num_screens)
+            .map(|i| {
+                let mut handle = XlibDisplayHandle::empty();
+                handle.display = display.cast();
+                handle.screen = i as _;
+                MonitorHandle::Xlib(handle)
+            })
+            .collect()
+    })
+    .unwrap_or_default()
 }
 
 #[cfg(xlib)]
@@ -1335,108 +2478,112 @@ fn available_monitors() -> VecDeque<MonitorHandle> {
     clippy::cast_sign_loss
 )]
 fn primary_monitor() -> Option<MonitorHandle> {
-    let display =
-        unsafe { XOpenDisplay(platform::display_server::xlib::display_name()) };
-    let screen = unsafe { XDefaultScreen(display) };
-    let root_window = unsafe { XRootWindow(display, screen) };
-    let white_pixel = unsafe { XWhitePixel(display, screen) };
-    let window = unsafe {
-        XCreateSimpleWindow(
-            display,
-            root_window,
-            0,
-            0,
-            1,
-            1,
-            1,
-            white_pixel,
-            white_pixel,
-        )
-    };
-    let mut nmonitors = 0;
-    let monitors_ptr = unsafe {
-        XRRGetMonitors(
-            display,
-            window,
-            x11::xlib::True,
-            addr_of_mut!(nmonitors),
-        )
-    };
-    // SAFETY: assuming the Xlib implementation is conforming,
-    // [monitors_ptr, monitors_ptr + nmonitors) should always
-    // be valid.
-    let monitors =
-        unsafe { core::slice::from_raw_parts(monitors_ptr, nmonitors as _) };
-    let primary_monitor = monitors
-        .iter()
-        .enumerate()
-        .find(|(_, m)| m.primary != 0)
-        .map(|(i, _)| {
-            let mut handle = XlibDisplayHandle::empty();
-            handle.display = display.cast();
-            handle.screen = i as _;
-            MonitorHandle::Xlib(handle)
-        });
+    platform::display_server::xlib::with_display(|display| {
+        let screen = unsafe { XDefaultScreen(display) };
+        let root_window = unsafe { XRootWindow(display, screen) };
+        let white_pixel = unsafe { XWhitePixel(display, screen) };
+        let window = unsafe {
+            XCreateSimpleWindow(
+                display,
+                root_window,
+                0,
+                0,
+                1,
+                1,
+                1,
+                white_pixel,
+                white_pixel,
+            )
+        };
+        let mut nmonitors = 0;
+        let monitors_ptr = unsafe {
+            XRRGetMonitors(
+                display,
+                window,
+                x11::xlib::True,
+                addr_of_mut!(nmonitors),
+            )
+        };
+        // SAFETY: assuming the Xlib implementation is conforming,
+        // [monitors_ptr, monitors_ptr + nmonitors) should always
+        // be valid.
+        let monitors = unsafe {
+            core::slice::from_raw_parts(monitors_ptr, nmonitors as _)
+        };
+        // Handles identify X screens rather than RandR monitors (see
+        // `available_monitors`), so the primary monitor's handle is for the
+        // screen it was found on
+        let primary_monitor =
+            monitors.iter().find(|m| m.primary != 0).map(|_| {
+                let mut handle = XlibDisplayHandle::empty();
+                handle.display = display.cast();
+                handle.screen = screen as _;
+                MonitorHandle::Xlib(handle)
+            });
 
-    unsafe {
-        XRRFreeMonitors(monitors_ptr);
-    }
-    unsafe {
-        XDestroyWindow(display, window);
-    }
-    primary_monitor
+        unsafe {
+            XRRFreeMonitors(monitors_ptr);
+        }
+        unsafe {
+            XDestroyWindow(display, window);
+        }
+        primary_monitor
+    })
+    .flatten()
 }
 
 #[cfg(xlib)]
-#[allow(clippy::undocumented_unsafe_blocks, clippy::unnecessary_wraps)]
+#[allow(clippy::undocumented_unsafe_blocks)]
 fn current_monitor(_: Option<WindowHandle>) -> Option<MonitorHandle> {
-    let display =
-        unsafe { XOpenDisplay(platform::display_server::xlib::display_name()) };
-    let screen = unsafe { XDefaultScreen(display) };
-    let mut handle = XlibDisplayHandle::empty();
-    handle.display = display.cast();
-    handle.screen = screen as _;
-    Some(MonitorHandle::Xlib(handle))
+    platform::display_server::xlib::with_display(|display| {
+        let screen = unsafe { XDefaultScreen(display) };
+        let mut handle = XlibDisplayHandle::empty();
+        handle.display = display.cast();
+        handle.screen = screen as _;
+        MonitorHandle::Xlib(handle)
+    })
 }
 
 #[cfg(xlib)]
 #[allow(clippy::undocumented_unsafe_blocks)]
 fn choose_monitor() -> MonitorHandle {
-    let monitor = dvar::get_int("r_monitor").unwrap();
-    let display =
-        unsafe { XOpenDisplay(platform::display_server::xlib::display_name()) };
-
-    let mut handle = XlibDisplayHandle::empty();
-    handle.display = display.cast();
+    // r_monitor counts from 1, with 0 meaning the default screen
+    let monitor = dvar::get_int("r_monitor").unwrap() - 1;
+    platform::display_server::xlib::with_display(|display| {
+        let mut handle = XlibDisplayHandle::empty();
+        handle.display = display.cast();
 
-    let screen = unsafe { XScreenOfDisplay(display, monitor) };
-    handle.screen = if screen.is_null() {
-        unsafe { XDefaultScreen(display) as _ }
-    } else {
-        monitor as _
-    };
+        let num_screens = unsafe { XScreenCount(display) };
+        handle.screen = if (0..num_screens).contains(&monitor) {
+            monitor as _
+        } else {
+            unsafe { XDefaultScreen(display) as _ }
+        };
 
-    MonitorHandle::Xlib(handle)
+        MonitorHandle::Xlib(handle)
+    })
+    .unwrap()
 }
 
 #[cfg(all(xlib, not(vulkan)))]
 #[allow(clippy::undocumented_unsafe_blocks, clippy::cast_sign_loss)]
 fn get_monitor_dimensions() -> Option<(u32, u32)> {
-    todo!()
-    // let display = monitor.get_xlib().unwrap().display as *mut _XDisplay;
-    // let screen_num = monitor.get_xlib().unwrap().screen;
-    // let screen = unsafe { XScreenOfDisplay(display, screen_num) };
-    // if screen.is_null() {
-    //     return None;
-    // }
+    let screen_num = choose_monitor().get_xlib().unwrap().screen;
+    platform::display_server::xlib::with_display(|display| {
+        let screen = unsafe { XScreenOfDisplay(display, screen_num) };
+        if screen.is_null() {
+            return None;
+        }
 
-    // let width = unsafe { XWidthOfScreen(screen) };
-    // let height = unsafe { XHeightOfScreen(screen) };
-    // if width <= 0 || height <= 0 {
-    //     return None;
-    // }
+        let width = unsafe { XWidthOfScreen(screen) };
+        let height = unsafe { XHeightOfScreen(screen) };
+        if width <= 0 || height <= 0 {
+            return None;
+        }
 
-    // Some((width as _, height as _))
+        Some((width as _, height as _))
+    })
+    .flatten()
 }
 
 #[cfg(xlib)]
@@ -1448,43 +2595,96 @@ fn get_monitor_dimensions() -> Option<(u32, u32)> {
     clippy::cast_possible_truncation
 )]
 fn monitor_info(monitor: MonitorHandle) -> Option<MonitorInfo> {
-    let display = monitor
-        .get_xlib()
-        .unwrap()
-        .display
-        .cast::<x11::xlib::Display>();
-    let screen_num = monitor.get_xlib().unwrap().screen;
-    let screen = unsafe { XScreenOfDisplay(display, screen_num) };
-    if screen.is_null() {
-        return None;
-    }
+    #[cfg(debug_assertions)]
+    let open_displays = platform::display_server::xlib::open_display_count();
+
+    let handle = monitor.get_xlib().unwrap();
+    let screen_num = handle.screen;
+    let info = platform::display_server::xlib::with_display(|display| {
+        // Handles from `available_monitors` and friends point at the shared
+        // connection. Any other display has already been closed.
+        debug_assert_eq!(
+            handle.display,
+            display.cast(),
+            "monitor_info was passed a handle to a closed display connection"
+        );
 
-    let root_window = unsafe { XRootWindow(display, screen_num) };
-    let white_pixel = unsafe { XWhitePixel(display, screen_num) };
-    let window = unsafe {
-        XCreateSimpleWindow(
-            display,
-            root_window,
-            0,
-            0,
-            1,
-            1,
-            1,
-            white_pixel,
-            white_pixel,
-        )
-    };
-    let screen_info = unsafe { XRRGetScreenInfo(display, window) };
-    if screen_info.is_null() {
-        return None;
-    }
+        let screen = unsafe { XScreenOfDisplay(display, screen_num) };
+        if screen.is_null() {
+            return None;
+        }
 
-    let width = unsafe { XWidthOfScreen(screen) };
-    let height = unsafe { XHeightOfScreen(screen) };
-    if width <= 0 || height <= 0 {
-        return None;
-    }
+        let width = unsafe { XWidthOfScreen(screen) };
+        let height = unsafe { XHeightOfScreen(screen) };
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+
+        let root_window = unsafe { XRootWindow(display, screen_num) };
+        let white_pixel = unsafe { XWhitePixel(display, screen_num) };
+        let window = unsafe {
+            XCreateSimpleWindow(
+                display,
+                root_window,
+                0,
+                0,
+                1,
+                1,
+                1,
+                white_pixel,
+                white_pixel,
+            )
+        };
+
+        // Everything past this point has to go through the cleanup below,
+        // so the fallible parts are split out into a separate function
+        // instead of returning early here.
+        let screen_info = unsafe { XRRGetScreenInfo(display, window) };
+        let info = if screen_info.is_null() {
+            None
+        } else {
+            let info = screen_config_monitor_info(
+                display,
+                screen_num,
+                screen_info,
+                (width as _, height as _),
+            );
+            unsafe {
+                XRRFreeScreenConfigInfo(screen_info);
+            }
+            info
+        };
+
+        unsafe {
+            XDestroyWindow(display, window);
+        }
+        info
+    })
+    .flatten();
+
+    #[cfg(debug_assertions)]
+    assert_eq!(
+        platform::display_server::xlib::open_display_count(),
+        open_displays,
+        "monitor_info leaked a display connection"
+    );
+
+    info
+}
 
+#[cfg(xlib)]
+#[allow(
+    clippy::undocumented_unsafe_blocks,
+    clippy::cast_sign_loss,
+    clippy::cast_possible_wrap,
+    clippy::cast_possible_truncation
+)]
+fn screen_config_monitor_info(
+    display: *mut x11::xlib::Display,
+    screen_num: i32,
+    screen_info: *mut x11::xrandr::XRRScreenConfiguration,
+    (width, height): (u32, u32),
+) -> Option<MonitorInfo> {
     let refresh = unsafe { XRRConfigCurrentRate(screen_info) };
     if refresh <= 0 {
         return None;
@@ -1526,17 +2726,10 @@ fn monitor_info(monitor: MonitorHandle) -> Option<MonitorInfo> {
         }
     }
 
-    unsafe {
-        XRRFreeScreenConfigInfo(screen_info);
-    }
-    unsafe {
-        XDestroyWindow(display, window);
-    }
-
     Some(MonitorInfo {
         name: String::new(), // TODO - get name
-        width: width as _,
-        height: height as _,
+        width,
+        height,
         refresh: f32::from(refresh),
         video_modes,
     })
@@ -1544,75 +2737,126 @@ fn monitor_info(monitor: MonitorHandle) -> Option<MonitorInfo> {
 
 #[cfg(xlib)]
 #[allow(clippy::undocumented_unsafe_blocks)]
-pub fn create_window_2(wnd_parms: &mut gfx::WindowParms) -> Result<(), ()> {
+pub fn create_window_2(
+    wnd_parms: &mut gfx::WindowParms,
+) -> Result<(), RenderError> {
     assert!(wnd_parms.window_handle.is_none());
 
-    let display =
-        unsafe { XOpenDisplay(platform::display_server::xlib::display_name()) };
-    let screen = unsafe { XDefaultScreen(display) };
-    let root_window = unsafe { XRootWindow(display, screen) };
-    let white_pixel = unsafe { XWhitePixel(display, screen) };
-    let window = unsafe {
-        XCreateSimpleWindow(
-            display,
-            root_window,
-            i32::from(wnd_parms.x),
-            i32::from(wnd_parms.y),
-            wnd_parms.display_width,
-            wnd_parms.display_height,
-            0,
-            white_pixel,
-            white_pixel,
-        )
-    };
-
-    if window == 0 {
-        com::println!(console::Channel::GFX, "Couldn't create a window.");
-        wnd_parms.window_handle = None;
-        Err(())
-    } else {
-        let window_name =
-            CString::new(com::get_official_build_name_r()).unwrap();
-        unsafe {
-            XStoreName(display, window, window_name.as_ptr());
-        }
+    // The window has to be created on the shared connection, otherwise its
+    // events would never reach `sys::next_main_window_event`
+    platform::display_server::xlib::with_display(|display| {
+        let screen = unsafe { XDefaultScreen(display) };
+        let root_window = unsafe { XRootWindow(display, screen) };
+        let white_pixel = unsafe { XWhitePixel(display, screen) };
+        let window = unsafe {
+            XCreateSimpleWindow(
+                display,
+                root_window,
+                i32::from(wnd_parms.x),
+                i32::from(wnd_parms.y),
+                wnd_parms.display_width,
+                wnd_parms.display_height,
+                0,
+                white_pixel,
+                white_pixel,
+            )
+        };
 
-        let mut handle = XlibWindowHandle::empty();
-        handle.window = window as _;
+        if window == 0 {
+            com::println!(console::Channel::GFX, "Couldn't create a window.");
+            wnd_parms.window_handle = None;
+            Err(RenderError::WindowCreationFailed)
+        } else {
+            let title = com::get_window_title();
+            let window_name = CString::new(title.clone()).unwrap();
+            unsafe {
+                XStoreName(display, window, window_name.as_ptr());
+            }
+            platform::display_server::xlib::set_net_wm_name(
+                display, window, &title,
+            );
+            platform::display_server::xlib::set_window_identity(
+                display, window,
+            );
 
-        let visual = unsafe { XDefaultVisual(display, screen) };
-        handle.visual_id = unsafe { XVisualIDFromVisual(visual) };
-        wnd_parms.window_handle =
-            Some(WindowHandle(RawWindowHandle::Xlib(handle)));
+            let mut handle = XlibWindowHandle::empty();
+            handle.window = window as _;
+
+            let visual = unsafe { XDefaultVisual(display, screen) };
+            handle.visual_id = unsafe { XVisualIDFromVisual(visual) };
+            wnd_parms.window_handle =
+                Some(WindowHandle(RawWindowHandle::Xlib(handle)));
+
+            if wnd_parms.fullscreen == false {
+                unsafe {
+                    XSetInputFocus(
+                        display,
+                        window,
+                        RevertToParent,
+                        CurrentTime,
+                    );
+                }
+            }
 
-        if wnd_parms.fullscreen == false {
+            let mut wm_delete_window = WM_DELETE_WINDOW.load_relaxed();
             unsafe {
-                XSetInputFocus(display, window, RevertToParent, CurrentTime);
+                XSetWMProtocols(
+                    display,
+                    window,
+                    addr_of_mut!(wm_delete_window),
+                    1,
+                );
             }
-        }
 
-        let mut wm_delete_window = WM_DELETE_WINDOW.load_relaxed();
-        unsafe {
-            XSetWMProtocols(display, window, addr_of_mut!(wm_delete_window), 1);
+            com::println!(
+                console::Channel::GFX,
+                "Game window successfully created."
+            );
+            Ok(())
         }
-
-        com::println!(
-            console::Channel::GFX,
-            "Game window successfully created."
-        );
-        Ok(())
-    }
+    })
+    .unwrap_or_else(|| {
+        com::println!(console::Channel::GFX, "Couldn't create a window.");
+        Err(RenderError::WindowCreationFailed)
+    })
 }
 
 #[cfg(wgpu)]
-fn enum_display_modes() {
+fn enum_display_modes_wgpu() -> Result<(), RenderError> {
     let info = monitor_info(
         primary_monitor().unwrap_or(*available_monitors().get(0).unwrap()),
     )
     .unwrap();
 
-    let valid_modes = info
-        .video_modes
+    register_video_modes(&info.video_modes, Some((info.width, info.height)))
+}
+
+/// Picks the default for `r_mode` out of `modes`: the monitor's `native`
+/// resolution if it's one of them, otherwise `fallback`.
+///
+/// This is only the default, so a resolution the user chose (and which got
+/// archived to their config) still wins.
+fn default_mode(
+    modes: &[String],
+    native: Option<(u32, u32)>,
+    fallback: &str,
+) -> String {
+    native
+        .map(|(width, height)| format!("{width}x{height}"))
+        .filter(|native| modes.contains(native))
+        .unwrap_or_else(|| fallback.to_owned())
+}
+
+/// Registers `r_mode` and `r_displayRefresh` with the resolutions and
+/// refresh rates in `video_modes`, and keeps the modes that meet the minimum
+/// resolution for [`RENDER_GLOBALS`]. `r_mode` defaults to the `native`
+/// resolution if there is one.
+#[cfg(any(wgpu, vulkan))]
+fn register_video_modes(
+    video_modes: &[VideoMode],
+    native: Option<(u32, u32)>,
+) -> Result<(), RenderError> {
+    let valid_modes = video_modes
         .iter()
         .filter(|m| {
             m.width >= MIN_HORIZONTAL_RESOLUTION as _
@@ -1625,50 +2869,61 @@ fn enum_display_modes() {
         .map(|m| format!("{}x{}", m.width, m.height))
         .collect::<Vec<_>>();
     if modes.is_empty() {
-        fatal_init_error!(
-            "No valid resolutions of {} x {} or above found",
-            MIN_HORIZONTAL_RESOLUTION,
-            MIN_VERTICAL_RESOLUTION
-        );
+        return Err(RenderError::NoValidVideoModes);
     }
 
     dvar::register_enumeration(
         "r_mode",
-        modes.get(0).unwrap().clone(),
-        Some(modes),
+        default_mode(&modes, native, modes.get(0).unwrap()),
+        Some(modes.clone()),
         dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::LATCHED,
         "Renderer resolution mode".into(),
     )
     .unwrap();
+    register_mode_live(modes);
 
-    let refreshes = info
-        .video_modes
-        .iter()
-        .map(|m| format!("{} Hz", m.refresh))
-        .collect::<Vec<_>>();
+    let refreshes = refresh_rate_names(video_modes, refresh_rounding());
     dvar::register_enumeration(
         "r_displayRefresh",
         refreshes.get(0).unwrap().clone(),
         Some(refreshes),
-        dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::LATCHED,
+        dvar::DvarFlags::ARCHIVE
+            | dvar::DvarFlags::LATCHED
+            | dvar::DvarFlags::CHANGEABLE_RESET,
         "Refresh rate".into(),
     )
     .unwrap();
+    // The previous refresh rate may not be valid for the new modes, in
+    // which case it needs to go back to the default
+    dvar::reset_changeable();
 
     RENDER_GLOBALS.write().unwrap().video_modes =
         valid_modes.iter().copied().cloned().collect();
+
+    Ok(())
+}
+
+fn pre_create_window() -> Result<(), RenderError> {
+    com::report_init_progress("Detecting graphics hardware", 0.5);
+    match backend() {
+        #[cfg(d3d9)]
+        RenderBackend::D3d9 => pre_create_window_d3d9(),
+        #[cfg(vulkan)]
+        RenderBackend::Vulkan => pre_create_window_vulkan(),
+        #[cfg(wgpu)]
+        RenderBackend::Wgpu => pre_create_window_wgpu(),
+    }
 }
 
 #[cfg(wgpu)]
-#[allow(clippy::unnecessary_wraps)]
-fn pre_create_window() -> Result<(), ()> {
+fn pre_create_window_wgpu() -> Result<(), RenderError> {
     com::println!(console::Channel::GFX, "Getting Device interface...");
     let instance = platform::render::wgpu::Instance::new();
     RENDER_GLOBALS.write().unwrap().instance = Some(instance);
 
-    let adapter = choose_adapter();
-    enum_display_modes();
-    RENDER_GLOBALS.write().unwrap().adapter = adapter;
+    let adapter = choose_adapter_wgpu().ok_or(RenderError::AdapterNotFound)?;
+    enum_display_modes_wgpu()?;
+    RENDER_GLOBALS.write().unwrap().adapter = Some(adapter);
 
     Ok(())
 }
@@ -1704,9 +2959,12 @@ fn get_direct3d_caps(adapter: Adapter) -> D3DCAPS9 {
 }
 
 #[cfg(d3d9)]
-fn respond_to_missing_caps(response: DxCapsResponse, message: &'static str) {
+fn respond_to_missing_caps(
+    response: DxCapsResponse,
+    message: &'static str,
+) -> Result<(), RenderError> {
     if response == DxCapsResponse::Warn {
-        com::warnln!(
+        com::warnln_once!(
             console::Channel::GFX,
             "Video card or driver {}.",
             message
@@ -1720,142 +2978,140 @@ fn respond_to_missing_caps(response: DxCapsResponse, message: &'static str) {
     }
 
     match response {
-        DxCapsResponse::Quit => com::errorln!(
-            com::ErrorParm::FATAL,
+        DxCapsResponse::Quit => Err(RenderError::UnsupportedCaps(format!(
             "Video card or driver {}.",
             message
-        ),
-        DxCapsResponse::ForbidSm3 => com::errorln!(
-            com::ErrorParm::FATAL,
-            "Shader model 3.0 not available."
-        ),
-        _ => {}
+        ))),
+        DxCapsResponse::ForbidSm3 => Err(RenderError::UnsupportedCaps(
+            "Shader model 3.0 not available.".into(),
+        )),
+        _ => Ok(()),
     }
 }
 
 #[cfg(d3d9)]
 const S_CAPS_CHECK_BITS: [DxCapsCheckBits; 32] = [
     DxCapsCheckBits {
-        offset: 12,
+        field: |caps| caps.Caps2,
         set_bits: 0x00000000,
         clear_bits: 0x20000000,
         response: DxCapsResponse::Quit,
         message: "doesn't support dynamic textures",
     },
     DxCapsCheckBits {
-        offset: 12,
+        field: |caps| caps.Caps2,
         set_bits: 0x00000000,
         clear_bits: 0x00020000,
         response: DxCapsResponse::Warn,
         message: "doesn't support fullscreen gamma",
     },
     DxCapsCheckBits {
-        offset: 16,
+        field: |caps| caps.Caps3,
         set_bits: 0x00000000,
         clear_bits: 0x00000020,
         response: DxCapsResponse::Quit,
         message: "doesn't support alpha blending",
     },
     DxCapsCheckBits {
-        offset: 16,
+        field: |caps| caps.Caps3,
         set_bits: 0x00000000,
         clear_bits: 0x00000100,
         response: DxCapsResponse::Warn,
         message: "doesn't accelerate dynamic textures",
     },
     DxCapsCheckBits {
-        offset: 20,
+        field: |caps| caps.PresentationIntervals,
         set_bits: 0x00000000,
         clear_bits: 0x80000000,
         response: DxCapsResponse::Warn,
         message: "doesn't support immediate frame buffer swapping",
     },
     DxCapsCheckBits {
-        offset: 20,
+        field: |caps| caps.PresentationIntervals,
         set_bits: 0x00000000,
         clear_bits: 0x00000001,
         response: DxCapsResponse::Warn,
         message: "doesn't support vertical sync",
     },
     DxCapsCheckBits {
-        offset: 28,
+        field: |caps| caps.DevCaps,
         set_bits: 0x00000000,
         clear_bits: 0x00008000,
         response: DxCapsResponse::Quit,
         message: "is not at least DirectX 7 compliant",
     },
     DxCapsCheckBits {
-        offset: 28,
+        field: |caps| caps.DevCaps,
         set_bits: 0x00000000,
         clear_bits: 0x00010400,
         response: DxCapsResponse::Warn,
         message: "doesn't accelerate transform and lighting",
     },
     DxCapsCheckBits {
-        offset: 28,
+        field: |caps| caps.DevCaps,
         set_bits: 0x00000000,
         clear_bits: 0x00080000,
         response: DxCapsResponse::Warn,
         message: "doesn't accelerate rasterization",
     },
     DxCapsCheckBits {
-        offset: 32,
+        field: |caps| caps.PrimitiveMiscCaps,
         set_bits: 0x00000000,
         clear_bits: 0x00000002,
         response: DxCapsResponse::Quit,
         message: "can't disable depth buffer writes",
     },
     DxCapsCheckBits {
-        offset: 32,
+        field: |caps| caps.PrimitiveMiscCaps,
         set_bits: 0x00000000,
         clear_bits: 0x00000080,
         response: DxCapsResponse::Quit,
         message: "can't disable individual color channel writes",
     },
     DxCapsCheckBits {
-        offset: 32,
+        field: |caps| caps.PrimitiveMiscCaps,
         set_bits: 0x00000000,
         clear_bits: 0x00000800,
         response: DxCapsResponse::Quit,
         message: "doesn't support frame buffer blending ops besides add",
     },
     DxCapsCheckBits {
-        offset: 32,
+        field: |caps| caps.PrimitiveMiscCaps,
         set_bits: 0x00000000,
         clear_bits: 0x00020000,
         response: DxCapsResponse::Quit,
         message: "doesn't support separate alpha blend, glow will be disabled",
     },
     DxCapsCheckBits {
-        offset: 32,
+        field: |caps| caps.PrimitiveMiscCaps,
         set_bits: 0x00000000,
         clear_bits: 0x00000070,
         response: DxCapsResponse::Quit,
         message: "doesn't support all face culling modes",
     },
     DxCapsCheckBits {
-        offset: 36,
+        field: |caps| caps.RasterCaps,
         set_bits: 0x00000000,
         clear_bits: 0x02000000,
         response: DxCapsResponse::Info,
         message: "doesn't support high-quality polygon offset",
     },
     DxCapsCheckBits {
-        offset: 40,
+        field: |caps| caps.ZCmpCaps,
         set_bits: 0x00000000,
         clear_bits: 0x0000008D,
         response: DxCapsResponse::Quit,
         message: "doesn't support the required depth comparison modes",
     },
     DxCapsCheckBits {
-        offset: 44,
+        field: |caps| caps.SrcBlendCaps,
         set_bits: 0x00000000,
         clear_bits: 0x000003FF,
         response: DxCapsResponse::Quit,
         message: "doesn't support the required frame buffer source blend modes",
     },
     DxCapsCheckBits {
-        offset: 48,
+        field: |caps| caps.DestBlendCaps,
         set_bits: 0x00000000,
         clear_bits: 0x000000D2,
         response: DxCapsResponse::Quit,
@@ -1863,91 +3119,91 @@ const S_CAPS_CHECK_BITS: [DxCapsCheckBits; 32] = [
                   modes",
     },
     DxCapsCheckBits {
-        offset: 60,
+        field: |caps| caps.TextureCaps,
         set_bits: 0x00000000,
         clear_bits: 0x00000004,
         response: DxCapsResponse::Quit,
         message: "doesn't support alpha in texture",
     },
     DxCapsCheckBits {
-        offset: 60,
+        field: |caps| caps.TextureCaps,
         set_bits: 0x00000000,
         clear_bits: 0x00000800,
         response: DxCapsResponse::Quit,
         message: "doesn't support cubemap textures",
     },
     DxCapsCheckBits {
-        offset: 60,
+        field: |caps| caps.TextureCaps,
         set_bits: 0x00000000,
         clear_bits: 0x00004000,
         response: DxCapsResponse::Quit,
         message: "doesn't support mipmapped textures",
     },
     DxCapsCheckBits {
-        offset: 60,
+        field: |caps| caps.TextureCaps,
         set_bits: 0x00000002,
         clear_bits: 0x00000100,
         response: DxCapsResponse::Quit,
         message: "doesn't support restricted use of non-power-of-2 textures",
     },
     DxCapsCheckBits {
-        offset: 60,
+        field: |caps| caps.TextureCaps,
         set_bits: 0x00000000,
         clear_bits: 0x00000001,
         response: DxCapsResponse::Warn,
         message: "doesn't support perspective correct texturing",
     },
     DxCapsCheckBits {
-        offset: 60,
+        field: |caps| caps.TextureCaps,
         set_bits: 0x00000020,
         clear_bits: 0x00000000,
         response: DxCapsResponse::Quit,
         message: "doesn't support non-square textures",
     },
     DxCapsCheckBits {
-        offset: 60,
+        field: |caps| caps.TextureCaps,
         set_bits: 0x00000000,
         clear_bits: 0x03030300,
         response: DxCapsResponse::Quit,
         message: "doesn't support the required texture filtering modes",
     },
     DxCapsCheckBits {
-        offset: 64,
+        field: |caps| caps.TextureFilterCaps,
         set_bits: 0x00000000,
         clear_bits: 0x03000300,
         response: DxCapsResponse::Quit,
         message: "doesn't support the required cubemap texture filtering modes",
     },
     DxCapsCheckBits {
-        offset: 72,
+        field: |caps| caps.VolumeTextureFilterCaps,
         set_bits: 0x00000000,
         clear_bits: 0x00000004,
         response: DxCapsResponse::Quit,
         message: "doesn't support texture clamping",
     },
     DxCapsCheckBits {
-        offset: 72,
+        field: |caps| caps.VolumeTextureFilterCaps,
         set_bits: 0x00000000,
         clear_bits: 0x00000001,
         response: DxCapsResponse::Quit,
         message: "doesn't support texture wrapping",
     },
     DxCapsCheckBits {
-        offset: 136,
+        field: |caps| caps.StencilCaps,
         set_bits: 0x00000000,
         clear_bits: 0x000001FF,
         response: DxCapsResponse::Info,
         message: "doesn't support the required stencil operations",
     },
     DxCapsCheckBits {
-        offset: 212,
+        field: |caps| caps.DevCaps2,
         set_bits: 0x00000000,
         clear_bits: 0x00000001,
         response: DxCapsResponse::Quit,
         message: "doesn't support vertex stream offsets",
     },
     DxCapsCheckBits {
-        offset: 244,
+        field: |caps| caps.StretchRectFilterCaps,
         set_bits: 0x00000000,
         clear_bits: 0x00000200,
         response: DxCapsResponse::Warn,
@@ -1955,7 +3211,7 @@ const S_CAPS_CHECK_BITS: [DxCapsCheckBits; 32] = [
                   the frame buffer",
     },
     DxCapsCheckBits {
-        offset: 236,
+        field: |caps| caps.DeclTypes,
         set_bits: 0x00000000,
         clear_bits: 0x00000001,
         response: DxCapsResponse::Quit,
@@ -1966,28 +3222,28 @@ const S_CAPS_CHECK_BITS: [DxCapsCheckBits; 32] = [
 #[cfg(d3d9)]
 const S_CAPS_CHECK_INT: [DxCapsCheckInteger; 10] = [
     DxCapsCheckInteger {
-        offset: 88,
+        field: |caps| caps.MaxTextureWidth,
         min: 0x00000800,
         max: 0xFFFFFFFF,
         response: DxCapsResponse::Quit,
         message: "doesn't support large enough 2D textures",
     },
     DxCapsCheckInteger {
-        offset: 92,
+        field: |caps| caps.MaxTextureHeight,
         min: 0x00000800,
         max: 0xFFFFFFFF,
         response: DxCapsResponse::Quit,
         message: "doesn't support large enough 2D textures",
     },
     DxCapsCheckInteger {
-        offset: 96,
+        field: |caps| caps.MaxVolumeExtent,
         min: 0x00000100,
         max: 0xFFFFFFFF,
         response: DxCapsResponse::Quit,
         message: "doesn't support large enough 3D textures",
     },
     DxCapsCheckInteger {
-        offset: 148,
+        field: |caps| caps.MaxTextureBlendStages,
         min: 0x00000100,
         max: 0xFFFFFFFF,
         response: DxCapsResponse::Quit,
@@ -1995,42 +3251,42 @@ const S_CAPS_CHECK_INT: [DxCapsCheckInteger; 10] = [
                   9 code path",
     },
     DxCapsCheckInteger {
-        offset: 152,
+        field: |caps| caps.MaxSimultaneousTextures,
         min: 0x00000008,
         max: 0xFFFFFFFF,
         response: DxCapsResponse::Quit,
         message: "doesn't support enough textures for the DirectX 9 code path",
     },
     DxCapsCheckInteger {
-        offset: 188,
+        field: |caps| caps.MaxStreams,
         min: 0x00000001,
         max: 0xFFFFFFFF,
         response: DxCapsResponse::Quit,
         message: "is not a DirectX 9 driver",
     },
     DxCapsCheckInteger {
-        offset: 196,
+        field: |caps| caps.VertexShaderVersion,
         min: 0xFFFE0200,
         max: 0xFFFFFFFF,
         response: DxCapsResponse::Quit,
         message: "doesn't support vertex shader 2.0 or better",
     },
     DxCapsCheckInteger {
-        offset: 204,
+        field: |caps| caps.PixelShaderVersion,
         min: 0xFFFE0200,
         max: 0xFFFFFFFF,
         response: DxCapsResponse::Quit,
         message: "doesn't support pixel shader 2.0 or better",
     },
     DxCapsCheckInteger {
-        offset: 196,
+        field: |caps| caps.VertexShaderVersion,
         min: 0xFFFE0300,
         max: 0xFFFFFFFF,
         response: DxCapsResponse::ForbidSm3,
         message: "doesn't support vertex shader 3.0 or better",
     },
     DxCapsCheckInteger {
-        offset: 204,
+        field: |caps| caps.PixelShaderVersion,
         min: 0xFFFE0300,
         max: 0xFFFFFFFF,
         response: DxCapsResponse::Quit,
@@ -2038,37 +3294,60 @@ const S_CAPS_CHECK_INT: [DxCapsCheckInteger; 10] = [
     },
 ];
 
+// The caps checks were originally reads at hardcoded offsets into
+// D3DCAPS9. Make sure the fields they read now are the ones that lived at
+// those offsets.
+#[cfg(d3d9)]
+const _: () = {
+    assert!(core::mem::offset_of!(D3DCAPS9, Caps2) == 12);
+    assert!(core::mem::offset_of!(D3DCAPS9, Caps3) == 16);
+    assert!(core::mem::offset_of!(D3DCAPS9, PresentationIntervals) == 20);
+    assert!(core::mem::offset_of!(D3DCAPS9, DevCaps) == 28);
+    assert!(core::mem::offset_of!(D3DCAPS9, PrimitiveMiscCaps) == 32);
+    assert!(core::mem::offset_of!(D3DCAPS9, RasterCaps) == 36);
+    assert!(core::mem::offset_of!(D3DCAPS9, ZCmpCaps) == 40);
+    assert!(core::mem::offset_of!(D3DCAPS9, SrcBlendCaps) == 44);
+    assert!(core::mem::offset_of!(D3DCAPS9, DestBlendCaps) == 48);
+    assert!(core::mem::offset_of!(D3DCAPS9, TextureCaps) == 60);
+    assert!(core::mem::offset_of!(D3DCAPS9, TextureFilterCaps) == 64);
+    assert!(core::mem::offset_of!(D3DCAPS9, VolumeTextureFilterCaps) == 72);
+    assert!(core::mem::offset_of!(D3DCAPS9, MaxTextureWidth) == 88);
+    assert!(core::mem::offset_of!(D3DCAPS9, MaxTextureHeight) == 92);
+    assert!(core::mem::offset_of!(D3DCAPS9, MaxVolumeExtent) == 96);
+    assert!(core::mem::offset_of!(D3DCAPS9, StencilCaps) == 136);
+    assert!(core::mem::offset_of!(D3DCAPS9, MaxTextureBlendStages) == 148);
+    assert!(core::mem::offset_of!(D3DCAPS9, MaxSimultaneousTextures) == 152);
+    assert!(core::mem::offset_of!(D3DCAPS9, MaxStreams) == 188);
+    assert!(core::mem::offset_of!(D3DCAPS9, VertexShaderVersion) == 196);
+    assert!(core::mem::offset_of!(D3DCAPS9, PixelShaderVersion) == 204);
+    assert!(core::mem::offset_of!(D3DCAPS9, DevCaps2) == 212);
+    assert!(core::mem::offset_of!(D3DCAPS9, DeclTypes) == 236);
+    assert!(core::mem::offset_of!(D3DCAPS9, StretchRectFilterCaps) == 244);
+};
+
 #[cfg(d3d9)]
-fn check_dx_caps(caps: &D3DCAPS9) {
+fn check_dx_caps(caps: &D3DCAPS9) -> Result<(), RenderError> {
     for bit in S_CAPS_CHECK_BITS {
-        let p = unsafe {
-            *addr_of!(*caps)
-                .cast::<u8>()
-                .offset(bit.offset)
-                .cast::<u32>()
-        };
+        let p = (bit.field)(caps);
         if ((bit.clear_bits == 0) || ((!p & bit.clear_bits) != 0))
             && (bit.set_bits == 0 || ((p & bit.set_bits) != 0))
         {
-            respond_to_missing_caps(bit.response, bit.message);
+            respond_to_missing_caps(bit.response, bit.message)?;
         }
     }
 
     for int in S_CAPS_CHECK_INT {
-        let p = unsafe {
-            *addr_of!(*caps)
-                .cast::<u8>()
-                .offset(int.offset)
-                .cast::<u32>()
-        };
+        let p = (int.field)(caps);
         if p < int.min || (int.max <= p && p != int.max) {
-            respond_to_missing_caps(int.response, int.message)
+            respond_to_missing_caps(int.response, int.message)?;
         }
     }
+
+    Ok(())
 }
 
 #[cfg(d3d9)]
-fn pick_renderer(caps: &D3DCAPS9) {
+fn pick_renderer(caps: &D3DCAPS9) -> Result<(), RenderError> {
     com::println!(
         console::Channel::GFX,
         "Pixel shader version is {}.{}",
@@ -2081,7 +3360,7 @@ fn pick_renderer(caps: &D3DCAPS9) {
         (caps.VertexShaderVersion & 0xFFFF) >> 8,
         caps.VertexShaderVersion & 0xFF
     );
-    check_dx_caps(caps);
+    check_dx_caps(caps)
 }
 
 #[cfg(d3d9)]
@@ -2172,7 +3451,7 @@ fn set_shadowmap_formats_dx(adapter: Adapter) {
 
     if !valid {
         dvar::set_bool("sm_enable", false).unwrap();
-        dvar::set_bool("ui_showShadowMapOptions", false).unwrap();
+        dvar::set_bool_internal("ui_showShadowMapOptions", false).unwrap();
         let mut gm = platform::render::d3d9::gfx_metrics_mut();
         gm.shadowmap_format_primary = D3DFMT_R32F;
         gm.shadowmap_format_secondary = D3DFMT_D24X8;
@@ -2180,7 +3459,7 @@ fn set_shadowmap_formats_dx(adapter: Adapter) {
         gm.has_hardware_shadowmap = false;
         gm.shadowmap_sampler_state = Some(ShadowmapSamplerState::A);
     } else {
-        dvar::set_bool("ui_showShadowMapOptions", true).unwrap();
+        dvar::set_bool_internal("ui_showShadowMapOptions", true).unwrap();
         let mut gm = platform::render::d3d9::gfx_metrics_mut();
         gm.shadowmap_format_primary = depth_stencil_format;
         gm.shadowmap_format_secondary = render_target_format;
@@ -2191,9 +3470,9 @@ fn set_shadowmap_formats_dx(adapter: Adapter) {
 }
 
 #[cfg(d3d9)]
-fn store_direct3d_caps(adapter: Adapter) {
+fn store_direct3d_caps(adapter: Adapter) -> Result<(), RenderError> {
     let caps = get_direct3d_caps(adapter);
-    pick_renderer(&caps);
+    pick_renderer(&caps)?;
     let max_texture_dimension =
         if (caps.MaxTextureHeight as i32) < caps.MaxTextureHeight as i32 {
             caps.MaxTextureWidth
@@ -2223,10 +3502,12 @@ fn store_direct3d_caps(adapter: Adapter) {
         gm.has_transparency_msaa = check_transparency_msaa(adapter);
         set_shadowmap_formats_dx(adapter);
     }
+
+    Ok(())
 }
 
 #[cfg(d3d9)]
-fn enum_display_modes(adapter: Adapter) {
+fn enum_display_modes_d3d9(adapter: Adapter) -> Result<(), RenderError> {
     let mut dx = platform::render::d3d9::dx_mut();
     let display_mode_count =
         unsafe { dx.d3d9.as_ref().unwrap().GetAdapterCount() };
@@ -2283,11 +3564,7 @@ fn enum_display_modes(adapter: Adapter) {
     }
 
     if dx.display_modes.is_empty() {
-        fatal_init_error!(
-            "No valid resolutions of {} x {} or above found",
-            MIN_HORIZONTAL_RESOLUTION,
-            MIN_VERTICAL_RESOLUTION
-        );
+        return Err(RenderError::NoValidVideoModes);
     }
 
     let mut resolutions = dx
@@ -2322,14 +3599,26 @@ fn enum_display_modes(adapter: Adapter) {
         dx.refresh_rate_name_table[i] = refresh_strings[i].clone();
     }
 
+    // The desktop's current mode is the best guess at the native one
+    let mut desktop_mode = D3DDISPLAYMODE::default();
+    let native = unsafe {
+        dx.d3d9.as_ref().unwrap().GetAdapterDisplayMode(
+            adapter.as_d3d9(),
+            addr_of_mut!(desktop_mode),
+        )
+    }
+    .ok()
+    .map(|()| (desktop_mode.Width, desktop_mode.Height));
+
     dvar::register_enumeration(
         "r_mode",
-        mode_strings.last().unwrap().clone(),
-        Some(mode_strings),
+        default_mode(&mode_strings, native, mode_strings.last().unwrap()),
+        Some(mode_strings.clone()),
         dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::LATCHED,
         Some("Direct X resolution mode"),
     )
     .unwrap();
+    register_mode_live(mode_strings);
     dvar::register_enumeration(
         "r_displayRefresh",
         refresh_strings.last().unwrap().clone(),
@@ -2340,10 +3629,15 @@ fn enum_display_modes(adapter: Adapter) {
         Some("Refresh rate"),
     )
     .unwrap();
+    // The previous refresh rate may not be valid for the new modes, in
+    // which case it needs to go back to the default
+    dvar::reset_changeable();
+
+    Ok(())
 }
 
 #[cfg(d3d9)]
-fn pre_create_window() -> Result<(), ()> {
+fn pre_create_window_d3d9() -> Result<(), RenderError> {
     let mut dx = platform::render::d3d9::dx_mut();
     assert!(
         dx.d3d9.is_none(),
@@ -2353,15 +3647,21 @@ fn pre_create_window() -> Result<(), ()> {
     com::println!(console::Channel::GFX, "Getting Direct3D 9 interface...");
     let Some(d3d9) = (unsafe { Direct3DCreate9(D3D_SDK_VERSION) }) else {
         com::println!(console::Channel::GFX, "Direct3D 9 failed to initialize");
-        return Err(());
+        return Err(RenderError::DeviceCreationFailed(
+            "Direct3DCreate9 failed".into(),
+        ));
     };
 
     dx.d3d9 = Some(d3d9);
 
-    dx.adapter = choose_adapter().unwrap_or_default();
+    dx.adapter = choose_adapter_d3d9().unwrap_or_default();
 
-    store_direct3d_caps(dx.adapter);
-    enum_display_modes(dx.adapter);
+    store_direct3d_caps(dx.adapter)?;
+    enum_display_modes_d3d9(dx.adapter)?;
+    pin_adapter(adapter_id_d3d9(
+        dx.d3d9.as_ref().unwrap(),
+        dx.adapter.as_d3d9(),
+    ));
 
     let mut identifier = D3DADAPTER_IDENTIFIER9::default();
     dx.vendor_id = identifier.VendorId;
@@ -2382,141 +3682,1162 @@ fn pre_create_window() -> Result<(), ()> {
 }
 
 #[cfg(vulkan)]
-fn pre_create_window() -> Result<(), ()> {
-    todo!()
+#[allow(clippy::undocumented_unsafe_blocks)]
+fn pre_create_window_vulkan() -> Result<(), RenderError> {
+    {
+        let mut vk = platform::render::vulkan::vk_mut();
+        assert!(
+            vk.instance.is_none(),
+            "Vulkan re-initialized before being shutdown"
+        );
+
+        com::println!(console::Channel::GFX, "Getting Vulkan instance...");
+        let entry = unsafe { ash::Entry::load() }
+            .map_err(|e| RenderError::DeviceCreationFailed(e.to_string()))?;
+        let instance = create_instance_vulkan(&entry)?;
+        let Some((physical_device, queue_family_index)) =
+            choose_adapter_vulkan(&entry, &instance)
+        else {
+            unsafe { instance.destroy_instance(None) };
+            return Err(RenderError::AdapterNotFound);
+        };
+
+        store_gpu_memory(gpu_memory_mb_vulkan(&instance, physical_device));
+        pin_adapter(adapter_id_vulkan(&instance, physical_device));
+        vk.entry = Some(entry);
+        vk.instance = Some(instance);
+        vk.physical_device = Some(physical_device);
+        vk.queue_family_index = Some(queue_family_index);
+    }
+
+    enum_display_modes_vulkan()
+}
+
+/// Instance extensions enabled if the driver has them: surfaces for the
+/// window, and `VK_KHR_display` for [`get_monitor_dimensions`] and
+/// [`enum_display_modes_vulkan`].
+#[cfg(vulkan)]
+fn wanted_instance_extensions_vulkan() -> Vec<&'static core::ffi::CStr> {
+    let mut extensions = vec![khr::Surface::name(), khr::Display::name()];
+    cfg_if! {
+        if #[cfg(windows)] {
+            extensions.push(khr::Win32Surface::name());
+        } else if #[cfg(macos)] {
+            extensions.push(ash::extensions::ext::MetalSurface::name());
+        } else {
+            extensions.push(khr::XlibSurface::name());
+            extensions.push(khr::WaylandSurface::name());
+        }
+    }
+    extensions
+}
+
+#[cfg(vulkan)]
+#[allow(clippy::undocumented_unsafe_blocks)]
+fn create_instance_vulkan(
+    entry: &ash::Entry,
+) -> Result<ash::Instance, RenderError> {
+    let available = entry
+        .enumerate_instance_extension_properties(None)
+        .unwrap_or_default();
+    let extensions = wanted_instance_extensions_vulkan()
+        .into_iter()
+        .filter(|&wanted| {
+            available.iter().any(|e| {
+                let name = unsafe {
+                    core::ffi::CStr::from_ptr(e.extension_name.as_ptr())
+                };
+                name == wanted
+            })
+        })
+        .map(core::ffi::CStr::as_ptr)
+        .collect::<Vec<_>>();
+
+    let app_info = ash::vk::ApplicationInfo {
+        api_version: ash::vk::make_api_version(0, 1, 3, 0),
+        ..Default::default()
+    };
+    let create_info = ash::vk::InstanceCreateInfo::builder()
+        .application_info(&app_info)
+        .enabled_extension_names(&extensions);
+    unsafe { entry.create_instance(&create_info, None) }
+        .map_err(|e| RenderError::DeviceCreationFailed(e.to_string()))
+}
+
+/// Picks the physical device to render with, along with the queue family
+/// to use on it. Discrete GPUs are preferred over anything else.
+#[cfg(vulkan)]
+#[allow(clippy::undocumented_unsafe_blocks)]
+fn choose_adapter_vulkan(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+) -> Option<(ash::vk::PhysicalDevice, u32)> {
+    let physical_devices =
+        unsafe { instance.enumerate_physical_devices() }.ok()?;
+    let candidates = physical_devices
+        .into_iter()
+        .filter_map(|pdev| {
+            Some((pdev, queue_family_vulkan(entry, instance, pdev)?))
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(pinned) = pinned_adapter_id() {
+        if let Some(&candidate) = candidates.iter().find(|&&(pdev, _)| {
+            adapter_id_vulkan(instance, pdev).as_deref()
+                == Some(pinned.as_str())
+        }) {
+            return Some(candidate);
+        }
+        warn_pinned_adapter_missing(&pinned);
+    }
+
+    candidates.into_iter().min_by_key(|&(pdev, _)| {
+        let props = unsafe { instance.get_physical_device_properties(pdev) };
+        props.device_type != ash::vk::PhysicalDeviceType::DISCRETE_GPU
+    })
+}
+
+/// Returns a string identifying `pdev` across runs, for `r_adapterLuid`:
+/// its LUID on Windows, or its PCI bus address elsewhere. Falls back to its
+/// UUID if the driver doesn't report either.
+#[cfg(vulkan)]
+#[allow(clippy::undocumented_unsafe_blocks)]
+fn adapter_id_vulkan(
+    instance: &ash::Instance,
+    pdev: ash::vk::PhysicalDevice,
+) -> Option<String> {
+    let has_pci_bus_info =
+        unsafe { instance.enumerate_device_extension_properties(pdev) }
+            .ok()?
+            .iter()
+            .any(|e| {
+                let name = unsafe {
+                    core::ffi::CStr::from_ptr(e.extension_name.as_ptr())
+                };
+                name == ash::vk::ExtPciBusInfoFn::name()
+            });
+
+    let mut ids = ash::vk::PhysicalDeviceIDProperties::default();
+    let mut pci_bus_info =
+        ash::vk::PhysicalDevicePCIBusInfoPropertiesEXT::default();
+    let mut properties =
+        ash::vk::PhysicalDeviceProperties2::builder().push_next(&mut ids);
+    // Structures from extensions the device doesn't have can't be chained
+    if has_pci_bus_info {
+        properties = properties.push_next(&mut pci_bus_info);
+    }
+    let mut properties = properties.build();
+    unsafe {
+        instance.get_physical_device_properties2(pdev, &mut properties);
+    }
+
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    };
+    if cfg!(windows) && ids.device_luid_valid == ash::vk::TRUE {
+        Some(format!("luid:{}", hex(&ids.device_luid)))
+    } else if has_pci_bus_info {
+        Some(format!(
+            "pci:{:04x}:{:02x}:{:02x}.{:x}",
+            pci_bus_info.pci_domain,
+            pci_bus_info.pci_bus,
+            pci_bus_info.pci_device,
+            pci_bus_info.pci_function
+        ))
+    } else {
+        Some(format!("uuid:{}", hex(&ids.device_uuid)))
+    }
+}
+
+/// Returns the index of a queue family on `pdev` that supports both
+/// graphics and presentation.
+#[cfg(vulkan)]
+#[allow(
+    clippy::undocumented_unsafe_blocks,
+    clippy::as_conversions,
+    clippy::cast_possible_truncation
+)]
+fn queue_family_vulkan(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    pdev: ash::vk::PhysicalDevice,
+) -> Option<u32> {
+    let families =
+        unsafe { instance.get_physical_device_queue_family_properties(pdev) };
+    families
+        .iter()
+        .enumerate()
+        .map(|(i, f)| (i as u32, f))
+        .find(|&(i, f)| {
+            f.queue_flags.contains(ash::vk::QueueFlags::GRAPHICS)
+                && can_present_vulkan(entry, instance, pdev, i)
+        })
+        .map(|(i, _)| i)
+}
+
+#[cfg(all(vulkan, windows))]
+#[allow(clippy::undocumented_unsafe_blocks)]
+fn can_present_vulkan(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    pdev: ash::vk::PhysicalDevice,
+    queue_family_index: u32,
+) -> bool {
+    unsafe {
+        khr::Win32Surface::new(entry, instance)
+            .get_physical_device_win32_presentation_support(
+                pdev,
+                queue_family_index,
+            )
+    }
+}
+
+// Only Win32 can check presentation support without a surface, which
+// doesn't exist yet at this point. Elsewhere, any graphics queue family is
+// assumed to be able to present, which holds for every desktop driver.
+#[cfg(all(vulkan, not(windows)))]
+const fn can_present_vulkan(
+    _entry: &ash::Entry,
+    _instance: &ash::Instance,
+    _pdev: ash::vk::PhysicalDevice,
+    _queue_family_index: u32,
+) -> bool {
+    true
+}
+
+/// Registers `r_mode` and `r_displayRefresh` from the primary display's
+/// modes.
+///
+/// Uses `VK_KHR_display` if it can, but most drivers only expose displays
+/// through it when no window system has claimed them, so the display
+/// server is asked instead if it comes up empty.
+#[cfg(vulkan)]
+fn enum_display_modes_vulkan() -> Result<(), RenderError> {
+    let (modes, native) = display_modes_vulkan()
+        .filter(|(m, _)| !m.is_empty())
+        .or_else(|| {
+            primary_monitor()
+                .or_else(|| available_monitors().get(0).copied())
+                .and_then(monitor_info)
+                .map(|i| (i.video_modes, (i.width, i.height)))
+        })
+        .map_or((Vec::new(), None), |(m, native)| (m, Some(native)));
+
+    register_video_modes(&modes, native)
+}
+
+#[cfg(vulkan)]
+#[allow(
+    clippy::undocumented_unsafe_blocks,
+    clippy::as_conversions,
+    clippy::cast_precision_loss
+)]
+// Returns the primary display's modes, along with its native resolution.
+fn display_modes_vulkan() -> Option<(Vec<VideoMode>, (u32, u32))> {
+    let vk = platform::render::vulkan::vk();
+    let display_ext =
+        khr::Display::new(vk.entry.as_ref()?, vk.instance.as_ref()?);
+    let pdev = vk.physical_device?;
+    let displays =
+        unsafe { display_ext.get_physical_device_display_properties(pdev) }
+            .ok()?;
+    let display = displays.get(0)?;
+    let modes = unsafe {
+        display_ext.get_display_mode_properties(pdev, display.display)
+    }
+    .ok()?;
+    let native = (
+        display.physical_resolution.width,
+        display.physical_resolution.height,
+    );
+
+    Some((
+        modes
+            .iter()
+            .map(|m| VideoMode {
+                width: m.parameters.visible_region.width,
+                height: m.parameters.visible_region.height,
+                // VK_KHR_display doesn't report depth, and the swapchain
+                // format is what decides it anyway
+                bit_depth: 32,
+                // Reported in millihertz
+                refresh: m.parameters.refresh_rate as f32 / 1000.0,
+            })
+            .collect(),
+        native,
+    ))
 }
 
 static HARDWARE_INITED: AtomicBool = AtomicBool::new(false);
 
-#[allow(clippy::unnecessary_wraps)]
-fn init_hardware(wnd_parms: &mut gfx::WindowParms) -> Result<(), ()> {
+fn init_hardware(wnd_parms: &mut gfx::WindowParms) -> Result<(), RenderError> {
     store_window_settings(wnd_parms).unwrap();
     if HARDWARE_INITED.load(Ordering::Relaxed) == false {
         finish_attaching_to_window(wnd_parms);
+        if let Err(e) = create_device(wnd_parms) {
+            detach_from_window();
+            return Err(e);
+        }
     }
 
     Ok(())
 }
 
+/// Returns how many backbuffer pixels make up one unit of window size on the
+/// monitor `wnd_parms` targets.
+///
+/// Always `1.0` if `r_dpiAware` is disabled, in which case the OS is left
+/// to scale the window instead.
+fn window_dpi_scale(wnd_parms: &gfx::WindowParms) -> f32 {
+    if dvar::get_bool("r_dpiAware").unwrap_or(false) {
+        wnd_parms
+            .monitor_handle
+            .map_or(1.0, platform::get_dpi_scale)
+    } else {
+        1.0
+    }
+}
+
 #[allow(clippy::semicolon_outside_block)]
-pub fn create_window(wnd_parms: &mut gfx::WindowParms) -> Result<(), ()> {
+pub fn create_window(
+    wnd_parms: &mut gfx::WindowParms,
+) -> Result<(), RenderError> {
+    com::report_init_progress("Creating the game window", 0.6);
     create_window_2(wnd_parms)?;
+    com::println!(
+        console::Channel::GFX,
+        "Display scale is {}%",
+        window_dpi_scale(wnd_parms) * 100.0
+    );
     init_hardware(wnd_parms)?;
-    RENDER_GLOBALS.write().unwrap().target_window_index = 0;
+    set_target_window(WindowIndex::PRIMARY).unwrap();
+    let (icon, icon_width, icon_height) = com::get_icon_rgba();
+    set_window_icon(icon, icon_width, icon_height);
     show_window(wnd_parms.window_handle.unwrap());
     Ok(())
 }
 
 // TODO - implement
 #[allow(clippy::unnecessary_wraps)]
-const fn init_systems() -> Result<(), ()> {
+const fn init_systems() -> Result<(), RenderError> {
     Ok(())
 }
 
+/// Every latched dvar the renderer reads, which [`vid_restart_f`] applies
+/// before bringing the renderer back up.
+const LATCHED_DVARS: [&str; 11] = [
+    "r_backend",
+    "r_headless",
+    "r_fullscreen",
+    "r_aspectRatioCustom",
+    "r_aaSamples",
+    "r_vsync",
+    "r_customMode",
+    "r_dpiAware",
+    "r_refreshRounding",
+    "r_mode",
+    "r_displayRefresh",
+];
+
+// Tears the renderer down and brings it back up with any latched settings
+// (including `r_backend`) applied.
+fn vid_restart_f() {
+    shutdown();
+    // Anything that was only worth warning about once for the old renderer
+    // is worth warning about again for the new one
+    com::reset_warn_once();
+    for name in LATCHED_DVARS {
+        // Not every backend registers every one of these
+        let _ = dvar::make_latched_value_current(name);
+    }
+    select_backend();
+    if let Err(e) = init_graphics_api() {
+        fatal_init_error!("Couldn't restart renderer: {}", e);
+    }
+}
+
+/// Releases the device and everything else [`init_graphics_api`] set up,
+/// and destroys the renderer's windows.
+///
+/// Does nothing if the renderer isn't initialized, so it's fine to call
+/// more than once. Afterwards, [`init_graphics_api`] will create everything
+/// again from scratch.
+pub fn shutdown() {
+    let windows = core::mem::take(&mut RENDER_GLOBALS.write().unwrap().windows);
+    let hardware_inited = HARDWARE_INITED.swap(false, Ordering::Relaxed);
+    if windows.is_empty() && hardware_inited == false && is_shut_down() {
+        return;
+    }
+
+    com::println!(console::Channel::GFX, "----- render::shutdown -----");
+
+    // Every compiled-in backend is checked, not just the current one, since
+    // `r_backend` may have changed since it was initialized. The device has
+    // to go before the windows it presents to.
+    #[cfg(d3d9)]
+    shutdown_d3d9();
+    #[cfg(vulkan)]
+    shutdown_vulkan();
+    #[cfg(wgpu)]
+    shutdown_wgpu();
+
+    for handle in windows.iter().filter_map(|w| w.handle) {
+        sys::destroy_window(handle);
+    }
+    RENDER_GLOBALS.write().unwrap().video_modes.clear();
+
+    debug_assert!(is_shut_down());
+}
+
+// Whether nothing is left for `init_graphics_api` to trip over.
+fn is_shut_down() -> bool {
+    let rg_clear = {
+        let rg = RENDER_GLOBALS.read().unwrap();
+        #[cfg(wgpu)]
+        let wgpu_clear = rg.surfaces.is_empty()
+            && rg.device.is_none()
+            && rg.adapter.is_none()
+            && rg.instance.is_none();
+        #[cfg(not(wgpu))]
+        let wgpu_clear = true;
+        rg.windows.is_empty() && wgpu_clear
+    };
+
+    #[cfg(d3d9)]
+    let d3d9_clear = {
+        let dx = platform::render::d3d9::dx();
+        dx.d3d9.is_none() && dx.device.is_none() && dx.window_count == 0
+    };
+    #[cfg(not(d3d9))]
+    let d3d9_clear = true;
+
+    #[cfg(vulkan)]
+    let vulkan_clear = platform::render::vulkan::vk().instance.is_none();
+    #[cfg(not(vulkan))]
+    let vulkan_clear = true;
+
+    rg_clear && d3d9_clear && vulkan_clear
+}
+
+#[cfg(d3d9)]
+fn shutdown_d3d9() {
+    let mut dx = platform::render::d3d9::dx_mut();
+    // Everything the device created has to go before the device itself.
+    // Dropping the interfaces releases them.
+    dx.release_default_pool_resources();
+    dx.device = None;
+    dx.d3d9 = None;
+    dx.window_count = 0;
+    // These get filled in again by `pre_create_window_d3d9`
+    dx.display_modes.clear();
+    dx.resolution_name_table.clear();
+    dx.refresh_rate_name_table.clear();
+}
+
+#[cfg(vulkan)]
+#[allow(clippy::undocumented_unsafe_blocks)]
+fn shutdown_vulkan() {
+    let mut vk = platform::render::vulkan::vk_mut();
+    if let Some(instance) = vk.instance.take() {
+        if let Some(device) = vk.device.take() {
+            unsafe {
+                (instance.fp_v1_0().destroy_device)(device, core::ptr::null());
+            }
+        }
+        unsafe { instance.destroy_instance(None) };
+    }
+    vk.physical_device = None;
+    vk.queue_family_index = None;
+    vk.entry = None;
+}
+
+#[cfg(wgpu)]
+fn shutdown_wgpu() {
+    let mut rg = RENDER_GLOBALS.write().unwrap();
+    // The surfaces have to be dropped before the device that presents to
+    // them, and the device before the adapter and instance it came from
+    rg.surfaces.clear();
+    rg.device = None;
+    rg.adapter = None;
+    rg.instance = None;
+}
+
+fn init_graphics_api() -> Result<(), RenderError> {
+    match backend() {
+        #[cfg(d3d9)]
+        RenderBackend::D3d9 => init_graphics_api_d3d9(),
+        #[cfg(vulkan)]
+        RenderBackend::Vulkan => init_graphics_api_vulkan(),
+        #[cfg(wgpu)]
+        RenderBackend::Wgpu => init_graphics_api_wgpu(),
+    }
+}
+
 #[cfg(wgpu)]
-fn init_graphics_api() -> Result<(), ()> {
+fn init_graphics_api_wgpu() -> Result<(), RenderError> {
     if RENDER_GLOBALS.read().unwrap().device.is_none() {
-        if pre_create_window().is_err() {
-            return Err(());
+        pre_create_window()?;
+
+        let mut wnd_parms: gfx::WindowParms = gfx::WindowParms::new();
+        loop {
+            set_wnd_parms(&mut wnd_parms);
+            let Err(e) = create_window(&mut wnd_parms) else {
+                break;
+            };
+            if reduce_window_settings().is_err() {
+                fatal_init_error!("Couldn't initialize renderer: {}", e)
+            }
         }
 
+        Ok(())
+    } else {
+        init_systems()
+    }
+}
+
+#[cfg(d3d9)]
+fn init_graphics_api_d3d9() -> Result<(), RenderError> {
+    let b = {
+        let dx = platform::render::d3d9::dx();
+        assert!(dx.device.is_some() == dx.d3d9.is_some());
+        dx.device.is_some()
+    };
+
+    if b == false {
+        pre_create_window()?;
+
         let mut wnd_parms: gfx::WindowParms = gfx::WindowParms::new();
         loop {
             set_wnd_parms(&mut wnd_parms);
-            if create_window(&mut wnd_parms).is_ok() {
+            let Err(e) = create_window(&mut wnd_parms) else {
                 break;
+            };
+            if reduce_window_settings().is_err() {
+                fatal_init_error!("Couldn't initialize renderer: {}", e)
             }
+        }
+
+        Ok(())
+    } else {
+        init_systems()
+    }
+}
+
+#[cfg(vulkan)]
+fn init_graphics_api_vulkan() -> Result<(), RenderError> {
+    let instance_created = {
+        let vk = platform::render::vulkan::vk();
+        // The instance and physical device are set up together by
+        // `pre_create_window_vulkan`, and the device only comes after
+        assert_eq!(vk.physical_device.is_some(), vk.instance.is_some());
+        assert!(vk.device.is_none() || vk.instance.is_some());
+        vk.instance.is_some()
+    };
+
+    if instance_created == false {
+        pre_create_window()?;
+
+        let mut wnd_parms: gfx::WindowParms = gfx::WindowParms::new();
+        loop {
+            set_wnd_parms(&mut wnd_parms);
+            let Err(e) = create_window(&mut wnd_parms) else {
+                break;
+            };
             if reduce_window_settings().is_err() {
-                fatal_init_error!("Couldn't initialize renderer")
+                fatal_init_error!("Couldn't initialize renderer: {}", e)
             }
         }
 
-        Ok(())
-    } else {
-        init_systems()
+        Ok(())
+    } else {
+        init_systems()
+    }
+}
+
+fn finish_attaching_to_window(wnd_parms: &gfx::WindowParms) {
+    let index = add_window(wnd_parms);
+    assert_eq!(index, WindowIndex::PRIMARY);
+    HARDWARE_INITED.store(true, Ordering::Relaxed);
+}
+
+// Undoes `finish_attaching_to_window` after the device couldn't be created,
+// destroying the window so that `init_graphics_api` can try again with a new
+// one.
+fn detach_from_window() {
+    let window = RENDER_GLOBALS.write().unwrap().windows.pop();
+    assert!(RENDER_GLOBALS.read().unwrap().windows.is_empty());
+    HARDWARE_INITED.store(false, Ordering::Relaxed);
+    if let Some(handle) = window.and_then(|w| w.handle) {
+        sys::destroy_window(handle);
+    }
+}
+
+/// Registers the window described by `wnd_parms` as one the renderer can
+/// draw to, returning the index to select it with in [`set_target_window`].
+///
+/// The first window added is the game's main window
+/// ([`WindowIndex::PRIMARY`]); any others are for things like tools views.
+///
+/// On wgpu, a surface is created for each window after the first (the
+/// primary window's is created along with the device). D3D9 presents its
+/// one back buffer to whichever window is targeted instead.
+pub fn add_window(wnd_parms: &gfx::WindowParms) -> WindowIndex {
+    let mut rg = RENDER_GLOBALS.write().unwrap();
+    let window = WindowTarget {
+        handle: wnd_parms.window_handle,
+        width: wnd_parms.display_width,
+        height: wnd_parms.display_height,
+    };
+    rg.windows.push(window);
+    let index = WindowIndex(rg.windows.len() - 1);
+
+    #[cfg(wgpu)]
+    if rg.surfaces.len() < rg.windows.len() && rg.device.is_some() {
+        if let Some(handle) = window.handle {
+            let _ = create_surface_wgpu(
+                &mut rg,
+                handle,
+                window.width,
+                window.height,
+            );
+        }
+    }
+
+    index
+}
+
+/// Selects the window that subsequent frames are cleared and drawn to.
+///
+/// Fails if `index` wasn't returned by [`add_window`].
+pub fn set_target_window(index: WindowIndex) -> Result<(), ()> {
+    let mut rg = RENDER_GLOBALS.write().unwrap();
+    if index.0 >= rg.windows.len() {
+        return Err(());
+    }
+
+    rg.target_window_index = index;
+    Ok(())
+}
+
+/// Returns the window frames are currently being drawn to, or [`None`] if
+/// no windows have been added yet.
+pub fn target_window() -> Option<WindowTarget> {
+    let rg = RENDER_GLOBALS.read().unwrap();
+    rg.windows.get(rg.target_window_index.0).copied()
+}
+
+/// Resizes the [`WindowTarget`] for the window `handle`, if the renderer has
+/// one.
+pub fn resize_window(handle: WindowHandle, width: u32, height: u32) {
+    let mut rg = RENDER_GLOBALS.write().unwrap();
+    let Some((index, target)) = rg
+        .windows
+        .iter_mut()
+        .enumerate()
+        .find(|(_, w)| w.handle == Some(handle))
+    else {
+        return;
+    };
+    target.resize(width, height);
+
+    #[cfg(wgpu)]
+    {
+        let (width, height) = (target.width, target.height);
+        let _ = configure_surface_wgpu(&rg, WindowIndex(index), width, height);
+    }
+}
+
+// Creates a surface for the window `handle` and its swapchain at
+// `width`x`height`, adding it after the last window's surface.
+#[cfg(wgpu)]
+fn create_surface_wgpu(
+    rg: &mut RenderGlobals,
+    handle: WindowHandle,
+    width: u32,
+    height: u32,
+) -> Result<(), RenderError> {
+    let window = WgpuSurface {
+        window_handle: handle,
+        monitor_handle: choose_monitor(),
+    };
+    let surface = platform::render::wgpu::Surface::new(
+        rg.instance.as_ref().unwrap(),
+        &window,
+    );
+    rg.surfaces.push(surface);
+    let index = WindowIndex(rg.surfaces.len() - 1);
+    configure_surface_wgpu(rg, index, width, height)
+}
+
+// Recreates the swapchain of the window `index` at `width`x`height`, if the
+// device and its surface have been created, logging a warning if it can't
+// be.
+#[cfg(wgpu)]
+fn configure_surface_wgpu(
+    rg: &RenderGlobals,
+    index: WindowIndex,
+    width: u32,
+    height: u32,
+) -> Result<(), RenderError> {
+    let (Some(surface), Some(adapter), Some(device)) = (
+        rg.surfaces.get(index.0),
+        rg.adapter.as_ref(),
+        rg.device.as_ref(),
+    ) else {
+        return Ok(());
+    };
+
+    let res = surface.configure(adapter, device, width, height);
+    if let Err(e) = &res {
+        com::warnln!(
+            console::Channel::GFX,
+            "WARNING: couldn't configure the window's surface: {}",
+            e
+        );
+    }
+    res
+}
+
+/// Sets the icon of the window frames are being drawn to to the
+/// `width`x`height` image `rgba`, given as rows of RGBA pixels from top to
+/// bottom. Does nothing if there's no window yet, or if `rgba` is too short
+/// to hold an image that size.
+pub fn set_window_icon(rgba: &[u8], width: u32, height: u32) {
+    let Some(handle) = target_window().and_then(|w| w.handle) else {
+        return;
+    };
+
+    let needed = usize::try_from(width)
+        .ok()
+        .zip(usize::try_from(height).ok())
+        .and_then(|(w, h)| w.checked_mul(h)?.checked_mul(4));
+    if needed.map_or(true, |n| rgba.len() < n) {
+        com::warnln!(
+            console::Channel::GFX,
+            "WARNING: window icon data is too short for {}x{}",
+            width,
+            height
+        );
+        return;
+    }
+
+    platform::set_window_icon(handle, rgba, width, height);
+}
+
+/// Changes the title of the game's main window to `title`. Does nothing if
+/// the window hasn't been created yet; it'll be titled from
+/// [`com::get_window_title`] when it is.
+pub fn set_window_title(title: &str) {
+    if let Some(handle) = platform::get_window_handle() {
+        sys::set_window_title(handle, title);
+    }
+}
+
+// Registers `r_modeLive`, which takes the same values as `r_mode` but isn't
+// latched - changes to it are applied by `mode_live_changed` as soon as
+// they're made, without a `vid_restart` where the backend allows it.
+fn register_mode_live(modes: Vec<String>) {
+    let current = dvar::get_enumeration("r_mode").unwrap();
+    dvar::register_enumeration(
+        "r_modeLive",
+        current,
+        Some(modes),
+        dvar::DvarFlags::empty(),
+        Some(
+            "Renderer resolution mode, changed without a vid_restart if \
+             possible",
+        ),
+    )
+    .unwrap();
+    dvar::set_change_callback("r_modeLive", mode_live_changed).unwrap();
+}
+
+// Applies a change to `r_modeLive`.
+fn mode_live_changed() {
+    let mode = dvar::get_enumeration("r_modeLive").unwrap();
+    let Ok((width, height)) = scanf!(mode, "{}x{}", u32, u32) else {
+        return;
+    };
+
+    // Keep r_mode in sync, so that the new mode is saved and is the one
+    // used if the renderer does have to be restarted
+    dvar::set_enumeration_internal("r_mode", &mode).unwrap();
+    let fullscreen = dvar::get_bool("r_fullscreen").unwrap();
+    if let Err(e) = try_apply_resolution(width, height, fullscreen) {
+        com::println!(console::Channel::GFX, "{}", e);
+    }
+}
+
+/// Switches the main window to `width`x`height` (and `fullscreen`) without
+/// tearing down the device, if the backend can (a surface reconfigure on
+/// wgpu, a device `Reset` on D3D9).
+///
+/// If it can't, a `vid_restart` is queued to make the change the slow way,
+/// and the reason the in-place change failed is returned.
+pub fn try_apply_resolution(
+    width: u32,
+    height: u32,
+    fullscreen: bool,
+) -> Result<(), RenderError> {
+    let mut wnd_parms = target_window_parms(width, height, fullscreen);
+    let res = if wnd_parms.window_handle.is_none() {
+        Err(RenderError::ModeChangeFailed("no window to resize".into()))
+    } else if fullscreen != vid::config().is_fullscreen {
+        // Fullscreen and windowed mode use different window styles, so
+        // switching between them needs a new window
+        Err(RenderError::ModeChangeFailed(
+            "switching to or from fullscreen needs a new window".into(),
+        ))
+    } else {
+        apply_resolution_in_place(&wnd_parms)
+    };
+
+    if let Err(e) = res {
+        cbuf::add_textln(0, "vid_restart");
+        return Err(e);
+    }
+
+    store_window_settings(&mut wnd_parms).unwrap();
+    resize_window(wnd_parms.window_handle.unwrap(), width, height);
+    com::println!(
+        console::Channel::GFX,
+        "Changed resolution to {} x {}",
+        width,
+        height
+    );
+    Ok(())
+}
+
+/// Switches the main window between fullscreen and windowed mode without
+/// tearing down the device, if the backend can (a device `Reset` on D3D9,
+/// after swapping the window's styles).
+///
+/// Unlike [`try_apply_resolution`], nothing is queued if it can't; the
+/// reason is returned, and it's up to the caller to fall back to a
+/// `vid_restart`.
+pub fn set_fullscreen(fullscreen: bool) -> Result<(), RenderError> {
+    let (width, height, hz, is_fullscreen) = {
+        let config = vid::config();
+        (
+            config.display_width,
+            config.display_height,
+            config.display_frequency,
+            config.is_fullscreen,
+        )
+    };
+    if fullscreen == is_fullscreen {
+        return Ok(());
+    }
+
+    let mut wnd_parms = target_window_parms(width, height, fullscreen);
+    if wnd_parms.window_handle.is_none() {
+        return Err(RenderError::ModeChangeFailed(
+            "no window to switch".into(),
+        ));
+    }
+    // Windowed mode always runs at 60 Hz (see `set_wnd_parms`), which the
+    // display might not support in fullscreen
+    wnd_parms.hz = if fullscreen {
+        closest_refresh_rate_for_mode(width, height, hz).unwrap_or(hz)
+    } else {
+        60.0
+    };
+
+    match backend() {
+        #[cfg(d3d9)]
+        RenderBackend::D3d9 => set_fullscreen_d3d9(&wnd_parms),
+        #[cfg(vulkan)]
+        RenderBackend::Vulkan => Err(RenderError::ModeChangeFailed(
+            "not supported by this renderer".into(),
+        )),
+        #[cfg(wgpu)]
+        RenderBackend::Wgpu => Err(RenderError::ModeChangeFailed(
+            "not supported by this renderer".into(),
+        )),
+    }?;
+
+    store_window_settings(&mut wnd_parms).unwrap();
+    dvar::set_bool_internal("r_fullscreen", fullscreen).unwrap();
+    com::println!(
+        console::Channel::GFX,
+        "Switched to {} mode",
+        if fullscreen { "fullscreen" } else { "windowed" },
+    );
+    Ok(())
+}
+
+// The window has to be a popup before the device goes fullscreen, and
+// can't get its frame back until the device has left fullscreen.
+#[cfg(d3d9)]
+fn set_fullscreen_d3d9(
+    wnd_parms: &gfx::WindowParms,
+) -> Result<(), RenderError> {
+    let handle = wnd_parms.window_handle.unwrap();
+    let restyle = || {
+        sys::set_window_fullscreen_style(
+            handle,
+            wnd_parms.fullscreen,
+            wnd_parms.display_width,
+            wnd_parms.display_height,
+        );
+    };
+
+    if wnd_parms.fullscreen {
+        restyle();
+    }
+    reset_device(wnd_parms)
+        .map_err(|e| RenderError::ModeChangeFailed(e.message().to_string()))?;
+    if wnd_parms.fullscreen == false {
+        restyle();
+    }
+    Ok(())
+}
+
+// Describes the target window as it would be at `width`x`height` (and
+// `fullscreen`), with everything else as it currently is.
+#[allow(clippy::cast_sign_loss)]
+fn target_window_parms(
+    width: u32,
+    height: u32,
+    fullscreen: bool,
+) -> gfx::WindowParms {
+    let mut wnd_parms = gfx::WindowParms::new();
+    wnd_parms.window_handle = target_window().and_then(|w| w.handle);
+    wnd_parms.fullscreen = fullscreen;
+    wnd_parms.display_width = width;
+    wnd_parms.display_height = height;
+    wnd_parms.scene_width = width;
+    wnd_parms.scene_height = height;
+    wnd_parms.hz = vid::config().display_frequency;
+    wnd_parms.aa_samples =
+        dvar::get_int("r_aaSamples").unwrap().clamp(1, 16) as _;
+    wnd_parms
+}
+
+fn apply_resolution_in_place(
+    wnd_parms: &gfx::WindowParms,
+) -> Result<(), RenderError> {
+    match backend() {
+        #[cfg(d3d9)]
+        RenderBackend::D3d9 => apply_resolution_in_place_d3d9(wnd_parms),
+        #[cfg(vulkan)]
+        RenderBackend::Vulkan => Err(RenderError::ModeChangeFailed(
+            "not supported by this renderer".into(),
+        )),
+        #[cfg(wgpu)]
+        RenderBackend::Wgpu => apply_resolution_in_place_wgpu(wnd_parms),
+    }
+}
+
+#[cfg(d3d9)]
+fn apply_resolution_in_place_d3d9(
+    wnd_parms: &gfx::WindowParms,
+) -> Result<(), RenderError> {
+    if wnd_parms.fullscreen == false {
+        // Reset only resizes the backbuffer, not the window
+        sys::set_window_size(
+            wnd_parms.window_handle.unwrap(),
+            wnd_parms.display_width,
+            wnd_parms.display_height,
+        );
+    }
+
+    reset_device(wnd_parms)
+        .map_err(|e| RenderError::ModeChangeFailed(e.message().to_string()))
+}
+
+// Resets the device with present parameters built from `wnd_parms`,
+// releasing the resources a reset requires be released beforehand.
+#[cfg(d3d9)]
+fn reset_device(
+    wnd_parms: &gfx::WindowParms,
+) -> Result<(), windows::core::Error> {
+    let mut d3dpp = D3DPRESENT_PARAMETERS::default();
+    set_d3d_present_parameters(&mut d3dpp, wnd_parms);
+
+    let mut dx = platform::render::d3d9::dx_mut();
+    dx.release_default_pool_resources();
+    let Some(device) = dx.device.as_ref() else {
+        return Err(D3DERR_INVALID_CALL.into());
+    };
+    // TODO - recreate the default pool resources after the reset once the
+    // renderer creates any of them outside of device creation
+    // SAFETY:
+    // Reset is an FFI function, requiring use of unsafe. `d3dpp` is valid
+    // for the duration of the call.
+    unsafe { device.Reset(addr_of_mut!(d3dpp)) }
+}
+
+/// Checks whether the device has been lost (e.g. by alt-tabbing out of
+/// exclusive fullscreen), resetting it as soon as the backend allows. Only
+/// the D3D9 backend can lose its device, but the wgpu backend is also
+/// unusable while its surface can't be configured.
+///
+/// Returns `false` while the device is unusable, in which case nothing
+/// should be drawn this frame. [`sys::query_render_device_ok_event`]
+/// reflects the same state for other threads.
+pub fn check_device() -> bool {
+    match backend() {
+        #[cfg(d3d9)]
+        RenderBackend::D3d9 => check_device_d3d9(),
+        #[cfg(vulkan)]
+        RenderBackend::Vulkan => true,
+        #[cfg(wgpu)]
+        RenderBackend::Wgpu => check_device_wgpu(),
+    }
+}
+
+// A wgpu device can't be lost, but the target window's surface can be left
+// unconfigured (e.g. if the adapter couldn't present to the window after a
+// resize). Tries to configure it again if so.
+#[cfg(wgpu)]
+fn check_device_wgpu() -> bool {
+    let rg = RENDER_GLOBALS.read().unwrap();
+    let index = rg.target_window_index;
+    let Some(surface) = rg.surfaces.get(index.0) else {
+        return false;
+    };
+    if surface.is_configured() {
+        return true;
     }
+
+    rg.windows.get(index.0).map_or(false, |w| {
+        configure_surface_wgpu(&rg, index, w.width, w.height).is_ok()
+    })
 }
 
 #[cfg(d3d9)]
-fn init_graphics_api() -> Result<(), ()> {
-    let b = {
+fn check_device_d3d9() -> bool {
+    let status = {
         let dx = platform::render::d3d9::dx();
-        assert!(dx.device.is_some() == dx.d3d9.is_some());
-        dx.device.is_some()
+        let Some(device) = dx.device.as_ref() else {
+            return false;
+        };
+        // SAFETY:
+        // TestCooperativeLevel is an FFI function, requiring use of unsafe.
+        // It takes no arguments and has no preconditions.
+        unsafe { device.TestCooperativeLevel() }
     };
 
-    if b {
-        if pre_create_window().is_err() {
-            return Err(());
+    let level = match status {
+        Ok(()) => CooperativeLevel::Ok,
+        Err(e) if e.code() == D3DERR_DEVICELOST => CooperativeLevel::Lost,
+        Err(e) if e.code() == D3DERR_DEVICENOTRESET => {
+            CooperativeLevel::NotReset
         }
-
-        let mut wnd_parms: gfx::WindowParms = gfx::WindowParms::new();
-        loop {
-            set_wnd_parms(&mut wnd_parms);
-            if create_window(&mut wnd_parms).is_ok() {
-                break;
-            }
-            if reduce_window_settings().is_err() {
-                fatal_init_error!("Couldn't initialize renderer")
-            }
+        Err(e) => {
+            com::println!(
+                console::Channel::GFX,
+                "TestCooperativeLevel failed: {}",
+                e.message()
+            );
+            CooperativeLevel::Failed
         }
+    };
+    update_device_state(level, reset_target_device_d3d9)
+}
 
-        Ok(())
-    } else {
-        init_systems()
-    }
+/// What `TestCooperativeLevel` said about the D3D9 device.
+#[cfg(d3d9)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum CooperativeLevel {
+    /// The device can be drawn to.
+    Ok,
+    /// The device is lost, and can't be reset until the app gets focus
+    /// back.
+    Lost,
+    /// The device is lost, but can be reset now.
+    NotReset,
+    /// Something else went wrong.
+    Failed,
 }
 
-#[cfg(vulkan)]
-fn init_graphics_api() -> Result<(), ()> {
-    let b = {
-        let vk = platform::render::vulkan::vk();
-        assert!(
-            vk.device.is_some() == vk.physical_device.is_some()
-                && vk.physical_device.is_some() == vk.instance.is_some()
-        );
-        vk.device.is_some()
+// Decides whether the device can be drawn to at `level`, resetting it with
+// `reset` first if it needs to be, and signals or clears the render device
+// ok event to match.
+#[cfg(d3d9)]
+fn update_device_state(
+    level: CooperativeLevel,
+    reset: impl FnOnce() -> bool,
+) -> bool {
+    let ok = match level {
+        CooperativeLevel::Ok => true,
+        // Nothing can be done until the app gets focus back, so just keep
+        // polling
+        CooperativeLevel::Lost | CooperativeLevel::Failed => false,
+        CooperativeLevel::NotReset => reset(),
     };
 
-    if b {
-        if pre_create_window().is_err() {
-            return Err(());
-        }
+    let was_ok = sys::query_render_device_ok_event() == SignalState::Signaled;
+    if ok && was_ok == false {
+        sys::set_render_device_ok_event();
+    } else if ok == false && was_ok {
+        com::println!(console::Channel::GFX, "Device lost.");
+        sys::clear_render_device_ok_event();
+    }
+    ok
+}
 
-        let mut wnd_parms: gfx::WindowParms = gfx::WindowParms::new();
-        loop {
-            set_wnd_parms(&mut wnd_parms);
-            if create_window(&mut wnd_parms).is_ok() {
-                break;
-            }
-            if reduce_window_settings().is_err() {
-                fatal_init_error!("Couldn't initialize renderer")
-            }
+// Resets the device for the target window at the current resolution,
+// returning whether it worked.
+#[cfg(d3d9)]
+fn reset_target_device_d3d9() -> bool {
+    let (width, height, fullscreen) = {
+        let config = vid::config();
+        (
+            config.display_width,
+            config.display_height,
+            config.is_fullscreen,
+        )
+    };
+    let wnd_parms = target_window_parms(width, height, fullscreen);
+    match reset_device(&wnd_parms) {
+        Ok(()) => {
+            com::println!(console::Channel::GFX, "Device reset.");
+            true
+        }
+        Err(e) => {
+            com::println!(
+                console::Channel::GFX,
+                "Couldn't reset the device: {}",
+                e.message()
+            );
+            false
         }
-
-        Ok(())
-    } else {
-        init_systems()
     }
 }
 
-fn finish_attaching_to_window(wnd_parms: &gfx::WindowParms) {
-    let mut rg = RENDER_GLOBALS.write().unwrap();
-    assert_eq!(rg.windows.len(), 0);
-    // swapchain
-    let window = WindowTarget {
-        handle: wnd_parms.window_handle,
-        width: wnd_parms.display_width,
-        height: wnd_parms.display_height,
-    };
-    rg.windows.push(window);
-    HARDWARE_INITED.store(true, Ordering::Relaxed);
+#[cfg(wgpu)]
+#[allow(clippy::unnecessary_wraps)]
+fn apply_resolution_in_place_wgpu(
+    wnd_parms: &gfx::WindowParms,
+) -> Result<(), RenderError> {
+    if wnd_parms.fullscreen {
+        return Err(RenderError::ModeChangeFailed(
+            "fullscreen mode changes aren't supported by this renderer".into(),
+        ));
+    }
+
+    sys::set_window_size(
+        wnd_parms.window_handle.unwrap(),
+        wnd_parms.display_width,
+        wnd_parms.display_height,
+    );
+    // The surface is reconfigured for the new size by `resize_window`
+    Ok(())
 }
 
 #[cfg(d3d9)]
 fn get_device_type() -> D3DDEVTYPE {
     let mut dx = platform::render::d3d9::dx_mut();
     dx.adapter = Adapter::from_d3d9(0);
+    if headless() {
+        // The reference rasterizer runs entirely on the CPU
+        return D3DDEVTYPE_REF;
+    }
 
     let mut i = 0;
     let mut d3d_id = D3DADAPTER_IDENTIFIER9::default();
@@ -2548,7 +4869,7 @@ fn get_device_type() -> D3DDEVTYPE {
 }
 
 #[cfg(d3d9)]
-fn create_device_internal(
+fn create_device_internal_d3d9(
     hwnd: HWND,
     behavior_flags: u32,
     d3dpp: &mut D3DPRESENT_PARAMETERS,
@@ -2625,7 +4946,7 @@ fn create_device_internal(
     if dx.adapter.as_d3d9() != 0 {
         dx.adapter = Adapter::from_d3d9(0);
         drop(dx);
-        return create_device_internal(hwnd, behavior_flags, d3dpp);
+        return create_device_internal_d3d9(hwnd, behavior_flags, d3dpp);
     }
 
     r
@@ -2751,13 +5072,31 @@ fn set_d3d_present_parameters(
     }
 }
 
+fn create_device(wnd_parms: &gfx::WindowParms) -> Result<(), RenderError> {
+    com::report_init_progress("Creating the render device", 0.75);
+    match backend() {
+        #[cfg(d3d9)]
+        RenderBackend::D3d9 => create_device_d3d9(wnd_parms),
+        #[cfg(vulkan)]
+        RenderBackend::Vulkan => create_device_vulkan(),
+        #[cfg(wgpu)]
+        RenderBackend::Wgpu => create_device_wgpu(wnd_parms),
+    }
+}
+
 #[cfg(d3d9)]
-fn create_device(wnd_parms: &gfx::WindowParms) -> Result<(), ()> {
-    let mut dx = platform::render::d3d9::dx_mut();
-    assert_eq!(dx.window_count, 0);
+fn create_device_d3d9(wnd_parms: &gfx::WindowParms) -> Result<(), RenderError> {
+    {
+        let dx = platform::render::d3d9::dx();
+        assert_eq!(dx.window_count, 0);
+        assert!(dx.device.is_none());
+    }
     assert!(wnd_parms.window_handle.is_some());
-    assert!(dx.device.is_some());
-    dx.depth_stencil_format = get_depth_stencil_format(D3DFMT_A8R8G8B8);
+    // Nothing here can hold onto `dx_mut()`, since everything called below
+    // takes the lock itself
+    let depth_stencil_format = get_depth_stencil_format(D3DFMT_A8R8G8B8);
+    platform::render::d3d9::dx_mut().depth_stencil_format =
+        depth_stencil_format;
     let mut d3dpp = D3DPRESENT_PARAMETERS::default();
     set_d3d_present_parameters(&mut d3dpp, wnd_parms);
     let behavior_flags =
@@ -2766,7 +5105,7 @@ fn create_device(wnd_parms: &gfx::WindowParms) -> Result<(), ()> {
         } else {
             D3DCREATE_HARDWARE_VERTEXPROCESSING
         };
-    if let Err(e) = create_device_internal(
+    if let Err(e) = create_device_internal_d3d9(
         HWND(wnd_parms.window_handle.unwrap().get_win32().unwrap().hwnd as _),
         behavior_flags as _,
         &mut d3dpp,
@@ -2776,53 +5115,145 @@ fn create_device(wnd_parms: &gfx::WindowParms) -> Result<(), ()> {
             "Couldn't create a Direct3D device: {}",
             e.message()
         );
-        Err(())
+        Err(RenderError::DeviceCreationFailed(e.message().to_string()))
     } else {
-        assert!(dx.device.is_some());
+        let gpu_memory_mb = {
+            let dx = platform::render::d3d9::dx();
+            assert!(dx.device.is_some());
+            gpu_memory_mb_d3d9(dx.device.as_ref().unwrap())
+        };
+        store_gpu_memory(gpu_memory_mb);
         Ok(())
     }
 }
 
 #[cfg(wgpu)]
-fn create_device_internal(_wnd_parms: &gfx::WindowParms) -> Result<(), ()> {
+fn create_device_internal_wgpu(
+    wnd_parms: &gfx::WindowParms,
+) -> Result<(), RenderError> {
     com::println!(console::Channel::GFX, "Creating Render device...");
 
     let mut rg = RENDER_GLOBALS.write().unwrap();
-    rg.device = block_on(Device::new(rg.adapter.as_ref().unwrap()));
-
+    // There's no monitor to ask when running headless
     (rg.adapter_native_width, rg.adapter_native_height) =
-        get_monitor_dimensions().unwrap();
+        get_monitor_dimensions()
+            .unwrap_or((wnd_parms.display_width, wnd_parms.display_height));
     rg.device = block_on(platform::render::wgpu::Device::new(
         rg.adapter.as_ref().unwrap(),
     ));
     if rg.device.is_none() {
-        return Err(());
+        return Err(RenderError::DeviceCreationFailed(
+            "request_device failed".into(),
+        ));
+    }
+
+    // The primary window's surface. Any other windows get theirs when
+    // they're added.
+    rg.surfaces.clear();
+    if let Err(e) = create_surface_wgpu(
+        &mut rg,
+        wnd_parms.window_handle.unwrap(),
+        wnd_parms.display_width,
+        wnd_parms.display_height,
+    ) {
+        rg.surfaces.clear();
+        rg.device = None;
+        return Err(e);
     }
 
+    store_wgpu_caps(rg.adapter.as_ref().unwrap());
+    // wgpu doesn't expose how much memory the adapter has or has free
+    store_gpu_memory(None);
     Ok(())
 }
 
+// The wgpu counterpart to `store_direct3d_caps`, so that code depending on
+// `vid::config()` sees the same fields filled in whichever backend is used.
+#[cfg(wgpu)]
+#[allow(clippy::as_conversions)]
+fn store_wgpu_caps(adapter: &platform::render::wgpu::Adapter) {
+    let caps = adapter.capabilities();
+    {
+        let mut vc = vid::config_mut();
+        // wgpu has no equivalent to D3D9's gamma ramp; gamma will need to be
+        // applied in a shader instead
+        vc.device_supports_gamma = false;
+        // Same cap as d3d9, so materials don't behave differently depending
+        // on backend
+        vc.max_texture_maps = (caps.max_sampled_textures as usize).min(16);
+        vc.max_texture_size = caps.max_texture_dimension as _;
+    }
+
+    com::println!(
+        console::Channel::GFX,
+        "Max texture size: {}, max sampled textures: {}, max bind groups: {}",
+        caps.max_texture_dimension,
+        caps.max_sampled_textures,
+        caps.max_bind_groups,
+    );
+    com::println!(
+        console::Channel::GFX,
+        "Anisotropic filtering: {} (max {}x), MSAA sample counts: {:?}",
+        if caps.supports_anisotropy {
+            "supported"
+        } else {
+            "unsupported"
+        },
+        caps.max_anisotropy,
+        caps.msaa_sample_counts,
+    );
+}
+
 #[cfg(wgpu)]
-fn create_device(wnd_parms: &gfx::WindowParms) -> Result<(), ()> {
+fn create_device_wgpu(wnd_parms: &gfx::WindowParms) -> Result<(), RenderError> {
     {
         let rg = RENDER_GLOBALS.read().unwrap();
         assert_ne!(rg.windows.len(), 0);
         assert_ne!(wnd_parms.window_handle, None);
-        assert!(rg.device.is_some());
+        assert!(rg.device.is_none());
     }
 
     // depth stencil
 
-    if create_device_internal(wnd_parms).is_err() {
-        com::print_errorln!(
-            console::Channel::GFX,
-            "Couldn't create a Renderer device"
-        );
-        return Err(());
+    if let Err(e) = create_device_internal_wgpu(wnd_parms) {
+        com::print_errorln!(console::Channel::GFX, "{}", e);
+        return Err(e);
     }
 
     let rg = RENDER_GLOBALS.read().unwrap();
     assert!(rg.device.is_some());
+    assert_ne!(vid::config().max_texture_size, 0);
+    Ok(())
+}
+
+#[cfg(vulkan)]
+#[allow(clippy::undocumented_unsafe_blocks)]
+fn create_device_vulkan() -> Result<(), RenderError> {
+    com::println!(console::Channel::GFX, "Creating Vulkan device...");
+
+    let mut vk = platform::render::vulkan::vk_mut();
+    assert!(vk.device.is_none());
+    let (Some(instance), Some(physical_device), Some(queue_family_index)) = (
+        vk.instance.as_ref(),
+        vk.physical_device,
+        vk.queue_family_index,
+    ) else {
+        return Err(RenderError::AdapterNotFound);
+    };
+
+    let queue_priorities = [1.0];
+    let queue_create_info = ash::vk::DeviceQueueCreateInfo::builder()
+        .queue_family_index(queue_family_index)
+        .queue_priorities(&queue_priorities);
+    let extensions = [khr::Swapchain::name().as_ptr()];
+    let create_info = ash::vk::DeviceCreateInfo::builder()
+        .queue_create_infos(core::slice::from_ref(&queue_create_info))
+        .enabled_extension_names(&extensions);
+    let device =
+        unsafe { instance.create_device(physical_device, &create_info, None) }
+            .map_err(|e| RenderError::DeviceCreationFailed(e.to_string()))?;
+    // `shutdown_vulkan` destroys it through the instance
+    vk.device = Some(device.handle());
     Ok(())
 }
 
@@ -2831,10 +5262,566 @@ lazy_static! {
         RwLock::new(gfx::Globals::default());
 }
 
+// A panic while `R_GLOB` is held (e.g. from one of the many assertions in
+// the render code) poisons it. Nothing in `gfx::Globals` is left in a state
+// worse than what the panicking code already observed, so rather than letting
+// every later access panic as well, take the guard anyway and clear the
+// poison.
+fn recover_r_glob<G>(e: PoisonError<G>) -> G {
+    com::warnln!(
+        console::Channel::GFX,
+        "Recovering gfx::Globals after a thread panicked while holding it"
+    );
+    let guard = e.into_inner();
+    R_GLOB.clear_poison();
+    guard
+}
+
 pub fn r_glob() -> RwLockReadGuard<'static, gfx::Globals> {
-    R_GLOB.read().unwrap()
+    R_GLOB.read().unwrap_or_else(recover_r_glob)
 }
 
 pub fn r_glob_mut() -> RwLockWriteGuard<'static, gfx::Globals> {
-    R_GLOB.write().unwrap()
+    R_GLOB.write().unwrap_or_else(recover_r_glob)
+}
+
+/// Returns a copy of the current [`gfx::Globals`], or [`None`] if they're
+/// locked for writing.
+///
+/// Unlike [`r_glob`], this never blocks, panics, or touches the poison flag,
+/// so it's safe to call from the panic hook, where the panicking thread may
+/// well be the one holding the lock.
+pub fn try_r_glob() -> Option<gfx::Globals> {
+    match R_GLOB.try_read() {
+        Ok(g) => Some(*g),
+        Err(TryLockError::Poisoned(e)) => Some(*e.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(xlib)]
+    #[test]
+    fn available_monitors_outlive_the_query() {
+        // Nothing to check without an X server to connect to
+        if platform::display_server::xlib::with_display(|_| ()).is_none() {
+            return;
+        }
+
+        let monitors = available_monitors();
+        assert_ne!(monitors.len(), 0);
+        for monitor in monitors {
+            let _ = monitor_info(monitor);
+        }
+    }
+
+    // Whether there's a display server to create the game window on, which
+    // the tests that bring up the whole renderer need.
+    #[cfg(all(wgpu, xlib))]
+    fn has_display() -> bool {
+        platform::display_server::xlib::with_display(|_| ()).is_some()
+    }
+
+    // Windows always has one.
+    #[cfg(d3d9)]
+    const fn has_display() -> bool {
+        true
+    }
+
+    #[cfg(any(d3d9, all(wgpu, xlib)))]
+    #[test]
+    fn init_recreates_everything_after_shutdown() {
+        let _guard = testing::fresh_state();
+        if has_display() == false {
+            return;
+        }
+        register();
+        dvar::set_bool_internal("r_headless", true).unwrap();
+        dvar::make_latched_value_current("r_headless").unwrap();
+
+        init().unwrap();
+        assert!(is_shut_down() == false);
+        shutdown();
+        assert!(is_shut_down());
+        // Shutting down twice is harmless
+        shutdown();
+
+        init().unwrap();
+        assert!(is_shut_down() == false);
+        assert!(HARDWARE_INITED.load(Ordering::Relaxed));
+        #[cfg(d3d9)]
+        if backend() == RenderBackend::D3d9 {
+            assert!(platform::render::d3d9::dx().device.is_some());
+        }
+        shutdown();
+    }
+
+    #[cfg(all(wgpu, xlib))]
+    #[test]
+    fn init_creates_a_device_that_presents_a_frame() {
+        let _guard = testing::fresh_state();
+        if has_display() == false {
+            return;
+        }
+        register();
+        // So a software adapter is used on machines without a GPU
+        dvar::set_bool_internal("r_headless", true).unwrap();
+        dvar::make_latched_value_current("r_headless").unwrap();
+
+        init().unwrap();
+        assert!(RENDER_GLOBALS.read().unwrap().device.is_some());
+        assert_eq!(RENDER_GLOBALS.read().unwrap().surfaces.len(), 1);
+        assert!(check_device());
+
+        let dropped = rb::dropped_command_lists();
+        submit_frame();
+        rb::swap_buffers();
+        // The next frame would be dropped if the first hadn't been taken
+        submit_frame();
+        assert_eq!(rb::dropped_command_lists(), dropped);
+        rb::swap_buffers();
+
+        shutdown();
+        assert!(is_shut_down());
+    }
+
+    #[cfg(all(wgpu, xlib))]
+    #[test]
+    fn device_creation_stores_the_wgpu_caps() {
+        let _guard = testing::fresh_state();
+        if has_display() == false {
+            return;
+        }
+        register();
+        dvar::set_bool_internal("r_headless", true).unwrap();
+        dvar::make_latched_value_current("r_headless").unwrap();
+        vid::config_mut().max_texture_size = 0;
+
+        init().unwrap();
+        let (max_texture_size, max_texture_maps) = {
+            let vc = vid::config();
+            (vc.max_texture_size, vc.max_texture_maps)
+        };
+        shutdown();
+
+        assert_ne!(max_texture_size, 0);
+        assert!((1..=16).contains(&max_texture_maps));
+    }
+
+    #[cfg(d3d9)]
+    #[test]
+    fn lost_device_is_usable_again_once_reset() {
+        let _guard = testing::fresh_state();
+        let resets = core::cell::Cell::new(0);
+        let reset = |ok| {
+            let resets = &resets;
+            move || {
+                resets.set(resets.get() + 1);
+                ok
+            }
+        };
+        let device_ok =
+            || sys::query_render_device_ok_event() == SignalState::Signaled;
+
+        sys::set_render_device_ok_event();
+        assert!(update_device_state(CooperativeLevel::Ok, reset(true)));
+        assert!(device_ok());
+
+        // Lost devices can't be reset yet, so it isn't tried
+        assert!(
+            update_device_state(CooperativeLevel::Lost, reset(true)) == false
+        );
+        assert!(device_ok() == false);
+        assert_eq!(resets.get(), 0);
+
+        // Stays unusable if resetting fails...
+        assert!(
+            update_device_state(CooperativeLevel::NotReset, reset(false))
+                == false
+        );
+        assert!(device_ok() == false);
+        assert_eq!(resets.get(), 1);
+
+        // ...and comes back once it works
+        assert!(update_device_state(CooperativeLevel::NotReset, reset(true)));
+        assert!(device_ok());
+        assert_eq!(resets.get(), 2);
+
+        assert!(update_device_state(CooperativeLevel::Ok, reset(true)));
+        assert_eq!(resets.get(), 2);
+    }
+
+    #[test]
+    fn store_window_settings_bumps_config_generation() {
+        let _guard = testing::fresh_state();
+        dvar::register_enumeration(
+            "r_aspectRatio",
+            ASPECT_RATIO_STANDARD.to_owned(),
+            Some(vec![ASPECT_RATIO_STANDARD.to_owned()]),
+            dvar::DvarFlags::empty(),
+            None,
+        )
+        .unwrap();
+        dvar::register_bool(
+            "wideScreen",
+            false,
+            dvar::DvarFlags::empty(),
+            None,
+        )
+        .unwrap();
+
+        let mut wnd_parms = gfx::WindowParms::new();
+        let before = vid::config_generation();
+        store_window_settings(&mut wnd_parms).unwrap();
+        let first = vid::config_generation();
+        assert_ne!(first, before);
+
+        wnd_parms.display_width = 1920;
+        wnd_parms.display_height = 1080;
+        store_window_settings(&mut wnd_parms).unwrap();
+        assert_ne!(vid::config_generation(), first);
+        assert_eq!(vid::config().display_width, 1920);
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    #[test]
+    fn custom_aspect_ratio_feeds_the_pixel_ratios() {
+        let _guard = testing::fresh_state();
+        dvar::register_enumeration(
+            "r_aspectRatio",
+            ASPECT_RATIO_CUSTOM.to_owned(),
+            Some(vec![
+                ASPECT_RATIO_STANDARD.to_owned(),
+                ASPECT_RATIO_CUSTOM.to_owned(),
+            ]),
+            dvar::DvarFlags::empty(),
+            None,
+        )
+        .unwrap();
+        dvar::register_float(
+            "r_aspectRatioCustom",
+            16.0 / 9.0,
+            Some(ASPECT_RATIO_CUSTOM_MIN),
+            Some(ASPECT_RATIO_CUSTOM_MAX),
+            dvar::DvarFlags::empty(),
+            None,
+        )
+        .unwrap();
+        dvar::register_bool(
+            "wideScreen",
+            false,
+            dvar::DvarFlags::empty(),
+            None,
+        )
+        .unwrap();
+
+        let (fullscreen_width, fullscreen_height) = {
+            let rg = RENDER_GLOBALS.read().unwrap();
+            (rg.adapter_fullscreen_width, rg.adapter_fullscreen_height)
+        };
+        let mut wnd_parms = gfx::WindowParms::new();
+        wnd_parms.scene_width = 1280;
+        wnd_parms.scene_height = 720;
+        for ratio in [2.0, 21.0 / 9.0] {
+            dvar::set_float_internal("r_aspectRatioCustom", ratio).unwrap();
+
+            wnd_parms.fullscreen = false;
+            store_window_settings(&mut wnd_parms).unwrap();
+            let config = vid::config();
+            assert!((config.aspect_ratio_window - ratio).abs() < 1e-5);
+            assert!(
+                (config.aspect_ratio_scene_pixel - 720.0 * ratio / 1280.0)
+                    .abs()
+                    < 1e-5
+            );
+            assert_eq!(config.aspect_ratio_display_pixel, 1.0);
+            assert_eq!(dvar::get_bool("wideScreen"), Some(true));
+            drop(config);
+
+            wnd_parms.fullscreen = true;
+            store_window_settings(&mut wnd_parms).unwrap();
+            let expected =
+                fullscreen_height as f32 * ratio / fullscreen_width as f32;
+            assert!(
+                (vid::config().aspect_ratio_display_pixel - expected).abs()
+                    < 1e-5
+            );
+        }
+    }
+
+    const fn video_mode(width: u32, height: u32, refresh: f32) -> VideoMode {
+        VideoMode {
+            width,
+            height,
+            bit_depth: 32,
+            refresh,
+        }
+    }
+
+    #[test]
+    fn refresh_rate_snaps_to_the_nearest_supported() {
+        let video_modes = [
+            video_mode(1920, 1080, 60.0),
+            video_mode(1920, 1080, 120.0),
+            video_mode(1920, 1080, 144.0),
+            video_mode(1280, 720, 60.0),
+            video_mode(1280, 720, 75.0),
+        ];
+        let nearest = |width, height, hz| {
+            nearest_refresh_rate(&video_modes, width, height, hz)
+        };
+
+        // Supported rates are left alone
+        assert_eq!(nearest(1920, 1080, 144.0), Some(144.0));
+        assert_eq!(nearest(1280, 720, 60.0), Some(60.0));
+        // Unsupported rates between supported ones go to the closest
+        assert_eq!(nearest(1920, 1080, 100.0), Some(120.0));
+        assert_eq!(nearest(1920, 1080, 130.0), Some(120.0));
+        assert_eq!(nearest(1920, 1080, 59.94), Some(60.0));
+        // And ones outside of the range go to the nearest end of it
+        assert_eq!(nearest(1920, 1080, 240.0), Some(144.0));
+        assert_eq!(nearest(1920, 1080, 30.0), Some(60.0));
+        // Only the rates at the requested resolution count
+        assert_eq!(nearest(1280, 720, 144.0), Some(75.0));
+        assert_eq!(nearest(2560, 1440, 60.0), None);
+    }
+
+    #[test]
+    fn close_refresh_rates_share_a_rounded_name() {
+        let video_modes = [
+            video_mode(1920, 1080, 59.94),
+            video_mode(1920, 1080, 60.0),
+            video_mode(1920, 1080, 143.98),
+            video_mode(1920, 1080, 144.0),
+            video_mode(1280, 720, 60.0),
+        ];
+
+        assert_eq!(refresh_rate_names(&video_modes, true), ["60 Hz", "144 Hz"]);
+        assert_eq!(
+            refresh_rate_names(&video_modes, false),
+            ["59.94 Hz", "60 Hz", "143.98 Hz", "144 Hz"]
+        );
+        // The exact rate is still there for setting the mode
+        assert_eq!(
+            nearest_refresh_rate(&video_modes, 1920, 1080, 143.98),
+            Some(143.98)
+        );
+    }
+
+    #[test]
+    fn headless_is_requested_by_dvar_or_environment() {
+        let _guard = testing::fresh_state();
+        // CI might set this for the whole run, so put it back afterwards
+        let old_env = std::env::var_os(HEADLESS_ENV_VAR);
+        std::env::remove_var(HEADLESS_ENV_VAR);
+        assert!(headless() == false);
+
+        dvar::register_bool(
+            "r_headless",
+            false,
+            dvar::DvarFlags::empty(),
+            None,
+        )
+        .unwrap();
+        assert!(headless() == false);
+        dvar::set_bool_internal("r_headless", true).unwrap();
+        assert!(headless());
+        dvar::set_bool_internal("r_headless", false).unwrap();
+
+        for (value, expected) in
+            [("1", true), ("yes", true), ("0", false), ("", false)]
+        {
+            std::env::set_var(HEADLESS_ENV_VAR, value);
+            assert_eq!(headless(), expected, "{:?}", value);
+        }
+
+        match old_env {
+            Some(value) => std::env::set_var(HEADLESS_ENV_VAR, value),
+            None => std::env::remove_var(HEADLESS_ENV_VAR),
+        }
+    }
+
+    // A smoke test of headless startup for GPU-less CI runners. They need a
+    // software rasterizer for wgpu to fall back to (e.g. lavapipe or
+    // llvmpipe on Linux, WARP on Windows), and an X server (e.g. Xvfb) for
+    // the window.
+    #[cfg(all(wgpu, xlib))]
+    #[test]
+    fn headless_renderer_inits_on_a_software_adapter() {
+        let _guard = testing::fresh_state();
+        if has_display() == false {
+            return;
+        }
+        let old_env = std::env::var_os(HEADLESS_ENV_VAR);
+        std::env::set_var(HEADLESS_ENV_VAR, "1");
+        register();
+
+        let res = init();
+        let device_type = RENDER_GLOBALS
+            .read()
+            .unwrap()
+            .adapter
+            .as_ref()
+            .and_then(platform::render::wgpu::Adapter::wgpu_info)
+            .map(|i| i.device_type);
+        shutdown();
+        match old_env {
+            Some(value) => std::env::set_var(HEADLESS_ENV_VAR, value),
+            None => std::env::remove_var(HEADLESS_ENV_VAR),
+        }
+
+        assert_eq!(res, Ok(()));
+        assert_eq!(device_type, Some(wgpu::DeviceType::Cpu));
+    }
+
+    #[test]
+    fn resize_window_clamps_the_matching_target() {
+        let _guard = testing::fresh_state();
+        let handle =
+            WindowHandle::new(raw_window_handle::RawWindowHandle::Web(
+                raw_window_handle::WebWindowHandle::empty(),
+            ));
+        let index = {
+            let mut rg = RENDER_GLOBALS.write().unwrap();
+            rg.windows.push(WindowTarget {
+                handle: Some(handle),
+                width: 1280,
+                height: 720,
+            });
+            rg.windows.len() - 1
+        };
+        let target = || {
+            RENDER_GLOBALS
+                .read()
+                .unwrap()
+                .windows
+                .get(index)
+                .copied()
+                .unwrap()
+        };
+
+        resize_window(handle, 1920, 1080);
+        assert_eq!((target().width, target().height), (1920, 1080));
+
+        resize_window(handle, 0, 0);
+        assert_eq!(
+            (target().width, target().height),
+            (MIN_HORIZONTAL_RESOLUTION, MIN_VERTICAL_RESOLUTION)
+        );
+
+        RENDER_GLOBALS.write().unwrap().windows.remove(index);
+    }
+
+    #[cfg(d3d9)]
+    #[allow(clippy::indexing_slicing)]
+    #[test]
+    fn caps_fields_match_the_original_offsets() {
+        // The byte offsets into D3DCAPS9 each check used to read at, in the
+        // same order as the checks
+        const BITS_OFFSETS: [usize; 32] = [
+            12, 12, 16, 16, 20, 20, 28, 28, 28, 32, 32, 32, 32, 32, 36, 40, 44,
+            48, 60, 60, 60, 60, 60, 60, 60, 64, 72, 72, 136, 212, 244, 236,
+        ];
+        const INT_OFFSETS: [usize; 10] =
+            [88, 92, 96, 148, 152, 188, 196, 204, 196, 204];
+
+        // Returns caps that are all zero except for a distinct value at
+        // `offset`, along with that value
+        let caps_with_value_at = |offset: usize| {
+            let mut caps = D3DCAPS9::default();
+            let value = 0xA5A5_0000 | offset as u32;
+            assert!(offset + 4 <= core::mem::size_of::<D3DCAPS9>());
+            // SAFETY: the write is within the struct (checked above), and
+            // every field at those offsets is a plain u32
+            unsafe {
+                core::ptr::addr_of_mut!(caps)
+                    .cast::<u8>()
+                    .add(offset)
+                    .cast::<u32>()
+                    .write_unaligned(value);
+            }
+            (caps, value)
+        };
+
+        for (i, check) in S_CAPS_CHECK_BITS.iter().enumerate() {
+            let (caps, value) = caps_with_value_at(BITS_OFFSETS[i]);
+            assert_eq!((check.field)(&caps), value, "{}", check.message);
+        }
+        for (i, check) in S_CAPS_CHECK_INT.iter().enumerate() {
+            let (caps, value) = caps_with_value_at(INT_OFFSETS[i]);
+            assert_eq!((check.field)(&caps), value, "{}", check.message);
+        }
+    }
+
+    #[cfg(d3d9)]
+    #[test]
+    fn known_good_caps_pass_every_check() {
+        let _guard = testing::fresh_state();
+        let caps = D3DCAPS9 {
+            Caps2: 0x2002_0000,
+            Caps3: 0x0000_0120,
+            PresentationIntervals: 0x8000_0001,
+            DevCaps: 0x0009_8400,
+            PrimitiveMiscCaps: 0x0002_08F2,
+            RasterCaps: 0x0200_0000,
+            ZCmpCaps: 0x0000_008D,
+            SrcBlendCaps: 0x0000_03FF,
+            DestBlendCaps: 0x0000_00D2,
+            TextureCaps: 0x0303_4B05,
+            TextureFilterCaps: 0x0300_0300,
+            VolumeTextureFilterCaps: 0x0000_0005,
+            StencilCaps: 0x0000_01FF,
+            DevCaps2: 0x0000_0001,
+            StretchRectFilterCaps: 0x0000_0200,
+            DeclTypes: 0x0000_0001,
+            MaxTextureWidth: 0x2000,
+            MaxTextureHeight: 0x2000,
+            MaxVolumeExtent: 0x800,
+            MaxTextureBlendStages: 0x100,
+            MaxSimultaneousTextures: 16,
+            MaxStreams: 16,
+            VertexShaderVersion: 0xFFFE_0300,
+            PixelShaderVersion: 0xFFFE_0300,
+            ..Default::default()
+        };
+        assert!(check_dx_caps(&caps).is_ok());
+
+        // Losing a required bit or falling short on a limit fails the check
+        let no_cubemaps = D3DCAPS9 {
+            TextureCaps: caps.TextureCaps & !0x0000_0800,
+            ..caps
+        };
+        assert!(check_dx_caps(&no_cubemaps).is_err());
+        let sm2_only = D3DCAPS9 {
+            VertexShaderVersion: 0xFFFE_0200,
+            ..caps
+        };
+        assert!(check_dx_caps(&sm2_only).is_err());
+    }
+
+    #[cfg(all(xlib, debug_assertions))]
+    #[test]
+    fn open_display_count_tracks_guards() {
+        use platform::display_server::xlib::{
+            open_display_count, XDisplayGuard,
+        };
+
+        let before = open_display_count();
+        // Nothing to check without an X server to connect to
+        let Some(display) = XDisplayGuard::open() else {
+            return;
+        };
+        assert_eq!(open_display_count(), before + 1);
+        drop(display);
+        assert_eq!(open_display_count(), before);
+
+        // monitor_info asserts this itself, but check from the outside too
+        for monitor in available_monitors() {
+            let _ = monitor_info(monitor);
+        }
+        assert_eq!(open_display_count(), before);
+    }
 }
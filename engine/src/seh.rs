@@ -14,14 +14,14 @@ pub fn update_current_language() {
             .unwrap();
     match lang {
         Language::RUSSIAN => {
-            dvar::set_bool("cg_subtitles", false).unwrap();
+            dvar::set_bool_internal("cg_subtitles", false).unwrap();
         }
         Language::KOREAN | Language::JAPANESE => {
             G_CURRENT_ASIAN.store(true, Ordering::SeqCst);
         }
         _ => {}
     };
-    dvar::set_string("language", &lang.to_string()).unwrap();
+    dvar::set_string_internal("language", &lang.to_string()).unwrap();
 }
 
 #[allow(
@@ -77,5 +77,130 @@ pub fn init_language() {
     )
     .unwrap();
 
+    dvar::register_string(
+        "language",
+        "",
+        dvar::DvarFlags::READ_ONLY,
+        Some("The name of the current language, set from loc_language"),
+    )
+    .unwrap();
+
+    dvar::register_bool(
+        "cg_subtitles",
+        true,
+        dvar::DvarFlags::ARCHIVE,
+        Some("Show subtitles"),
+    )
+    .unwrap();
+
     update_current_language();
 }
+
+static IN_PANIC_HOOK: AtomicBool = AtomicBool::new(false);
+
+fn panic_message(info: &std::panic::PanicInfo) -> String {
+    let payload = info.payload();
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| (*s).to_owned())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "Unknown panic payload".to_owned())
+}
+
+/// Number of console lines included in a render crash report.
+const CRASH_REPORT_CONSOLE_LINES: usize = 32;
+
+// Whether a panic at `location` came from the renderer, where most of the
+// engine's assertions live.
+fn is_render_code(location: &std::panic::Location) -> bool {
+    let file = location.file().replace('\\', "/");
+    file.ends_with("src/render.rs") || file.contains("src/platform/render/")
+}
+
+// Builds a dump of the renderer's state for a panic in render code. Only uses
+// the non-blocking `try_*` accessors, since the panicking thread might be
+// holding any of the locks involved.
+fn render_crash_report() -> String {
+    fn or_locked<T: core::fmt::Debug>(value: Option<T>) -> String {
+        value.map_or_else(|| "<locked>".to_owned(), |v| format!("{v:#?}"))
+    }
+
+    let console_lines = console::try_recent_lines(CRASH_REPORT_CONSOLE_LINES)
+        .map_or_else(|| "<locked>".to_owned(), |l| l.join("\n"));
+    format!(
+        "--- Renderer state ---\ngfx::Globals: {}\nvid::Config: {}\n\
+         --- Last {} console lines ---\n{}\n--- End of renderer state ---",
+        or_locked(render::try_r_glob()),
+        or_locked(vid::try_config()),
+        CRASH_REPORT_CONSOLE_LINES,
+        console_lines,
+    )
+}
+
+// Clears `IN_PANIC_HOOK` when the hook returns, so a later panic is reported
+// too.
+struct PanicHookGuard;
+
+impl Drop for PanicHookGuard {
+    fn drop(&mut self) {
+        IN_PANIC_HOOK.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Installs a panic hook that reports panics to the user.
+///
+/// The panic message and location are written to stderr and the log file,
+/// and then shown in a message box, since a GUI user will never see
+/// anything written to stderr. The previously-installed hook is still run
+/// afterwards.
+///
+/// The report deliberately bypasses the console and [`com::println!`], since
+/// the panicking thread might be holding one of their locks. For the same
+/// reason, it's dropped from the log file if the log file is locked.
+///
+/// Panics in render code (typically failed assertions) also get the current
+/// [`gfx::Globals`], [`vid::Config`], and recent console output written out,
+/// since the assertion message alone rarely says enough.
+///
+/// If another panic happens while the hook is running (e.g. on another
+/// thread), that panic skips straight to the previous hook instead of
+/// showing a second message box.
+#[allow(clippy::print_stderr)]
+pub fn install_panic_hook() {
+    let prev_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if IN_PANIC_HOOK.swap(true, Ordering::SeqCst) {
+            prev_hook(info);
+            return;
+        }
+        let _guard = PanicHookGuard;
+
+        let location = info.location().map_or_else(
+            || "unknown location".to_owned(),
+            |l| format!("{}:{}:{}", l.file(), l.line(), l.column()),
+        );
+        let text = format!("{}\n\nat {}", panic_message(info), location);
+        let report = info
+            .location()
+            .filter(|l| is_render_code(l))
+            .map(|_| render_crash_report());
+
+        let mut log_text = format!("Error: {}\n", text);
+        if let Some(report) = report {
+            log_text.push_str(&report);
+            log_text.push('\n');
+        }
+        eprint!("{}", log_text);
+        com::try_write_to_log_file(&log_text);
+        platform::system_beep();
+        sys::message_box(
+            None,
+            "Fatal Error",
+            &text,
+            sys::MessageBoxType::Ok,
+            Some(sys::MessageBoxIcon::Stop),
+        );
+
+        prev_hook(info);
+    }));
+}
@@ -17,7 +17,7 @@ use alloc::collections::VecDeque;
 use cfg_if::cfg_if;
 use core::{
     fmt::Display,
-    sync::atomic::{AtomicBool, AtomicIsize, Ordering::SeqCst},
+    sync::atomic::{AtomicBool, AtomicIsize, AtomicUsize, Ordering::SeqCst},
 };
 #[allow(unused_imports)]
 use lazy_static::lazy_static;
@@ -27,7 +27,7 @@ use std::{ffi::OsStr, path::Path};
 use std::{
     io::{Read, Write},
     path::PathBuf,
-    sync::{Mutex, RwLock},
+    sync::{Condvar, Mutex, RwLock},
     thread::{JoinHandle, ThreadId},
 };
 
@@ -54,7 +54,7 @@ cfg_if! {
             s, w,
             Win32::{
                 Media::timeGetTime,
-                Foundation::{HWND, LPARAM, MAX_PATH, RECT, WPARAM, CloseHandle},
+                Foundation::{HWND, LPARAM, MAX_PATH, RECT, WPARAM},
                 Graphics::Gdi::{
                     CreateFontW, GetDC, GetDeviceCaps, ReleaseDC,
                     CLIP_DEFAULT_PRECIS, COLOR_WINDOW, DEFAULT_CHARSET,
@@ -75,10 +75,12 @@ cfg_if! {
                         GetModuleFileNameW,
                         GetModuleHandleA
                     },
-                    Performance::QueryPerformanceFrequency,
+                    Performance::{
+                        QueryPerformanceCounter, QueryPerformanceFrequency,
+                    },
                     SystemInformation::{
-                        GetNativeSystemInfo, GlobalMemoryStatus, MEMORYSTATUS,
-                        SYSTEM_INFO,
+                        GetNativeSystemInfo, GlobalMemoryStatusEx,
+                        MEMORYSTATUSEX, SYSTEM_INFO,
                     },
                     Threading::{OpenProcess, PROCESS_ALL_ACCESS, Sleep},
                     WindowsProgramming::MulDiv,
@@ -86,8 +88,11 @@ cfg_if! {
                 UI::{
                     Controls::EM_LINESCROLL,
                     Input::KeyboardAndMouse::SetFocus,
+                    Shell::ShellExecuteA,
                     WindowsAndMessaging::{
-                        AdjustWindowRect, CloseWindow, CreateWindowExA,
+                        AdjustWindowRect, AdjustWindowRectEx, CloseWindow,
+                        CreateWindowExA, GetWindowLongA, SetWindowPos,
+                        GWL_EXSTYLE, GWL_STYLE, SWP_NOMOVE, SWP_NOZORDER,
                         DestroyWindow, DispatchMessageA, GetDesktopWindow,
                         GetMessageA, LoadCursorA, LoadIconA, LoadImageA,
                         MessageBoxA, PeekMessageA, RegisterClassA,
@@ -98,33 +103,36 @@ cfg_if! {
                         IDYES, IMAGE_BITMAP, LR_LOADFROMFILE,
                         MB_ICONINFORMATION, MB_ICONSTOP, MB_OK, MB_YESNO,
                         MB_YESNOCANCEL, MESSAGEBOX_STYLE, MSG, PM_NOREMOVE,
-                        STM_SETIMAGE, SW_HIDE, SW_SHOW, WINDOW_EX_STYLE,
+                        STM_SETIMAGE, SW_HIDE, SW_SHOW, SW_SHOWNORMAL,
+                        WINDOW_EX_STYLE,
                         WINDOW_STYLE, WM_SETFONT, WNDCLASSA, WS_BORDER,
                         WS_CAPTION, WS_CHILD, WS_POPUPWINDOW, WS_VISIBLE,
-                        WS_VSCROLL,
+                        WS_VSCROLL, HWND_NOTOPMOST, HWND_TOPMOST,
+                        SWP_FRAMECHANGED, SWP_SHOWWINDOW, WS_EX_LEFT,
+                        WS_EX_TOPMOST, WS_POPUP, WS_SYSMENU,
                     },
                 },
             },
         };
         use std::os::windows::prelude::*;
         use platform::{
-            os::win32::{con_wnd_proc, input_line_wnd_proc},
+            os::win32::{con_wnd_proc, input_line_wnd_proc, HandleGuard},
             FontHandle,
         };
     } else if #[cfg(xlib)] {
         use x11::xlib::{
-            CurrentTime, RevertToParent, XMapWindow, XOpenDisplay,
-            XSetInputFocus, XCloseDisplay, ClientMessage, XDestroyWindow,
-            XEvent, XNextEvent, XPending,
+            CurrentTime, RevertToParent, XMapWindow, XSetInputFocus,
+            ClientMessage, XDestroyWindow, XEvent, XNextEvent, XPending,
+            XFlush, XResizeWindow, XStoreName,
         };
         use platform::display_server::target::{
-            WindowEventExtXlib, XlibContext, WM_DELETE_WINDOW
+            WindowEventExtXlib, XlibContext, WM_DELETE_WINDOW, with_display,
         };
     } else if #[cfg(appkit)] {
         use platform::display_server::appkit::AppKitWindowHandleExt;
         use icrate::{
             AppKit::{NSApp, NSAlert},
-            Foundation::{NSDefaultRunLoopMode, NSDate, NSString}
+            Foundation::{NSDefaultRunLoopMode, NSDate, NSSize, NSString}
         };
         use objc2::ffi::NSUIntegerMax;
     }
@@ -149,17 +157,20 @@ cfg_if! {
     }
 }
 
-#[cfg(all(windows, any(x86_64, i686)))]
-use platform::arch::x86::target::cpuid;
+#[cfg(x86)]
+use platform::arch::x86::target::{cpuid, cpuid_count, xcr0};
 
 cfg_if! {
     if #[cfg(d3d9)] {
-        use cstr::cstr;
         use windows::Win32::Graphics::Direct3D9::{
             Direct3DCreate9, D3D_SDK_VERSION, D3DADAPTER_IDENTIFIER9,
             D3DADAPTER_DEFAULT
         };
-    } else if #[cfg(vulkan)] {
+    }
+}
+
+cfg_if! {
+    if #[cfg(any(d3d9, vulkan))] {
         use cstr::cstr;
     }
 }
@@ -258,7 +269,19 @@ pub fn init() {
         "Video card is \"{}\"",
         info.gpu_description,
     );
-    // TODO - vector support
+    com::println!(
+        console::Channel::SYSTEM,
+        "Supported SIMD extensions: {}",
+        info.simd_features,
+    );
+    dvar::register_string(
+        "sys_simd",
+        &info.simd_features,
+        dvar::DvarFlags::READ_ONLY,
+        Some("SIMD instruction set extensions supported by the CPU"),
+    )
+    .unwrap();
+    archive_info(&info);
     com::println!(console::Channel::SYSTEM, "");
     input::init();
 }
@@ -300,6 +323,58 @@ pub fn milliseconds() -> isize {
     time - TIME_BASE.load(SeqCst)
 }
 
+/// Returns the time in microseconds elapsed since the first call.
+///
+/// Unlike [`milliseconds`], this is high-resolution (`timeGetTime` only
+/// ticks every ~15ms by default) and monotonic, so it never goes backwards
+/// when the wall clock is adjusted. Use it for frame pacing and profiling;
+/// [`milliseconds`] is fine for coarse gameplay timing.
+#[cfg(windows)]
+#[allow(
+    clippy::undocumented_unsafe_blocks,
+    clippy::as_conversions,
+    clippy::cast_sign_loss,
+    clippy::cast_possible_truncation
+)]
+pub fn micros() -> u64 {
+    fn counter() -> i64 {
+        let mut count = 0i64;
+        // QueryPerformanceCounter can't fail on XP or later
+        let _ = unsafe { QueryPerformanceCounter(addr_of_mut!(count)) };
+        count
+    }
+
+    lazy_static! {
+        static ref BASE: i64 = counter();
+        static ref FREQUENCY: i64 = {
+            let mut frequency = 0i64;
+            let _ = unsafe {
+                QueryPerformanceFrequency(addr_of_mut!(frequency))
+            };
+            frequency.max(1)
+        };
+    }
+
+    // Done in 128 bits since the multiplication would overflow a 64-bit
+    // tick count after a few days at typical QPC frequencies
+    let ticks = (counter() - *BASE).max(0) as u128;
+    (ticks * 1_000_000 / *FREQUENCY as u128) as u64
+}
+
+/// Returns the time in microseconds elapsed since the first call.
+///
+/// Unlike [`milliseconds`], this is monotonic, so it never goes backwards
+/// when the wall clock is adjusted (e.g. by NTP). Use it for frame pacing
+/// and profiling; [`milliseconds`] is fine for coarse gameplay timing.
+#[cfg(not(windows))]
+pub fn micros() -> u64 {
+    lazy_static! {
+        static ref BASE: std::time::Instant = std::time::Instant::now();
+    }
+
+    u64::try_from(BASE.elapsed().as_micros()).unwrap_or(u64::MAX)
+}
+
 /// Returns true if `dir` contains one or more files.
 pub fn directory_has_contents(dir: impl AsRef<Path>) -> bool {
     if let Ok(mut d) = dir.as_ref().read_dir() {
@@ -470,6 +545,21 @@ pub fn get_semaphore_file_name() -> String {
     format!("__{}", get_executable_name())
 }
 
+/// Plays the platform's alert sound to go with an error dialog, if
+/// `sys_beepOnError` is set.
+pub fn beep_on_error() {
+    // Errors can be shown before the dvar's registered, so go with its
+    // default until then
+    if dvar::get_bool("sys_beepOnError").unwrap_or(true) == false {
+        return;
+    }
+
+    // There's no sound system yet, so this is the only cue there is whether
+    // or not nosnd was passed. Once there is one, it should play the cue
+    // itself unless S_NOSND is set, and only fall back to this if it is.
+    platform::system_beep();
+}
+
 /// Called when a file cannot be created (most likely because the target disk
 /// is full). Exits the process.
 pub fn no_free_files_error() -> ! {
@@ -478,6 +568,7 @@ pub fn no_free_files_error() -> ! {
     let title = locale::localize_ref("WIN_DISK_FULL_TITLE");
     let text = locale::localize_ref("WIN_DISK_FULL_BODY");
     let handle = None;
+    beep_on_error();
     message_box(handle, &title, &text, msg_box_type, Some(msg_box_icon));
     // DoSetEvent_UNK();
     std::process::exit(-1);
@@ -489,18 +580,18 @@ fn is_game_process(pid: u32) -> bool {
     else {
         return false;
     };
-
-    unsafe { CloseHandle(hprocess) };
+    let _hprocess = HandleGuard(hprocess);
 
     let Ok(hsnapshot) =
         (unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPMODULE, pid) })
     else {
         return false;
     };
+    let hsnapshot = HandleGuard(hsnapshot);
 
     let mut me = MODULEENTRY32W::default();
     me.dwSize = size_of_val(&me) as _;
-    if unsafe { Module32FirstW(hsnapshot, addr_of_mut!(me)) }.0 != 0 {
+    if unsafe { Module32FirstW(hsnapshot.get(), addr_of_mut!(me)) }.0 != 0 {
         let mut buf = [0u16; MAX_PATH as _];
         unsafe { GetModuleFileNameW(None, &mut buf) };
         buf[buf.len() - 1] = 0x0000;
@@ -511,13 +602,13 @@ fn is_game_process(pid: u32) -> bool {
                 break true;
             }
 
-            if unsafe { Module32NextW(hsnapshot, addr_of_mut!(me)) }.0 == 0 {
+            if unsafe { Module32NextW(hsnapshot.get(), addr_of_mut!(me)) }.0
+                == 0
+            {
                 break false;
             }
         };
 
-        unsafe { CloseHandle(hsnapshot) };
-
         ret
     } else {
         false
@@ -557,15 +648,54 @@ fn is_game_process(pid: u32) -> bool {
         },
     );
 
-    this == other
+    !other.is_empty() && this == other
 }
 
+// Without a way to inspect other processes, assume the recorded one is
+// gone, so that the improper-quit prompt still shows after a crash.
 #[cfg(not(any(windows, linux)))]
 const fn is_game_process(_pid: u32) -> bool {
-    true
+    false
+}
+
+/// Decides whether the instance that wrote `pid_read` to the semaphore file
+/// went away without cleaning up after itself. If the recorded process is
+/// still alive and is another copy of the game, it's simply a second
+/// instance that's running legitimately, not a crash. If the recorded PID
+/// is our own, the old process must be dead, since its PID was handed out
+/// again.
+fn previous_instance_died(
+    pid_read: u32,
+    this_pid: u32,
+    is_game_process: impl Fn(u32) -> bool,
+) -> bool {
+    pid_read == this_pid || !is_game_process(pid_read)
 }
 
-pub fn check_crash_or_rerun() -> bool {
+/// Reads the PID of the instance that wrote the semaphore file at `path`,
+/// or returns [`None`] if the file can't be read or doesn't hold one.
+fn read_semaphore_pid(path: &Path) -> Option<u32> {
+    let mut buf = [0u8; 4];
+    match std::fs::File::open(path).and_then(|mut f| f.read(&mut buf)) {
+        Ok(4) => Some(u32::from_ne_bytes(buf)),
+        _ => None,
+    }
+}
+
+/// Guards against the game having quit improperly last time, using a
+/// semaphore file holding the PID of the instance that wrote it. The file
+/// is written on startup and removed on a normal exit, so finding it means
+/// either the last instance crashed or another instance is still running.
+///
+/// The recorded PID is validated with [`is_game_process`], so that a second
+/// copy of the game that's running legitimately isn't mistaken for a crash.
+/// If `allow_multiple_instances` is set, the existing file isn't checked at
+/// all, since it may well belong to another live instance; it's still
+/// overwritten with this instance's PID, so that a later launch can detect
+/// this one crashing.
+///
+/// Returns `false` if the game shouldn't start.
+pub fn check_crash_or_rerun(allow_multiple_instances: bool) -> bool {
     let Some(semaphore_folder_path) = get_semaphore_folder_path() else {
         return true;
     };
@@ -578,33 +708,31 @@ pub fn check_crash_or_rerun() -> bool {
         semaphore_folder_path.join(get_semaphore_file_name());
     let semaphore_file_exists = semaphore_file_path.exists();
 
-    if semaphore_file_exists {
-        if let Ok(mut f) = std::fs::File::open(semaphore_file_path.clone()) {
-            let mut buf = [0u8; 4];
-            if let Ok(4) = f.read(&mut buf) {
-                // let pid_read = u32::from_ne_bytes(buf);
-                // if pid_read != std::process::id()
-                // || is_game_process(pid_read) == false
-                // {
-                // return true;
-                // }
-
-                let msg_box_type = MessageBoxType::YesNoCancel;
-                let msg_box_icon = MessageBoxIcon::Stop;
-                let title = locale::localize_ref("WIN_IMPROPER_QUIT_TITLE");
-                let text = locale::localize_ref("WIN_IMPROPER_QUIT_BODY");
-                let handle = None;
-                match message_box(
-                    handle,
-                    &title,
-                    &text,
-                    msg_box_type,
-                    Some(msg_box_icon),
-                ) {
-                    Some(MessageBoxResult::Yes) => com::force_safe_mode(),
-                    Some(MessageBoxResult::Cancel) | None => return false,
-                    _ => {}
-                };
+    if semaphore_file_exists && !allow_multiple_instances {
+        if let Some(pid_read) = read_semaphore_pid(&semaphore_file_path) {
+            if !previous_instance_died(
+                pid_read,
+                std::process::id(),
+                is_game_process,
+            ) {
+                return true;
+            }
+
+            let msg_box_type = MessageBoxType::YesNoCancel;
+            let msg_box_icon = MessageBoxIcon::Stop;
+            let title = locale::localize_ref("WIN_IMPROPER_QUIT_TITLE");
+            let text = locale::localize_ref("WIN_IMPROPER_QUIT_BODY");
+            let handle = None;
+            match message_box(
+                handle,
+                &title,
+                &text,
+                msg_box_type,
+                Some(msg_box_icon),
+            ) {
+                Some(MessageBoxResult::Yes) => com::force_safe_mode(),
+                Some(MessageBoxResult::Cancel) | None => return false,
+                _ => {}
             };
         }
     }
@@ -639,12 +767,10 @@ pub fn check_crash_or_rerun() -> bool {
     )
 }
 
+/// Returns the arguments the game was launched with (excluding the
+/// executable's path), separated by spaces.
 pub fn get_cmdline() -> String {
-    let mut cmd_line = String::new();
-    std::env::args().for_each(|arg| {
-        cmd_line.push_str(&arg);
-    });
-    cmd_line.trim().to_owned()
+    std::env::args().skip(1).collect::<Vec<_>>().join(" ")
 }
 
 pub fn start_minidump(b: bool) {
@@ -681,13 +807,17 @@ pub fn get_physical_cpu_count() -> usize {
     system_info.dwNumberOfProcessors as _
 }
 
-// TODO - use GlobalMemoryStatusEx
+/// Returns the total amount of physical memory, in MB, or 0 if it couldn't
+/// be determined.
 #[cfg(all(windows, x86))]
 pub fn system_memory_mb() -> u64 {
-    let mut memory_status = MEMORYSTATUS::default();
+    let mut memory_status = MEMORYSTATUSEX::default();
     memory_status.dwLength = size_of_val(&memory_status) as _;
-    unsafe { GlobalMemoryStatus(addr_of_mut!(memory_status)) };
-    memory_status.dwAvailPhys as _
+    let ok = unsafe { GlobalMemoryStatusEx(addr_of_mut!(memory_status)) };
+    if ok.as_bool() == false {
+        return 0;
+    }
+    memory_status.ullTotalPhys / (1024 * 1024)
 }
 
 #[cfg(all(windows, x86))]
@@ -762,9 +892,10 @@ pub fn get_physical_cpu_count() -> usize {
 
 #[cfg(not(all(windows, x86)))]
 pub fn system_memory_mb() -> u64 {
-    let mut system = sysinfo::System::new_all();
-    system.refresh_all();
-    system.total_memory()
+    let mut system = sysinfo::System::new();
+    system.refresh_memory();
+    // sysinfo reports memory in bytes
+    system.total_memory() / (1024 * 1024)
 }
 
 #[cfg(not(all(windows, x86)))]
@@ -814,8 +945,103 @@ pub fn get_cpu_name() -> String {
     "Unknown CPU name".to_owned()
 }
 
-#[cfg(wgpu)]
+/// Detects the SIMD instruction set extensions supported by the CPU.
+///
+/// Returns a space-separated list of the detected extensions, or `"none"`
+/// if none were detected.
+#[cfg(x86)]
+pub fn get_cpu_simd_features() -> String {
+    let max_leaf = cpuid(0x0000_0000).eax;
+    let leaf_1 = cpuid(0x0000_0001);
+    let mut features = Vec::new();
+
+    if leaf_1.edx & (1 << 26) != 0 {
+        features.push("SSE2");
+    }
+    if leaf_1.ecx & (1 << 0) != 0 {
+        features.push("SSE3");
+    }
+    if leaf_1.ecx & (1 << 9) != 0 {
+        features.push("SSSE3");
+    }
+    if leaf_1.ecx & (1 << 19) != 0 {
+        features.push("SSE4.1");
+    }
+    if leaf_1.ecx & (1 << 20) != 0 {
+        features.push("SSE4.2");
+    }
+
+    // AVX is only usable if the OS saves the YMM registers on a context
+    // switch. OSXSAVE (bit 27) says XCR0 can be read, and XCR0 bits 1 and 2
+    // say whether the XMM and YMM state are actually being saved.
+    let osxsave = leaf_1.ecx & (1 << 27) != 0;
+    let avx = leaf_1.ecx & (1 << 28) != 0 && osxsave && xcr0() & 0b110 == 0b110;
+    if avx {
+        features.push("AVX");
+    }
+    if avx && max_leaf >= 0x0000_0007 {
+        let leaf_7 = cpuid_count(0x0000_0007, 0);
+        if leaf_7.ebx & (1 << 5) != 0 {
+            features.push("AVX2");
+        }
+    }
+
+    if features.is_empty() {
+        "none".to_owned()
+    } else {
+        features.join(" ")
+    }
+}
+
+/// Detects the SIMD instruction set extensions supported by the CPU.
+///
+/// Returns a space-separated list of the detected extensions, or `"none"`
+/// if none were detected.
+#[cfg(aarch64)]
+pub fn get_cpu_simd_features() -> String {
+    let mut features = Vec::new();
+
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        features.push("NEON");
+    }
+    if std::arch::is_aarch64_feature_detected!("sve") {
+        features.push("SVE");
+    }
+    if std::arch::is_aarch64_feature_detected!("sve2") {
+        features.push("SVE2");
+    }
+
+    if features.is_empty() {
+        "none".to_owned()
+    } else {
+        features.join(" ")
+    }
+}
+
+/// Detects the SIMD instruction set extensions supported by the CPU.
+///
+/// No detection is done for other architectures (including wasm), so this
+/// always returns `"none"`.
+#[cfg(not(any(x86, aarch64)))]
+pub fn get_cpu_simd_features() -> String {
+    "none".to_owned()
+}
+
+/// Returns the name of the video card the selected render backend would
+/// use.
 pub fn detect_video_card() -> String {
+    match render::backend() {
+        #[cfg(d3d9)]
+        render::RenderBackend::D3d9 => detect_video_card_d3d9(),
+        #[cfg(vulkan)]
+        render::RenderBackend::Vulkan => detect_video_card_vulkan(),
+        #[cfg(wgpu)]
+        render::RenderBackend::Wgpu => detect_video_card_wgpu(),
+    }
+}
+
+#[cfg(wgpu)]
+fn detect_video_card_wgpu() -> String {
     let adapter = pollster::block_on(platform::render::wgpu::Adapter::new(
         &platform::render::wgpu::Instance::new(),
         None,
@@ -824,7 +1050,7 @@ pub fn detect_video_card() -> String {
 }
 
 #[cfg(d3d9)]
-pub fn detect_video_card() -> String {
+fn detect_video_card_d3d9() -> String {
     let Some(d3d9) = (unsafe { Direct3DCreate9(D3D_SDK_VERSION) }) else {
         return String::from("Unknown video card");
     };
@@ -849,7 +1075,7 @@ pub fn detect_video_card() -> String {
 }
 
 #[cfg(vulkan)]
-pub fn detect_video_card() -> String {
+fn detect_video_card_vulkan() -> String {
     use ash::{vk, Entry};
 
     let entry = unsafe { Entry::load().unwrap() };
@@ -944,9 +1170,12 @@ pub fn init_timing() {
 }
 
 /// A collection of different info about the system, including amount of RAM,
-/// CPU clock speed, core counts, and CPU and GPU descriptions.
+/// CPU clock speed, core counts, CPU and GPU descriptions, and OS version.
 #[derive(Clone, Default)]
 pub struct SysInfo {
+    /// Name and version of the OS. Retrieved with
+    /// [`platform::os_version`].
+    pub os_version: String,
     /// The description of the GPU (usually its name).
     pub gpu_description: String,
     /// Number of logical, not physical, cores in the system.
@@ -962,19 +1191,23 @@ pub struct SysInfo {
     /// CPU clock rate in GHz.
     pub cpu_ghz: f32,
     pub configure_ghz: f32,
+    /// Space-separated list of the SIMD extensions supported by the CPU.
+    pub simd_features: String,
 }
 
 impl Display for SysInfo {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(
             f,
-            "GPU Description: {}\nCPU: {} ({})\nCores: {} ({} \
-             physical)\nSystem RAM: {}MiB",
+            "OS: {}\nGPU Description: {}\nCPU: {} ({})\nCores: {} ({} \
+             physical)\nSIMD: {}\nSystem RAM: {}MiB",
+            self.os_version,
             self.gpu_description,
             self.cpu_name,
             self.cpu_vendor,
             self.logical_cpu_count,
             self.physical_cpu_count,
+            self.simd_features,
             self.sys_mb
         )
     }
@@ -1001,18 +1234,20 @@ lazy_static! {
 pub fn find_info() -> SysInfo {
     let mut sys_info = SYS_INFO.write().unwrap();
     if sys_info.is_none() {
+        let os_version = platform::os_version();
         let gpu_description = detect_video_card();
         let logical_cpu_count = get_logical_cpu_count();
         let physical_cpu_count = get_physical_cpu_count();
-        let sys_mb = (system_memory_mb() as f64 / (1024f64 * 1024f64))
-            .clamp(0f64, f64::MAX) as u64;
+        let sys_mb = system_memory_mb();
         let cpu_vendor = get_cpu_vendor();
         let cpu_name = get_cpu_name();
         let cpu_ghz = 1.0f64
             / (*MSEC_PER_RAW_TIMER_TICK.read().unwrap() * 1_000_000.0f64);
         let configure_ghz = cpu_ghz;
+        let simd_features = get_cpu_simd_features();
 
         *sys_info = Some(SysInfo {
+            os_version,
             gpu_description,
             logical_cpu_count,
             physical_cpu_count,
@@ -1021,6 +1256,7 @@ pub fn find_info() -> SysInfo {
             cpu_name,
             cpu_ghz: cpu_ghz as _,
             configure_ghz: configure_ghz as _,
+            simd_features,
         });
     }
     sys_info.as_ref().unwrap().clone()
@@ -1038,6 +1274,10 @@ pub enum EventType {
 #[derive(Clone, Debug)]
 pub struct Event {
     time: isize,
+    // When the event was queued, as returned by `milliseconds`. Unlike
+    // `time`, which may come from the OS's own clock, this can be compared
+    // against the current time.
+    queued_at: isize,
     event_type: EventType,
 }
 
@@ -1045,9 +1285,24 @@ impl Event {
     pub fn new(time: Option<isize>, event_type: EventType) -> Self {
         Self {
             time: time.unwrap_or_default(),
+            queued_at: 0,
             event_type,
         }
     }
+
+    pub const fn time(&self) -> isize {
+        self.time
+    }
+
+    /// Returns when the event was passed to [`enqueue_event`], as returned
+    /// by [`milliseconds`].
+    pub const fn queued_at(&self) -> isize {
+        self.queued_at
+    }
+
+    pub const fn event_type(&self) -> &EventType {
+        &self.event_type
+    }
 }
 
 lazy_static! {
@@ -1056,13 +1311,36 @@ lazy_static! {
 }
 
 pub fn enqueue_event(mut ev: Event) {
+    ev.queued_at = milliseconds();
     if ev.time == 0 {
-        ev.time = milliseconds();
+        ev.time = ev.queued_at;
     }
 
+    input::record::record_event(&ev);
+    show_event("queue", &ev);
     EVENT_QUEUE.write().unwrap().push_back(ev);
 }
 
+/// Prints a one-line description of `ev` if `com_showEvents` is set, with
+/// `action` saying what's happening to it (e.g. `"queue"`).
+pub fn show_event(action: &str, ev: &Event) {
+    if dvar::get_bool("com_showEvents").unwrap_or(false) {
+        com::println!(
+            console::Channel::SYSTEM,
+            "event {}: {} {:?}",
+            action,
+            ev.time,
+            ev.event_type
+        );
+    }
+}
+
+/// Retrieves the oldest event queued with [`enqueue_event`], or [`None`] if
+/// there aren't any.
+pub fn next_event() -> Option<Event> {
+    EVENT_QUEUE.write().unwrap().pop_front()
+}
+
 /// Called when the renderer encounters an unrecoverable fatal error. Exits the
 /// process.
 pub fn render_fatal_error() -> ! {
@@ -1071,6 +1349,7 @@ pub fn render_fatal_error() -> ! {
     let title = locale::localize_ref("WIN_RENDER_INIT_TITLE");
     let text = locale::localize_ref("WIN_RENDER_INIT_BODY");
     let handle = None;
+    beep_on_error();
     message_box(handle, &title, &text, msg_box_type, Some(msg_box_icon));
     // DoSetEvent_UNK();
     std::process::exit(-1);
@@ -1146,6 +1425,16 @@ pub fn query_render_device_ok_event() -> SignalState {
     ev.query()
 }
 
+pub fn set_render_device_ok_event() {
+    let mut ev = RENDER_DEVICE_OK_EVENT.lock().unwrap().clone();
+    ev.set();
+}
+
+pub fn clear_render_device_ok_event() {
+    let mut ev = RENDER_DEVICE_OK_EVENT.lock().unwrap().clone();
+    ev.clear();
+}
+
 lazy_static! {
     static ref RENDER_COMPLETED_EVENT: Mutex<SmpEvent> =
         Mutex::new(SmpEvent::new(SignalState::Signaled, true));
@@ -1160,9 +1449,11 @@ pub fn create_thread<T, F: Fn() -> T + Send + Sync + 'static>(
     name: &str,
     function: F,
 ) -> Option<JoinHandle<()>> {
+    let thread_name = name.to_owned();
     match std::thread::Builder::new()
         .name(name.into())
         .spawn(move || {
+            platform::set_thread_name(&thread_name);
             std::thread::park();
             function();
         }) {
@@ -1182,212 +1473,269 @@ pub fn create_thread<T, F: Fn() -> T + Send + Sync + 'static>(
 pub fn spawn_render_thread<F: Fn() -> ! + Send + Sync + 'static>(
     function: F,
 ) -> bool {
+    let function = move || {
+        lock_thread_affinity(true);
+        function()
+    };
     create_thread("Backend", function).map_or(false, |h| {
         h.thread().unpark();
         true
     })
 }
 
-// const MAX_CPUS: usize = 32;
-//
-// lazy_static! {
-// static ref S_CPU_COUNT: AtomicUsize = AtomicUsize::new(0);
-// static ref S_AFFINITY_MASK_FOR_PROCESS: AtomicUsize = AtomicUsize::new(0);
-// static ref S_AFFINITY_MASK_FOR_CPU: Arc<RwLock<ArrayVec<usize, MAX_CPUS>>> =
-// Arc::new(RwLock::new(ArrayVec::new())); }
-//
-// cfg_if! {
-// if #[cfg(target_os = "windows")] {
-// pub fn init_threads() {
-// let hprocess = unsafe { GetCurrentProcess() };
-// let systemaffinitymask: c_ulonglong = 0;
-// let processaffinitymask: c_ulonglong = 0;
-// unsafe { GetProcessAffinityMask(hprocess, addr_of!(processaffinitymask) as
-// *mut _, addr_of!(systemaffinitymask) as *mut _) };
-// S_AFFINITY_MASK_FOR_PROCESS.store(processaffinitymask as _,
-// Ordering::SeqCst); let mut cpu_count = 0usize;
-// let mut affinity_mask_for_cpu: Vec<usize> = Vec::new();
-// affinity_mask_for_cpu.push(1);
-// while (!affinity_mask_for_cpu[0] + 1 & processaffinitymask as usize) != 0 {
-// if (affinity_mask_for_cpu[0] & processaffinitymask as usize) != 0 {
-// affinity_mask_for_cpu[cpu_count + 1] = affinity_mask_for_cpu[0];
-// cpu_count += 1;
-// if cpu_count == MAX_CPUS { break; }
-// }
-// affinity_mask_for_cpu[0] = affinity_mask_for_cpu[0] << 1;
-// }
-//
-// if cpu_count == 0 || cpu_count == 1 {
-// S_CPU_COUNT.store(1, Ordering::SeqCst);
-// S_AFFINITY_MASK_FOR_CPU.clone().write().unwrap()[0] = 0xFFFFFFFF;
-// return;
-// }
-//
-// S_CPU_COUNT.store(cpu_count, Ordering::SeqCst);
-// let lock = S_AFFINITY_MASK_FOR_CPU.clone();
-// let mut writer = lock.write().unwrap();
-// writer[0] = affinity_mask_for_cpu[1];
-// writer[1] = affinity_mask_for_cpu[cpu_count];
-// if cpu_count > 2 {
-// if cpu_count == 3 {
-// writer[2] = affinity_mask_for_cpu[2];
-// } else if cpu_count == 4 {
-// writer[2] = affinity_mask_for_cpu[2];
-// writer[3] = affinity_mask_for_cpu[3];
-// } else {
-// writer.iter_mut().for_each(|a| *a = 0xFFFFFFFF);
-// if cpu_count > MAX_CPUS {
-// S_CPU_COUNT.store(MAX_CPUS, Ordering::SeqCst);
-// }
-// }
-// }
-// }
-// }
-// }
-
-// cfg_if! {
-// if #[cfg(target_os = "windows")] {
-// lazy_static! {
-// static ref THREAD_LOCK: Arc<Mutex<()>> = Arc::new(Mutex::new(()));
-// static ref H_THREADS: Arc<RwLock<ArrayVec<HANDLE, 15>>> =
-// Arc::new(RwLock::new(ArrayVec::new())); }
-//
-// pub fn lock_thread_affinity() {
-// let cpu_count = S_CPU_COUNT.load(Ordering::SeqCst);
-//
-// if cpu_count == 1 {
-// return;
-// }
-//
-// let thread_lock = THREAD_LOCK.clone();
-// let _thread_lock_2 = thread_lock.lock().unwrap();
-//
-// let threads_lock = H_THREADS.clone();
-// let threads_reader = threads_lock.read().unwrap();
-//
-// let affinity_mask_lock = S_AFFINITY_MASK_FOR_CPU.clone();
-// let affinity_mask_reader = affinity_mask_lock.read().unwrap();
-//
-// if threads_reader[0].0 != 0 {
-// unsafe { SetThreadAffinityMask(threads_reader[0], affinity_mask_reader[0]) };
-// }
-//
-// if threads_reader[1].0 != 0 {
-// unsafe { SetThreadAffinityMask(threads_reader[1], affinity_mask_reader[1]) };
-// }
-//
-// if cpu_count < 3 && threads_reader[13].0 != 0 {
-// unsafe { SetThreadAffinityMask(threads_reader[13], affinity_mask_reader[1])
-// }; } else if threads_reader[13].0 != 0 {
-// unsafe { SetThreadAffinityMask(threads_reader[13], affinity_mask_reader[2])
-// }; }
-//
-// if cpu_count > 2 && threads_reader[2].0 != 0 {
-// unsafe { SetThreadAffinityMask(threads_reader[2], affinity_mask_reader[2]) };
-// }
-//
-// if cpu_count > 3 && threads_reader[3].0 != 0 {
-// unsafe { SetThreadAffinityMask(threads_reader[3], affinity_mask_reader[3]) };
-// }
-// }
-// }
-// }
-
-// cfg_if! {
-// if #[cfg(target_os = "windows")] {
-// pub fn unlock_thread_affinity() {
-// let cpu_count = S_CPU_COUNT.load(Ordering::SeqCst);
-//
-// if cpu_count == 1 {
-// return;
-// }
-//
-// let thread_lock = THREAD_LOCK.clone();
-// let _thread_lock_2 = thread_lock.lock().unwrap();
-//
-// let threads_lock = H_THREADS.clone();
-// let threads_reader = threads_lock.read().unwrap();
-//
-// let affinity_mask = S_AFFINITY_MASK_FOR_PROCESS.load(Ordering::SeqCst);
-//
-// if threads_reader[0].0 != 0 {
-// unsafe { SetThreadAffinityMask(threads_reader[0], affinity_mask) };
-// }
-//
-// if threads_reader[1].0 != 0 {
-// unsafe { SetThreadAffinityMask(threads_reader[1], affinity_mask) };
-// }
-//
-// if threads_reader[13].0 != 0 {
-// unsafe { SetThreadAffinityMask(threads_reader[0], affinity_mask) };
-// }
-//
-// if threads_reader[2].0 != 0 {
-// unsafe { SetThreadAffinityMask(threads_reader[1], affinity_mask) };
-// }
-//
-// if threads_reader[3].0 != 0 {
-// unsafe { SetThreadAffinityMask(threads_reader[1], affinity_mask) };
-// }
-// }
-// }
-// }
-
-// fn register_info_dvars() {
-// dvar::register_float(
-// "sys_configureGHz",
-// 0.0,
-// Some(f32::MIN),
-// Some(f32::MAX),
-// dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::WRITE_PROTECTED,
-// Some("Normalized total CPU power, based on cpu type, count, and speed; used
-// in autoconfigure") );
-// dvar::register_int(
-// "sys_sysMB",
-// 0,
-// Some(i32::MIN),
-// Some(i32::MAX),
-// dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::WRITE_PROTECTED,
-// Some("Physical memory in the system"),
-// );
-// dvar::register_string(
-// "sys_gpu",
-// "",
-// dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::WRITE_PROTECTED,
-// Some("GPU description"),
-// );
-// dvar::register_int(
-// "sys_configSum",
-// 0,
-// Some(i32::MIN),
-// Some(i32::MAX),
-// dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::WRITE_PROTECTED,
-// Some("Configuration checksum"),
-// );
-// TODO - SIMD support Dvar
-// dvar::register_float(
-// "sys_cpuGHz",
-// info().unwrap().cpu_ghz,
-// Some(f32::MIN),
-// Some(f32::MAX),
-// dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::WRITE_PROTECTED,
-// Some("Measured CPU speed"),
-// );
-// dvar::register_string(
-// "sys_cpuName",
-// &info().unwrap().cpu_name,
-// dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::WRITE_PROTECTED,
-// Some("CPU name description"),
-// );
-// }
-//
-// fn archive_info(sum: i32) {
-// register_info_dvars();
-// dvar::set_float_internal("sys_configureGHz", info().unwrap().configure_ghz);
-// dvar::set_int_internal("sys_sysMB", info().unwrap().sys_mb as _);
-// dvar::set_string_internal("sys_gpu", &info().unwrap().gpu_description);
-// dvar::set_int_internal("sys_configSum", sum);
-// }
+/// The most worker threads there can be, one for each of the `Worker0`
+/// through `Worker7` slots in the thread table.
+pub const MAX_WORKER_THREADS: usize = 8;
+
+// Where the worker threads start in `THREAD_ID`.
+const FIRST_WORKER_THREAD: usize = 2;
+
+// The physical cores left to the main and render threads.
+const RESERVED_CPUS: usize = 2;
+
+type Work = Box<dyn FnOnce() + Send + 'static>;
+
+lazy_static! {
+    static ref WORK_QUEUE: Mutex<VecDeque<Work>> = Mutex::new(VecDeque::new());
+    static ref WORK_AVAILABLE: Condvar = Condvar::new();
+    static ref WORKER_THREAD_COUNT: AtomicUsize = AtomicUsize::new(0);
+}
+
+/// Returns how many worker threads to run on a machine with
+/// `physical_cpu_count` physical cores: one per core that the main and
+/// render threads don't already have, up to [`MAX_WORKER_THREADS`].
+pub const fn worker_thread_count_for(physical_cpu_count: usize) -> usize {
+    let count = physical_cpu_count.saturating_sub(RESERVED_CPUS);
+    if count > MAX_WORKER_THREADS {
+        MAX_WORKER_THREADS
+    } else {
+        count
+    }
+}
+
+/// Returns how many worker threads [`init_worker_threads`] started.
+pub fn worker_thread_count() -> usize {
+    WORKER_THREAD_COUNT.load_relaxed()
+}
+
+// Runs `work`, logging it if it panics instead of letting the panic take
+// the thread down with it, so that one bad job can't shrink the pool.
+fn run_work(work: Work) {
+    if let Err(payload) =
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(work))
+    {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| (*s).to_owned())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic payload".to_owned());
+        com::print_errorln!(
+            console::Channel::SYSTEM,
+            "queued work panicked: {}",
+            message
+        );
+    }
+}
+
+// Runs whatever's queued with `queue_work`, waiting for more whenever the
+// queue's empty.
+fn worker_thread() -> ! {
+    loop {
+        let work = {
+            let mut queue = WORK_QUEUE.lock().unwrap();
+            loop {
+                if let Some(work) = queue.pop_front() {
+                    break work;
+                }
+                queue = WORK_AVAILABLE.wait(queue).unwrap();
+            }
+        };
+        run_work(work);
+    }
+}
+
+// Called on worker thread `worker` as it starts. The workers are left to
+// the OS's scheduler for now; pinning them (with `set_thread_affinity`)
+// should happen here.
+const fn set_worker_thread_affinity(_worker: usize) {}
+
+/// Starts the worker threads that run work queued with [`queue_work`] (see
+/// [`worker_thread_count_for`] for how many). Each one is registered in the
+/// thread table under its `WorkerN` context before it starts running.
+///
+/// Shouldn't be called more than once.
+pub fn init_worker_threads() {
+    assert_eq!(worker_thread_count(), 0);
+
+    let count = worker_thread_count_for(get_physical_cpu_count());
+    for worker in 0..count {
+        let name = format!("Worker{}", worker);
+        let Some(handle) = create_thread(&name, move || {
+            set_worker_thread_affinity(worker);
+            worker_thread()
+        }) else {
+            break;
+        };
+        *THREAD_ID
+            .write()
+            .unwrap()
+            .get_mut(FIRST_WORKER_THREAD + worker)
+            .unwrap() = Some(handle.thread().id());
+        WORKER_THREAD_COUNT.increment_wrapping();
+        handle.thread().unpark();
+    }
+
+    let count = worker_thread_count();
+    let c = if count == 1 { "" } else { "s" };
+    com::println!(
+        console::Channel::SYSTEM,
+        "Started {} worker thread{}",
+        count,
+        c,
+    );
+}
+
+/// Hands `work` to the next idle worker thread. If there aren't any worker
+/// threads, runs it on the calling thread instead.
+///
+/// If `work` panics, the panic is logged and the worker moves on to the
+/// next job.
+pub fn queue_work(work: impl FnOnce() + Send + 'static) {
+    if worker_thread_count() == 0 {
+        run_work(Box::new(work));
+        return;
+    }
+
+    WORK_QUEUE.lock().unwrap().push_back(Box::new(work));
+    WORK_AVAILABLE.notify_one();
+}
+
+/// Restricts the thread with ID `thread` (see
+/// [`platform::current_thread_id`]) to the logical CPUs set in `cpu_mask`,
+/// bit `n` being CPU `n`. Returns [`Err`] if that isn't supported here.
+pub fn set_thread_affinity(thread: u64, cpu_mask: u64) -> Result<(), ()> {
+    platform::set_thread_affinity(thread, cpu_mask)
+}
+
+/// Returns the CPU masks to pin the main and render threads to, in that
+/// order, or [`None`] if there aren't enough cores to give them one each.
+///
+/// Each gets a single logical CPU on a different physical core. Which
+/// logical CPUs share a core varies, but with `n` threads per core, CPUs 0
+/// and `n` are on different ones whether siblings are numbered together
+/// (Windows) or a core count apart (Linux).
+pub const fn main_and_render_affinity(
+    logical_cpu_count: usize,
+    physical_cpu_count: usize,
+) -> Option<(u64, u64)> {
+    if physical_cpu_count < 2 || logical_cpu_count < physical_cpu_count {
+        return None;
+    }
+
+    let threads_per_core = logical_cpu_count / physical_cpu_count;
+    if threads_per_core >= 64 {
+        return None;
+    }
+    Some((1, 1 << threads_per_core))
+}
+
+// Pins the calling thread, which has to be either the main or the render
+// thread, to its own core, if sys_smp_allowed is set and there are enough
+// cores to go round.
+fn lock_thread_affinity(render_thread: bool) {
+    if dvar::get_bool("sys_smp_allowed").unwrap_or(false) == false {
+        return;
+    }
+
+    let Some((main, render)) = main_and_render_affinity(
+        get_logical_cpu_count(),
+        get_physical_cpu_count(),
+    ) else {
+        return;
+    };
+
+    let mask = if render_thread { render } else { main };
+    if set_thread_affinity(platform::current_thread_id(), mask).is_err() {
+        com::dprintln!(
+            console::Channel::SYSTEM,
+            "sys::lock_thread_affinity: couldn't pin {} thread to {:#x}",
+            if render_thread { "render" } else { "main" },
+            mask,
+        );
+    }
+}
+
+/// Pins the main thread to a core of its own. Has to be called on the main
+/// thread; the render thread pins itself when it starts.
+pub fn lock_main_thread_affinity() {
+    assert!(is_main_thread());
+    lock_thread_affinity(false);
+}
+
+/// Registers the dvars describing the system. They're write-protected, so
+/// only the engine itself can change them; see [`archive_info`].
+fn register_info_dvars() {
+    dvar::register_float(
+        "sys_configureGHz",
+        0.0,
+        Some(0.0),
+        Some(f32::MAX),
+        dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::WRITE_PROTECTED,
+        Some(
+            "Normalized total CPU power, based on cpu type, count, and \
+             speed; used in autoconfigure",
+        ),
+    )
+    .unwrap();
+    dvar::register_int(
+        "sys_sysMB",
+        0,
+        Some(0),
+        Some(i32::MAX),
+        dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::WRITE_PROTECTED,
+        Some("Physical memory in the system"),
+    )
+    .unwrap();
+    dvar::register_string(
+        "sys_gpu",
+        "",
+        dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::WRITE_PROTECTED,
+        Some("GPU description"),
+    )
+    .unwrap();
+    dvar::register_float(
+        "sys_cpuGHz",
+        0.0,
+        Some(0.0),
+        Some(f32::MAX),
+        dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::WRITE_PROTECTED,
+        Some("Measured CPU speed"),
+    )
+    .unwrap();
+    dvar::register_string(
+        "sys_cpuName",
+        "",
+        dvar::DvarFlags::ARCHIVE | dvar::DvarFlags::WRITE_PROTECTED,
+        Some("CPU name description"),
+    )
+    .unwrap();
+}
+
+/// Registers the system info dvars and fills them in from `info`. Has to go
+/// through the internal setters, since the user ones refuse to touch
+/// write-protected dvars.
+fn archive_info(info: &SysInfo) {
+    register_info_dvars();
+    dvar::set_float_internal("sys_configureGHz", info.configure_ghz).unwrap();
+    dvar::set_int_internal(
+        "sys_sysMB",
+        i32::try_from(info.sys_mb).unwrap_or(i32::MAX),
+    )
+    .unwrap();
+    dvar::set_string_internal("sys_gpu", &info.gpu_description).unwrap();
+    dvar::set_float_internal("sys_cpuGHz", info.cpu_ghz).unwrap();
+    dvar::set_string_internal("sys_cpuName", &info.cpu_name).unwrap();
+}
 
 fn should_update_for_info_change() -> bool {
     let msg_box_type = MessageBoxType::YesNo;
@@ -1729,6 +2077,68 @@ pub fn message_box(
     None
 }
 
+/// Opens `url` with the user's default browser (or whatever else handles
+/// that kind of URL).
+#[cfg(windows)]
+pub fn open_url(url: &str) -> Result<(), ()> {
+    let Ok(curl) = CString::new(url) else {
+        return Err(());
+    };
+
+    // SAFETY:
+    // ShellExecuteA is an FFI function, requiring use of unsafe. Both
+    // strings passed are valid and NUL-terminated for the duration of the
+    // call.
+    let res = unsafe {
+        ShellExecuteA(
+            HWND(0),
+            s!("open"),
+            PCSTR(curl.as_ptr().cast()),
+            PCSTR::null(),
+            PCSTR::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+
+    // ShellExecuteA returns a value greater than 32 on success
+    if res.0 > 32 {
+        Ok(())
+    } else {
+        Err(())
+    }
+}
+
+/// Opens `url` with the user's default browser (or whatever else handles
+/// that kind of URL).
+///
+/// Doesn't wait for the browser to open, so only fails if the launcher
+/// couldn't be started at all.
+#[cfg(all(unix, not(wasm)))]
+pub fn open_url(url: &str) -> Result<(), ()> {
+    let opener = if cfg!(macos) { "open" } else { "xdg-open" };
+    let mut child = std::process::Command::new(opener)
+        .arg(url)
+        .spawn()
+        .map_err(|_| ())?;
+    // Reap the launcher once it exits so it doesn't linger as a zombie
+    std::thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok(())
+}
+
+/// Opens `url` with the user's default browser (or whatever else handles
+/// that kind of URL).
+#[cfg(not(any(windows, all(unix, not(wasm)))))]
+pub fn open_url(url: &str) -> Result<(), ()> {
+    com::println!(
+        console::Channel::SYSTEM,
+        "sys::open_url: opening URLs isn't supported on this platform ({})",
+        url
+    );
+    Err(())
+}
+
 cfg_if! {
     if #[cfg(debug_assertions)] {
         static DEBUG_OUTPUT: AtomicBool = AtomicBool::new(true);
@@ -1974,10 +2384,16 @@ pub fn create_console() {
         )
     };
     unsafe { SetFocus(hwnd_input_line) };
+    conbuf::set_text_limit(console::scrollback_bytes());
     unsafe {
         SetWindowTextA(
             hwnd_buffer,
-            PCSTR(conbuf::clean_text(&console::get_text_copy(0x4000)).as_ptr()),
+            PCSTR(
+                conbuf::clean_text(&console::get_text_copy(
+                    console::scrollback_bytes(),
+                ))
+                .as_ptr(),
+            ),
         )
     };
 }
@@ -2036,6 +2452,7 @@ fn set_error_text(error: &str) {
     destroy_window(conbuf::s_wcd().input_line_window.unwrap());
     conbuf::s_wcd_mut().input_line_window = None;
 
+    beep_on_error();
     message_box(
         None,
         "Error",
@@ -2086,7 +2503,7 @@ pub fn cwd() -> PathBuf {
     std::env::current_dir().unwrap()
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, FromPrimitive)]
 pub enum KeyboardScancode {
     Esc,
     F1,
@@ -2296,7 +2713,7 @@ impl Modifiers {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum MouseScancode {
     LClick,
     RClick,
@@ -2304,6 +2721,11 @@ pub enum MouseScancode {
     Button4,
     Button5,
     ButtonN(u8),
+    /// Not a real button, but a "press" for each notch the wheel is scrolled
+    /// up, so that scrolling can be bound like any other button.
+    WheelUp,
+    /// Like [`MouseScancode::WheelUp`], but for scrolling down.
+    WheelDown,
 }
 
 impl KeyboardScancode {
@@ -2323,6 +2745,181 @@ impl KeyboardScancode {
                 | Self::NumPeriod,
         )
     }
+
+    /// Every key, in declaration order.
+    pub fn all() -> impl Iterator<Item = Self> {
+        (0u8..).map_while(num::FromPrimitive::from_u8)
+    }
+
+    /// Returns the name the key is bound by (e.g. `bind SPACE +gostand`)
+    /// and saved to configs with. The names match CoD's where it has one
+    /// for the key.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Esc => "ESCAPE",
+            Self::F1 => "F1",
+            Self::F2 => "F2",
+            Self::F3 => "F3",
+            Self::F4 => "F4",
+            Self::F5 => "F5",
+            Self::F6 => "F6",
+            Self::F7 => "F7",
+            Self::F8 => "F8",
+            Self::F9 => "F9",
+            Self::F10 => "F10",
+            Self::F11 => "F11",
+            Self::F12 => "F12",
+            Self::PrtScSysRq => "PRINTSCREEN",
+            Self::ScrLk => "SCROLLLOCK",
+            Self::PauseBreak => "PAUSE",
+            Self::Tilde => "`",
+            Self::Key1 => "1",
+            Self::Key2 => "2",
+            Self::Key3 => "3",
+            Self::Key4 => "4",
+            Self::Key5 => "5",
+            Self::Key6 => "6",
+            Self::Key7 => "7",
+            Self::Key8 => "8",
+            Self::Key9 => "9",
+            Self::Key0 => "0",
+            Self::Hyphen => "-",
+            Self::Equals => "=",
+            Self::Backspace => "BACKSPACE",
+            Self::Insert => "INS",
+            Self::Home => "HOME",
+            Self::PgUp => "PGUP",
+            Self::NumLk => "KP_NUMLOCK",
+            Self::NumSlash => "KP_SLASH",
+            Self::NumAsterisk => "KP_STAR",
+            Self::NumHyphen => "KP_MINUS",
+            Self::Tab => "TAB",
+            Self::Q => "Q",
+            Self::W => "W",
+            Self::E => "E",
+            Self::R => "R",
+            Self::T => "T",
+            Self::Y => "Y",
+            Self::U => "U",
+            Self::I => "I",
+            Self::O => "O",
+            Self::P => "P",
+            Self::OpenBracket => "[",
+            Self::CloseBracket => "]",
+            Self::BackSlash => "\\",
+            Self::Del => "DEL",
+            Self::End => "END",
+            Self::PgDn => "PGDN",
+            Self::Num7 => "KP_HOME",
+            Self::Num8 => "KP_UPARROW",
+            Self::Num9 => "KP_PGUP",
+            Self::NumPlus => "KP_PLUS",
+            Self::CapsLk => "CAPSLOCK",
+            Self::A => "A",
+            Self::S => "S",
+            Self::D => "D",
+            Self::F => "F",
+            Self::G => "G",
+            Self::H => "H",
+            Self::J => "J",
+            Self::K => "K",
+            Self::L => "L",
+            Self::Semicolon => "SEMICOLON",
+            Self::Apostrophe => "'",
+            Self::Enter => "ENTER",
+            Self::Num4 => "KP_LEFTARROW",
+            Self::Num5 => "KP_5",
+            Self::Num6 => "KP_RIGHTARROW",
+            Self::LShift => "SHIFT",
+            Self::Z => "Z",
+            Self::X => "X",
+            Self::C => "C",
+            Self::V => "V",
+            Self::B => "B",
+            Self::N => "N",
+            Self::M => "M",
+            Self::Comma => ",",
+            Self::Period => ".",
+            Self::ForwardSlash => "/",
+            Self::RShift => "RSHIFT",
+            Self::ArrowUp => "UPARROW",
+            Self::Num1 => "KP_END",
+            Self::Num2 => "KP_DOWNARROW",
+            Self::Num3 => "KP_PGDN",
+            Self::NumEnter => "KP_ENTER",
+            Self::LCtrl => "CTRL",
+            Self::LSys => "LWIN",
+            Self::LAlt => "ALT",
+            Self::Space => "SPACE",
+            Self::RAlt => "RALT",
+            Self::RSys => "RWIN",
+            Self::Fn => "FN",
+            Self::Menu => "MENU",
+            Self::RCtrl => "RCTRL",
+            Self::ArrowLeft => "LEFTARROW",
+            Self::ArrowDown => "DOWNARROW",
+            Self::ArrowRight => "RIGHTARROW",
+            Self::Num0 => "KP_INS",
+            Self::NumPeriod => "KP_DEL",
+        }
+    }
+
+    /// Returns the key named `name` (ignoring case), or [`None`] if `name`
+    /// doesn't name a key.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().find(|k| k.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// The names mouse buttons are bound by. Any other
+/// [`MouseScancode::ButtonN`] is named [`MOUSE_BUTTON_PREFIX`] followed by
+/// its number.
+const MOUSE_BUTTON_NAMES: [(MouseScancode, &str); 7] = [
+    (MouseScancode::LClick, "MOUSE1"),
+    (MouseScancode::RClick, "MOUSE2"),
+    (MouseScancode::MClick, "MOUSE3"),
+    (MouseScancode::Button4, "MOUSE4"),
+    (MouseScancode::Button5, "MOUSE5"),
+    (MouseScancode::WheelUp, "MWHEELUP"),
+    (MouseScancode::WheelDown, "MWHEELDOWN"),
+];
+
+const MOUSE_BUTTON_PREFIX: &str = "MOUSE";
+
+impl MouseScancode {
+    /// Returns the name the button is bound by (e.g. `bind MOUSE4 +melee`)
+    /// and saved to configs with. [`MouseScancode::ButtonN`] is named
+    /// `MOUSE<N>`.
+    pub fn name(self) -> String {
+        if let Self::ButtonN(n) = self {
+            return format!("{}{}", MOUSE_BUTTON_PREFIX, n);
+        }
+
+        MOUSE_BUTTON_NAMES
+            .iter()
+            .find(|(b, _)| *b == self)
+            .map(|(_, name)| (*name).to_owned())
+            .unwrap()
+    }
+
+    /// Returns the button named `name` (ignoring case), or [`None`] if
+    /// `name` doesn't name a mouse button.
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some(&(button, _)) = MOUSE_BUTTON_NAMES
+            .iter()
+            .find(|(_, n)| n.eq_ignore_ascii_case(name))
+        {
+            return Some(button);
+        }
+
+        name.get(..MOUSE_BUTTON_PREFIX.len())
+            .filter(|prefix| prefix.eq_ignore_ascii_case(MOUSE_BUTTON_PREFIX))
+            .and_then(|_| name.get(MOUSE_BUTTON_PREFIX.len()..))
+            .and_then(|n| n.parse::<u8>().ok())
+            // Buttons 1-5 have their own variants, so they were found above
+            .filter(|&n| n > 5)
+            .map(Self::ButtonN)
+    }
 }
 
 bitflags! {
@@ -2382,6 +2979,48 @@ pub enum WindowEvent {
     },
 }
 
+impl WindowEvent {
+    /// Returns the physical scancode of a [`WindowEvent::KeyDown`] or
+    /// [`WindowEvent::KeyUp`], if the platform was able to provide one.
+    pub const fn physical_scancode(&self) -> Option<KeyboardScancode> {
+        match *self {
+            Self::KeyDown {
+                physical_scancode, ..
+            }
+            | Self::KeyUp {
+                physical_scancode, ..
+            } => physical_scancode,
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if this is a key event carrying a physical scancode.
+    pub const fn has_physical_scancode(&self) -> bool {
+        self.physical_scancode().is_some()
+    }
+}
+
+/// Decides which scancode a key event is routed to the binds by.
+///
+/// The console key is the one below Escape, whatever the keyboard layout
+/// calls it, so it's matched on the physical scancode. Every other key is
+/// routed by its logical scancode, except that a key the layout happens to
+/// put grave/tilde on is routed by its physical scancode instead, so that
+/// it doesn't also open the console. Without a physical scancode, the
+/// logical one is all there is to go on.
+pub const fn routed_key_scancode(
+    logical: KeyboardScancode,
+    physical: Option<KeyboardScancode>,
+) -> KeyboardScancode {
+    match physical {
+        Some(KeyboardScancode::Tilde) => KeyboardScancode::Tilde,
+        Some(physical) if matches!(logical, KeyboardScancode::Tilde) => {
+            physical
+        }
+        _ => logical,
+    }
+}
+
 lazy_static! {
     pub static ref MAIN_WINDOW_EVENTS: Mutex<VecDeque<WindowEvent>> =
         Mutex::new(VecDeque::new());
@@ -2415,10 +3054,12 @@ pub fn next_main_window_event() -> Option<WindowEvent> {
                 DispatchMessageA(addr_of!(msg));
             }
         }
-        None
-    } else {
-        MAIN_WINDOW_EVENTS.lock().unwrap().pop_front()
     }
+
+    // Dispatching the message above may have pushed an event onto the
+    // queue, so check again instead of making the caller wait for the
+    // next call.
+    MAIN_WINDOW_EVENTS.lock().unwrap().pop_front()
 }
 
 #[cfg(wayland)]
@@ -2477,21 +3118,20 @@ pub fn next_main_window_event() -> Option<WindowEvent> {
     }
 
     if MAIN_WINDOW_EVENTS.lock().unwrap().is_empty() {
-        let mut ev =
-            unsafe { core::mem::MaybeUninit::<XEvent>::zeroed().assume_init() };
-        let display = unsafe {
-            XOpenDisplay(platform::display_server::xlib::display_name())
-        };
-        if unsafe { XPending(display) } == 0 {
-            return None;
-        }
+        let ev = with_display(|display| {
+            if unsafe { XPending(display) } == 0 {
+                return None;
+            }
 
-        unsafe {
-            XNextEvent(display, addr_of_mut!(ev));
-        }
-        unsafe {
-            XCloseDisplay(display);
-        }
+            let mut ev = unsafe {
+                core::mem::MaybeUninit::<XEvent>::zeroed().assume_init()
+            };
+            unsafe {
+                XNextEvent(display, addr_of_mut!(ev));
+            }
+            Some(ev)
+        })
+        .flatten()?;
 
         // Since XEvents don't have a timestamp associated with them
         // like Windows MSGs do, we do the next best thing and acquire
@@ -2564,6 +3204,105 @@ pub fn destroy_window(handle: WindowHandle) {
     }
 }
 
+/// Resizes the client area of the window `handle` to `width`x`height`.
+#[cfg(windows)]
+#[allow(clippy::undocumented_unsafe_blocks, clippy::cast_possible_wrap)]
+pub fn set_window_size(handle: WindowHandle, width: u32, height: u32) {
+    let hwnd = HWND(handle.get_win32().unwrap().hwnd as _);
+    // SetWindowPos sizes the whole window, borders and all, so grow the
+    // rect by however much the window's styles add around the client area
+    let style = WINDOW_STYLE(unsafe { GetWindowLongA(hwnd, GWL_STYLE) } as _);
+    let ex_style =
+        WINDOW_EX_STYLE(unsafe { GetWindowLongA(hwnd, GWL_EXSTYLE) } as _);
+    let mut rect = RECT {
+        left: 0,
+        top: 0,
+        right: width as _,
+        bottom: height as _,
+    };
+    unsafe {
+        AdjustWindowRectEx(addr_of_mut!(rect), style, false, ex_style);
+        SetWindowPos(
+            hwnd,
+            None,
+            0,
+            0,
+            rect.right - rect.left,
+            rect.bottom - rect.top,
+            SWP_NOMOVE | SWP_NOZORDER,
+        );
+    }
+}
+
+/// Gives the window `handle` the styles of a fullscreen window (a topmost
+/// popup covering the top-left of the screen) or of a regular window,
+/// depending on `fullscreen`, with a client area of `width`x`height`.
+///
+/// Matches the styles the renderer creates its windows with, so that
+/// switching between the two doesn't need a new window.
+#[cfg(windows)]
+#[allow(clippy::undocumented_unsafe_blocks, clippy::cast_possible_wrap)]
+pub fn set_window_fullscreen_style(
+    handle: WindowHandle,
+    fullscreen: bool,
+    width: u32,
+    height: u32,
+) {
+    let hwnd = HWND(handle.get_win32().unwrap().hwnd as _);
+    let (ex_style, style, insert_after, move_flag) = if fullscreen {
+        (
+            WS_EX_TOPMOST,
+            WS_POPUP | WS_VISIBLE,
+            HWND_TOPMOST,
+            SWP_SHOWWINDOW,
+        )
+    } else {
+        (
+            WS_EX_LEFT,
+            WS_SYSMENU | WS_CAPTION | WS_VISIBLE,
+            HWND_NOTOPMOST,
+            SWP_NOMOVE,
+        )
+    };
+    let mut rect = RECT {
+        left: 0,
+        top: 0,
+        right: width as _,
+        bottom: height as _,
+    };
+    unsafe {
+        SetWindowLongPtrA(hwnd, GWL_STYLE, style.0 as _);
+        SetWindowLongPtrA(hwnd, GWL_EXSTYLE, ex_style.0 as _);
+        AdjustWindowRectEx(addr_of_mut!(rect), style, false, ex_style);
+        // The frame has to be told to redraw for the style change to be
+        // picked up
+        SetWindowPos(
+            hwnd,
+            insert_after,
+            0,
+            0,
+            rect.right - rect.left,
+            rect.bottom - rect.top,
+            move_flag | SWP_FRAMECHANGED,
+        );
+    }
+}
+
+/// Sets the title of the window `handle` to `title`. Anything after a NUL
+/// in `title` is dropped.
+#[cfg(windows)]
+#[allow(clippy::undocumented_unsafe_blocks)]
+pub fn set_window_title(handle: WindowHandle, title: &str) {
+    let title = title.split('\0').next().unwrap_or_default();
+    let title = CString::new(title).unwrap_or_default();
+    unsafe {
+        SetWindowTextA(
+            HWND(handle.get_win32().unwrap().hwnd as _),
+            PCSTR(title.as_ptr().cast()),
+        );
+    }
+}
+
 #[cfg(wayland)]
 pub fn show_window(handle: WindowHandle) {
     let handle = handle.get_wayland().unwrap();
@@ -2582,6 +3321,33 @@ pub fn destroy_window(handle: WindowHandle) {
     todo!()
 }
 
+/// Would resize the window `handle` to `width`x`height`, but resizing it
+/// needs the xdg-shell toplevel, which the Wayland backend doesn't keep
+/// track of yet, so this only logs the size instead.
+// TODO - resize it through xdg_toplevel/xdg_surface::set_window_geometry
+#[cfg(wayland)]
+pub fn set_window_size(_handle: WindowHandle, width: u32, height: u32) {
+    com::dprintln!(
+        console::Channel::SYSTEM,
+        "Can't resize windows on Wayland yet, ignoring {}x{}",
+        width,
+        height
+    );
+}
+
+/// Would set the title of the window `handle` to `title`, but setting it
+/// needs the xdg-shell toplevel, which the Wayland backend doesn't keep
+/// track of yet, so this only logs the title instead.
+// TODO - set it through xdg_toplevel::set_title
+#[cfg(wayland)]
+pub fn set_window_title(_handle: WindowHandle, title: &str) {
+    com::dprintln!(
+        console::Channel::SYSTEM,
+        "Can't set window titles on Wayland yet, ignoring \"{}\"",
+        title
+    );
+}
+
 #[cfg(appkit)]
 pub fn show_window(handle: WindowHandle) {
     unsafe {
@@ -2603,40 +3369,108 @@ pub fn destroy_window(handle: WindowHandle) {
     unsafe { handle.get_appkit().unwrap().ns_window().close() };
 }
 
+#[cfg(appkit)]
+pub fn set_window_size(handle: WindowHandle, width: u32, height: u32) {
+    let size = NSSize::new(f64::from(width), f64::from(height));
+    unsafe {
+        handle.get_appkit().unwrap().ns_window().setContentSize(size);
+    }
+}
+
+#[cfg(appkit)]
+pub fn set_window_title(handle: WindowHandle, title: &str) {
+    let title = NSString::from_str(title);
+    unsafe { handle.get_appkit().unwrap().ns_window().setTitle(&title) };
+}
+
 #[cfg(xlib)]
 #[allow(clippy::undocumented_unsafe_blocks)]
 pub fn show_window(handle: WindowHandle) {
     let handle = handle.get_xlib().unwrap();
-    let display =
-        unsafe { XOpenDisplay(platform::display_server::xlib::display_name()) };
-    unsafe {
+    with_display(|display| unsafe {
         XMapWindow(display, handle.window);
-    }
+        XFlush(display);
+    });
 }
 
 #[cfg(xlib)]
 #[allow(clippy::undocumented_unsafe_blocks)]
 pub fn focus_window(handle: WindowHandle) {
     let handle = handle.get_xlib().unwrap();
-    let display =
-        unsafe { XOpenDisplay(platform::display_server::xlib::display_name()) };
-    unsafe {
+    with_display(|display| unsafe {
         XSetInputFocus(display, handle.window, RevertToParent, CurrentTime);
-    }
+        XFlush(display);
+    });
 }
 
 #[cfg(xlib)]
+#[allow(clippy::undocumented_unsafe_blocks)]
 pub fn destroy_window(handle: WindowHandle) {
     let handle = handle.get_xlib().unwrap();
-    let display =
-        unsafe { XOpenDisplay(platform::display_server::xlib::display_name()) };
-    unsafe {
+    with_display(|display| unsafe {
         XDestroyWindow(display, handle.window);
-    }
+        XFlush(display);
+    });
+}
+
+#[cfg(xlib)]
+#[allow(clippy::undocumented_unsafe_blocks)]
+pub fn set_window_size(handle: WindowHandle, width: u32, height: u32) {
+    let handle = handle.get_xlib().unwrap();
+    with_display(|display| unsafe {
+        XResizeWindow(display, handle.window, width, height);
+        XFlush(display);
+    });
+}
+
+/// Sets the title of the window `handle` to `title`. Anything after a NUL
+/// in `title` is dropped.
+#[cfg(xlib)]
+#[allow(clippy::undocumented_unsafe_blocks)]
+pub fn set_window_title(handle: WindowHandle, title: &str) {
+    let handle = handle.get_xlib().unwrap();
+    let title = title.split('\0').next().unwrap_or_default();
+    let c_title = CString::new(title).unwrap_or_default();
+    with_display(|display| unsafe {
+        XStoreName(display, handle.window, c_title.as_ptr());
+        platform::display_server::xlib::set_net_wm_name(
+            display,
+            handle.window,
+            title,
+        );
+        XFlush(display);
+    });
 }
 
 static MODIFIERS: RwLock<Modifiers> = RwLock::new(Modifiers::empty());
 
+/// Returns the modifier keys currently held down.
+pub fn modifiers() -> Modifiers {
+    *MODIFIERS.read().unwrap()
+}
+
+/// Translates a press (`down`) or release of the mouse button `button` into
+/// an engine event, stamped with the time of the window message it came
+/// from, so that it goes through the same queue as keyboard input.
+fn mouse_button_event(button: MouseScancode, down: bool) -> sys::Event {
+    sys::Event::new(
+        Some(platform::get_msg_time()),
+        sys::EventType::Mouse(button, down),
+    )
+}
+
+/// Asks for the user's attention (see [`platform::request_attention`]) if
+/// the main window exists but isn't focused.
+pub fn request_attention_if_inactive(level: platform::AttentionLevel) {
+    if platform::get_active_app() {
+        return;
+    }
+
+    if let Some(handle) = platform::get_window_handle() {
+        platform::request_attention(handle, level);
+    }
+}
+
 /// Handles a [`WindowEvent`].
 pub fn handle_main_window_event(ev: WindowEvent) {
     match ev {
@@ -2660,8 +3494,7 @@ pub fn handle_main_window_event(ev: WindowEvent) {
             .unwrap();
         }
         WindowEvent::CloseRequested => {
-            cbuf::add_textln(0, "quit");
-            sys::set_quit_event();
+            com::request_quit();
         }
         WindowEvent::Destroyed => {
             // FUN_004dfd60()
@@ -2676,7 +3509,51 @@ pub fn handle_main_window_event(ev: WindowEvent) {
                 dvar::clear_modified("vid_xpos").unwrap();
                 dvar::clear_modified("vid_ypos").unwrap();
                 if platform::get_platform_vars().active_app {
-                    input::activate(true);
+                    input::set_reason(input::InputReason::WindowFocus, true);
+                }
+            }
+        }
+        // Minimizing reports a size of 0x0 on some platforms, which there's
+        // no point in resizing the render target to
+        WindowEvent::Resized { width, height } if width != 0 && height != 0 => {
+            if let Some(handle) = platform::get_window_handle() {
+                render::resize_window(handle, width, height);
+            }
+        }
+        WindowEvent::MouseButtonDown(button) => {
+            sys::enqueue_event(mouse_button_event(button, true));
+        }
+        WindowEvent::MouseButtonUp(button) => {
+            sys::enqueue_event(mouse_button_event(button, false));
+        }
+        // Something's changed about the display the user probably wants to
+        // check on (e.g. the game was dropped out of fullscreen)
+        WindowEvent::DisplayChange { .. } => {
+            request_attention_if_inactive(
+                platform::AttentionLevel::Informational,
+            );
+        }
+        WindowEvent::CursorMoved { x, y } => {
+            input::mouse::cursor_moved(x, y);
+            input::set_cursor_from_window(x, y);
+        }
+        // The cursor could've been anywhere while the window didn't have
+        // focus, so start it off somewhere predictable
+        WindowEvent::SetFocus => {
+            input::center_cursor();
+        }
+        WindowEvent::MouseWheelScroll(delta) => {
+            let notches = input::mouse::accumulate_wheel(delta);
+            let button = if notches > 0 {
+                MouseScancode::WheelUp
+            } else {
+                MouseScancode::WheelDown
+            };
+            // Each notch is a separate press and release of the wheel's
+            // "button"
+            for _ in 0..notches.unsigned_abs() {
+                for down in [true, false] {
+                    sys::enqueue_event(mouse_button_event(button, down));
                 }
             }
         }
@@ -2704,7 +3581,8 @@ pub fn handle_main_window_event(ev: WindowEvent) {
             *MODIFIERS.write().unwrap() = modifiers;
         }
         WindowEvent::KeyDown {
-            logical_scancode, ..
+            logical_scancode,
+            physical_scancode,
         } => {
             if logical_scancode == KeyboardScancode::Enter
                 && MODIFIERS.read().unwrap().contains(Modifiers::LALT)
@@ -2715,27 +3593,43 @@ pub fn handle_main_window_event(ev: WindowEvent) {
                     return;
                 }
 
-                if dvar::get_int("developer").unwrap() != 0 {
+                if com::is_developer() {
                     // FUN_005a5360()
-                    dvar::set_bool(
-                        "r_fullscreen",
-                        dvar::get_bool("r_fullscreen").unwrap() == false,
-                    )
-                    .unwrap();
-                    cbuf::add_textln(0, "vid_restart");
+                    let fullscreen =
+                        dvar::get_bool("r_fullscreen").unwrap() == false;
+                    // Only restart the renderer if the backend can't switch
+                    // in place
+                    if let Err(e) = render::set_fullscreen(fullscreen) {
+                        com::println!(console::Channel::GFX, "{}", e);
+                        dvar::set_bool("r_fullscreen", fullscreen).unwrap();
+                        cbuf::add_textln(0, "vid_restart");
+                    }
                 }
             }
             sys::enqueue_event(sys::Event::new(
                 Some(platform::get_msg_time() as _),
-                sys::EventType::Key(logical_scancode, true),
+                sys::EventType::Key(
+                    routed_key_scancode(logical_scancode, physical_scancode),
+                    true,
+                ),
             ));
         }
         WindowEvent::KeyUp {
-            logical_scancode, ..
+            logical_scancode,
+            physical_scancode,
         } => {
             sys::enqueue_event(sys::Event::new(
                 Some(platform::get_msg_time() as _),
-                sys::EventType::Key(logical_scancode, false),
+                sys::EventType::Key(
+                    routed_key_scancode(logical_scancode, physical_scancode),
+                    false,
+                ),
+            ));
+        }
+        WindowEvent::Character(c) => {
+            sys::enqueue_event(sys::Event::new(
+                Some(platform::get_msg_time() as _),
+                sys::EventType::Character(c),
             ));
         }
         _ => {}
@@ -2797,28 +3691,65 @@ pub fn get_current_thread_name() -> String {
     get_thread_context().to_string()
 }
 
+static MAIN_THREAD_INITIALIZED: util::InitFlag =
+    util::InitFlag::new("sys::init_main_thread");
+
 pub fn init_main_thread() {
     *THREAD_ID.write().unwrap().get_mut(0).unwrap() =
         Some(get_current_thread_id());
+    MAIN_THREAD_INITIALIZED.set();
+}
+
+/// Makes the process DPI-aware unless `+set r_dpiAware 0` was passed on the
+/// command line. With DPI awareness on, Windows stops scaling windows for
+/// us and sizes everything in physical pixels, which is exactly what the
+/// backbuffer is sized in.
+///
+/// Has to be called at start-up, before any window (including the console)
+/// is created, since the awareness doesn't apply to windows that already
+/// exist and can only be set once per process. That's before the configs
+/// are loaded, so an archived `r_dpiAware` only affects how windows are
+/// sized, not the process' awareness.
+#[cfg(windows)]
+pub fn init_dpi_awareness() {
+    let aware = com::startup_variable_value("r_dpiAware")
+        .map_or(true, |v| v != "0" && !v.eq_ignore_ascii_case("false"));
+    if aware {
+        platform::display_server::win32::set_dpi_aware();
+    }
+}
+
+// Returns `true` if the calling thread is the one registered in slot
+// `index` of `THREAD_ID`.
+fn is_thread(index: usize, caller: &str) -> bool {
+    MAIN_THREAD_INITIALIZED.debug_check(caller);
+    Some(get_current_thread_id())
+        == *THREAD_ID.read().unwrap().get(index).unwrap()
 }
 
 pub fn is_main_thread() -> bool {
-    Some(get_current_thread_id()) == *THREAD_ID.read().unwrap().get(0).unwrap()
+    is_thread(0, "sys::is_main_thread")
 }
 
 pub fn is_render_thread() -> bool {
-    Some(get_current_thread_id()) == *THREAD_ID.read().unwrap().get(1).unwrap()
+    is_thread(1, "sys::is_render_thread")
 }
+
+pub fn is_worker_thread() -> bool {
+    (FIRST_WORKER_THREAD..FIRST_WORKER_THREAD + MAX_WORKER_THREADS)
+        .any(|i| is_thread(i, "sys::is_worker_thread"))
+}
+
 pub fn is_server_thread() -> bool {
-    Some(get_current_thread_id()) == *THREAD_ID.read().unwrap().get(10).unwrap()
+    is_thread(10, "sys::is_server_thread")
 }
 
 pub fn is_database_thread() -> bool {
-    Some(get_current_thread_id()) == *THREAD_ID.read().unwrap().get(13).unwrap()
+    is_thread(13, "sys::is_database_thread")
 }
 
 pub fn is_stream_thread() -> bool {
-    Some(get_current_thread_id()) == *THREAD_ID.read().unwrap().get(14).unwrap()
+    is_thread(14, "sys::is_stream_thread")
 }
 
 pub fn notify_renderer() {
@@ -2826,6 +3757,7 @@ pub fn notify_renderer() {
 }
 
 pub fn quit() -> ! {
+    com::shutdown();
     normal_exit();
     std::process::exit(0);
 }
@@ -2839,6 +3771,7 @@ pub fn wait_renderer() {
     }
 
     while query_render_completed_event() == SignalState::Cleared {
+        rb::check_render_thread_hung();
         if com::ERROR_ENTERED.load_relaxed() == true {
             std::thread::sleep(Duration::from_millis(100));
             return;
@@ -2847,3 +3780,249 @@ pub fn wait_renderer() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn mouse_button_down_becomes_mouse_event() {
+        let _guard = testing::fresh_state();
+        while next_event().is_some() {}
+
+        platform::set_msg_time(1234);
+        handle_main_window_event(WindowEvent::MouseButtonDown(
+            MouseScancode::LClick,
+        ));
+
+        let ev = next_event().unwrap();
+        assert!(matches!(
+            ev.event_type(),
+            EventType::Mouse(MouseScancode::LClick, true)
+        ));
+        assert_eq!(ev.time(), 1234);
+        assert!(next_event().is_none());
+    }
+
+    #[test]
+    fn wheel_scroll_presses_the_wheel_once_per_notch() {
+        let _guard = testing::fresh_state();
+        while next_event().is_some() {}
+        input::mouse::consume_wheel();
+
+        handle_main_window_event(WindowEvent::MouseWheelScroll(
+            -2.0 * input::mouse::WHEEL_DELTA,
+        ));
+
+        for _ in 0..2 {
+            for down in [true, false] {
+                let ev = next_event().unwrap();
+                assert!(matches!(
+                    ev.event_type(),
+                    EventType::Mouse(MouseScancode::WheelDown, d) if *d == down
+                ));
+            }
+        }
+        assert!(next_event().is_none());
+        assert_eq!(input::mouse::consume_wheel(), -2.0);
+    }
+
+    #[test]
+    fn micros_never_goes_backwards() {
+        let mut last = micros();
+        for _ in 0..10_000 {
+            let now = micros();
+            assert!(now >= last, "micros went from {} to {}", last, now);
+            last = now;
+        }
+
+        // And it does actually advance
+        let start = micros();
+        std::thread::sleep(core::time::Duration::from_millis(2));
+        assert!(micros() - start >= 2000);
+    }
+
+    #[test]
+    fn affinity_reads_back_what_was_set() {
+        // Pin a thread of our own rather than one of the test harness's
+        std::thread::spawn(|| {
+            let thread = platform::current_thread_id();
+            // Nothing to check where the OS can't report affinity
+            let Some(allowed) = platform::thread_affinity(thread) else {
+                return;
+            };
+            // Only CPUs the thread's already allowed on (e.g. in a
+            // container) can be picked
+            let cpu = allowed.trailing_zeros();
+            assert!(cpu < 64);
+            let mask = 1 << cpu;
+
+            set_thread_affinity(thread, mask).unwrap();
+            assert_eq!(platform::thread_affinity(thread), Some(mask));
+            set_thread_affinity(thread, allowed).unwrap();
+            assert_eq!(platform::thread_affinity(thread), Some(allowed));
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn main_and_render_threads_get_separate_cores() {
+        // With 2 threads per core, CPUs 0 and 2 are on different cores
+        // however the siblings are numbered
+        assert_eq!(main_and_render_affinity(8, 4), Some((0b1, 0b100)));
+        assert_eq!(main_and_render_affinity(4, 4), Some((0b1, 0b10)));
+        assert_eq!(main_and_render_affinity(2, 1), None);
+        assert_eq!(main_and_render_affinity(1, 1), None);
+    }
+
+    #[test]
+    fn console_key_is_routed_by_physical_scancode() {
+        use KeyboardScancode::{Key1, Semicolon, Tilde};
+
+        // e.g. a layout with something else below Escape
+        assert_eq!(routed_key_scancode(Key1, Some(Tilde)), Tilde);
+        // and grave/tilde somewhere else, which mustn't open the console
+        assert_eq!(routed_key_scancode(Tilde, Some(Semicolon)), Semicolon);
+        // Other keys go by what the layout calls them
+        assert_eq!(routed_key_scancode(Key1, Some(Semicolon)), Key1);
+        assert_eq!(routed_key_scancode(Tilde, Some(Tilde)), Tilde);
+        // With no physical scancode, the logical one is all there is
+        assert_eq!(routed_key_scancode(Tilde, None), Tilde);
+        assert_eq!(routed_key_scancode(Key1, None), Key1);
+    }
+
+    #[test]
+    fn key_events_are_queued_with_the_routed_scancode() {
+        let _guard = testing::fresh_state();
+        while next_event().is_some() {}
+
+        let key_down = WindowEvent::KeyDown {
+            logical_scancode: KeyboardScancode::Key1,
+            physical_scancode: Some(KeyboardScancode::Tilde),
+        };
+        assert!(key_down.has_physical_scancode());
+        handle_main_window_event(key_down);
+        let key_up = WindowEvent::KeyUp {
+            logical_scancode: KeyboardScancode::Key1,
+            physical_scancode: None,
+        };
+        assert!(key_up.has_physical_scancode() == false);
+        handle_main_window_event(key_up);
+
+        assert!(matches!(
+            next_event().unwrap().event_type(),
+            EventType::Key(KeyboardScancode::Tilde, true)
+        ));
+        assert!(matches!(
+            next_event().unwrap().event_type(),
+            EventType::Key(KeyboardScancode::Key1, false)
+        ));
+        assert!(next_event().is_none());
+    }
+
+    // Writes a semaphore file recording `pid` the way check_crash_or_rerun
+    // does, returning its path
+    fn write_semaphore(name: &str, pid: u32) -> PathBuf {
+        let path = std::env::temp_dir()
+            .join(format!(".opent5-{}-{}", name, std::process::id()));
+        std::fs::write(&path, pid.to_ne_bytes()).unwrap();
+        path
+    }
+
+    #[test]
+    fn stale_semaphore_file_means_previous_instance_died() {
+        let this_pid = 1000;
+        let path = write_semaphore("stale", 1234);
+        let pid = read_semaphore_pid(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(pid, 1234);
+        // Nothing is running under the recorded PID any more
+        assert!(previous_instance_died(pid, this_pid, |_| false));
+        // The PID has been handed out again, to this instance
+        assert!(previous_instance_died(this_pid, this_pid, |_| true));
+        assert!(read_semaphore_pid(&path).is_none());
+    }
+
+    #[test]
+    fn live_semaphore_file_means_another_instance_is_running() {
+        let this_pid = 1000;
+        let path = write_semaphore("live", 1234);
+        let pid = read_semaphore_pid(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(previous_instance_died(pid, this_pid, |p| p == 1234) == false);
+    }
+
+    #[cfg(linux)]
+    #[test]
+    fn is_game_process_checks_the_executable() {
+        // This test binary is "another copy" of itself, and PIDs never get
+        // anywhere near u32::MAX
+        assert!(is_game_process(std::process::id()));
+        assert!(is_game_process(u32::MAX) == false);
+    }
+
+    #[test]
+    #[allow(clippy::panic)]
+    fn queued_work_all_runs_despite_panics() {
+        static STARTED: std::sync::Once = std::sync::Once::new();
+        STARTED.call_once(init_worker_threads);
+
+        // More jobs than there are workers, so that each worker has to
+        // carry on past a panic for them all to run
+        let jobs = MAX_WORKER_THREADS * 4;
+        let (tx, rx) = std::sync::mpsc::channel();
+        for i in 0..jobs {
+            let tx = tx.clone();
+            queue_work(move || {
+                if i % 2 == 0 {
+                    panic!("test job {} panicking", i);
+                }
+                tx.send(i).unwrap();
+            });
+        }
+
+        let mut ran = (0..jobs / 2)
+            .map(|_| rx.recv_timeout(Duration::from_secs(10)).unwrap())
+            .collect::<Vec<_>>();
+        ran.sort_unstable();
+        assert_eq!(ran, (0..jobs).filter(|i| i % 2 != 0).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn keyboard_names_round_trip() {
+        for key in KeyboardScancode::all() {
+            assert_eq!(
+                KeyboardScancode::from_name(key.name()),
+                Some(key),
+                "{:?}",
+                key
+            );
+            assert_eq!(
+                KeyboardScancode::from_name(&key.name().to_lowercase()),
+                Some(key)
+            );
+        }
+        assert_eq!(KeyboardScancode::from_name("NOTAKEY"), None);
+    }
+
+    #[test]
+    fn mouse_names_round_trip() {
+        let buttons = MOUSE_BUTTON_NAMES
+            .iter()
+            .map(|&(b, _)| b)
+            .chain((6..=u8::MAX).map(MouseScancode::ButtonN));
+        for button in buttons {
+            assert_eq!(MouseScancode::from_name(&button.name()), Some(button));
+        }
+        assert_eq!(
+            MouseScancode::from_name("mouse4"),
+            Some(MouseScancode::Button4)
+        );
+        assert_eq!(MouseScancode::from_name("MOUSE0"), None);
+        assert_eq!(MouseScancode::from_name("MOUSE"), None);
+    }
+}
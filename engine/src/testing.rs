@@ -0,0 +1,28 @@
+// Shared setup for the unit tests.
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use lazy_static::lazy_static;
+
+use crate::*;
+
+lazy_static! {
+    // Held by every test that touches the Dvar, command, or command buffer
+    // globals, since the test harness runs tests on several threads at once
+    static ref LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Gives the calling test exclusive use of the Dvar, command, and command
+/// buffer globals until the returned guard is dropped, starting them off
+/// freshly initialized with nothing else registered.
+pub fn fresh_state() -> MutexGuard<'static, ()> {
+    // A failed test poisons the lock, which shouldn't fail every test after
+    let guard = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+    cbuf::clear();
+    cmd::clear_commands();
+    dvar::reset_registry();
+    dvar::set_loading_autoexec(false);
+    cmd::init();
+    dvar::init();
+    guard
+}
@@ -691,6 +691,11 @@ impl Angle {
     pub const fn as_radians(self) -> Radians {
         self.0
     }
+
+    /// Returns this angle turned by `degrees`.
+    pub const fn turned_by(self, degrees: Degrees) -> Self {
+        Self(Radians(self.0 .0 + degrees.0 * DEGREES_TO_RADIANS))
+    }
 }
 
 #[derive(Copy, Clone, Default, Debug, PartialEq)]
@@ -736,3 +741,88 @@ impl Point {
         Self { x, y, z }
     }
 }
+
+/// Returns the Levenshtein (edit) distance between `a` and `b`, i.e. the
+/// fewest single-character insertions, deletions, or substitutions needed to
+/// turn one into the other.
+#[allow(clippy::indexing_slicing)]
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    let mut cur = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        cur[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != cb);
+            cur[j + 1] = substitution.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        core::mem::swap(&mut prev, &mut cur);
+    }
+
+    prev[b.len()]
+}
+
+/// Returns the longest prefix of `s` that's at most `max_len` bytes long
+/// without splitting a character in two.
+pub fn truncate_at_char_boundary(s: &str, max_len: usize) -> &str {
+    if s.len() <= max_len {
+        return s;
+    }
+
+    let end = (0..=max_len)
+        .rev()
+        .find(|&i| s.is_char_boundary(i))
+        .unwrap_or(0);
+    s.get(..end).unwrap_or_default()
+}
+
+/// Tracks whether a subsystem's `init` has run, so that the subsystem's
+/// entry points can catch being called too early with a clear message
+/// instead of failing somewhere deep inside.
+///
+/// The checks are `debug_assert!`s, so they cost nothing in release builds.
+#[derive(Debug)]
+pub struct InitFlag {
+    init_fn: &'static str,
+    initialized: AtomicBool,
+}
+
+impl InitFlag {
+    /// Creates a flag for the subsystem initialized by `init_fn` (e.g.
+    /// `"dvar::init"`), which is used in the assertion message.
+    pub const fn new(init_fn: &'static str) -> Self {
+        Self {
+            init_fn,
+            initialized: AtomicBool::new(false),
+        }
+    }
+
+    /// Marks the subsystem as initialized.
+    pub fn set(&self) {
+        self.initialized.store(true, Ordering::Release);
+    }
+
+    /// Marks the subsystem as no longer initialized (e.g. after shutting
+    /// it down).
+    pub fn clear(&self) {
+        self.initialized.store(false, Ordering::Release);
+    }
+
+    /// Returns `true` if the subsystem has been initialized.
+    pub fn is_set(&self) -> bool {
+        self.initialized.load(Ordering::Acquire)
+    }
+
+    /// Debug-asserts that the subsystem has been initialized. `caller`
+    /// names the entry point being called.
+    #[track_caller]
+    pub fn debug_check(&self, caller: &str) {
+        debug_assert!(
+            self.is_set(),
+            "called {} before {}",
+            caller,
+            self.init_fn
+        );
+    }
+}
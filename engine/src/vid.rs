@@ -1,12 +1,12 @@
 #![allow(dead_code)]
 
-use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
 
 use crate::*;
 
 use lazy_static::lazy_static;
 
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone, Default, Debug)]
 pub struct Config {
     pub scene_width: u32,
     pub scene_height: u32,
@@ -23,12 +23,24 @@ pub struct Config {
     pub max_texture_size: usize,
     pub max_texture_maps: usize,
     pub device_supports_gamma: bool,
+    /// GPU memory available to the game, in MiB, if the backend and driver
+    /// report it (see [`render::gpu_memory_mb`]).
+    pub gpu_memory_mb: Option<u64>,
+    /// Incremented whenever the renderer commits new window settings (see
+    /// [`config_generation`]).
+    generation: u64,
 }
 
 impl Config {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Records that the config's values have changed, so that systems
+    /// checking [`config_generation`] know to pick up the new ones.
+    pub fn bump_generation(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
 }
 
 lazy_static! {
@@ -39,10 +51,29 @@ pub fn config() -> RwLockReadGuard<'static, Config> {
     CONFIG.read().unwrap()
 }
 
+/// Returns a copy of the current [`Config`], or [`None`] if it's locked for
+/// writing. Never blocks or panics, for use from the panic hook.
+pub fn try_config() -> Option<Config> {
+    match CONFIG.try_read() {
+        Ok(c) => Some(*c),
+        Err(TryLockError::Poisoned(e)) => Some(*e.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
 pub fn config_mut() -> RwLockWriteGuard<'static, Config> {
     CONFIG.write().unwrap()
 }
 
+/// Returns a number that changes every time the video config does.
+///
+/// Anything derived from the config (e.g. UI layout based on the aspect
+/// ratio or scene size) can hold on to the generation it was computed for,
+/// and recompute once this no longer matches.
+pub fn config_generation() -> u64 {
+    config().generation
+}
+
 #[allow(clippy::print_stdout)]
 pub fn app_activate(active_app: bool, is_minimized: bool) {
     key::clear_states(0);
@@ -59,6 +90,6 @@ pub fn app_activate(active_app: bool, is_minimized: bool) {
         println!("TODO: com::touch_memory");
     }
 
-    input::activate(active_app);
+    input::set_reason(input::InputReason::WindowFocus, active_app);
     // _DAT_027706dc = 0;
 }